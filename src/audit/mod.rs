@@ -0,0 +1,372 @@
+//! Append-only audit trail of security relevant events.
+//!
+//! One record per line, fields separated by tabs:
+//!
+//! ```text
+//! seq  time  kind  outcome  user  pid  tty  detail  hash
+//! ```
+//!
+//! `hash` is the SHA-256 of the previous record's hash followed by the other
+//! fields of this one, so editing or removing a record breaks the chain from
+//! there on. Removing records at the end can only be noticed against a hash
+//! noted down elsewhere, `auditlog --head` prints the current one.
+
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+use sha2::{Digest, Sha256};
+use crate::auth::modules::faillock::now_secs;
+use crate::auth::modules::session_log::get_tty;
+
+pub const DEFAULT_AUDIT_LOG_PATH: &str = "/var/log/audit.log";
+/// What the first record chains to
+pub const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EventKind {
+    /// Result of an auth stack, for any service
+    Auth,
+    Login,
+    Logout,
+    Su,
+    Sudo,
+    Passwd,
+    /// Service commands sent to init over the control socket
+    Service,
+}
+
+impl EventKind {
+    pub const ALL: [EventKind; 7] = [
+        EventKind::Auth,
+        EventKind::Login,
+        EventKind::Logout,
+        EventKind::Su,
+        EventKind::Sudo,
+        EventKind::Passwd,
+        EventKind::Service,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EventKind::Auth => "auth",
+            EventKind::Login => "login",
+            EventKind::Logout => "logout",
+            EventKind::Su => "su",
+            EventKind::Sudo => "sudo",
+            EventKind::Passwd => "passwd",
+            EventKind::Service => "service",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.as_str() == value)
+    }
+}
+
+impl fmt::Display for EventKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Outcome {
+    Success,
+    Failure,
+}
+
+impl Outcome {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Outcome::Success => "success",
+            Outcome::Failure => "failure",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AuditEvent {
+    pub seq: u64,
+    pub time: i64,
+    pub kind: EventKind,
+    pub outcome: Outcome,
+    pub user: String,
+    pub pid: u32,
+    /// Without the `/dev/` prefix
+    pub tty: String,
+    pub detail: String,
+    pub hash: String,
+}
+
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('\t', "\\t").replace('\n', "\\n")
+}
+
+fn unescape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => out.push('\t'),
+            Some('n') => out.push('\n'),
+            Some(other) => out.push(other),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+impl AuditEvent {
+    /// Every field but the hash, as written to the log.
+    fn body(&self) -> String {
+        format!(
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            self.seq,
+            self.time,
+            self.kind,
+            self.outcome.as_str(),
+            escape(&self.user),
+            self.pid,
+            escape(&self.tty),
+            escape(&self.detail)
+        )
+    }
+
+    pub fn compute_hash(&self, prev_hash: &str) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(prev_hash.as_bytes());
+        hasher.update(self.body().as_bytes());
+        hex::encode(hasher.finalize())
+    }
+
+    pub fn to_line(&self) -> String {
+        format!("{}\t{}\n", self.body(), self.hash)
+    }
+
+    pub fn parse(line: &str) -> Option<Self> {
+        let fields: Vec<&str> = line.trim_end_matches('\n').split('\t').collect();
+        if fields.len() != 9 {
+            return None;
+        }
+        Some(AuditEvent {
+            seq: fields[0].parse().ok()?,
+            time: fields[1].parse().ok()?,
+            kind: EventKind::parse(fields[2])?,
+            outcome: match fields[3] {
+                "success" => Outcome::Success,
+                "failure" => Outcome::Failure,
+                _ => return None,
+            },
+            user: unescape(fields[4]),
+            pid: fields[5].parse().ok()?,
+            tty: unescape(fields[6]),
+            detail: unescape(fields[7]),
+            hash: fields[8].to_string(),
+        })
+    }
+}
+
+/// The last complete line of `file`, read from the end so long logs stay cheap.
+fn last_line(file: &mut File) -> io::Result<Option<String>> {
+    let len = file.seek(SeekFrom::End(0))?;
+    let mut window = 4096u64;
+    loop {
+        let start = len.saturating_sub(window);
+        file.seek(SeekFrom::Start(start))?;
+        let mut tail = Vec::new();
+        file.read_to_end(&mut tail)?;
+
+        let content = tail.strip_suffix(b"\n").unwrap_or(&tail);
+        match content.iter().rposition(|b| *b == b'\n') {
+            Some(pos) => return Ok(Some(String::from_utf8_lossy(&content[pos + 1..]).into_owned())),
+            None if start == 0 => {
+                return Ok((!content.is_empty()).then(|| String::from_utf8_lossy(content).into_owned()))
+            }
+            None => window *= 2,
+        }
+    }
+}
+
+/// Append an event to the log at `path`, chained to the last one in it.
+pub fn append_to(path: &Path, kind: EventKind, outcome: Outcome, user: &str, detail: &str) -> io::Result<AuditEvent> {
+    let mut file = OpenOptions::new().read(true).append(true).create(true).mode(0o600).open(path)?;
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let (seq, prev_hash) = match last_line(&mut file)? {
+        Some(line) => match AuditEvent::parse(&line) {
+            Some(last) => (last.seq + 1, last.hash),
+            // Keep recording after a damaged record, verifying points at it anyway
+            None => {
+                let seq: u64 = line.split('\t').next().and_then(|s| s.parse().ok()).unwrap_or(0);
+                (seq + 1, hex::encode(Sha256::digest(line.as_bytes())))
+            }
+        },
+        None => (1, GENESIS_HASH.to_string()),
+    };
+
+    let mut event = AuditEvent {
+        seq,
+        time: now_secs() as i64,
+        kind,
+        outcome,
+        user: user.to_string(),
+        pid: std::process::id(),
+        tty: get_tty().strip_prefix("/dev/").unwrap_or_default().to_string(),
+        detail: detail.to_string(),
+        hash: String::new(),
+    };
+    event.hash = event.compute_hash(&prev_hash);
+    file.write_all(event.to_line().as_bytes())?;
+    Ok(event) // the lock goes with the file
+}
+
+/// Record an event in the system audit log. Auditing must not stop the action
+/// it records, so failures are ignored.
+pub fn record(kind: EventKind, outcome: Outcome, user: &str, detail: &str) {
+    let _ = append_to(Path::new(DEFAULT_AUDIT_LOG_PATH), kind, outcome, user, detail);
+}
+
+#[derive(Debug)]
+pub struct ChainBreak {
+    /// 1-based line of the first record that doesn't fit
+    pub line: usize,
+    pub reason: String,
+}
+
+/// The records of the log up to the first place where the chain is broken,
+/// and where that is.
+pub fn read_verified(path: &Path) -> io::Result<(Vec<AuditEvent>, Option<ChainBreak>)> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut events = Vec::new();
+    let mut prev_hash = GENESIS_HASH.to_string();
+
+    for (index, line) in reader.lines().enumerate() {
+        let line = line?;
+        let broken = |reason: String| Some(ChainBreak { line: index + 1, reason });
+
+        let event = match AuditEvent::parse(&line) {
+            Some(event) => event,
+            None => return Ok((events, broken("unreadable record".into()))),
+        };
+        let expected_seq = events.len() as u64 + 1;
+        if event.seq != expected_seq {
+            return Ok((events, broken(format!("sequence {} where {} was expected", event.seq, expected_seq))));
+        }
+        if event.compute_hash(&prev_hash) != event.hash {
+            return Ok((events, broken(format!("hash of record {} does not match", event.seq))));
+        }
+        prev_hash = event.hash.clone();
+        events.push(event);
+    }
+    Ok((events, None))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::PathBuf;
+
+    fn log_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("vantara-audit-{}-{}", name, std::process::id()));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    fn write_three(path: &Path) -> Vec<String> {
+        append_to(path, EventKind::Login, Outcome::Success, "alice", "tty1").unwrap();
+        append_to(path, EventKind::Sudo, Outcome::Failure, "bob", "/bin/ls").unwrap();
+        append_to(path, EventKind::Logout, Outcome::Success, "alice", "tty1").unwrap();
+        fs::read_to_string(path).unwrap().lines().map(String::from).collect()
+    }
+
+    fn break_in(lines: &[String], path: &Path) -> ChainBreak {
+        fs::write(path, lines.join("\n") + "\n").unwrap();
+        read_verified(path).unwrap().1.expect("chain should be broken")
+    }
+
+    #[test]
+    fn round_trip() {
+        let path = log_path("round-trip");
+        write_three(&path);
+        let (events, broken) = read_verified(&path).unwrap();
+        assert!(broken.is_none());
+        assert_eq!(events.len(), 3);
+        assert_eq!(events.iter().map(|e| e.seq).collect::<Vec<_>>(), [1, 2, 3]);
+        assert_eq!(events[1].kind, EventKind::Sudo);
+        assert_eq!(events[1].outcome, Outcome::Failure);
+        assert_eq!(events[1].user, "bob");
+        assert_eq!(events[1].detail, "/bin/ls");
+        assert_eq!(events[1].hash, events[1].compute_hash(&events[0].hash));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn edited_detail_breaks_the_chain() {
+        let path = log_path("edited");
+        let mut lines = write_three(&path);
+        lines[1] = lines[1].replace("/bin/ls", "/bin/id");
+        let broken = break_in(&lines, &path);
+        assert_eq!(broken.line, 2);
+        assert_eq!(broken.reason, "hash of record 2 does not match");
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn removed_and_reordered_records() {
+        let path = log_path("removed");
+        let lines = write_three(&path);
+
+        let broken = break_in(&[lines[0].clone(), lines[2].clone()], &path);
+        assert_eq!(broken.line, 2);
+        assert_eq!(broken.reason, "sequence 3 where 2 was expected");
+
+        let broken = break_in(&[lines[1].clone(), lines[0].clone(), lines[2].clone()], &path);
+        assert_eq!(broken.line, 1);
+        assert_eq!(broken.reason, "sequence 2 where 1 was expected");
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn tabs_and_newlines_in_fields() {
+        assert_eq!(unescape(&escape("a\tb\nc\\d")), "a\tb\nc\\d");
+        assert_eq!(escape("a\tb\nc"), "a\\tb\\nc");
+
+        let path = log_path("escape");
+        append_to(&path, EventKind::Service, Outcome::Success, "root", "start\tweb\nnow").unwrap();
+        let content = fs::read_to_string(&path).unwrap();
+        assert_eq!(content.lines().count(), 1);
+        let (events, broken) = read_verified(&path).unwrap();
+        assert!(broken.is_none());
+        assert_eq!(events[0].detail, "start\tweb\nnow");
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn appends_after_a_damaged_record() {
+        let path = log_path("damaged");
+        let mut lines = write_three(&path);
+        lines.push("4\tgarbage".to_string());
+        fs::write(&path, lines.join("\n") + "\n").unwrap();
+
+        let event = append_to(&path, EventKind::Auth, Outcome::Failure, "mallory", "").unwrap();
+        assert_eq!(event.seq, 5);
+        assert_eq!(event.hash, event.compute_hash(&hex::encode(Sha256::digest(b"4\tgarbage"))));
+
+        let (events, broken) = read_verified(&path).unwrap();
+        assert_eq!(events.len(), 3);
+        let broken = broken.unwrap();
+        assert_eq!(broken.line, 4);
+        assert_eq!(broken.reason, "unreadable record");
+        fs::remove_file(&path).unwrap();
+    }
+}
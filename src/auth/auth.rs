@@ -6,6 +6,28 @@ pub struct AuthContext {
     pub metadata: HashMap<String, String>, // untuk info tambahan
 }
 
+/// Metadata keys under this prefix are environment variables for the session.
+const ENV_PREFIX: &str = "env:";
+
+impl AuthContext {
+    /// Like pam_putenv: session modules hand variables to the program that
+    /// starts the session.
+    pub fn set_env(&mut self, key: &str, value: &str) {
+        self.metadata.insert(format!("{}{}", ENV_PREFIX, key), value.to_string());
+    }
+
+    /// Variables set with `set_env`, sorted by name.
+    pub fn session_env(&self) -> Vec<(String, String)> {
+        let mut env: Vec<(String, String)> = self
+            .metadata
+            .iter()
+            .filter_map(|(k, v)| k.strip_prefix(ENV_PREFIX).map(|k| (k.to_string(), v.clone())))
+            .collect();
+        env.sort();
+        env
+    }
+}
+
 #[derive(Debug)]
 pub enum AuthResult {
     Success,
@@ -18,43 +40,11 @@ pub trait AuthModule {
     /// Fasa account: check expiry, status akaun, dsb.
     fn account(&self, ctx: &mut AuthContext) -> AuthResult;
     /// Fasa session: setup environment, log session, dsb.
-    fn session(&self, ctx: &mut AuthContext) -> AuthResult;
-}
-
-/// Fungsi utama untuk jalankan login flow:
-pub fn run_login(modules: &[Box<dyn AuthModule>], ctx: &mut AuthContext) -> bool {
-    // Fasa authentication
-    for module in modules {
-        match module.auth(ctx) {
-            AuthResult::Success => continue,
-            AuthResult::Failure(reason) => {
-                println!("Auth failed: {}", reason);
-                return false;
-            }
-        }
-    }
-
-    // Fasa account checking
-    for module in modules {
-        match module.account(ctx) {
-            AuthResult::Success => continue,
-            AuthResult::Failure(reason) => {
-                println!("Account check failed: {}", reason);
-                return false;
-            }
-        }
+    fn open_session(&self, ctx: &mut AuthContext) -> AuthResult;
+    /// Tamat session: undo apa yang open_session buat, dipanggil dalam susunan terbalik
+    fn close_session(&self, _ctx: &mut AuthContext) -> AuthResult {
+        AuthResult::Success
     }
-
-    // Fasa session setup
-    for module in modules {
-        match module.session(ctx) {
-            AuthResult::Success => continue,
-            AuthResult::Failure(reason) => {
-                println!("Session setup failed: {}", reason);
-                return false;
-            }
-        }
-    }
-
-    true
+    /// Dipanggil pada semua module bila fasa auth gagal, cth. untuk kira cubaan gagal
+    fn auth_failed(&self, _ctx: &mut AuthContext) {}
 }
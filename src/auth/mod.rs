@@ -1,4 +1,7 @@
 pub mod auth;
 pub mod modules;
+pub mod stack;
+pub mod sudoers;
+pub mod privilege;
 
 pub use auth::*;
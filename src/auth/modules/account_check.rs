@@ -0,0 +1,236 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::Path;
+use crate::auth::modules::account_db::{is_valid_name, AccountDb, DbEntry, Record, Table};
+use crate::auth::modules::shadow::today_days;
+
+/// One problem found by pwck or grpck.
+#[derive(Debug)]
+pub struct Finding {
+    pub message: String,
+    /// Whether `--fix` knows what to do about it
+    pub fixable: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct Findings {
+    pub items: Vec<Finding>,
+}
+
+impl Findings {
+    fn fixable(&mut self, message: String) {
+        self.items.push(Finding { message, fixable: true });
+    }
+
+    fn manual(&mut self, message: String) {
+        self.items.push(Finding { message, fixable: false });
+    }
+}
+
+/// Blank lines and lines with the wrong number of fields. Table drops blank
+/// lines when it reads a file, so they are counted on the raw content.
+fn check_lines<T: DbEntry>(table: &mut Table<T>, fields: usize, fix: bool, found: &mut Findings) {
+    let content = fs::read_to_string(&table.path).unwrap_or_default();
+    let file = table.path.display().to_string();
+
+    let before = found.items.len();
+    let blank = content.lines().filter(|l| l.trim().is_empty()).count();
+    if blank > 0 {
+        found.fixable(format!("{}: {} blank lines", file, blank));
+    }
+    for (lineno, line) in content.lines().enumerate().filter(|(_, l)| !l.trim().is_empty()) {
+        let count = line.split(':').count();
+        if count != fields {
+            found.fixable(format!("{}:{}: invalid entry, {} fields instead of {}: '{}'", file, lineno + 1, count, fields, line));
+        }
+    }
+
+    // Lines that don't parse are dropped, the others are written back with
+    // the right number of fields
+    if fix && found.items.len() != before {
+        table.records.retain(|r| matches!(r, Record::Entry(_)));
+        table.touch();
+    }
+}
+
+/// Names that appear more than once. With `fix`, later entries go and `merge`
+/// folds each of them into the first.
+fn check_duplicates<T: DbEntry>(table: &mut Table<T>, what: &str, fix: bool, found: &mut Findings, merge: fn(&mut T, T)) {
+    let mut seen = HashSet::new();
+    let duplicates: Vec<String> = table
+        .entries()
+        .filter(|e| !seen.insert(e.name().to_string()))
+        .map(|e| e.name().to_string())
+        .collect();
+    for name in &duplicates {
+        found.fixable(format!("{}: duplicate {} '{}'", table.path.display(), what, name));
+    }
+    if !fix || duplicates.is_empty() {
+        return;
+    }
+
+    let mut kept: Vec<Record<T>> = Vec::new();
+    for record in table.records.drain(..) {
+        match record {
+            Record::Entry(entry) => match kept.iter_mut().find_map(|r| match r {
+                Record::Entry(first) if first.name() == entry.name() => Some(first),
+                _ => None,
+            }) {
+                Some(first) => merge(first, entry),
+                None => kept.push(Record::Entry(entry)),
+            },
+            raw => kept.push(raw),
+        }
+    }
+    table.records = kept;
+    table.touch();
+}
+
+/// Mode of an account file: anything in `allowed`, owned by root. With `fix`
+/// the mode becomes `allowed[0]`.
+fn check_mode(path: &Path, allowed: &[u32], fix: bool, found: &mut Findings) {
+    let meta = match fs::metadata(path) {
+        Ok(meta) => meta,
+        Err(e) => return found.manual(format!("{}: {}", path.display(), e)),
+    };
+    let mode = meta.mode() & 0o7777;
+    if !allowed.contains(&mode) {
+        let expected: Vec<String> = allowed.iter().map(|m| format!("{:04o}", m)).collect();
+        found.fixable(format!("{}: mode {:04o}, should be {}", path.display(), mode, expected.join(" or ")));
+        if fix {
+            let _ = fs::set_permissions(path, fs::Permissions::from_mode(allowed[0]));
+        }
+    }
+    if meta.uid() != 0 {
+        found.manual(format!("{}: owned by uid {} instead of root", path.display(), meta.uid()));
+    }
+}
+
+fn ids_used_twice<'a>(ids: impl Iterator<Item = (u32, &'a str)>) -> Vec<(u32, Vec<&'a str>)> {
+    let mut by_id: HashMap<u32, Vec<&str>> = HashMap::new();
+    for (id, name) in ids {
+        let names = by_id.entry(id).or_default();
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    let mut twice: Vec<(u32, Vec<&str>)> = by_id.into_iter().filter(|(_, names)| names.len() > 1).collect();
+    twice.sort();
+    twice
+}
+
+/// passwd and shadow: what pwck reports.
+pub fn check_users(db: &mut AccountDb, fix: bool) -> Findings {
+    let mut found = Findings::default();
+
+    check_mode(&db.passwd.path.clone(), &[0o644, 0o444], fix, &mut found);
+    check_mode(&db.shadow.path.clone(), &[0o600, 0o640], fix, &mut found);
+    check_lines(&mut db.passwd, 7, fix, &mut found);
+    check_lines(&mut db.shadow, 9, fix, &mut found);
+    check_duplicates(&mut db.passwd, "user", fix, &mut found, |_, _| {});
+    check_duplicates(&mut db.shadow, "shadow entry", fix, &mut found, |_, _| {});
+
+    for (uid, names) in ids_used_twice(db.passwd.entries().map(|u| (u.uid, u.username.as_str()))) {
+        found.manual(format!("uid {} is shared by {}", uid, names.join(", ")));
+    }
+
+    let mut missing_shadow = Vec::new();
+    for user in db.passwd.entries() {
+        let name = &user.username;
+        if !is_valid_name(name) {
+            found.manual(format!("user '{}': invalid user name", name));
+        }
+        if db.group.entries().all(|g| g.gid != user.gid) {
+            found.manual(format!("user '{}': no group {}", name, user.gid));
+        }
+        if !db.path(&user.home).is_dir() {
+            found.manual(format!("user '{}': directory '{}' does not exist", name, user.home));
+        }
+        if !user.shell.is_empty() {
+            let shell = fs::metadata(db.path(&user.shell));
+            if !shell.is_ok_and(|m| m.is_file() && m.mode() & 0o111 != 0) {
+                found.manual(format!("user '{}': program '{}' does not exist", name, user.shell));
+            }
+        }
+        if user.password == "x" && db.shadow.find(name).is_none() {
+            found.fixable(format!("user '{}': no entry in {}", name, db.shadow.path.display()));
+            missing_shadow.push(name.clone());
+        }
+    }
+
+    let today = today_days();
+    let mut orphans = Vec::new();
+    for entry in db.shadow.entries() {
+        if db.passwd.find(&entry.username).is_none() {
+            found.fixable(format!("{}: '{}' has no entry in {}", db.shadow.path.display(), entry.username, db.passwd.path.display()));
+            orphans.push(entry.username.clone());
+        }
+        if entry.last_change.is_some_and(|day| day > today) {
+            found.manual(format!("user '{}': last password change in the future", entry.username));
+        }
+    }
+
+    if fix {
+        // A locked entry, the password has to be set before the account can log in
+        for name in missing_shadow {
+            let entry = db.new_shadow_entry(&name, "!");
+            db.shadow.insert(entry);
+        }
+        for name in orphans {
+            db.shadow.remove(&name);
+        }
+    }
+    found
+}
+
+/// group: what grpck reports.
+pub fn check_groups(db: &mut AccountDb, fix: bool) -> Findings {
+    let mut found = Findings::default();
+
+    check_mode(&db.group.path.clone(), &[0o644, 0o444], fix, &mut found);
+    check_lines(&mut db.group, 4, fix, &mut found);
+    // Members of a duplicate are kept in the first group of that name
+    check_duplicates(&mut db.group, "group", fix, &mut found, |first, dup| {
+        for member in dup.members {
+            if !first.members.contains(&member) {
+                first.members.push(member);
+            }
+        }
+    });
+
+    for (gid, names) in ids_used_twice(db.group.entries().map(|g| (g.gid, g.groupname.as_str()))) {
+        found.manual(format!("gid {} is shared by {}", gid, names.join(", ")));
+    }
+
+    let users: HashSet<String> = db.passwd.entries().map(|u| u.username.clone()).collect();
+    let mut changed = false;
+    for group in db.group.records.iter_mut().filter_map(|r| match r {
+        Record::Entry(g) => Some(g),
+        Record::Raw(_) => None,
+    }) {
+        if !is_valid_name(&group.groupname) {
+            found.manual(format!("group '{}': invalid group name", group.groupname));
+        }
+
+        let mut seen = HashSet::new();
+        let mut keep = Vec::new();
+        for member in &group.members {
+            if !users.contains(member) {
+                found.fixable(format!("group '{}': no user '{}'", group.groupname, member));
+            } else if !seen.insert(member.clone()) {
+                found.fixable(format!("group '{}': '{}' listed twice", group.groupname, member));
+            } else {
+                keep.push(member.clone());
+            }
+        }
+        if fix && keep.len() != group.members.len() {
+            group.members = keep;
+            changed = true;
+        }
+    }
+    if changed {
+        db.group.touch();
+    }
+    found
+}
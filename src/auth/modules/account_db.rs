@@ -0,0 +1,499 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{ErrorKind, Read, Write};
+use std::os::unix::io::AsRawFd;
+use std::os::unix::fs::{MetadataExt, OpenOptionsExt};
+use std::path::{Path, PathBuf};
+use std::thread::sleep;
+use std::time::Duration;
+use crate::auth::modules::group::{GroupEntry, DEFAULT_GROUP_FILE};
+use crate::auth::modules::login_defs::{LoginDefs, DEFAULT_LOGIN_DEFS_PATH};
+use crate::auth::modules::passwd::{PasswdEntry, DEFAULT_PASSWD_FILE};
+use crate::auth::modules::shadow::{today_days, ShadowEntry, DEFAULT_SHADOW_FILE};
+use crate::nss;
+
+/// How long to wait for another tool to release a lock.
+const LOCK_ATTEMPTS: u32 = 50;
+const LOCK_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+const MAX_NAME_LEN: usize = 32;
+pub const DEFAULT_SHELL: &str = "/bin/shell";
+
+/// `<file>.lock`, created exclusively and holding our pid. A lock left behind by a
+/// process that no longer exists is taken over.
+pub struct Lock {
+    path: PathBuf,
+}
+
+impl Lock {
+    pub fn acquire(target: &Path) -> Result<Self, String> {
+        let path = PathBuf::from(format!("{}.lock", target.display()));
+
+        for _ in 0..LOCK_ATTEMPTS {
+            match OpenOptions::new().write(true).create_new(true).mode(0o600).open(&path) {
+                Ok(mut file) => {
+                    let _ = write!(file, "{}", std::process::id());
+                    return Ok(Lock { path });
+                }
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                    if !Self::remove_if_stale(&path) {
+                        sleep(LOCK_RETRY_DELAY);
+                    }
+                }
+                Err(e) => return Err(format!("cannot lock {}: {}", target.display(), e)),
+            }
+        }
+
+        Err(format!("{} is locked by another process, try again later", target.display()))
+    }
+
+    /// Remove the lock at `path` when its owner is gone. Takers of the same stale lock
+    /// are serialised with flock, and the file is only unlinked while `path` still
+    /// names the file whose pid was just read, never a lock created since.
+    fn remove_if_stale(path: &Path) -> bool {
+        let mut file = match File::open(path) {
+            Ok(file) => file,
+            // Released in the meantime
+            Err(_) => return true,
+        };
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } != 0 {
+            return false;
+        }
+
+        let same_file = match (file.metadata(), fs::metadata(path)) {
+            (Ok(ours), Ok(current)) => ours.dev() == current.dev() && ours.ino() == current.ino(),
+            // Another taker already removed it
+            _ => return true,
+        };
+        if !same_file {
+            return true;
+        }
+
+        let mut content = String::new();
+        let pid: i32 = match file.read_to_string(&mut content).ok().and_then(|_| content.trim().parse().ok()) {
+            Some(pid) => pid,
+            // Being written right now, or garbage
+            None => return false,
+        };
+        let alive = unsafe { libc::kill(pid, 0) } == 0
+            || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM);
+        !alive && fs::remove_file(path).is_ok()
+    }
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// A line of one of the account files.
+pub trait DbEntry: Sized {
+    fn parse(line: &str) -> Option<Self>;
+    fn to_line(&self) -> String;
+    fn name(&self) -> &str;
+}
+
+impl DbEntry for PasswdEntry {
+    fn parse(line: &str) -> Option<Self> { PasswdEntry::parse(line) }
+    fn to_line(&self) -> String { PasswdEntry::to_line(self) }
+    fn name(&self) -> &str { &self.username }
+}
+
+impl DbEntry for ShadowEntry {
+    fn parse(line: &str) -> Option<Self> { ShadowEntry::parse(line) }
+    fn to_line(&self) -> String { ShadowEntry::to_line(self) }
+    fn name(&self) -> &str { &self.username }
+}
+
+impl DbEntry for GroupEntry {
+    fn parse(line: &str) -> Option<Self> { GroupEntry::parse(line) }
+    fn to_line(&self) -> String { GroupEntry::to_line(self) }
+    fn name(&self) -> &str { &self.groupname }
+}
+
+pub enum Record<T> {
+    Entry(T),
+    /// A line that doesn't parse, written back untouched (pwck/grpck report them)
+    Raw(String),
+}
+
+/// One locked account file, read into memory.
+pub struct Table<T: DbEntry> {
+    pub path: PathBuf,
+    pub records: Vec<Record<T>>,
+    mode: u32,
+    dirty: bool,
+    _lock: Lock,
+}
+
+impl<T: DbEntry> Table<T> {
+    pub fn open(path: PathBuf, mode: u32) -> Result<Self, String> {
+        let lock = Lock::acquire(&path)?;
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(format!("cannot read {}: {}", path.display(), e)),
+        };
+
+        let records = content
+            .lines()
+            // Older Vantara versions left empty lines behind
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| match T::parse(line) {
+                Some(entry) => Record::Entry(entry),
+                None => Record::Raw(line.to_string()),
+            })
+            .collect();
+
+        Ok(Table { path, records, mode, dirty: false, _lock: lock })
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = &T> {
+        self.records.iter().filter_map(|r| match r {
+            Record::Entry(e) => Some(e),
+            Record::Raw(_) => None,
+        })
+    }
+
+    pub fn find(&self, name: &str) -> Option<&T> {
+        self.entries().find(|e| e.name() == name)
+    }
+
+    pub fn find_mut(&mut self, name: &str) -> Option<&mut T> {
+        let dirty = &mut self.dirty;
+        let found = self.records.iter_mut().find_map(|r| match r {
+            Record::Entry(e) if e.name() == name => Some(e),
+            _ => None,
+        });
+        if found.is_some() {
+            *dirty = true;
+        }
+        found
+    }
+
+    pub fn entries_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.dirty = true;
+        self.records.iter_mut().filter_map(|r| match r {
+            Record::Entry(e) => Some(e),
+            Record::Raw(_) => None,
+        })
+    }
+
+    pub fn insert(&mut self, entry: T) {
+        self.dirty = true;
+        self.records.push(Record::Entry(entry));
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<T> {
+        let pos = self.records.iter().position(|r| matches!(r, Record::Entry(e) if e.name() == name))?;
+        self.dirty = true;
+        match self.records.remove(pos) {
+            Record::Entry(e) => Some(e),
+            Record::Raw(_) => None,
+        }
+    }
+
+    /// Mark the table changed when records were edited directly.
+    pub fn touch(&mut self) {
+        self.dirty = true;
+    }
+
+    /// Keep the old file as `<file>-`, write `<file>+` and rename it over the original.
+    pub fn write(&self) -> Result<(), String> {
+        if !self.dirty {
+            return Ok(());
+        }
+
+        let mut content = String::new();
+        for record in &self.records {
+            match record {
+                Record::Entry(e) => content.push_str(&e.to_line()),
+                Record::Raw(line) => content.push_str(line),
+            }
+            content.push('\n');
+        }
+
+        let display = self.path.display();
+        let tmp = PathBuf::from(format!("{}+", display));
+        let backup = PathBuf::from(format!("{}-", display));
+        let owner = fs::metadata(&self.path).map(|m| (m.uid(), m.gid())).unwrap_or((0, 0));
+
+        // A leftover from an interrupted run may have the wrong mode
+        let _ = fs::remove_file(&tmp);
+        let result = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(self.mode)
+            .open(&tmp)
+            .and_then(|mut f| {
+                f.write_all(content.as_bytes())?;
+                f.sync_all()
+            })
+            .and_then(|_| std::os::unix::fs::chown(&tmp, Some(owner.0), Some(owner.1)))
+            .and_then(|_| match fs::copy(&self.path, &backup) {
+                Err(e) if e.kind() != ErrorKind::NotFound => Err(e),
+                _ => Ok(()),
+            })
+            .and_then(|_| fs::rename(&tmp, &self.path));
+
+        result.map_err(|e| {
+            let _ = fs::remove_file(&tmp);
+            format!("cannot update {}: {}", display, e)
+        })
+    }
+}
+
+/// passwd, shadow and group of a system (or of an image mounted at `root`), locked
+/// together in a fixed order so tools can't deadlock each other.
+pub struct AccountDb {
+    pub root: PathBuf,
+    pub defs: LoginDefs,
+    pub passwd: Table<PasswdEntry>,
+    pub shadow: Table<ShadowEntry>,
+    pub group: Table<GroupEntry>,
+}
+
+impl AccountDb {
+    pub fn open() -> Result<Self, String> {
+        Self::open_at(Path::new("/"))
+    }
+
+    pub fn open_at(root: &Path) -> Result<Self, String> {
+        let at = |path: &str| root.join(path.trim_start_matches('/'));
+        Ok(AccountDb {
+            root: root.to_path_buf(),
+            defs: LoginDefs::load_from(&at(DEFAULT_LOGIN_DEFS_PATH)),
+            passwd: Table::open(at(DEFAULT_PASSWD_FILE), 0o644)?,
+            shadow: Table::open(at(DEFAULT_SHADOW_FILE), 0o600)?,
+            group: Table::open(at(DEFAULT_GROUP_FILE), 0o644)?,
+        })
+    }
+
+    /// A path inside the root the database belongs to.
+    pub fn path(&self, path: &str) -> PathBuf {
+        self.root.join(path.trim_start_matches('/'))
+    }
+
+    /// Write every changed file. Locks are released when the database is dropped.
+    pub fn commit(self) -> Result<(), String> {
+        self.shadow.write()?;
+        self.group.write()?;
+        self.passwd.write()?;
+        nss::invalidate();
+        Ok(())
+    }
+
+    pub fn user(&self, name: &str) -> Option<&PasswdEntry> {
+        self.passwd.find(name)
+    }
+
+    pub fn user_by_uid(&self, uid: u32) -> Option<&PasswdEntry> {
+        self.passwd.entries().find(|u| u.uid == uid)
+    }
+
+    pub fn group(&self, name: &str) -> Option<&GroupEntry> {
+        self.group.find(name)
+    }
+
+    pub fn group_by_gid(&self, gid: u32) -> Option<&GroupEntry> {
+        self.group.entries().find(|g| g.gid == gid)
+    }
+
+    /// A group given by name or number.
+    pub fn resolve_group(&self, group: &str) -> Option<&GroupEntry> {
+        self.group(group).or_else(|| group.parse().ok().and_then(|gid| self.group_by_gid(gid)))
+    }
+
+    fn id_range(&self, prefix: &str, system: bool) -> (u32, u32) {
+        let (min_key, max_key, min, max) = if system {
+            (format!("SYS_{}_MIN", prefix), format!("SYS_{}_MAX", prefix), 100, 999)
+        } else {
+            (format!("{}_MIN", prefix), format!("{}_MAX", prefix), 1000, 60000)
+        };
+        (self.defs.get_num(&min_key, min) as u32, self.defs.get_num(&max_key, max) as u32)
+    }
+
+    /// Next uid after the highest one in use within the range, or the first gap when
+    /// the top of the range is taken.
+    pub fn next_uid(&self, system: bool) -> Result<u32, String> {
+        let used: Vec<u32> = self.passwd.entries().map(|u| u.uid).collect();
+        next_free(&used, self.id_range("UID", system)).ok_or_else(|| "no free uid left in range".to_string())
+    }
+
+    /// Like `next_uid`, trying `preferred` first so user groups can match the uid.
+    pub fn next_gid(&self, system: bool, preferred: Option<u32>) -> Result<u32, String> {
+        let used: Vec<u32> = self.group.entries().map(|g| g.gid).collect();
+        if let Some(gid) = preferred.filter(|gid| !used.contains(gid)) {
+            return Ok(gid);
+        }
+        next_free(&used, self.id_range("GID", system)).ok_or_else(|| "no free gid left in range".to_string())
+    }
+
+    /// Shadow entry for a new account with ageing from login.defs.
+    pub fn new_shadow_entry(&self, username: &str, hash: &str) -> ShadowEntry {
+        ShadowEntry {
+            username: username.to_string(),
+            password: hash.to_string(),
+            last_change: Some(today_days()),
+            min_days: Some(self.defs.get_num("PASS_MIN_DAYS", 0)),
+            max_days: Some(self.defs.get_num("PASS_MAX_DAYS", 99999)),
+            warn_days: Some(self.defs.get_num("PASS_WARN_AGE", 7)),
+            inactive_days: None,
+            expire_date: None,
+            reserved: String::new(),
+        }
+    }
+}
+
+/// What `AccountDb::add_user` needs, everything optional falls back to login.defs
+/// and the usual defaults.
+#[derive(Debug, Default)]
+pub struct NewUser {
+    pub name: String,
+    pub uid: Option<u32>,
+    /// Primary group by name or gid, `None` creates a group named after the user
+    pub group: Option<String>,
+    pub groups: Vec<String>,
+    pub comment: String,
+    pub home: Option<String>,
+    pub shell: Option<String>,
+    pub system: bool,
+    /// Shadow hash field, `None` leaves the account locked until a password is set
+    pub password_hash: Option<String>,
+}
+
+impl AccountDb {
+    pub fn add_user(&mut self, new: &NewUser) -> Result<PasswdEntry, String> {
+        if !is_valid_name(&new.name) {
+            return Err(format!("invalid user name '{}'", new.name));
+        }
+        if self.user(&new.name).is_some() {
+            return Err(format!("user '{}' already exists", new.name));
+        }
+        for field in [Some(&new.comment), new.home.as_ref(), new.shell.as_ref()].into_iter().flatten() {
+            if !is_valid_field(field) {
+                return Err(format!("invalid field '{}'", field));
+            }
+        }
+
+        let uid = match new.uid {
+            Some(uid) if self.user_by_uid(uid).is_some() => return Err(format!("uid {} is not unique", uid)),
+            Some(uid) => uid,
+            None => self.next_uid(new.system)?,
+        };
+
+        let mut supplementary = Vec::new();
+        for name in &new.groups {
+            let group = self.resolve_group(name).ok_or_else(|| format!("group '{}' does not exist", name))?;
+            supplementary.push(group.groupname.clone());
+        }
+
+        let gid = match &new.group {
+            Some(name) => self.resolve_group(name).ok_or_else(|| format!("group '{}' does not exist", name))?.gid,
+            None => {
+                if self.group(&new.name).is_some() {
+                    return Err(format!("group '{}' exists, use -g to make it the primary group", new.name));
+                }
+                let gid = self.next_gid(new.system, Some(uid))?;
+                self.group.insert(GroupEntry {
+                    groupname: new.name.clone(),
+                    password: "x".to_string(),
+                    gid,
+                    members: Vec::new(),
+                });
+                gid
+            }
+        };
+
+        for name in supplementary {
+            if let Some(group) = self.group.find_mut(&name) {
+                if !group.members.contains(&new.name) {
+                    group.members.push(new.name.clone());
+                }
+            }
+        }
+
+        let home_base = self.defs.get("HOME").unwrap_or("/home").to_string();
+        let entry = PasswdEntry {
+            username: new.name.clone(),
+            password: "x".to_string(),
+            uid,
+            gid,
+            fullname: new.comment.clone(),
+            home: new.home.clone().unwrap_or_else(|| format!("{}/{}", home_base.trim_end_matches('/'), new.name)),
+            shell: new.shell.clone().unwrap_or_else(|| DEFAULT_SHELL.to_string()),
+        };
+        self.passwd.insert(entry.clone());
+
+        let mut shadow = self.new_shadow_entry(&new.name, new.password_hash.as_deref().unwrap_or("!"));
+        if new.system {
+            // System accounts don't age
+            shadow.min_days = None;
+            shadow.max_days = None;
+            shadow.warn_days = None;
+        }
+        self.shadow.remove(&new.name);
+        self.shadow.insert(shadow);
+
+        Ok(entry)
+    }
+}
+
+fn next_free(used: &[u32], (min, max): (u32, u32)) -> Option<u32> {
+    let highest = used.iter().copied().filter(|id| (min..=max).contains(id)).max();
+    match highest {
+        Some(id) if id < max => Some(id + 1),
+        Some(_) => (min..=max).find(|id| !used.contains(id)),
+        None => Some(min),
+    }
+}
+
+/// Lowercase letters, digits, `_` and `-`, not starting with a digit or `-`, and an
+/// optional trailing `$` for machine accounts.
+pub fn is_valid_name(name: &str) -> bool {
+    let body = name.strip_suffix('$').unwrap_or(name);
+    !body.is_empty()
+        && name.len() <= MAX_NAME_LEN
+        && body.chars().next().is_some_and(|c| c.is_ascii_lowercase() || c == '_')
+        && body.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '-')
+}
+
+/// Comment fields can't hold `:` or newlines.
+pub fn is_valid_field(value: &str) -> bool {
+    !value.contains(':') && !value.contains('\n')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn target(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("vantara-lock-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir.join("passwd")
+    }
+
+    #[test]
+    fn stale_lock_is_taken_over() {
+        let target = target("stale");
+        let lock_path = PathBuf::from(format!("{}.lock", target.display()));
+        // No process has this pid
+        fs::write(&lock_path, i32::MAX.to_string()).unwrap();
+
+        let lock = Lock::acquire(&target).unwrap();
+        assert_eq!(fs::read_to_string(&lock_path).unwrap(), std::process::id().to_string());
+        drop(lock);
+        assert!(!lock_path.exists());
+    }
+
+    #[test]
+    fn live_lock_is_kept() {
+        let target = target("live");
+        let lock_path = PathBuf::from(format!("{}.lock", target.display()));
+        fs::write(&lock_path, std::process::id().to_string()).unwrap();
+
+        assert!(!Lock::remove_if_stale(&lock_path));
+        assert!(lock_path.exists());
+        let _ = fs::remove_file(&lock_path);
+    }
+}
@@ -1,4 +1,11 @@
+use std::io::{self, Write};
 use crate::auth::{AuthModule, AuthContext, AuthResult};
+use crate::auth::modules::pwquality::PasswordPolicy;
+use crate::auth::modules::shadow::{change_shadow_password, get_shadow_entry, today_days, AgeingStatus};
+use crate::common::{read_password, safe_print, safe_println};
+
+/// Attempts at choosing a new password when one is forced at login.
+const CHANGE_ATTEMPTS: usize = 3;
 
 pub struct AccountExpiry {}
 
@@ -8,20 +15,72 @@ impl AccountExpiry {
     }
 }
 
+/// Ask for a new password twice and store it, the login only continues on success.
+fn force_password_change(ctx: &mut AuthContext) -> AuthResult {
+    safe_println(format_args!("You are required to change your password immediately."));
+
+    for _ in 0..CHANGE_ATTEMPTS {
+        safe_print(format_args!("New password: "));
+        let _ = io::stdout().flush();
+        let password = read_password();
+        if password.is_empty() {
+            safe_println(format_args!("No password supplied"));
+            continue;
+        }
+        if let Err(reason) = PasswordPolicy::load().check(&password, Some(&ctx.password), &ctx.username) {
+            safe_println(format_args!("BAD PASSWORD: {}", reason));
+            continue;
+        }
+
+        safe_print(format_args!("Retype new password: "));
+        let _ = io::stdout().flush();
+        if read_password() != password {
+            safe_println(format_args!("Sorry, passwords do not match"));
+            continue;
+        }
+
+        return match change_shadow_password(&ctx.username, &password) {
+            Ok(_) => {
+                ctx.password = password;
+                safe_println(format_args!("Password changed."));
+                AuthResult::Success
+            }
+            Err(e) => AuthResult::Failure(format!("Password change failed: {}", e)),
+        };
+    }
+
+    AuthResult::Failure("Password change required but no new password set".into())
+}
+
 impl AuthModule for AccountExpiry {
     fn auth(&self, _ctx: &mut AuthContext) -> AuthResult {
         AuthResult::Success
     }
 
     fn account(&self, ctx: &mut AuthContext) -> AuthResult {
-        if ctx.username == "expired_user" {
-            AuthResult::Failure("Account expired".into())
-        } else {
-            AuthResult::Success
+        // Accounts without a shadow entry have no ageing information
+        let entry = match get_shadow_entry(&ctx.username) {
+            Some(entry) => entry,
+            None => return AuthResult::Success,
+        };
+
+        match entry.ageing_status(today_days()) {
+            AgeingStatus::Valid => AuthResult::Success,
+            AgeingStatus::ExpiresSoon(days) => {
+                safe_println(format_args!("Warning: your password will expire in {} day(s)", days));
+                AuthResult::Success
+            }
+            AgeingStatus::MustChange => force_password_change(ctx),
+            AgeingStatus::Inactive => {
+                AuthResult::Failure("Password expired and account inactive, contact your administrator".into())
+            }
+            AgeingStatus::AccountExpired => {
+                AuthResult::Failure("Account expired, contact your administrator".into())
+            }
         }
     }
 
-    fn session(&self, _ctx: &mut AuthContext) -> AuthResult {
+    fn open_session(&self, _ctx: &mut AuthContext) -> AuthResult {
         AuthResult::Success
     }
 }
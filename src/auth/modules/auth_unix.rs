@@ -1,36 +1,70 @@
 use crate::auth::{AuthModule, AuthContext, AuthResult};
-use crate::auth::modules::passwd::get_passwd_entry;
-use crate::auth::modules::shadow::{get_shadow_entry, hash_password_with_salt};
-use crate::auth::modules::session_log::get_last_login;
-use libc::{setuid, setgid};
-use crate::common::{safe_eprintln, safe_println};
+use crate::nss::getpwnam;
+use crate::auth::modules::shadow::{get_shadow_entry, update_shadow_password};
+use crate::auth::modules::crypt::{hash_password, verify_password, HashMethod, Verification};
+use crate::auth::privilege::log_auth;
+use crate::common::safe_eprintln;
 
-pub struct AuthUnix {}
+/// The one reason given for a refused password, so it doesn't tell which names exist.
+const AUTH_FAILURE: &str = "Authentication failure";
+
+pub struct AuthUnix {
+    /// Accept an empty password for accounts whose password was deleted (`passwd -d`)
+    nullok: bool,
+}
+
+/// Upgrade a legacy or outdated hash now that we know the plaintext. A failure only
+/// means the old hash stays, so it doesn't fail the login.
+fn rehash(username: &str, password: &str, verified: &str, method: &HashMethod) {
+    let result = hash_password(password, method)
+        .and_then(|hash| update_shadow_password(username, verified, &hash).map_err(|e| e.to_string()));
+    if let Err(e) = result {
+        safe_eprintln(format_args!("Failed to upgrade password hash for {}: {}", username, e));
+    }
+}
 
 impl AuthUnix {
     pub fn new() -> Self {
-        AuthUnix {}
+        AuthUnix { nullok: false }
+    }
+
+    pub fn with_args(args: &[String]) -> Self {
+        AuthUnix { nullok: args.iter().any(|a| a == "nullok") }
     }
 }
 
 impl AuthModule for AuthUnix {
     fn auth(&self, ctx: &mut AuthContext) -> AuthResult {
-        match get_passwd_entry(&ctx.username) {
-            Some(_) => match get_shadow_entry(&ctx.username) {
-                Some(shadow) => {
-                    let input_hash = hash_password_with_salt(&shadow.salt, &ctx.password);
-                    if input_hash == shadow.hash {
-                        if let Some(last) = get_last_login(&ctx.username) {
-                            safe_println(format_args!("Last login: {}", last));
-                        }
-                        AuthResult::Success
-                    } else {
-                        AuthResult::Failure("Invalid password".into())
+        let method = HashMethod::configured();
+        let shadow = getpwnam(&ctx.username).and_then(|_| get_shadow_entry(&ctx.username));
+
+        // Every refusal looks the same, whether the name exists or not
+        let verified = match shadow {
+            Some(shadow) if shadow.password.is_empty() => self.nullok && ctx.password.is_empty(),
+            Some(shadow) => match verify_password(&ctx.password, &shadow.password, &method) {
+                Verification::Match { needs_rehash } => {
+                    if needs_rehash {
+                        rehash(&ctx.username, &ctx.password, &shadow.password, &method);
                     }
+                    true
+                }
+                Verification::Mismatch => false,
+                Verification::Unsupported(reason) => {
+                    log_auth("auth_unix", &format!("cannot verify password of {}: {}", ctx.username, reason));
+                    false
                 }
-                None => AuthResult::Failure("User not found".into()),
             },
-            None => AuthResult::Failure("User not found".into()),
+            None => {
+                // Take as long as checking a real hash would
+                let _ = hash_password(&ctx.password, &method);
+                false
+            }
+        };
+
+        if verified {
+            AuthResult::Success
+        } else {
+            AuthResult::Failure(AUTH_FAILURE.into())
         }
     }
 
@@ -42,28 +76,12 @@ impl AuthModule for AuthUnix {
         }
     }
 
-    fn session(&self, ctx: &mut AuthContext) -> AuthResult {
-        match get_passwd_entry(&ctx.username) {
-            Some(user) => {
-                unsafe {
-                    if setgid(user.gid) != 0 {
-                        safe_eprintln(format_args!("Failed to setgid to {}", user.gid));
-                    }
-                    if setuid(user.uid) != 0 {
-                        safe_eprintln(format_args!("Failed to setuid to {}", user.uid));
-                    }
-                }
-
-                std::env::set_var("HOME", &user.home);
-                std::env::set_var("USER", &user.username);
-                std::env::set_var("SHELL", &user.shell);
-
-                std::env::set_current_dir(&user.home).unwrap_or_else(|_| {
-                    safe_eprintln(format_args!("Failed to set home dir to {}", &user.home));
-                });
-
-                AuthResult::Success
-            },
+    /// Only checks the account is still there. login builds the environment of the
+    /// shell (see `login_env`) and switches uid/gid in the child that runs it, so it
+    /// stays privileged to close the session afterwards.
+    fn open_session(&self, ctx: &mut AuthContext) -> AuthResult {
+        match getpwnam(&ctx.username) {
+            Some(_) => AuthResult::Success,
             None => AuthResult::Failure("User not found".into()),
         }
     }
@@ -0,0 +1,287 @@
+use argon2::{Argon2, PasswordHash, PasswordHasher, PasswordVerifier};
+use argon2::password_hash::SaltString;
+use rand::Rng;
+use sha2::{Digest, Sha512};
+use crate::auth::modules::login_defs::LoginDefs;
+use crate::auth::modules::shadow::generate_salt;
+
+/// Rounds used for new `$6$` hashes unless login.defs says otherwise.
+pub const DEFAULT_SHA512_ROUNDS: u32 = 100_000;
+const SHA512_ROUNDS_DEFAULT: u32 = 5000; // implied when a hash has no rounds=
+const SHA512_ROUNDS_MIN: u32 = 1000;
+const SHA512_ROUNDS_MAX: u32 = 999_999_999;
+const SHA512_SALT_MAX: usize = 16;
+
+const CRYPT_ALPHABET: &[u8; 64] = b"./0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum HashMethod {
+    /// `$6$`, SHA-512-crypt as in glibc/libxcrypt
+    Sha512 { rounds: u32 },
+    /// `$argon2id$`, memory-hard
+    Argon2id,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Verification {
+    Match { needs_rehash: bool },
+    Mismatch,
+    Unsupported(String),
+}
+
+impl HashMethod {
+    /// `ENCRYPT_METHOD` (SHA512 or ARGON2ID) and `SHA_CRYPT_ROUNDS` from /etc/login.defs.
+    pub fn configured() -> Self {
+        let defs = LoginDefs::load();
+        match defs.get("ENCRYPT_METHOD").map(|m| m.to_ascii_uppercase()).as_deref() {
+            Some("ARGON2ID") => HashMethod::Argon2id,
+            _ => {
+                let rounds = defs.get_num("SHA_CRYPT_ROUNDS", DEFAULT_SHA512_ROUNDS as i64);
+                HashMethod::Sha512 { rounds: rounds.clamp(SHA512_ROUNDS_MIN as i64, SHA512_ROUNDS_MAX as i64) as u32 }
+            }
+        }
+    }
+}
+
+pub fn hash_password(password: &str, method: &HashMethod) -> Result<String, String> {
+    match method {
+        HashMethod::Sha512 { rounds } => {
+            Ok(sha512_crypt(password.as_bytes(), &generate_salt(SHA512_SALT_MAX), Some(*rounds)))
+        }
+        HashMethod::Argon2id => {
+            let salt = SaltString::encode_b64(&rand::rng().random::<[u8; 16]>()).map_err(|e| e.to_string())?;
+            Argon2::default()
+                .hash_password(password.as_bytes(), &salt)
+                .map(|hash| hash.to_string())
+                .map_err(|e| format!("argon2 failed: {}", e))
+        }
+    }
+}
+
+/// Check `password` against the hash field of a shadow entry. `needs_rehash` is set
+/// when the stored hash is legacy or weaker than `preferred`.
+pub fn verify_password(password: &str, stored: &str, preferred: &HashMethod) -> Verification {
+    // "!" and "*" prefixes mark locked or password-less accounts
+    if stored.is_empty() || stored.starts_with('!') || stored.starts_with('*') {
+        return Verification::Mismatch;
+    }
+
+    if let Some(legacy_hex) = legacy_hash(stored) {
+        let salt = stored.split('$').nth(2).unwrap_or("");
+        let computed = legacy_hash_with_salt(salt, password);
+        return if constant_time_eq(computed.as_bytes(), legacy_hex.as_bytes()) {
+            Verification::Match { needs_rehash: true }
+        } else {
+            Verification::Mismatch
+        };
+    }
+
+    if stored.starts_with("$6$") {
+        let (salt, rounds) = match parse_sha512_setting(stored) {
+            Some(setting) => setting,
+            None => return Verification::Unsupported("malformed $6$ hash".into()),
+        };
+        let computed = sha512_crypt(password.as_bytes(), salt, rounds);
+        if !constant_time_eq(computed.as_bytes(), stored.as_bytes()) {
+            return Verification::Mismatch;
+        }
+        let needs_rehash = match preferred {
+            HashMethod::Sha512 { rounds: wanted } => rounds.unwrap_or(SHA512_ROUNDS_DEFAULT) < *wanted,
+            HashMethod::Argon2id => true,
+        };
+        return Verification::Match { needs_rehash };
+    }
+
+    if stored.starts_with("$argon2id$") {
+        let parsed = match PasswordHash::new(stored) {
+            Ok(p) => p,
+            Err(e) => return Verification::Unsupported(format!("malformed argon2id hash: {}", e)),
+        };
+        return match Argon2::default().verify_password(password.as_bytes(), &parsed) {
+            Ok(_) => Verification::Match { needs_rehash: *preferred != HashMethod::Argon2id },
+            Err(_) => Verification::Mismatch,
+        };
+    }
+
+    let id = stored.split('$').nth(1).unwrap_or("");
+    Verification::Unsupported(format!("unsupported hash algorithm '${}$'", id))
+}
+
+/// Returns the hex digest when `stored` is the old `$6$salt$<sha512 hex>` format that
+/// Vantara wrote before real SHA-512-crypt. Real `$6$` hashes are 86 crypt-base64
+/// characters, the old ones 128 lowercase hex digits.
+fn legacy_hash(stored: &str) -> Option<&str> {
+    let parts: Vec<&str> = stored.split('$').collect();
+    if parts.len() == 4 && parts[1] == "6" && !parts[2].starts_with("rounds=") {
+        let hash = parts[3];
+        if hash.len() == 128 && hash.bytes().all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b)) {
+            return Some(hash);
+        }
+    }
+    None
+}
+
+fn legacy_hash_with_salt(salt: &str, password: &str) -> String {
+    let mut hasher = Sha512::new();
+    hasher.update(salt.as_bytes());
+    hasher.update(password.as_bytes());
+    hex::encode(hasher.finalize())
+}
+
+/// (salt, rounds) from `$6$[rounds=N$]salt[$hash]`
+fn parse_sha512_setting(setting: &str) -> Option<(&str, Option<u32>)> {
+    let rest = setting.strip_prefix("$6$")?;
+    let (rounds, rest) = match rest.strip_prefix("rounds=") {
+        Some(r) => {
+            let (n, rest) = r.split_once('$')?;
+            (Some(n.parse().ok()?), rest)
+        }
+        None => (None, rest),
+    };
+    let salt = rest.split('$').next()?;
+    Some((salt, rounds))
+}
+
+/// SHA-512-crypt as specified by Ulrich Drepper, compatible with `crypt(3)` `$6$`.
+/// `rounds` of `None` means the implicit default and is left out of the output.
+pub fn sha512_crypt(password: &[u8], salt: &str, rounds: Option<u32>) -> String {
+    let salt = &salt.as_bytes()[..salt.len().min(SHA512_SALT_MAX)];
+    let round_count = rounds.unwrap_or(SHA512_ROUNDS_DEFAULT).clamp(SHA512_ROUNDS_MIN, SHA512_ROUNDS_MAX);
+
+    let digest_b = Sha512::new().chain_update(password).chain_update(salt).chain_update(password).finalize();
+
+    let mut ctx = Sha512::new().chain_update(password).chain_update(salt);
+    let mut remaining = password.len();
+    while remaining > 64 {
+        ctx.update(digest_b);
+        remaining -= 64;
+    }
+    ctx.update(&digest_b[..remaining]);
+
+    let mut bits = password.len();
+    while bits > 0 {
+        if bits & 1 != 0 {
+            ctx.update(digest_b);
+        } else {
+            ctx.update(password);
+        }
+        bits >>= 1;
+    }
+    let mut digest_a = ctx.finalize();
+
+    let mut dp = Sha512::new();
+    for _ in 0..password.len() {
+        dp.update(password);
+    }
+    let p_bytes = repeat_to_len(&dp.finalize(), password.len());
+
+    let mut ds = Sha512::new();
+    for _ in 0..(16 + digest_a[0] as usize) {
+        ds.update(salt);
+    }
+    let s_bytes = repeat_to_len(&ds.finalize(), salt.len());
+
+    for i in 0..round_count {
+        let mut c = Sha512::new();
+        if i & 1 != 0 { c.update(&p_bytes) } else { c.update(digest_a) }
+        if i % 3 != 0 { c.update(&s_bytes) }
+        if i % 7 != 0 { c.update(&p_bytes) }
+        if i & 1 != 0 { c.update(digest_a) } else { c.update(&p_bytes) }
+        digest_a = c.finalize();
+    }
+
+    const ORDER: [(usize, usize, usize); 21] = [
+        (0, 21, 42), (22, 43, 1), (44, 2, 23), (3, 24, 45), (25, 46, 4), (47, 5, 26), (6, 27, 48),
+        (28, 49, 7), (50, 8, 29), (9, 30, 51), (31, 52, 10), (53, 11, 32), (12, 33, 54), (34, 55, 13),
+        (56, 14, 35), (15, 36, 57), (37, 58, 16), (59, 17, 38), (18, 39, 60), (40, 61, 19), (62, 20, 41),
+    ];
+    let mut encoded = String::with_capacity(86);
+    for (a, b, c) in ORDER {
+        encode_24bit(&mut encoded, digest_a[a], digest_a[b], digest_a[c], 4);
+    }
+    encode_24bit(&mut encoded, 0, 0, digest_a[63], 2);
+
+    let salt = String::from_utf8_lossy(salt);
+    match rounds {
+        Some(_) => format!("$6$rounds={}${}${}", round_count, salt, encoded),
+        None => format!("$6${}${}", salt, encoded),
+    }
+}
+
+fn repeat_to_len(digest: &[u8], len: usize) -> Vec<u8> {
+    digest.iter().cycle().take(len).copied().collect()
+}
+
+fn encode_24bit(out: &mut String, b2: u8, b1: u8, b0: u8, chars: usize) {
+    let mut w = ((b2 as u32) << 16) | ((b1 as u32) << 8) | b0 as u32;
+    for _ in 0..chars {
+        out.push(CRYPT_ALPHABET[(w & 0x3f) as usize] as char);
+        w >>= 6;
+    }
+}
+
+/// Compare without an early exit, so timing doesn't leak how much of a hash matched.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test vectors from Drepper's SHA-crypt specification
+    #[test]
+    fn sha512_crypt_known_answers() {
+        assert_eq!(
+            sha512_crypt(b"Hello world!", "saltstring", None),
+            "$6$saltstring$svn8UoSVapNtMuq1ukKS4tPQd8iKwSMHWjl/O817G3uBnIFNjnQJuesI68u4OTLiBFdcbYEdFCoEOfaS35inz1"
+        );
+        // Salt is cut to 16 characters
+        assert_eq!(
+            sha512_crypt(b"Hello world!", "saltstringsaltstring", Some(10000)),
+            "$6$rounds=10000$saltstringsaltst$OW1/O6BYHV6BcXZu8QVeXbDWra3Oeqh0sbHbbMCVNSnCM/UrjmM0Dp8vOuZeHBy/YTBmSK6H9qs/y3RnOaw5v."
+        );
+        assert_eq!(
+            sha512_crypt(b"This is just a test", "toolongsaltstring", Some(5000)),
+            "$6$rounds=5000$toolongsaltstrin$lQ8jolhgVRVhY4b5pZKaysCLi0QBxGoNeKQzQ3glMhwllF7oGDZxUhx1yxdYcz/e1JSbq3y6JMxxl8audkUEm0"
+        );
+        // Too few rounds are raised to the minimum
+        assert_eq!(
+            sha512_crypt(b"the minimum number is still observed", "roundstoolow", Some(10)),
+            "$6$rounds=1000$roundstoolow$kUMsbe306n21p9R.FRkW3IGn.S9NPN0x50YhH1xhLsPuWGsUSklZt58jaTfF4ZEQpyUNGc0dqbpBYYBaHHrsX."
+        );
+    }
+
+    #[test]
+    fn verify_sha512() {
+        let stored = "$6$saltstring$svn8UoSVapNtMuq1ukKS4tPQd8iKwSMHWjl/O817G3uBnIFNjnQJuesI68u4OTLiBFdcbYEdFCoEOfaS35inz1";
+        let preferred = HashMethod::Sha512 { rounds: 1000 };
+        assert_eq!(verify_password("Hello world!", stored, &preferred), Verification::Match { needs_rehash: false });
+        assert_eq!(verify_password("Hello world", stored, &preferred), Verification::Mismatch);
+        // The implied 5000 rounds are fewer than wanted
+        let preferred = HashMethod::Sha512 { rounds: DEFAULT_SHA512_ROUNDS };
+        assert_eq!(verify_password("Hello world!", stored, &preferred), Verification::Match { needs_rehash: true });
+        assert_eq!(verify_password("Hello world!", &format!("!{}", stored), &preferred), Verification::Mismatch);
+    }
+
+    #[test]
+    fn argon2id_round_trip() {
+        let hash = hash_password("correct horse", &HashMethod::Argon2id).unwrap();
+        assert!(hash.starts_with("$argon2id$"));
+        assert_eq!(verify_password("correct horse", &hash, &HashMethod::Argon2id), Verification::Match { needs_rehash: false });
+        assert_eq!(verify_password("battery staple", &hash, &HashMethod::Argon2id), Verification::Mismatch);
+        let sha512 = HashMethod::Sha512 { rounds: DEFAULT_SHA512_ROUNDS };
+        assert_eq!(verify_password("correct horse", &hash, &sha512), Verification::Match { needs_rehash: true });
+    }
+
+    #[test]
+    fn legacy_hashes_need_rehash() {
+        let stored = format!("$6$abc${}", legacy_hash_with_salt("abc", "secret"));
+        let preferred = HashMethod::Sha512 { rounds: DEFAULT_SHA512_ROUNDS };
+        assert_eq!(verify_password("secret", &stored, &preferred), Verification::Match { needs_rehash: true });
+        assert_eq!(verify_password("Secret", &stored, &preferred), Verification::Mismatch);
+    }
+}
@@ -0,0 +1,362 @@
+use std::collections::hash_map::DefaultHasher;
+use std::fs::{self, OpenOptions};
+use std::hash::{Hash, Hasher};
+use std::io::Write;
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::thread::sleep;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use chrono::{Local, TimeZone};
+use crate::auth::{AuthModule, AuthContext, AuthResult};
+use crate::nss::getpwnam;
+use crate::common::safe_println;
+
+pub const DEFAULT_TALLY_DIR: &str = "/var/lib/faillock";
+pub const DEFAULT_FAILLOCK_CONF: &str = "/etc/security/faillock.conf";
+
+/// Old records beyond this are dropped, only recent ones matter for locking.
+const MAX_RECORDS: usize = 256;
+const MAX_DELAY_SECS: u64 = 30;
+
+#[derive(Debug, Clone)]
+pub struct FaillockConfig {
+    /// Failures within `fail_interval` that lock the account
+    pub deny: usize,
+    pub fail_interval: u64,
+    /// Seconds until a lock expires by itself, 0 means only `faillock --reset` unlocks
+    pub unlock_time: u64,
+    pub even_deny_root: bool,
+    pub dir: PathBuf,
+}
+
+impl Default for FaillockConfig {
+    fn default() -> Self {
+        FaillockConfig {
+            deny: 5,
+            fail_interval: 900,
+            unlock_time: 600,
+            even_deny_root: false,
+            dir: PathBuf::from(DEFAULT_TALLY_DIR),
+        }
+    }
+}
+
+impl FaillockConfig {
+    /// `key = value` lines and bare flags, as in pam_faillock's faillock.conf.
+    pub fn load() -> Self {
+        let mut config = FaillockConfig::default();
+        let content = fs::read_to_string(DEFAULT_FAILLOCK_CONF).unwrap_or_default();
+
+        for line in content.lines() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            config.apply_option(line);
+        }
+        config
+    }
+
+    /// One `key=value` or flag, from faillock.conf or from the module arguments of an
+    /// auth stack line.
+    pub fn apply_option(&mut self, option: &str) {
+        let (key, value) = match option.split_once('=') {
+            Some((k, v)) => (k.trim(), v.trim()),
+            None => (option, ""),
+        };
+        match key {
+            "deny" => self.deny = value.parse().unwrap_or(self.deny),
+            "fail_interval" => self.fail_interval = value.parse().unwrap_or(self.fail_interval),
+            "unlock_time" => self.unlock_time = value.parse().unwrap_or(self.unlock_time),
+            "even_deny_root" => self.even_deny_root = true,
+            "dir" if !value.is_empty() => self.dir = PathBuf::from(value),
+            _ => {}
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FailRecord {
+    pub time: u64,
+    pub source: String,
+}
+
+/// Failed attempts of one user since their last successful login.
+#[derive(Debug, Default)]
+pub struct Tally {
+    pub records: Vec<FailRecord>,
+}
+
+impl Tally {
+    pub fn path(dir: &Path, username: &str) -> PathBuf {
+        dir.join(username)
+    }
+
+    /// One `<unix time> <source>` line per failure.
+    pub fn load(dir: &Path, username: &str) -> Self {
+        let content = fs::read_to_string(Self::path(dir, username)).unwrap_or_default();
+        let records = content
+            .lines()
+            .filter_map(|line| {
+                let (time, source) = line.split_once(' ').unwrap_or((line, ""));
+                Some(FailRecord { time: time.parse().ok()?, source: source.to_string() })
+            })
+            .collect();
+        Tally { records }
+    }
+
+    pub fn save(&self, dir: &Path, username: &str) -> std::io::Result<()> {
+        fs::create_dir_all(dir)?;
+        fs::set_permissions(dir, fs::Permissions::from_mode(0o700))?;
+
+        let start = self.records.len().saturating_sub(MAX_RECORDS);
+        let content: String = self.records[start..]
+            .iter()
+            .map(|r| format!("{} {}\n", r.time, r.source))
+            .collect();
+
+        let tmp = dir.join(format!(".{}.tmp", username));
+        OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(&tmp)
+            .and_then(|mut f| f.write_all(content.as_bytes()))?;
+        fs::rename(&tmp, Self::path(dir, username))
+    }
+
+    pub fn reset(dir: &Path, username: &str) -> std::io::Result<()> {
+        match fs::remove_file(Self::path(dir, username)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    /// Usernames that have a tally file.
+    pub fn users(dir: &Path) -> Vec<String> {
+        let mut users: Vec<String> = fs::read_dir(dir)
+            .map(|entries| {
+                entries
+                    .flatten()
+                    .filter_map(|e| e.file_name().into_string().ok())
+                    .filter(|n| !n.starts_with('.'))
+                    .collect()
+            })
+            .unwrap_or_default();
+        users.sort();
+        users
+    }
+
+    pub fn failures_since(&self, since: u64) -> usize {
+        self.records.iter().filter(|r| r.time >= since).count()
+    }
+
+    /// When the lock ends: `Some(0)` for locked until reset, `None` when not locked.
+    /// An account is locked by `deny` failures within `fail_interval` of each other,
+    /// and stays locked for `unlock_time` from the last failure of that burst.
+    pub fn locked_until(&self, config: &FaillockConfig, now: u64) -> Option<u64> {
+        if config.deny == 0 {
+            return None;
+        }
+        let mut times: Vec<u64> = self.records.iter().map(|r| r.time).collect();
+        times.sort_unstable();
+
+        // The latest burst decides, failures aren't recorded while locked
+        let burst_end = times
+            .windows(config.deny)
+            .rev()
+            .find(|w| w[w.len() - 1] - w[0] <= config.fail_interval)?
+            .last()
+            .copied()?;
+
+        if config.unlock_time == 0 {
+            Some(0)
+        } else if burst_end + config.unlock_time > now {
+            Some(burst_end + config.unlock_time)
+        } else {
+            None
+        }
+    }
+}
+
+pub fn now_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+pub fn format_time(secs: u64) -> String {
+    Local
+        .timestamp_opt(secs as i64, 0)
+        .single()
+        .map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or_else(|| secs.to_string())
+}
+
+fn current_tty() -> String {
+    unsafe {
+        let ptr = libc::ttyname(0);
+        if ptr.is_null() {
+            return "unknown".to_string();
+        }
+        std::ffi::CStr::from_ptr(ptr).to_string_lossy().into_owned()
+    }
+}
+
+/// Put first in the module list: refuses locked accounts before the password is even
+/// checked, counts failed attempts and clears the tally once a login gets through.
+pub struct Faillock {
+    config: FaillockConfig,
+}
+
+impl Faillock {
+    pub fn new() -> Self {
+        Faillock { config: FaillockConfig::load() }
+    }
+
+    /// Module arguments override faillock.conf, e.g. `faillock deny=3 unlock_time=0`.
+    pub fn with_args(args: &[String]) -> Self {
+        let mut config = FaillockConfig::load();
+        for arg in args {
+            config.apply_option(arg);
+        }
+        Faillock { config }
+    }
+
+    /// Root is only ever locked with `even_deny_root`, and users that don't exist
+    /// get no tally file.
+    fn tracked(&self, username: &str) -> bool {
+        match getpwnam(username) {
+            Some(user) => user.uid != 0 || self.config.even_deny_root,
+            None => false,
+        }
+    }
+
+    /// Where failures of a name that is never locked are counted. Hidden, so it
+    /// isn't listed as a user, and hashed, as the name may be anything typed at login.
+    fn untracked_name(username: &str) -> String {
+        let mut hasher = DefaultHasher::new();
+        username.hash(&mut hasher);
+        format!(".untracked-{:016x}", hasher.finish())
+    }
+
+    /// 1, 2, 4, 8... seconds, counting only the recent failures
+    fn delay(&self, tally: &Tally, now: u64) {
+        let recent = tally.failures_since(now.saturating_sub(self.config.fail_interval)).max(1);
+        let delay = 1u64 << (recent - 1).min(5);
+        sleep(Duration::from_secs(delay.min(MAX_DELAY_SECS)));
+    }
+}
+
+impl Default for Faillock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AuthModule for Faillock {
+    fn auth(&self, ctx: &mut AuthContext) -> AuthResult {
+        if !self.tracked(&ctx.username) {
+            return AuthResult::Success;
+        }
+
+        let tally = Tally::load(&self.config.dir, &ctx.username);
+        match tally.locked_until(&self.config, now_secs()) {
+            Some(0) => AuthResult::Failure("Account locked due to failed logins, ask an administrator to unlock it".into()),
+            Some(until) => AuthResult::Failure(format!(
+                "Account locked due to failed logins, try again after {}", format_time(until)
+            )),
+            None => AuthResult::Success,
+        }
+    }
+
+    fn auth_failed(&self, ctx: &mut AuthContext) {
+        let now = now_secs();
+
+        if !self.tracked(&ctx.username) {
+            // Counted too, so the delay grows like it does for a real user and
+            // doesn't tell which names exist. Nothing here is ever locked, only
+            // what the delay needs is kept.
+            let name = Self::untracked_name(&ctx.username);
+            let mut tally = Tally::load(&self.config.dir, &name);
+            let since = now.saturating_sub(self.config.fail_interval);
+            tally.records.retain(|r| r.time >= since);
+            tally.records.push(FailRecord { time: now, source: current_tty() });
+            let _ = tally.save(&self.config.dir, &name);
+            self.delay(&tally, now);
+            return;
+        }
+
+        let mut tally = Tally::load(&self.config.dir, &ctx.username);
+        // A login refused while locked isn't a new guess, don't extend the lock
+        if tally.locked_until(&self.config, now).is_none() {
+            tally.records.push(FailRecord { time: now, source: current_tty() });
+            let _ = tally.save(&self.config.dir, &ctx.username);
+        }
+        self.delay(&tally, now);
+    }
+
+    fn account(&self, ctx: &mut AuthContext) -> AuthResult {
+        let tally = Tally::load(&self.config.dir, &ctx.username);
+        if let Some(last) = tally.records.last() {
+            safe_println(format_args!(
+                "{} failed attempts since last login. Last failure at {} from {}.",
+                tally.records.len(), format_time(last.time), last.source
+            ));
+            let _ = Tally::reset(&self.config.dir, &ctx.username);
+        }
+        AuthResult::Success
+    }
+
+    fn open_session(&self, _ctx: &mut AuthContext) -> AuthResult {
+        AuthResult::Success
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tally(times: &[u64]) -> Tally {
+        Tally { records: times.iter().map(|&time| FailRecord { time, source: "tty1".into() }).collect() }
+    }
+
+    fn config(deny: usize, fail_interval: u64, unlock_time: u64) -> FaillockConfig {
+        FaillockConfig { deny, fail_interval, unlock_time, ..Default::default() }
+    }
+
+    #[test]
+    fn locks_after_deny_failures_within_interval() {
+        let config = config(3, 100, 600);
+        assert_eq!(tally(&[1000, 1050]).locked_until(&config, 1060), None);
+        assert_eq!(tally(&[1000, 1050, 1100]).locked_until(&config, 1110), Some(1700));
+        // Spread out over more than fail_interval
+        assert_eq!(tally(&[1000, 1060, 1120]).locked_until(&config, 1130), None);
+        assert_eq!(tally(&[1000, 1060, 1120, 1150]).locked_until(&config, 1160), Some(1750));
+    }
+
+    #[test]
+    fn lock_outlives_fail_interval() {
+        let config = config(3, 100, 600);
+        let tally = tally(&[1000, 1010, 1020]);
+        // The failures are long out of the interval, the lock still holds
+        assert_eq!(tally.locked_until(&config, 1500), Some(1620));
+        assert_eq!(tally.locked_until(&config, 1620), None);
+    }
+
+    #[test]
+    fn unlock_time_zero_never_expires() {
+        let config = config(3, 100, 0);
+        assert_eq!(tally(&[1000, 1010, 1020]).locked_until(&config, 1_000_000), Some(0));
+        assert_eq!(tally(&[1000, 1200, 1400]).locked_until(&config, 1500), None);
+    }
+
+    #[test]
+    fn deny_zero_never_locks() {
+        assert_eq!(tally(&[1000, 1001, 1002]).locked_until(&config(0, 100, 0), 1003), None);
+    }
+
+    #[test]
+    fn untracked_names_are_hidden() {
+        let name = Faillock::untracked_name("no/such user");
+        assert!(name.starts_with('.') && !name.contains('/'));
+        assert_eq!(name, Faillock::untracked_name("no/such user"));
+        assert_ne!(name, Faillock::untracked_name("other"));
+    }
+}
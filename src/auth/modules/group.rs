@@ -1,46 +1,91 @@
-use std::fs::File;
-use std::io::{BufRead, BufReader};
-use std::fs::OpenOptions;
-use std::io::Write;
+use std::io;
 
+#[derive(Debug, Clone)]
 pub struct GroupEntry {
     pub groupname: String,
+    pub password: String,
     pub gid: u32,
+    /// Users that have this group as a supplementary group
+    pub members: Vec<String>,
 }
 
-const DEFAULT_GROUP_FILE: &str = "/etc/group";
+pub const DEFAULT_GROUP_FILE: &str = "/etc/group";
 
-pub fn get_group_entry(username: &str) -> Option<GroupEntry> {
-    let file = File::open(DEFAULT_GROUP_FILE).ok()?;
-    let reader = BufReader::new(file);
+impl GroupEntry {
+    pub fn parse(line: &str) -> Option<Self> {
+        let fields: Vec<&str> = line.split(':').collect();
+        if fields.len() != 4 || fields[0].is_empty() {
+            return None;
+        }
+        Some(GroupEntry {
+            groupname: fields[0].to_string(),
+            password: fields[1].to_string(),
+            gid: fields[2].parse().ok()?,
+            members: fields[3].split(',').filter(|m| !m.is_empty()).map(|m| m.to_string()).collect(),
+        })
+    }
+
+    pub fn to_line(&self) -> String {
+        format!("{}:{}:{}:{}", self.groupname, self.password, self.gid, self.members.join(","))
+    }
+}
 
-    for line in reader.lines() {
-        if let Ok(entry) = line {
-            let fields: Vec<&str> = entry.split(':').collect();
+/// Supplementary groups of the calling process.
+pub fn current_gids() -> Vec<u32> {
+    let count = unsafe { libc::getgroups(0, std::ptr::null_mut()) };
+    if count <= 0 {
+        return Vec::new();
+    }
+    let mut gids = vec![0 as libc::gid_t; count as usize];
+    let count = unsafe { libc::getgroups(count, gids.as_mut_ptr()) };
+    gids.truncate(count.max(0) as usize);
+    gids
+}
 
-            if fields.len() >= 7 && fields[0] == username {
-                return Some(GroupEntry {
-                    groupname: fields[0].to_string(),
-                    gid: fields[2].parse().ok()?,
-                });
-            }
+/// Switch the calling process to `uid`, `gid` and the supplementary `groups`.
+///
+/// Groups go first since only root may change them. Nothing here allocates, so
+/// it is safe to call between fork and exec (`CommandExt::pre_exec`).
+pub fn drop_privileges(uid: u32, gid: u32, groups: &[u32]) -> io::Result<()> {
+    unsafe {
+        if libc::setgroups(groups.len() as _, groups.as_ptr()) != 0
+            || libc::setgid(gid) != 0
+            || libc::setuid(uid) != 0
+        {
+            return Err(io::Error::last_os_error());
         }
     }
-    None
+    Ok(())
 }
 
-pub fn add_user_to_group_file(username: &str, gid: u32) -> std::io::Result<()> {
-    let entry = format!(
-        "{}:x:{}:\n",
-        username, gid
-    );
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    let mut file = OpenOptions::new()
-        .append(true)
-        .create(true)
-        .open(DEFAULT_GROUP_FILE)?;
+    #[test]
+    fn members() {
+        let group = GroupEntry::parse("wheel:x:10:alice,bob").unwrap();
+        assert_eq!((group.groupname.as_str(), group.gid), ("wheel", 10));
+        assert_eq!(group.members, ["alice", "bob"]);
 
-    writeln!(file, "{}", entry)?;
+        assert!(GroupEntry::parse("users:x:100:").unwrap().members.is_empty());
+        // Stray commas don't make empty members
+        assert_eq!(GroupEntry::parse("audio:x:12:,alice,,bob,").unwrap().members, ["alice", "bob"]);
+    }
 
-    Ok(())
-}
\ No newline at end of file
+    #[test]
+    fn malformed_lines() {
+        assert!(GroupEntry::parse("").is_none());
+        assert!(GroupEntry::parse("wheel:x:10").is_none());
+        assert!(GroupEntry::parse("wheel:x:10:a:b").is_none());
+        assert!(GroupEntry::parse(":x:10:").is_none());
+        assert!(GroupEntry::parse("wheel:x:ten:").is_none());
+    }
+
+    #[test]
+    fn line_round_trip() {
+        for line in ["wheel:x:10:alice,bob", "users:!:100:"] {
+            assert_eq!(GroupEntry::parse(line).unwrap().to_line(), line);
+        }
+    }
+}
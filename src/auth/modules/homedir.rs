@@ -0,0 +1,88 @@
+use std::fs;
+use std::os::unix::fs::{lchown, symlink, MetadataExt, PermissionsExt};
+use std::path::Path;
+
+pub const DEFAULT_SKEL_PATH: &str = "/etc/skel";
+
+/// Create `home` with `mode`, fill it from `skel` (which may be missing) and give
+/// everything to `uid:gid`. An existing directory is left alone.
+pub fn create_home(home: &Path, skel: &Path, uid: u32, gid: u32, mode: u32) -> Result<(), String> {
+    if home.exists() {
+        return Err(format!("home directory {} already exists, not copying skel", home.display()));
+    }
+    if let Some(parent) = home.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("cannot create {}: {}", parent.display(), e))?;
+    }
+
+    fs::create_dir(home).map_err(|e| format!("cannot create {}: {}", home.display(), e))?;
+    lchown(home, Some(uid), Some(gid)).map_err(|e| format!("cannot chown {}: {}", home.display(), e))?;
+    fs::set_permissions(home, fs::Permissions::from_mode(mode))
+        .map_err(|e| format!("cannot chmod {}: {}", home.display(), e))?;
+
+    if skel.is_dir() {
+        copy_tree(skel, home, uid, gid).map_err(|e| format!("cannot copy {}: {}", skel.display(), e))?;
+    }
+    Ok(())
+}
+
+fn copy_tree(src: &Path, dst: &Path, uid: u32, gid: u32) -> std::io::Result<()> {
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+        let from = entry.path();
+        let to = dst.join(entry.file_name());
+        let meta = fs::symlink_metadata(&from)?;
+
+        if meta.file_type().is_symlink() {
+            symlink(fs::read_link(&from)?, &to)?;
+        } else if meta.is_dir() {
+            fs::create_dir(&to)?;
+            fs::set_permissions(&to, fs::Permissions::from_mode(meta.mode() & 0o7777))?;
+            copy_tree(&from, &to, uid, gid)?;
+        } else if meta.is_file() {
+            fs::copy(&from, &to)?;
+        } else {
+            // Device nodes and fifos don't belong in skel
+            continue;
+        }
+        lchown(&to, Some(uid), Some(gid))?;
+    }
+    Ok(())
+}
+
+/// Remove a home directory, but only one that belongs to `uid`, so a shared or
+/// mistyped home like `/` or `/home` can't be wiped.
+pub fn remove_home(home: &Path, uid: u32) -> Result<(), String> {
+    let meta = match fs::symlink_metadata(home) {
+        Ok(meta) => meta,
+        Err(_) => return Ok(()),
+    };
+    if !meta.is_dir() || meta.uid() != uid || home.parent().is_none() {
+        return Err(format!("{} is not owned by the user, not removing it", home.display()));
+    }
+    fs::remove_dir_all(home).map_err(|e| format!("cannot remove {}: {}", home.display(), e))
+}
+
+/// Rename the home directory. Moving across filesystems isn't supported.
+pub fn move_home(old: &Path, new: &Path) -> Result<(), String> {
+    if new.exists() {
+        return Err(format!("{} already exists", new.display()));
+    }
+    if let Some(parent) = new.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("cannot create {}: {}", parent.display(), e))?;
+    }
+    fs::rename(old, new).map_err(|e| format!("cannot move {} to {}: {}", old.display(), new.display(), e))
+}
+
+/// Give every file under `home` that belonged to `old_uid` to `uid`, after a uid change.
+pub fn chown_tree(path: &Path, old_uid: u32, uid: u32, gid: Option<u32>) -> std::io::Result<()> {
+    let meta = fs::symlink_metadata(path)?;
+    if meta.uid() == old_uid {
+        lchown(path, Some(uid), gid)?;
+    }
+    if meta.is_dir() {
+        for entry in fs::read_dir(path)? {
+            chown_tree(&entry?.path(), old_uid, uid, gid)?;
+        }
+    }
+    Ok(())
+}
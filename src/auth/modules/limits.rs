@@ -0,0 +1,361 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use crate::auth::{AuthModule, AuthContext, AuthResult};
+use crate::nss::{getgrgid, getgrouplist, getpwnam};
+use crate::common::safe_eprintln;
+
+pub const DEFAULT_LIMITS_PATH: &str = "/etc/security/limits.conf";
+/// `*.conf` files here are read after limits.conf, in name order
+pub const DEFAULT_LIMITS_DIR: &str = "/etc/security/limits.d";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LimitItem {
+    /// Open files
+    NoFile,
+    /// Processes of the user
+    NProc,
+    /// Core file size, in KiB
+    Core,
+    /// Locked memory, in KiB
+    MemLock,
+    /// Nice value the session starts with
+    Priority,
+}
+
+impl LimitItem {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "nofile" => Some(LimitItem::NoFile),
+            "nproc" => Some(LimitItem::NProc),
+            "core" => Some(LimitItem::Core),
+            "memlock" => Some(LimitItem::MemLock),
+            "priority" => Some(LimitItem::Priority),
+            _ => None,
+        }
+    }
+
+    /// Sizes are written in KiB, as in pam_limits.
+    fn scale(&self) -> u64 {
+        match self {
+            LimitItem::Core | LimitItem::MemLock => 1024,
+            _ => 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Domain {
+    User(String),
+    Group(String),
+    /// `*`, everyone
+    Default,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LimitType {
+    Soft,
+    Hard,
+    /// `-`, both
+    Both,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LimitValue {
+    Unlimited,
+    Value(i64),
+}
+
+/// One line of limits.conf: `<domain> <soft|hard|-> <item> <value>`, where the
+/// domain is a user, `@group` or `*`.
+#[derive(Debug, Clone)]
+pub struct LimitEntry {
+    pub domain: Domain,
+    pub kind: LimitType,
+    pub item: LimitItem,
+    pub value: LimitValue,
+}
+
+impl LimitEntry {
+    pub fn parse(line: &str) -> Option<Self> {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() != 4 {
+            return None;
+        }
+        let domain = match fields[0] {
+            "*" => Domain::Default,
+            d => match d.strip_prefix('@') {
+                Some(group) => Domain::Group(group.to_string()),
+                None => Domain::User(d.to_string()),
+            },
+        };
+        let kind = match fields[1] {
+            "soft" => LimitType::Soft,
+            "hard" => LimitType::Hard,
+            "-" => LimitType::Both,
+            _ => return None,
+        };
+        let item = LimitItem::parse(fields[2])?;
+        let value = match fields[3] {
+            "unlimited" | "infinity" | "-1" if item != LimitItem::Priority => LimitValue::Unlimited,
+            v => LimitValue::Value(v.parse().ok()?),
+        };
+        Some(LimitEntry { domain, kind, item, value })
+    }
+
+    /// 3 for the user itself, 2 for one of their groups, 1 for `*`, 0 when the
+    /// entry is for someone else.
+    fn specificity(&self, username: &str, groups: &[String]) -> u8 {
+        match &self.domain {
+            Domain::User(u) if u == username => 3,
+            Domain::Group(g) if groups.contains(g) => 2,
+            Domain::Default => 1,
+            _ => 0,
+        }
+    }
+}
+
+/// Entries of limits.conf and limits.d, in the order they were read.
+pub fn load_limits(path: &Path, dir: &Path) -> Vec<LimitEntry> {
+    let mut files = vec![path.to_path_buf()];
+    if let Ok(entries) = fs::read_dir(dir) {
+        let mut extra: Vec<PathBuf> = entries
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| p.extension().is_some_and(|ext| ext == "conf"))
+            .collect();
+        extra.sort();
+        files.extend(extra);
+    }
+
+    let mut entries = Vec::new();
+    for file in files {
+        let content = fs::read_to_string(&file).unwrap_or_default();
+        for (lineno, line) in content.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            match LimitEntry::parse(line) {
+                Some(entry) => entries.push(entry),
+                None => safe_eprintln(format_args!("limits: {}:{}: ignoring '{}'", file.display(), lineno + 1, line)),
+            }
+        }
+    }
+    entries
+}
+
+/// What one item is set to for a user, `None` leaves that side as it is.
+#[derive(Debug, Clone, Copy)]
+pub struct ResolvedLimit {
+    pub item: LimitItem,
+    pub soft: Option<LimitValue>,
+    pub hard: Option<LimitValue>,
+}
+
+/// The soft and hard value of each item for one user. Entries for the user
+/// win over entries for their groups, which win over `*`; among equally
+/// specific entries the last one counts.
+pub fn limits_for(entries: &[LimitEntry], username: &str, groups: &[String]) -> Vec<ResolvedLimit> {
+    // Each side remembers how specific the entry that set it was
+    type Ranked = Option<(u8, LimitValue)>;
+    let mut result: Vec<(LimitItem, Ranked, Ranked)> = Vec::new();
+
+    for entry in entries {
+        let rank = entry.specificity(username, groups);
+        if rank == 0 {
+            continue;
+        }
+        let index = match result.iter().position(|(item, _, _)| *item == entry.item) {
+            Some(index) => index,
+            None => {
+                result.push((entry.item, None, None));
+                result.len() - 1
+            }
+        };
+        let (_, soft, hard) = &mut result[index];
+        for (slot, applies) in [(soft, entry.kind != LimitType::Hard), (hard, entry.kind != LimitType::Soft)] {
+            if applies && slot.is_none_or(|(r, _)| rank >= r) {
+                *slot = Some((rank, entry.value));
+            }
+        }
+    }
+
+    result
+        .into_iter()
+        .map(|(item, soft, hard)| ResolvedLimit { item, soft: soft.map(|(_, v)| v), hard: hard.map(|(_, v)| v) })
+        .collect()
+}
+
+fn to_rlim(value: LimitValue, scale: u64) -> libc::rlim_t {
+    match value {
+        LimitValue::Unlimited => libc::RLIM_INFINITY,
+        LimitValue::Value(v) => (v.max(0) as u64).saturating_mul(scale) as libc::rlim_t,
+    }
+}
+
+/// Set one limit of this process, children inherit it.
+fn apply_limit(limit: ResolvedLimit) -> io::Result<()> {
+    let ResolvedLimit { item, soft, hard } = limit;
+    let resource = match item {
+        LimitItem::NoFile => libc::RLIMIT_NOFILE,
+        LimitItem::NProc => libc::RLIMIT_NPROC,
+        LimitItem::Core => libc::RLIMIT_CORE,
+        LimitItem::MemLock => libc::RLIMIT_MEMLOCK,
+        // The nice value, soft and hard mean the same here
+        LimitItem::Priority => {
+            if let Some(LimitValue::Value(nice)) = soft.or(hard) {
+                if unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, nice.clamp(-20, 19) as libc::c_int) } != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+            }
+            return Ok(());
+        }
+    };
+
+    let mut current = libc::rlimit { rlim_cur: 0, rlim_max: 0 };
+    if unsafe { libc::getrlimit(resource, &mut current) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    if let Some(hard) = hard {
+        current.rlim_max = to_rlim(hard, item.scale());
+    }
+    if let Some(soft) = soft {
+        current.rlim_cur = to_rlim(soft, item.scale());
+    }
+    current.rlim_cur = current.rlim_cur.min(current.rlim_max);
+    if unsafe { libc::setrlimit(resource, &current) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Session module that applies limits.conf to the login process, so the shell
+/// starts with them. Argument `conf=PATH` reads another file than limits.conf.
+pub struct Limits {
+    path: PathBuf,
+    dir: PathBuf,
+}
+
+impl Limits {
+    pub fn new() -> Self {
+        Limits { path: PathBuf::from(DEFAULT_LIMITS_PATH), dir: PathBuf::from(DEFAULT_LIMITS_DIR) }
+    }
+
+    pub fn with_args(args: &[String]) -> Self {
+        let mut limits = Self::new();
+        for arg in args {
+            if let Some(path) = arg.strip_prefix("conf=") {
+                limits.path = PathBuf::from(path);
+            }
+        }
+        limits
+    }
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AuthModule for Limits {
+    fn auth(&self, _ctx: &mut AuthContext) -> AuthResult {
+        AuthResult::Success
+    }
+
+    fn account(&self, _ctx: &mut AuthContext) -> AuthResult {
+        AuthResult::Success
+    }
+
+    fn open_session(&self, ctx: &mut AuthContext) -> AuthResult {
+        let user = match getpwnam(&ctx.username) {
+            Some(user) => user,
+            None => return AuthResult::Failure("User not found".into()),
+        };
+        let groups: Vec<String> = getgrouplist(&user.username, user.gid)
+            .into_iter()
+            .filter_map(|gid| getgrgid(gid).map(|g| g.groupname))
+            .collect();
+
+        let entries = load_limits(&self.path, &self.dir);
+        for limit in limits_for(&entries, &user.username, &groups) {
+            if let Err(e) = apply_limit(limit) {
+                return AuthResult::Failure(format!("Cannot set {:?} limit: {}", limit.item, e));
+            }
+        }
+        AuthResult::Success
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn groups(names: &[&str]) -> Vec<String> {
+        names.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn parse_entries() {
+        let entry = LimitEntry::parse("@staff  hard  nofile  4096").unwrap();
+        assert_eq!(entry.domain, Domain::Group("staff".into()));
+        assert_eq!(entry.kind, LimitType::Hard);
+        assert_eq!(entry.item, LimitItem::NoFile);
+        assert_eq!(entry.value, LimitValue::Value(4096));
+
+        let entry = LimitEntry::parse("* - core unlimited").unwrap();
+        assert_eq!(entry.domain, Domain::Default);
+        assert_eq!(entry.kind, LimitType::Both);
+        assert_eq!(entry.value, LimitValue::Unlimited);
+
+        assert_eq!(LimitEntry::parse("alice soft nproc -1").unwrap().value, LimitValue::Unlimited);
+        assert_eq!(LimitEntry::parse("alice soft nproc -1").unwrap().domain, Domain::User("alice".into()));
+        // A nice value of -1 is a value, not unlimited
+        assert_eq!(LimitEntry::parse("alice - priority -1").unwrap().value, LimitValue::Value(-1));
+        assert!(LimitEntry::parse("alice - priority unlimited").is_none());
+    }
+
+    #[test]
+    fn parse_rejects_bad_lines() {
+        assert!(LimitEntry::parse("alice soft nofile").is_none());
+        assert!(LimitEntry::parse("alice soft nofile 10 20").is_none());
+        assert!(LimitEntry::parse("alice both nofile 10").is_none());
+        assert!(LimitEntry::parse("alice soft stack 10").is_none());
+        assert!(LimitEntry::parse("alice soft nofile lots").is_none());
+    }
+
+    #[test]
+    fn specificity() {
+        let staff = groups(&["staff"]);
+        let spec = |line: &str| LimitEntry::parse(line).unwrap().specificity("alice", &staff);
+        assert_eq!(spec("alice - nofile 1"), 3);
+        assert_eq!(spec("@staff - nofile 1"), 2);
+        assert_eq!(spec("* - nofile 1"), 1);
+        assert_eq!(spec("bob - nofile 1"), 0);
+        assert_eq!(spec("@wheel - nofile 1"), 0);
+    }
+
+    #[test]
+    fn most_specific_entry_wins() {
+        let entries: Vec<LimitEntry> = [
+            "alice soft nofile 100",
+            "@staff - nofile 200",
+            "* - nofile 300",
+            "* soft nproc 10",
+            "* soft nproc 20",
+            "bob - core 0",
+        ]
+        .iter()
+        .map(|line| LimitEntry::parse(line).unwrap())
+        .collect();
+
+        let limits = limits_for(&entries, "alice", &groups(&["staff"]));
+        assert_eq!(limits.len(), 2);
+        let nofile = limits.iter().find(|l| l.item == LimitItem::NoFile).unwrap();
+        assert_eq!(nofile.soft, Some(LimitValue::Value(100)));
+        assert_eq!(nofile.hard, Some(LimitValue::Value(200)));
+        let nproc = limits.iter().find(|l| l.item == LimitItem::NProc).unwrap();
+        assert_eq!(nproc.soft, Some(LimitValue::Value(20)));
+        assert_eq!(nproc.hard, None);
+    }
+}
@@ -0,0 +1,52 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+pub const DEFAULT_LOGIN_DEFS_PATH: &str = "/etc/login.defs";
+
+/// `KEY value` settings from /etc/login.defs, shared by login and the account tools.
+#[derive(Debug, Default)]
+pub struct LoginDefs {
+    values: HashMap<String, String>,
+}
+
+impl LoginDefs {
+    pub fn load() -> Self {
+        Self::load_from(Path::new(DEFAULT_LOGIN_DEFS_PATH))
+    }
+
+    pub fn load_from(path: &Path) -> Self {
+        Self::parse(&fs::read_to_string(path).unwrap_or_default())
+    }
+
+    pub fn parse(content: &str) -> Self {
+        let mut values = HashMap::new();
+        for line in content.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut parts = line.splitn(2, char::is_whitespace);
+            if let (Some(key), Some(value)) = (parts.next(), parts.next()) {
+                values.insert(key.to_string(), value.trim().trim_matches('"').to_string());
+            }
+        }
+        LoginDefs { values }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(|v| v.as_str())
+    }
+
+    pub fn get_num(&self, key: &str, default: i64) -> i64 {
+        self.get(key).and_then(|v| v.parse().ok()).unwrap_or(default)
+    }
+
+    /// `yes`/`no` settings
+    pub fn get_bool(&self, key: &str, default: bool) -> bool {
+        match self.get(key) {
+            Some(v) => v.eq_ignore_ascii_case("yes"),
+            None => default,
+        }
+    }
+}
@@ -0,0 +1,179 @@
+use std::ffi::CStr;
+use std::fs;
+use std::io;
+use std::os::unix::fs::{chown, MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use chrono::Local;
+use crate::nss::getgrnam;
+use crate::host::os_pretty_name;
+use crate::i18n::system_locale;
+use crate::auth::modules::login_defs::LoginDefs;
+use crate::auth::modules::passwd::PasswdEntry;
+use crate::auth::modules::session_log::active_sessions;
+
+pub const DEFAULT_ENVIRONMENT_PATH: &str = "/etc/environment";
+pub const DEFAULT_ISSUE_PATH: &str = "/etc/issue";
+pub const DEFAULT_MOTD_PATH: &str = "/etc/motd";
+pub const DEFAULT_NOLOGIN_PATH: &str = "/etc/nologin";
+
+const DEFAULT_PATH: &str = "/bin:/usr/bin";
+const DEFAULT_SUPATH: &str = "/sbin:/bin:/usr/sbin:/usr/bin";
+
+/// `KEY=value` lines, optionally prefixed with `export` and with the value quoted.
+pub fn parse_environment(content: &str) -> Vec<(String, String)> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let line = line.strip_prefix("export ").unwrap_or(line);
+            let (key, value) = line.split_once('=')?;
+            let key = key.trim();
+            if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                return None;
+            }
+            let value = value.trim();
+            let value = value
+                .strip_prefix('"').and_then(|v| v.strip_suffix('"'))
+                .or_else(|| value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')))
+                .unwrap_or(value);
+            Some((key.to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+/// The whole environment of a login shell. /etc/environment can change PATH or
+/// LANG, but not the variables that say who the user is.
+pub fn login_environment(user: &PasswdEntry, defs: &LoginDefs) -> Vec<(String, String)> {
+    let path = if user.uid == 0 {
+        defs.get("ENV_SUPATH").unwrap_or(DEFAULT_SUPATH)
+    } else {
+        defs.get("ENV_PATH").unwrap_or(DEFAULT_PATH)
+    };
+    // login.defs writes these as `PATH=/bin:...`
+    let path = path.strip_prefix("PATH=").unwrap_or(path);
+    let mail_dir = defs.get("MAIL_DIR").unwrap_or("/var/mail");
+
+    let mut env: Vec<(String, String)> = vec![
+        ("TERM".to_string(), std::env::var("TERM").unwrap_or_else(|_| "linux".to_string())),
+        ("PATH".to_string(), path.to_string()),
+        // The language chosen at firstboot, /etc/environment can still override it
+        ("LANG".to_string(), system_locale().unwrap_or_else(|| "C.UTF-8".to_string())),
+    ];
+
+    let extra = parse_environment(&fs::read_to_string(DEFAULT_ENVIRONMENT_PATH).unwrap_or_default());
+    for (key, value) in extra {
+        if matches!(key.as_str(), "HOME" | "USER" | "LOGNAME" | "SHELL" | "MAIL") {
+            continue;
+        }
+        env.retain(|(k, _)| *k != key);
+        env.push((key, value));
+    }
+
+    env.extend([
+        ("HOME".to_string(), user.home.clone()),
+        ("USER".to_string(), user.username.clone()),
+        ("LOGNAME".to_string(), user.username.clone()),
+        ("SHELL".to_string(), user.shell.clone()),
+        ("MAIL".to_string(), format!("{}/{}", mail_dir.trim_end_matches('/'), user.username)),
+    ]);
+    env
+}
+
+/// UMASK from login.defs, octal, 022 when missing or invalid.
+pub fn login_umask(defs: &LoginDefs) -> u32 {
+    defs.get("UMASK")
+        .and_then(|v| u32::from_str_radix(v, 8).ok())
+        .filter(|mask| *mask <= 0o777)
+        .unwrap_or(0o022)
+}
+
+fn uname_field(field: fn(&libc::utsname) -> &[libc::c_char]) -> String {
+    let mut data: libc::utsname = unsafe { std::mem::zeroed() };
+    if unsafe { libc::uname(&mut data) } != 0 {
+        return String::new();
+    }
+    unsafe { CStr::from_ptr(field(&data).as_ptr()) }.to_string_lossy().into_owned()
+}
+
+/// /etc/issue escapes as agetty knows them: `\n` host name, `\s` system, `\S`
+/// os-release name, `\r` release, `\m` machine, `\l` tty, `\d` date, `\t` time,
+/// `\u` user count.
+pub fn expand_issue(content: &str, tty: &str) -> String {
+    let mut out = String::with_capacity(content.len());
+    let mut chars = content.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push_str(&uname_field(|u| &u.nodename)),
+            Some('s') => out.push_str(&uname_field(|u| &u.sysname)),
+            Some('S') => out.push_str(&os_pretty_name()),
+            Some('r') => out.push_str(&uname_field(|u| &u.release)),
+            Some('v') => out.push_str(&uname_field(|u| &u.version)),
+            Some('m') => out.push_str(&uname_field(|u| &u.machine)),
+            Some('l') => out.push_str(tty.trim_start_matches("/dev/")),
+            Some('d') => out.push_str(&Local::now().format("%a %b %e %Y").to_string()),
+            Some('t') => out.push_str(&Local::now().format("%H:%M:%S").to_string()),
+            Some('u') => out.push_str(&active_sessions().len().to_string()),
+            Some('\\') => out.push('\\'),
+            Some(other) => {
+                out.push('\\');
+                out.push(other);
+            }
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+/// While /etc/nologin exists only root may log in, its content says why.
+pub fn nologin_message() -> Option<String> {
+    let content = fs::read_to_string(DEFAULT_NOLOGIN_PATH).ok()?;
+    if content.trim().is_empty() {
+        Some("The system is closed for logins.".to_string())
+    } else {
+        Some(content.trim_end().to_string())
+    }
+}
+
+/// Hands the login terminal to the user for the session and gives it back
+/// afterwards, so other users can't read from it or write to it.
+pub struct TtyOwnership {
+    path: PathBuf,
+    uid: u32,
+    gid: u32,
+    mode: u32,
+}
+
+impl TtyOwnership {
+    /// TTYGROUP and TTYPERM from login.defs, `tty` and 0620 by default.
+    pub fn take(tty: &str, user: &PasswdEntry, defs: &LoginDefs) -> io::Result<Self> {
+        let path = Path::new(tty).to_path_buf();
+        let meta = fs::metadata(&path)?;
+        let saved = TtyOwnership { path, uid: meta.uid(), gid: meta.gid(), mode: meta.mode() & 0o7777 };
+
+        let group = defs.get("TTYGROUP").unwrap_or("tty");
+        let gid = group
+            .parse()
+            .ok()
+            .or_else(|| getgrnam(group).map(|g| g.gid))
+            .unwrap_or(user.gid);
+        let mode = defs
+            .get("TTYPERM")
+            .and_then(|v| u32::from_str_radix(v, 8).ok())
+            .unwrap_or(0o620);
+
+        chown(&saved.path, Some(user.uid), Some(gid))?;
+        fs::set_permissions(&saved.path, fs::Permissions::from_mode(mode))?;
+        Ok(saved)
+    }
+
+    pub fn restore(self) {
+        let _ = chown(&self.path, Some(self.uid), Some(self.gid));
+        let _ = fs::set_permissions(&self.path, fs::Permissions::from_mode(self.mode));
+    }
+}
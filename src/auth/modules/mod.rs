@@ -3,5 +3,17 @@ pub mod session_logger;
 pub mod account_expiry;
 pub mod passwd;
 pub mod shadow;
+pub mod crypt;
+pub mod faillock;
+pub mod login_defs;
+pub mod login_env;
+pub mod account_db;
+pub mod homedir;
+pub mod pwquality;
+pub mod rootok;
+pub mod totp;
+pub mod limits;
+pub mod runtime_dir;
+pub mod account_check;
 pub mod group;
 pub mod session_log;
@@ -0,0 +1,205 @@
+use std::fs;
+use std::path::PathBuf;
+
+pub const DEFAULT_PWQUALITY_CONF: &str = "/etc/security/pwquality.conf";
+pub const DEFAULT_DICT_PATH: &str = "/etc/security/bad-passwords";
+
+/// Rules a new password has to pass, from `key = value` lines in pwquality.conf.
+#[derive(Debug, Clone)]
+pub struct PasswordPolicy {
+    pub min_length: usize,
+    /// Of lowercase, uppercase, digits and other characters
+    pub min_classes: usize,
+    /// Characters of the new password that must not appear in the old one
+    pub difok: usize,
+    /// Longest run of the same character, 0 for no limit
+    pub max_repeat: usize,
+    pub check_username: bool,
+    /// One word per line, compared case-insensitively
+    pub dict_path: PathBuf,
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        PasswordPolicy {
+            min_length: 8,
+            min_classes: 2,
+            difok: 3,
+            max_repeat: 3,
+            check_username: true,
+            dict_path: PathBuf::from(DEFAULT_DICT_PATH),
+        }
+    }
+}
+
+impl PasswordPolicy {
+    pub fn load() -> Self {
+        Self::parse(&fs::read_to_string(DEFAULT_PWQUALITY_CONF).unwrap_or_default())
+    }
+
+    /// Unknown keys and values that don't parse keep the default.
+    pub fn parse(content: &str) -> Self {
+        let mut policy = PasswordPolicy::default();
+        for line in content.lines() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            let (key, value) = match line.split_once('=') {
+                Some((k, v)) => (k.trim(), v.trim()),
+                None => continue,
+            };
+            match key {
+                "minlen" => policy.min_length = value.parse().unwrap_or(policy.min_length),
+                "minclass" => policy.min_classes = value.parse().unwrap_or(policy.min_classes),
+                "difok" => policy.difok = value.parse().unwrap_or(policy.difok),
+                "maxrepeat" => policy.max_repeat = value.parse().unwrap_or(policy.max_repeat),
+                "usercheck" => policy.check_username = value != "0",
+                "dictpath" => policy.dict_path = PathBuf::from(value),
+                _ => {}
+            }
+        }
+        policy
+    }
+
+    /// `Err` says what is wrong with `password`, worded to be shown to the user.
+    pub fn check(&self, password: &str, old: Option<&str>, username: &str) -> Result<(), String> {
+        if password.chars().count() < self.min_length {
+            return Err(format!("The password is shorter than {} characters", self.min_length));
+        }
+
+        let classes = [
+            password.chars().any(|c| c.is_lowercase()),
+            password.chars().any(|c| c.is_uppercase()),
+            password.chars().any(|c| c.is_ascii_digit()),
+            password.chars().any(|c| !c.is_alphanumeric()),
+        ];
+        if classes.iter().filter(|c| **c).count() < self.min_classes {
+            return Err(format!(
+                "The password contains less than {} character classes (lowercase, uppercase, digits, others)",
+                self.min_classes
+            ));
+        }
+
+        if self.max_repeat > 0 && longest_run(password) > self.max_repeat {
+            return Err(format!("The password contains more than {} same characters consecutively", self.max_repeat));
+        }
+
+        let lower = password.to_lowercase();
+        if self.check_username && !username.is_empty() {
+            let name = username.to_lowercase();
+            let reversed: String = name.chars().rev().collect();
+            if lower.contains(&name) || lower.contains(&reversed) {
+                return Err("The password contains the user name in some form".into());
+            }
+        }
+
+        if let Some(old) = old.filter(|o| !o.is_empty()) {
+            if password == old {
+                return Err("The password is the same as the old one".into());
+            }
+            let old_lower = old.to_lowercase();
+            if lower.contains(&old_lower) || old_lower.contains(&lower) {
+                return Err("The password is too similar to the old one".into());
+            }
+            let new_chars = password.chars().filter(|c| !old.contains(*c)).count();
+            if new_chars < self.difok {
+                return Err("The password is too similar to the old one".into());
+            }
+        }
+
+        if self.in_dictionary(&lower) {
+            return Err("The password is based on a dictionary word".into());
+        }
+
+        Ok(())
+    }
+
+    /// The password itself, or with digits and symbols stripped off the ends, is listed.
+    fn in_dictionary(&self, lower: &str) -> bool {
+        let content = match fs::read_to_string(&self.dict_path) {
+            Ok(content) => content,
+            Err(_) => return false,
+        };
+        let stripped = lower.trim_matches(|c: char| !c.is_alphabetic());
+        content
+            .lines()
+            .map(|w| w.trim().to_lowercase())
+            .any(|w| !w.is_empty() && (w == lower || (w.len() >= 4 && w == stripped)))
+    }
+}
+
+fn longest_run(s: &str) -> usize {
+    let mut longest = 0;
+    let mut run = 0;
+    let mut prev = None;
+    for c in s.chars() {
+        run = if Some(c) == prev { run + 1 } else { 1 };
+        longest = longest.max(run);
+        prev = Some(c);
+    }
+    longest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> PasswordPolicy {
+        PasswordPolicy { dict_path: PathBuf::from("/nonexistent"), ..Default::default() }
+    }
+
+    #[test]
+    fn parse_config() {
+        let policy = PasswordPolicy::parse("minlen = 12\n# minclass = 4\nmaxrepeat=0 # off\nusercheck = 0\ndifok = many\nbogus = 1\n");
+        assert_eq!(policy.min_length, 12);
+        assert_eq!(policy.min_classes, 2);
+        assert_eq!(policy.max_repeat, 0);
+        assert!(!policy.check_username);
+        assert_eq!(policy.difok, 3);
+    }
+
+    #[test]
+    fn rules() {
+        let p = policy();
+        assert!(p.check("Tr0ub4dor", None, "alice").is_ok());
+        assert!(p.check("Sh0rt", None, "alice").unwrap_err().contains("shorter than 8"));
+        assert!(p.check("alllowercase", None, "alice").unwrap_err().contains("character classes"));
+        assert!(p.check("Passsss1word", None, "alice").unwrap_err().contains("more than 3 same"));
+        assert!(p.check("xAlice123", None, "alice").unwrap_err().contains("user name"));
+        assert!(p.check("xecila123", None, "alice").unwrap_err().contains("user name"));
+
+        let relaxed = PasswordPolicy { max_repeat: 0, check_username: false, ..policy() };
+        assert!(relaxed.check("Passsss1word", None, "alice").is_ok());
+        assert!(relaxed.check("xAlice123", None, "alice").is_ok());
+    }
+
+    #[test]
+    fn against_the_old_password() {
+        let p = policy();
+        assert!(p.check("Tr0ub4dor", Some("Tr0ub4dor"), "alice").unwrap_err().contains("same as the old"));
+        assert!(p.check("Tr0ub4dor!", Some("Tr0ub4dor"), "alice").unwrap_err().contains("too similar"));
+        // Only two characters that weren't in the old one
+        assert!(p.check("Dor4buort", Some("Tr0ub4dor"), "alice").unwrap_err().contains("too similar"));
+        assert!(p.check("Correct9Horse", Some("Tr0ub4dor"), "alice").is_ok());
+        assert!(p.check("Correct9Horse", Some(""), "alice").is_ok());
+    }
+
+    #[test]
+    fn dictionary() {
+        let dict = std::env::temp_dir().join(format!("vantara-dict-{}", std::process::id()));
+        fs::write(&dict, "dragon\nSunshine\n\nabc\n").unwrap();
+        let p = PasswordPolicy { dict_path: dict.clone(), ..policy() };
+
+        assert!(p.check("SUNSHINE1", None, "alice").unwrap_err().contains("dictionary"));
+        assert!(p.check("123dragon!", None, "alice").unwrap_err().contains("dictionary"));
+        // Short words only count when they are the whole password
+        assert!(p.check("12abc!!x", None, "alice").is_ok());
+        assert!(p.check("Dragonfly9", None, "alice").is_ok());
+        let _ = fs::remove_file(&dict);
+    }
+
+    #[test]
+    fn runs() {
+        assert_eq!(longest_run(""), 0);
+        assert_eq!(longest_run("abc"), 1);
+        assert_eq!(longest_run("aabbbbc"), 4);
+    }
+}
@@ -0,0 +1,35 @@
+use crate::auth::{AuthModule, AuthContext, AuthResult};
+
+/// Succeeds when the caller already is root, for `auth sufficient rootok` in
+/// stacks like su where root shouldn't need a password.
+pub struct RootOk {}
+
+impl RootOk {
+    pub fn new() -> Self {
+        RootOk {}
+    }
+}
+
+impl Default for RootOk {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AuthModule for RootOk {
+    fn auth(&self, _ctx: &mut AuthContext) -> AuthResult {
+        if unsafe { libc::getuid() } == 0 {
+            AuthResult::Success
+        } else {
+            AuthResult::Failure("Not called by root".into())
+        }
+    }
+
+    fn account(&self, _ctx: &mut AuthContext) -> AuthResult {
+        AuthResult::Success
+    }
+
+    fn open_session(&self, _ctx: &mut AuthContext) -> AuthResult {
+        AuthResult::Success
+    }
+}
@@ -0,0 +1,117 @@
+use std::fs::{self, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::os::unix::fs::{chown, DirBuilderExt, OpenOptionsExt, PermissionsExt};
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use crate::auth::{AuthModule, AuthContext, AuthResult};
+use crate::nss::getpwnam;
+
+pub const DEFAULT_RUNTIME_ROOT: &str = "/run/user";
+/// One file per uid listing the pids of its open sessions
+const SESSIONS_DIR: &str = ".sessions";
+
+fn is_alive(pid: i32) -> bool {
+    pid > 0 && (unsafe { libc::kill(pid, 0) } == 0 || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM))
+}
+
+/// Add or remove this process in the session list of `uid`, dropping sessions
+/// whose process is gone. Returns how many sessions are left.
+fn update_sessions(root: &Path, uid: u32, add: bool) -> io::Result<usize> {
+    let dir = root.join(SESSIONS_DIR);
+    fs::DirBuilder::new().recursive(true).mode(0o700).create(&dir)?;
+    let mut file = OpenOptions::new().read(true).write(true).create(true).truncate(false).mode(0o600).open(dir.join(uid.to_string()))?;
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let mut content = String::new();
+    file.read_to_string(&mut content)?;
+    let own = std::process::id() as i32;
+    let mut pids: Vec<i32> = content
+        .lines()
+        .filter_map(|l| l.trim().parse().ok())
+        .filter(|pid| *pid != own && is_alive(*pid))
+        .collect();
+    if add {
+        pids.push(own);
+    }
+
+    let text: String = pids.iter().map(|pid| format!("{}\n", pid)).collect();
+    file.set_len(0)?;
+    file.seek(SeekFrom::Start(0))?;
+    file.write_all(text.as_bytes())?;
+    Ok(pids.len()) // the lock goes with the file
+}
+
+/// Session module that gives each logged in user `/run/user/<uid>`, private to
+/// them and set as XDG_RUNTIME_DIR. It lives while the user has a session open
+/// and is removed with the last one.
+pub struct RuntimeDir {
+    root: PathBuf,
+}
+
+impl RuntimeDir {
+    pub fn new() -> Self {
+        RuntimeDir { root: PathBuf::from(DEFAULT_RUNTIME_ROOT) }
+    }
+}
+
+impl Default for RuntimeDir {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AuthModule for RuntimeDir {
+    fn auth(&self, _ctx: &mut AuthContext) -> AuthResult {
+        AuthResult::Success
+    }
+
+    fn account(&self, _ctx: &mut AuthContext) -> AuthResult {
+        AuthResult::Success
+    }
+
+    fn open_session(&self, ctx: &mut AuthContext) -> AuthResult {
+        let user = match getpwnam(&ctx.username) {
+            Some(user) => user,
+            None => return AuthResult::Failure("User not found".into()),
+        };
+        let path = self.root.join(user.uid.to_string());
+
+        let result = fs::DirBuilder::new()
+            .recursive(true)
+            .mode(0o755)
+            .create(&self.root)
+            .and_then(|_| match fs::DirBuilder::new().mode(0o700).create(&path) {
+                Err(e) if e.kind() != io::ErrorKind::AlreadyExists => Err(e),
+                _ => Ok(()),
+            })
+            // Left over from a crash or made by someone else, take it back
+            .and_then(|_| chown(&path, Some(user.uid), Some(user.gid)))
+            .and_then(|_| fs::set_permissions(&path, fs::Permissions::from_mode(0o700)))
+            .and_then(|_| update_sessions(&self.root, user.uid, true));
+
+        match result {
+            Ok(_) => {
+                ctx.set_env("XDG_RUNTIME_DIR", &path.to_string_lossy());
+                AuthResult::Success
+            }
+            Err(e) => AuthResult::Failure(format!("Cannot create {}: {}", path.display(), e)),
+        }
+    }
+
+    fn close_session(&self, ctx: &mut AuthContext) -> AuthResult {
+        let user = match getpwnam(&ctx.username) {
+            Some(user) => user,
+            None => return AuthResult::Success,
+        };
+        match update_sessions(&self.root, user.uid, false) {
+            Ok(0) => match fs::remove_dir_all(self.root.join(user.uid.to_string())) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => AuthResult::Failure(format!("Cannot remove runtime directory: {}", e)),
+                _ => AuthResult::Success,
+            },
+            Ok(_) => AuthResult::Success,
+            Err(e) => AuthResult::Failure(format!("Cannot update session list: {}", e)),
+        }
+    }
+}
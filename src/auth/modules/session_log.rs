@@ -1,25 +1,126 @@
-use chrono::Local;
-use chrono::{NaiveDateTime};
-use std::fs::OpenOptions;
-use std::io::Write;
-use std::os::unix::fs::MetadataExt;
+use chrono::{Local, TimeZone};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::os::unix::fs::{FileExt, OpenOptionsExt};
+use std::os::unix::io::AsRawFd;
 use libc::{isatty, ttyname};
 use std::ffi::CStr;
-use std::fs::File;
-use std::io::{BufReader, BufRead};
-use crate::common::{safe_println};
+use crate::auth::modules::faillock::now_secs;
+use crate::nss::getpwnam;
+use crate::common::safe_println;
 
-const DEFAULT_USER_LOG_PATH: &str = "/var/.session.log";
+/// Sessions that are open right now, one slot per terminal
+pub const DEFAULT_UTMP_PATH: &str = "/run/utmp";
+/// Every login, logout, boot and shutdown, only ever appended to
+pub const DEFAULT_WTMP_PATH: &str = "/var/log/wtmp";
+/// Last login of each user, the record of uid N is at N * LASTLOG_RECORD_SIZE
+pub const DEFAULT_LASTLOG_PATH: &str = "/var/log/lastlog";
+
+const TTY_LEN: usize = 32;
+const USER_LEN: usize = 32;
+const HOST_LEN: usize = 64;
+/// kind, 3 bytes padding, pid, time, tty, user, host
+pub const RECORD_SIZE: usize = 1 + 3 + 4 + 8 + TTY_LEN + USER_LEN + HOST_LEN;
+/// time, tty, host
+pub const LASTLOG_RECORD_SIZE: usize = 8 + TTY_LEN + HOST_LEN;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RecordKind {
+    /// A free utmp slot
+    Empty = 0,
+    Boot = 1,
+    Shutdown = 2,
+    Login = 3,
+    Logout = 4,
+}
 
 #[derive(Debug, Clone)]
-struct SessionEntry {
-    username: String,
-    tty: String,
-    ip: String,
-    time: String,
+pub struct SessionRecord {
+    pub kind: RecordKind,
+    /// The process that owns the session, it is over once that process is gone
+    pub pid: u32,
+    pub time: i64,
+    /// Without the `/dev/` prefix, e.g. `tty1` or `pts/0`
+    pub tty: String,
+    pub user: String,
+    /// Empty for local logins
+    pub host: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct LastLogin {
+    pub time: i64,
+    pub tty: String,
+    pub host: String,
+}
+
+fn put_str(buf: &mut [u8], value: &str) {
+    let mut len = value.len().min(buf.len());
+    while !value.is_char_boundary(len) {
+        len -= 1;
+    }
+    buf[..len].copy_from_slice(&value.as_bytes()[..len]);
+}
+
+fn get_str(buf: &[u8]) -> String {
+    let end = buf.iter().position(|b| *b == 0).unwrap_or(buf.len());
+    String::from_utf8_lossy(&buf[..end]).into_owned()
+}
+
+impl SessionRecord {
+    fn new(kind: RecordKind, user: &str) -> Self {
+        SessionRecord {
+            kind,
+            pid: std::process::id(),
+            time: now_secs() as i64,
+            tty: get_tty().trim_start_matches("/dev/").to_string(),
+            user: user.to_string(),
+            host: get_env_ip().unwrap_or_default(),
+        }
+    }
+
+    pub fn encode(&self) -> [u8; RECORD_SIZE] {
+        let mut buf = [0u8; RECORD_SIZE];
+        buf[0] = self.kind as u8;
+        buf[4..8].copy_from_slice(&self.pid.to_le_bytes());
+        buf[8..16].copy_from_slice(&self.time.to_le_bytes());
+        let (tty, rest) = buf[16..].split_at_mut(TTY_LEN);
+        let (user, host) = rest.split_at_mut(USER_LEN);
+        put_str(tty, &self.tty);
+        put_str(user, &self.user);
+        put_str(host, &self.host);
+        buf
+    }
+
+    pub fn decode(buf: &[u8; RECORD_SIZE]) -> Option<Self> {
+        let kind = match buf[0] {
+            0 => RecordKind::Empty,
+            1 => RecordKind::Boot,
+            2 => RecordKind::Shutdown,
+            3 => RecordKind::Login,
+            4 => RecordKind::Logout,
+            _ => return None,
+        };
+        let tty_end = 16 + TTY_LEN;
+        let user_end = tty_end + USER_LEN;
+        Some(SessionRecord {
+            kind,
+            pid: u32::from_le_bytes(buf[4..8].try_into().ok()?),
+            time: i64::from_le_bytes(buf[8..16].try_into().ok()?),
+            tty: get_str(&buf[16..tty_end]),
+            user: get_str(&buf[tty_end..user_end]),
+            host: get_str(&buf[user_end..]),
+        })
+    }
+
+    /// Whether the owning process still runs.
+    pub fn is_alive(&self) -> bool {
+        let pid = self.pid as libc::pid_t;
+        pid > 0 && (unsafe { libc::kill(pid, 0) } == 0 || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM))
+    }
 }
 
-fn get_tty() -> String {
+pub fn get_tty() -> String {
     unsafe {
         if isatty(0) == 1 {
             let ptr = ttyname(0);
@@ -32,149 +133,190 @@ fn get_tty() -> String {
     "unknown".to_string()
 }
 
-fn get_uid() -> u32 {
-    std::fs::metadata("/proc/self").map(|m| m.uid()).unwrap_or(0)
-}
-
-pub fn log_login(username: &str) {
-    write_log("LOGIN", username);
+fn get_env_ip() -> Option<String> {
+    std::env::var("SSH_CLIENT")
+        .ok()
+        .and_then(|v| v.split_whitespace().next().map(|s| s.to_string()))
 }
 
-pub fn log_logout(username: &str) {
-    write_log("LOGOUT", username);
+fn read_records(file: &mut File) -> io::Result<Vec<SessionRecord>> {
+    let mut content = Vec::new();
+    file.seek(SeekFrom::Start(0))?;
+    file.read_to_end(&mut content)?;
+    Ok(content
+        .chunks_exact(RECORD_SIZE)
+        .filter_map(|chunk| chunk.try_into().ok().and_then(SessionRecord::decode))
+        .collect())
 }
 
-fn write_log(event: &str, username: &str) {
-    let now = Local::now();
-    let tty = get_tty();
-    let uid = get_uid();
-    let ip = get_env_ip().unwrap_or_else(|| "local".to_string());
-
-    let log_line = format!(
-        "[{}] user={} uid={} tty={} ip={} time={}\n",
-        event,
-        username,
-        uid,
-        tty,
-        ip,
-        now.format("%Y-%m-%d %H:%M:%S")
-    );
+/// Run `update` on the utmp records while holding an exclusive lock, then write
+/// back the slots it changed.
+fn update_utmp<F: FnOnce(&mut Vec<SessionRecord>)>(update: F) -> io::Result<()> {
+    let mut file = OpenOptions::new().read(true).write(true).create(true).truncate(false).mode(0o644).open(DEFAULT_UTMP_PATH)?;
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
 
-    let mut file = OpenOptions::new()
-        .append(true)
-        .create(true)
-        .open(DEFAULT_USER_LOG_PATH)
-        .unwrap_or_else(|_| panic!("Failed to open session log file"));
+    let mut records = read_records(&mut file)?;
+    let before: Vec<[u8; RECORD_SIZE]> = records.iter().map(SessionRecord::encode).collect();
+    update(&mut records);
 
-    let _ = file.write_all(log_line.as_bytes());
+    for (slot, record) in records.iter().enumerate() {
+        let encoded = record.encode();
+        if before.get(slot) != Some(&encoded) {
+            file.write_all_at(&encoded, (slot * RECORD_SIZE) as u64)?;
+        }
+    }
+    Ok(()) // the lock goes with the file
 }
 
-fn get_env_ip() -> Option<String> {
-    std::env::var("SSH_CLIENT")
-        .ok()
-        .and_then(|v| v.split_whitespace().next().map(|s| s.to_string()))
+fn append_wtmp(record: &SessionRecord) -> io::Result<()> {
+    let mut file = OpenOptions::new().append(true).create(true).mode(0o644).open(DEFAULT_WTMP_PATH)?;
+    file.write_all(&record.encode())
 }
 
-pub fn print_logged_in_users() {
-    let file = File::open(DEFAULT_USER_LOG_PATH)
-        .unwrap_or_else(|_| panic!("Failed to open session log file"));
+fn write_lastlog(uid: u32, record: &SessionRecord) -> io::Result<()> {
+    let file = OpenOptions::new().write(true).create(true).truncate(false).mode(0o644).open(DEFAULT_LASTLOG_PATH)?;
+    let mut buf = [0u8; LASTLOG_RECORD_SIZE];
+    buf[..8].copy_from_slice(&record.time.to_le_bytes());
+    put_str(&mut buf[8..8 + TTY_LEN], &record.tty);
+    put_str(&mut buf[8 + TTY_LEN..], &record.host);
+    file.write_all_at(&buf, uid as u64 * LASTLOG_RECORD_SIZE as u64)
+}
 
-    let reader = BufReader::new(file);
-    let mut active_sessions: Vec<SessionEntry> = vec![];
+/// Record a session opened by this process on its terminal. Failing to write
+/// the records must not stop a login, so errors are only reported.
+pub fn log_login(username: &str) {
+    let record = SessionRecord::new(RecordKind::Login, username);
 
-    for line in reader.lines().flatten() {
-        if line.starts_with("[LOGIN]") {
-            if let Some(entry) = parse_log_line(&line) {
-                active_sessions.push(entry);
-            }
-        } else if line.starts_with("[LOGOUT]") {
-            if let Some(entry) = parse_log_line(&line) {
-                // Cari sesi padan (username + tty + ip)
-                if let Some(pos) = active_sessions.iter().position(|s| {
-                    s.username == entry.username && s.tty == entry.tty && s.ip == entry.ip
-                }) {
-                    active_sessions.remove(pos);
-                }
-            }
+    let result = update_utmp(|records| {
+        // Reuse the slot of this terminal, or one whose session is over
+        let free = records.iter().position(|r| {
+            r.tty == record.tty || r.kind != RecordKind::Login || !r.is_alive()
+        });
+        match free {
+            Some(slot) => records[slot] = record.clone(),
+            None => records.push(record.clone()),
         }
-    }
+    })
+    .and_then(|_| append_wtmp(&record))
+    .and_then(|_| match getpwnam(username) {
+        Some(user) => write_lastlog(user.uid, &record),
+        None => Ok(()),
+    });
 
-    for session in active_sessions {
-        safe_println(format_args!(
-            "{:<12} {:<15} {:<20} {}",
-            session.username, session.tty, session.ip, session.time
-        ));
+    if let Err(e) = result {
+        safe_println(format_args!("Failed to record login: {}", e));
     }
 }
 
-pub fn get_last_login(username: &str) -> Option<String> {
-    use std::fs::File;
-    use std::io::{BufRead, BufReader};
-
-    let file = File::open(DEFAULT_USER_LOG_PATH).ok()?;
-    let reader = BufReader::new(file);
-
-    let mut last_time_raw: Option<String> = None;
-
-    for line in reader.lines().flatten() {
-        if line.contains("[LOGIN]") && line.contains(&format!("user={}", username)) {
-            let parts: Vec<&str> = line.split_whitespace().collect();
-            for i in 0..parts.len() {
-                if parts[i].starts_with("time=") && i + 1 < parts.len() {
-                    let date_part = parts[i].strip_prefix("time=").unwrap_or("unknown");
-                    let time_part = parts[i + 1];
-                    let full = format!("{} {}", date_part, time_part);
-                    last_time_raw = Some(full);
-                }
-            }
+pub fn log_logout(username: &str) {
+    let record = SessionRecord::new(RecordKind::Logout, username);
+
+    let result = update_utmp(|records| {
+        for slot in records.iter_mut().filter(|r| r.kind == RecordKind::Login && r.pid == record.pid) {
+            slot.kind = RecordKind::Empty;
         }
+    })
+    .and_then(|_| append_wtmp(&record));
+
+    if let Err(e) = result {
+        safe_println(format_args!("Failed to record logout: {}", e));
     }
+}
 
-    last_time_raw.and_then(|s| format_login_time(&s))
+/// Called by init once filesystems are up. Sessions from before the boot are
+/// all gone, so the live records start empty.
+pub fn log_boot() -> io::Result<()> {
+    File::create(DEFAULT_UTMP_PATH)?;
+    let mut record = SessionRecord::new(RecordKind::Boot, "reboot");
+    record.tty = "~".to_string();
+    append_wtmp(&record)
 }
 
-fn format_login_time(datetime_str: &str) -> Option<String> {
-    // Expect string like "2025-07-28 11:29:00"
-    let dt = NaiveDateTime::parse_from_str(datetime_str, "%Y-%m-%d %H:%M:%S").ok()?;
+/// For a clean shutdown, so `last` can tell it apart from a crash.
+pub fn log_shutdown() -> io::Result<()> {
+    let mut record = SessionRecord::new(RecordKind::Shutdown, "shutdown");
+    record.tty = "~".to_string();
+    append_wtmp(&record)
+}
 
-    // Format seperti: "Mon Jul 28 11:29:00 2025"
-    let formatted = dt.format("%a %b %d %H:%M:%S %Y").to_string();
+/// Open sessions whose process still runs. Stale slots, from sessions that
+/// ended without a logout record, are freed on the way when we may write.
+pub fn active_sessions() -> Vec<SessionRecord> {
+    let mut active = Vec::new();
+    let pruned = update_utmp(|records| {
+        for record in records.iter_mut().filter(|r| r.kind == RecordKind::Login) {
+            if record.is_alive() {
+                active.push(record.clone());
+            } else {
+                record.kind = RecordKind::Empty;
+            }
+        }
+    });
 
-    Some(formatted)
+    if pruned.is_err() {
+        active = File::open(DEFAULT_UTMP_PATH)
+            .and_then(|mut file| read_records(&mut file))
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|r| r.kind == RecordKind::Login && r.is_alive())
+            .collect();
+    }
+    active
 }
 
-fn parse_log_line(line: &str) -> Option<SessionEntry> {
-    let mut username = String::from("unknown");
-    let mut tty = String::from("unknown");
-    let mut ip = String::from("unknown");
-    let mut time = String::from("unknown");
+/// Everything in wtmp, oldest first.
+pub fn session_history() -> Vec<SessionRecord> {
+    File::open(DEFAULT_WTMP_PATH)
+        .and_then(|mut file| read_records(&mut file))
+        .unwrap_or_default()
+}
 
-    let parts: Vec<&str> = line.split_whitespace().collect();
-    let mut i = 0;
+pub fn get_lastlog(uid: u32) -> Option<LastLogin> {
+    let file = File::open(DEFAULT_LASTLOG_PATH).ok()?;
+    let mut buf = [0u8; LASTLOG_RECORD_SIZE];
+    file.read_exact_at(&mut buf, uid as u64 * LASTLOG_RECORD_SIZE as u64).ok()?;
 
-    while i < parts.len() {
-        let part = parts[i];
+    let time = i64::from_le_bytes(buf[..8].try_into().ok()?);
+    if time == 0 {
+        return None;
+    }
+    Some(LastLogin {
+        time,
+        tty: get_str(&buf[8..8 + TTY_LEN]),
+        host: get_str(&buf[8 + TTY_LEN..]),
+    })
+}
 
-        if let Some(val) = part.strip_prefix("user=") {
-            username = val.to_string();
-        } else if let Some(val) = part.strip_prefix("tty=") {
-            tty = val.to_string();
-        } else if let Some(val) = part.strip_prefix("ip=") {
-            ip = val.to_string();
-        } else if part.starts_with("time=") {
-            let date_part = part.strip_prefix("time=").unwrap_or("unknown");
-            let time_part = if i + 1 < parts.len() { parts[i + 1] } else { "unknown" };
-            time = format!("{} {}", date_part, time_part);
-            i += 1; // skip time_part
-        }
+/// e.g. `Mon Jul 28 11:29:00 2025`
+pub fn format_login_time(time: i64) -> String {
+    Local
+        .timestamp_opt(time, 0)
+        .single()
+        .map(|t| t.format("%a %b %d %H:%M:%S %Y").to_string())
+        .unwrap_or_else(|| time.to_string())
+}
 
-        i += 1;
+/// e.g. `Mon Jul 28 11:29:00 2025 on tty1`, for the "Last login" line.
+pub fn get_last_login(username: &str) -> Option<String> {
+    let user = getpwnam(username)?;
+    let last = get_lastlog(user.uid)?;
+    let mut line = format!("{} on {}", format_login_time(last.time), last.tty);
+    if !last.host.is_empty() {
+        line.push_str(&format!(" from {}", last.host));
     }
+    Some(line)
+}
 
-    Some(SessionEntry {
-        username,
-        tty,
-        ip,
-        time,
-    })
+pub fn print_logged_in_users() {
+    for session in active_sessions() {
+        let time = Local
+            .timestamp_opt(session.time, 0)
+            .single()
+            .map(|t| t.format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or_default();
+        let host = if session.host.is_empty() { String::new() } else { format!("({})", session.host) };
+        safe_println(format_args!("{:<12} {:<12} {} {}", session.user, session.tty, time, host));
+    }
 }
@@ -1,5 +1,6 @@
 use crate::auth::auth::{AuthModule, AuthContext, AuthResult};
-use crate::auth::modules::session_log::log_login;
+use crate::audit::{self, EventKind, Outcome};
+use crate::auth::modules::session_log::{log_login, log_logout};
 
 pub struct SessionLogger {}
 
@@ -18,8 +19,15 @@ impl AuthModule for SessionLogger {
         AuthResult::Success
     }
 
-    fn session(&self, ctx: &mut AuthContext) -> AuthResult {
+    fn open_session(&self, ctx: &mut AuthContext) -> AuthResult {
         log_login(&ctx.username);
+        audit::record(EventKind::Login, Outcome::Success, &ctx.username, "");
+        AuthResult::Success
+    }
+
+    fn close_session(&self, ctx: &mut AuthContext) -> AuthResult {
+        log_logout(&ctx.username);
+        audit::record(EventKind::Logout, Outcome::Success, &ctx.username, "");
         AuthResult::Success
     }
 }
@@ -1,45 +1,204 @@
 use std::fs::File;
 use std::io::{BufRead, BufReader};
-use sha2::{Sha512, Digest};
-use std::fs::OpenOptions;
-use std::io::Write;
 use rand::Rng;
 use rand::distr::Alphanumeric;
+use crate::auth::modules::account_db::AccountDb;
+pub use crate::auth::modules::crypt::{hash_password, verify_password, HashMethod, Verification};
 
+/// One line of /etc/shadow. Day counts are days since 1970-01-01, `None` for an
+/// empty field.
+#[derive(Debug, Clone)]
 pub struct ShadowEntry {
     pub username: String,
-    pub algo_id: String,
-    pub salt: String,
-    pub hash: String
+    /// The hash field as stored, e.g. `$6$rounds=N$salt$hash`, or `!`/`*` for locked accounts
+    pub password: String,
+    /// Day of the last password change, `Some(0)` forces a change at next login
+    pub last_change: Option<i64>,
+    pub min_days: Option<i64>,
+    pub max_days: Option<i64>,
+    pub warn_days: Option<i64>,
+    /// Days after the password expired that it can still be changed at login
+    pub inactive_days: Option<i64>,
+    /// Day the account expires
+    pub expire_date: Option<i64>,
+    pub reserved: String,
 }
 
-const DEFAULT_SHADOW_FILE: &str = "/etc/shadow";
+/// What the ageing fields say about an account on a given day.
+#[derive(Debug, PartialEq)]
+pub enum AgeingStatus {
+    Valid,
+    /// Password expires in this many days
+    ExpiresSoon(i64),
+    /// Must be changed before the login goes on
+    MustChange,
+    /// Expired longer than the inactivity period, only root can help
+    Inactive,
+    AccountExpired,
+}
+
+/// `max_days` at or above this means the password never expires, as in shadow-utils.
+const NEVER_EXPIRES: i64 = 10000;
+
+pub const DEFAULT_SHADOW_FILE: &str = "/etc/shadow";
+
+impl ShadowEntry {
+    pub fn parse(line: &str) -> Option<Self> {
+        let fields: Vec<&str> = line.split(':').collect();
+        if fields.len() < 2 || fields[0].is_empty() {
+            return None;
+        }
+        let day = |i: usize| fields.get(i).and_then(|f| f.parse::<i64>().ok());
+        Some(ShadowEntry {
+            username: fields[0].to_string(),
+            password: fields[1].to_string(),
+            last_change: day(2),
+            min_days: day(3),
+            max_days: day(4),
+            warn_days: day(5),
+            inactive_days: day(6),
+            expire_date: day(7),
+            reserved: fields.get(8).unwrap_or(&"").to_string(),
+        })
+    }
+
+    pub fn to_line(&self) -> String {
+        let day = |d: Option<i64>| d.map(|d| d.to_string()).unwrap_or_default();
+        format!(
+            "{}:{}:{}:{}:{}:{}:{}:{}:{}",
+            self.username,
+            self.password,
+            day(self.last_change),
+            day(self.min_days),
+            day(self.max_days),
+            day(self.warn_days),
+            day(self.inactive_days),
+            day(self.expire_date),
+            self.reserved
+        )
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.password.starts_with('!')
+    }
+
+    pub fn lock(&mut self) {
+        if !self.is_locked() {
+            self.password.insert(0, '!');
+        }
+    }
+
+    /// Refuses when that would leave an account without any password.
+    pub fn unlock(&mut self) -> Result<(), String> {
+        let unlocked = self.password.trim_start_matches('!');
+        if unlocked.is_empty() {
+            return Err("unlocking would leave the account without a password, set one with passwd".into());
+        }
+        self.password = unlocked.to_string();
+        Ok(())
+    }
+
+    /// `L` locked, `NP` no password, `P` usable password, as `passwd -S` shows it.
+    pub fn status(&self) -> &'static str {
+        if self.is_locked() || self.password.starts_with('*') {
+            "L"
+        } else if self.password.is_empty() {
+            "NP"
+        } else {
+            "P"
+        }
+    }
+
+    /// Day the password expires, `None` when ageing is off.
+    pub fn password_expires(&self) -> Option<i64> {
+        match (self.last_change, self.max_days) {
+            (Some(last), Some(max)) if last > 0 && (0..NEVER_EXPIRES).contains(&max) => Some(last + max),
+            _ => None,
+        }
+    }
+
+    /// Last day the expired password can still be changed at login.
+    pub fn password_inactive(&self) -> Option<i64> {
+        Some(self.password_expires()? + self.inactive_days.filter(|d| *d >= 0)?)
+    }
+
+    pub fn ageing_status(&self, today: i64) -> AgeingStatus {
+        if self.expire_date.is_some_and(|e| e > 0 && today >= e) {
+            return AgeingStatus::AccountExpired;
+        }
+        if self.password_inactive().is_some_and(|d| today >= d) {
+            return AgeingStatus::Inactive;
+        }
+        if self.last_change == Some(0) || self.password_expires().is_some_and(|d| today >= d) {
+            return AgeingStatus::MustChange;
+        }
+        match (self.password_expires(), self.warn_days) {
+            (Some(expires), Some(warn)) if expires - today <= warn => AgeingStatus::ExpiresSoon(expires - today),
+            _ => AgeingStatus::Valid,
+        }
+    }
+}
+
+pub fn today_days() -> i64 {
+    chrono::Utc::now().timestamp().div_euclid(86400)
+}
+
+/// `Oct 18, 2026`, like chage prints dates.
+pub fn format_day(day: i64) -> String {
+    chrono::DateTime::from_timestamp(day * 86400, 0)
+        .map(|d| d.format("%b %d, %Y").to_string())
+        .unwrap_or_else(|| day.to_string())
+}
+
+/// `YYYY-MM-DD` or a plain day number.
+pub fn parse_day(value: &str) -> Option<i64> {
+    if let Ok(n) = value.parse::<i64>() {
+        return Some(n);
+    }
+    let date = chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d").ok()?;
+    Some(date.and_hms_opt(0, 0, 0)?.and_utc().timestamp() / 86400)
+}
 
 pub fn get_shadow_entry(username: &str) -> Option<ShadowEntry> {
     let file = File::open(DEFAULT_SHADOW_FILE).ok()?;
-    for line in BufReader::new(file).lines().flatten() {
-        let parts: Vec<&str> = line.split(':').collect();
-        if parts.len() >= 2 && parts[0] == username && parts[1].starts_with('$') {
-            let hash_parts: Vec<&str> = parts[1].split('$').collect();
-            if hash_parts.len() >= 4 {
-                return Some(ShadowEntry {
-                    username: hash_parts[0].to_string(),
-                    algo_id: hash_parts[1].parse().ok()?,
-                    salt: hash_parts[2].to_string(),
-                    hash: hash_parts[3].to_string(),
-                });
-            }
+    BufReader::new(file)
+        .lines()
+        .map_while(Result::ok)
+        .filter_map(|line| ShadowEntry::parse(&line))
+        .find(|entry| entry.username == username)
+}
+
+/// Change the entry of `username` while the account database is locked, so changes
+/// made by others since it was last read are kept.
+pub fn modify_shadow_entry<F>(username: &str, change: F) -> std::io::Result<()>
+where
+    F: FnOnce(&mut ShadowEntry),
+{
+    let mut db = AccountDb::open().map_err(std::io::Error::other)?;
+    let entry = db.shadow.find_mut(username).ok_or_else(|| {
+        std::io::Error::new(std::io::ErrorKind::NotFound, format!("no shadow entry for {}", username))
+    })?;
+    change(entry);
+    db.commit().map_err(std::io::Error::other)
+}
+
+/// Replace only the hash field, ageing stays as it was (used when rehashing). Left
+/// alone when the stored hash is no longer `verified`, e.g. it was locked meanwhile.
+pub fn update_shadow_password(username: &str, verified: &str, hash: &str) -> std::io::Result<()> {
+    modify_shadow_entry(username, |entry| {
+        if entry.password == verified {
+            entry.password = hash.to_string();
         }
-    }
-    None
+    })
 }
 
-pub fn hash_password_with_salt(salt: &str, password: &str) -> String {
-    let mut hasher = Sha512::new();
-    hasher.update(salt.as_bytes());
-    hasher.update(password.as_bytes());
-    let result = hasher.finalize();
-    hex::encode(result)
+/// Set a new password chosen by the user, which restarts the ageing clock.
+pub fn change_shadow_password(username: &str, password: &str) -> std::io::Result<()> {
+    let hash = hash_password(password, &HashMethod::configured()).map_err(std::io::Error::other)?;
+    modify_shadow_entry(username, |entry| {
+        entry.password = hash;
+        entry.last_change = Some(today_days());
+    })
 }
 
 pub fn generate_salt(len: usize) -> String {
@@ -50,23 +209,84 @@ pub fn generate_salt(len: usize) -> String {
     .collect()
 }
 
-pub fn add_user_to_shadow_file(username: &str, password: &str) -> std::io::Result<()> {
-    let salt = generate_salt(16);
-    let hash = hash_password_with_salt(&salt, password);
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    let entry = format!(
-        "{}:$6${}${}:::::::\n",
-        username,
-        salt,
-        hash
-    );
+    fn entry(line: &str) -> ShadowEntry {
+        ShadowEntry::parse(line).unwrap()
+    }
 
-    let mut file = OpenOptions::new()
-        .append(true)
-        .create(true)
-        .open(DEFAULT_SHADOW_FILE)?;
+    #[test]
+    fn parse_and_write_back() {
+        let line = "alice:$6$salt$hash:19000:1:90:7:14:19500:";
+        let e = entry(line);
+        assert_eq!(e.password, "$6$salt$hash");
+        assert_eq!((e.last_change, e.min_days, e.max_days, e.warn_days), (Some(19000), Some(1), Some(90), Some(7)));
+        assert_eq!((e.inactive_days, e.expire_date), (Some(14), Some(19500)));
+        assert_eq!(e.to_line(), line);
 
-    writeln!(file, "{}", entry)?;
+        // Empty and missing fields are None, and written back empty
+        let e = entry("bob:!");
+        assert_eq!((e.last_change, e.max_days, e.expire_date), (None, None, None));
+        assert_eq!(e.to_line(), "bob:!:::::::");
+        assert!(ShadowEntry::parse(":x:1").is_none());
+        assert!(ShadowEntry::parse("nocolon").is_none());
+    }
+
+    #[test]
+    fn lock_and_status() {
+        let mut e = entry("alice:$6$x$y:19000::::::");
+        assert_eq!(e.status(), "P");
+        e.lock();
+        e.lock();
+        assert_eq!(e.password, "!$6$x$y");
+        assert_eq!(e.status(), "L");
+        e.unlock().unwrap();
+        assert_eq!(e.password, "$6$x$y");
+
+        let mut e = entry("bob:!::::::");
+        assert!(e.unlock().is_err());
+        assert_eq!(entry("carol:::::::").status(), "NP");
+        assert_eq!(entry("daemon:*:::::::").status(), "L");
+    }
 
-    Ok(())
+    #[test]
+    fn expiry_days() {
+        assert_eq!(entry("a:x:100:0:30:7:5::").password_expires(), Some(130));
+        assert_eq!(entry("a:x:100:0:30:7:5::").password_inactive(), Some(135));
+        // No ageing without a last change, a max, or with the "never" max
+        assert_eq!(entry("a:x::0:30:7:5::").password_expires(), None);
+        assert_eq!(entry("a:x:100:0::7:5::").password_expires(), None);
+        assert_eq!(entry("a:x:100:0:99999:7:5::").password_expires(), None);
+        assert_eq!(entry("a:x:100:0:30:7:::").password_inactive(), None);
+    }
+
+    #[test]
+    fn ageing_status() {
+        // Expires on day 130, warned from 123, inactive from 135, account ends on 200
+        let e = entry("a:x:100:0:30:7:5:200:");
+        assert_eq!(e.ageing_status(110), AgeingStatus::Valid);
+        assert_eq!(e.ageing_status(123), AgeingStatus::ExpiresSoon(7));
+        assert_eq!(e.ageing_status(129), AgeingStatus::ExpiresSoon(1));
+        assert_eq!(e.ageing_status(130), AgeingStatus::MustChange);
+        assert_eq!(e.ageing_status(134), AgeingStatus::MustChange);
+        assert_eq!(e.ageing_status(135), AgeingStatus::Inactive);
+        assert_eq!(e.ageing_status(200), AgeingStatus::AccountExpired);
+
+        // Last change 0 forces a change, whatever the rest says
+        assert_eq!(entry("a:x:0:::::").ageing_status(10), AgeingStatus::MustChange);
+        // Without inactive days an expired password can always be changed
+        assert_eq!(entry("a:x:100:0:30::::").ageing_status(10_000), AgeingStatus::MustChange);
+        assert_eq!(entry("a:x:100::::::").ageing_status(10_000), AgeingStatus::Valid);
+        assert_eq!(entry("a:x:100:::::1:").ageing_status(10_000), AgeingStatus::AccountExpired);
+    }
+
+    #[test]
+    fn days() {
+        assert_eq!(parse_day("2026-10-18"), Some(20744));
+        assert_eq!(parse_day("20744"), Some(20744));
+        assert_eq!(parse_day("18/10/2026"), None);
+        assert_eq!(format_day(20744), "Oct 18, 2026");
+    }
 }
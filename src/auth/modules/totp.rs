@@ -0,0 +1,448 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt};
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use hmac::{Hmac, Mac};
+use rand::Rng;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use crate::auth::{AuthModule, AuthContext, AuthResult};
+use crate::auth::modules::crypt::constant_time_eq;
+use crate::auth::modules::faillock::now_secs;
+use crate::common::{read_password, safe_print, safe_println};
+
+/// One file per enrolled user, only readable by root
+pub const DEFAULT_TOTP_DIR: &str = "/etc/security/totp";
+pub const TOTP_STEP: u64 = 30;
+pub const TOTP_DIGITS: u32 = 6;
+/// Steps before and after the current one that are still accepted, for clock skew
+pub const DEFAULT_WINDOW: u64 = 1;
+const SECRET_LEN: usize = 20;
+const RECOVERY_CODES: usize = 10;
+
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+/// RFC 4648 base32 without padding, the form authenticator apps expect.
+pub fn base32_encode(data: &[u8]) -> String {
+    let mut out = String::new();
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+    for byte in data {
+        buffer = (buffer << 8) | *byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            out.push(BASE32_ALPHABET[((buffer >> bits) & 31) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        out.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 31) as usize] as char);
+    }
+    out
+}
+
+/// Accepts lowercase, spaces and padding, as secrets are often typed in by hand.
+pub fn base32_decode(text: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::new();
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+    for c in text.chars().filter(|c| !c.is_whitespace() && *c != '=') {
+        let value = BASE32_ALPHABET.iter().position(|a| *a as char == c.to_ascii_uppercase())? as u32;
+        buffer = (buffer << 5) | value;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((buffer >> bits) as u8);
+        }
+    }
+    Some(out)
+}
+
+/// RFC 4226 HOTP with HMAC-SHA1 and dynamic truncation.
+pub fn hotp(secret: &[u8], counter: u64, digits: u32) -> u32 {
+    let mut mac = Hmac::<Sha1>::new_from_slice(secret).expect("HMAC takes keys of any length");
+    mac.update(&counter.to_be_bytes());
+    let hash = mac.finalize().into_bytes();
+
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([hash[offset] & 0x7f, hash[offset + 1], hash[offset + 2], hash[offset + 3]]);
+    binary % 10u32.pow(digits)
+}
+
+/// RFC 6238 code for the step `time` falls in, zero padded.
+pub fn totp_at(secret: &[u8], time: u64) -> String {
+    format!("{:0width$}", hotp(secret, time / TOTP_STEP, TOTP_DIGITS), width = TOTP_DIGITS as usize)
+}
+
+/// The step `code` belongs to, when it is within `window` steps of `time` and
+/// newer than `last_counter`. Codes that were used once never work again.
+pub fn verify_code(secret: &[u8], code: &str, time: u64, window: u64, last_counter: Option<u64>) -> Option<u64> {
+    if code.len() != TOTP_DIGITS as usize || !code.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let current = time / TOTP_STEP;
+    (current.saturating_sub(window)..=current + window)
+        .filter(|counter| last_counter.is_none_or(|last| *counter > last))
+        .find(|counter| {
+            let expected = format!("{:0width$}", hotp(secret, *counter, TOTP_DIGITS), width = TOTP_DIGITS as usize);
+            constant_time_eq(expected.as_bytes(), code.as_bytes())
+        })
+}
+
+fn hash_recovery_code(code: &str) -> String {
+    let normalized: String = code.chars().filter(|c| c.is_ascii_alphanumeric()).collect::<String>().to_lowercase();
+    hex::encode(Sha256::digest(normalized.as_bytes()))
+}
+
+/// The enrollment of one user:
+///
+/// ```text
+/// secret <base32>
+/// last_counter <step of the last accepted code>
+/// recovery <sha256 of an unused recovery code>
+/// ```
+#[derive(Debug, Clone)]
+pub struct TotpState {
+    pub secret: Vec<u8>,
+    pub last_counter: Option<u64>,
+    /// Hashes only, the codes themselves are shown once at enrollment
+    pub recovery: Vec<String>,
+}
+
+impl TotpState {
+    /// A fresh secret and recovery codes. The plain codes are returned so they can
+    /// be shown to the user, the state keeps only their hashes.
+    pub fn generate() -> (Self, Vec<String>) {
+        let mut rng = rand::rng();
+        let secret: Vec<u8> = (0..SECRET_LEN).map(|_| rng.random()).collect();
+        let codes: Vec<String> = (0..RECOVERY_CODES)
+            .map(|_| {
+                let n: u64 = rng.random_range(0..100_000_000);
+                format!("{:04}-{:04}", n / 10_000, n % 10_000)
+            })
+            .collect();
+        let recovery = codes.iter().map(|c| hash_recovery_code(c)).collect();
+        (TotpState { secret, last_counter: None, recovery }, codes)
+    }
+
+    pub fn parse(content: &str) -> Option<Self> {
+        let mut secret = None;
+        let mut last_counter = None;
+        let mut recovery = Vec::new();
+        for line in content.lines() {
+            match line.trim().split_once(' ') {
+                Some(("secret", value)) => secret = base32_decode(value),
+                Some(("last_counter", value)) => last_counter = value.trim().parse().ok(),
+                Some(("recovery", value)) => recovery.push(value.trim().to_string()),
+                _ => {}
+            }
+        }
+        Some(TotpState { secret: secret.filter(|s| !s.is_empty())?, last_counter, recovery })
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = format!("secret {}\n", base32_encode(&self.secret));
+        if let Some(counter) = self.last_counter {
+            text.push_str(&format!("last_counter {}\n", counter));
+        }
+        for hash in &self.recovery {
+            text.push_str(&format!("recovery {}\n", hash));
+        }
+        text
+    }
+
+    pub fn path(dir: &Path, username: &str) -> PathBuf {
+        dir.join(username)
+    }
+
+    /// `Ok(None)` when the user never enrolled.
+    pub fn load(dir: &Path, username: &str) -> io::Result<Option<Self>> {
+        match fs::read_to_string(Self::path(dir, username)) {
+            Ok(content) => Self::parse(&content)
+                .map(Some)
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("invalid TOTP file for {}", username))),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Written to a temporary file and renamed, so a crash never leaves half a secret.
+    pub fn save(&self, dir: &Path, username: &str) -> io::Result<()> {
+        fs::DirBuilder::new().recursive(true).mode(0o700).create(dir)?;
+        let path = Self::path(dir, username);
+        let temp = dir.join(format!(".{}.tmp", username));
+        let mut file = OpenOptions::new().write(true).create(true).truncate(true).mode(0o600).open(&temp)?;
+        file.write_all(self.to_text().as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&temp, &path)
+    }
+
+    /// Exclusive lock on the user's state, released when the file is dropped. `save`
+    /// replaces the state file, so the lock is on a separate `.<user>.lock`.
+    pub fn lock(dir: &Path, username: &str) -> io::Result<File> {
+        fs::DirBuilder::new().recursive(true).mode(0o700).create(dir)?;
+        let file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .mode(0o600)
+            .open(dir.join(format!(".{}.lock", username)))?;
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(file)
+    }
+
+    pub fn remove(dir: &Path, username: &str) -> io::Result<()> {
+        fs::remove_file(Self::path(dir, username))
+    }
+
+    /// Consumes `code` when it is one of the unused recovery codes.
+    pub fn use_recovery_code(&mut self, code: &str) -> bool {
+        let hash = hash_recovery_code(code);
+        match self.recovery.iter().position(|h| constant_time_eq(h.as_bytes(), hash.as_bytes())) {
+            Some(index) => {
+                self.recovery.remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Accepts a current code or a recovery code and records that it was used.
+    pub fn verify(&mut self, input: &str, time: u64, window: u64) -> bool {
+        let input = input.trim();
+        if let Some(counter) = verify_code(&self.secret, input, time, window, self.last_counter) {
+            self.last_counter = Some(counter);
+            return true;
+        }
+        self.use_recovery_code(input)
+    }
+}
+
+/// `otpauth://` URI that authenticator apps import, usually from a QR code.
+pub fn otpauth_uri(issuer: &str, account: &str, secret: &[u8]) -> String {
+    let encode = |s: &str| -> String {
+        s.bytes()
+            .map(|b| match b {
+                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (b as char).to_string(),
+                _ => format!("%{:02X}", b),
+            })
+            .collect()
+    };
+    format!(
+        "otpauth://totp/{}:{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+        encode(issuer),
+        encode(account),
+        base32_encode(secret),
+        encode(issuer),
+        TOTP_DIGITS,
+        TOTP_STEP
+    )
+}
+
+/// Asks for a code after the password. Arguments: `nullok` lets users without
+/// an enrollment through, `window=N` and `dir=PATH` override the defaults.
+pub struct Totp {
+    dir: PathBuf,
+    window: u64,
+    nullok: bool,
+    clock: fn() -> u64,
+}
+
+impl Totp {
+    pub fn new() -> Self {
+        Totp { dir: PathBuf::from(DEFAULT_TOTP_DIR), window: DEFAULT_WINDOW, nullok: false, clock: now_secs }
+    }
+
+    pub fn with_args(args: &[String]) -> Self {
+        let mut totp = Self::new();
+        for arg in args {
+            match arg.split_once('=') {
+                Some(("window", value)) => totp.window = value.parse().unwrap_or(totp.window),
+                Some(("dir", value)) => totp.dir = PathBuf::from(value),
+                _ if arg == "nullok" => totp.nullok = true,
+                _ => {}
+            }
+        }
+        totp
+    }
+
+    /// Use another time source, e.g. a fixed one.
+    pub fn with_clock(mut self, clock: fn() -> u64) -> Self {
+        self.clock = clock;
+        self
+    }
+}
+
+impl Default for Totp {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Totp {
+    /// Check `input` and record it as used. The state is read again under the lock,
+    /// so two logins at once can't both accept the same code.
+    fn check(&self, username: &str, input: &str) -> AuthResult {
+        let _lock = match TotpState::lock(&self.dir, username) {
+            Ok(lock) => lock,
+            Err(e) => return AuthResult::Failure(format!("Cannot lock two-factor state: {}", e)),
+        };
+        let mut state = match TotpState::load(&self.dir, username) {
+            Ok(Some(state)) => state,
+            Ok(None) => return AuthResult::Failure("Two-factor authentication is not set up".into()),
+            Err(e) => return AuthResult::Failure(format!("Cannot read two-factor secret: {}", e)),
+        };
+
+        let recovery_left = state.recovery.len();
+        if !state.verify(input, (self.clock)(), self.window) {
+            return AuthResult::Failure("Invalid verification code".into());
+        }
+        if state.recovery.len() < recovery_left {
+            safe_println(format_args!("Recovery code used, {} left", state.recovery.len()));
+        }
+
+        // Without the saved counter the same code would work again
+        match state.save(&self.dir, username) {
+            Ok(_) => AuthResult::Success,
+            Err(e) => AuthResult::Failure(format!("Cannot update two-factor state: {}", e)),
+        }
+    }
+}
+
+impl AuthModule for Totp {
+    fn auth(&self, ctx: &mut AuthContext) -> AuthResult {
+        match TotpState::load(&self.dir, &ctx.username) {
+            Ok(Some(_)) => {}
+            Ok(None) if self.nullok => return AuthResult::Success,
+            Ok(None) => return AuthResult::Failure("Two-factor authentication is not set up".into()),
+            Err(e) => return AuthResult::Failure(format!("Cannot read two-factor secret: {}", e)),
+        }
+
+        // Not locked while waiting for the user to type
+        safe_print(format_args!("Verification code: "));
+        let _ = io::stdout().flush();
+        let input = read_password();
+
+        self.check(&ctx.username, &input)
+    }
+
+    fn account(&self, _ctx: &mut AuthContext) -> AuthResult {
+        AuthResult::Success
+    }
+
+    fn open_session(&self, _ctx: &mut AuthContext) -> AuthResult {
+        AuthResult::Success
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // RFC 6238 appendix B, SHA-1
+    const RFC_SECRET: &[u8] = b"12345678901234567890";
+    const RFC_VECTORS: [(u64, u32); 6] = [
+        (59, 94287082),
+        (1111111109, 7081804),
+        (1111111111, 14050471),
+        (1234567890, 89005924),
+        (2000000000, 69279037),
+        (20000000000, 65353130),
+    ];
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("vantara-totp-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn rfc6238_vectors() {
+        for (time, code) in RFC_VECTORS {
+            assert_eq!(hotp(RFC_SECRET, time / TOTP_STEP, 8), code, "time {}", time);
+            // Six digits are the last six of the eight
+            assert_eq!(totp_at(RFC_SECRET, time), format!("{:06}", code % 1_000_000));
+        }
+    }
+
+    #[test]
+    fn base32_round_trip() {
+        assert_eq!(base32_encode(RFC_SECRET), "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+        assert_eq!(base32_decode("gezd gnbv gy3t qojq gezd gnbv gy3t qojq").unwrap(), RFC_SECRET);
+        assert!(base32_decode("not base32!").is_none());
+    }
+
+    #[test]
+    fn window_allows_clock_skew() {
+        let time = 1111111111;
+        let previous = totp_at(RFC_SECRET, time - TOTP_STEP);
+        let next = totp_at(RFC_SECRET, time + TOTP_STEP);
+        let two_ahead = totp_at(RFC_SECRET, time + 2 * TOTP_STEP);
+        let current = time / TOTP_STEP;
+
+        assert_eq!(verify_code(RFC_SECRET, &previous, time, 1, None), Some(current - 1));
+        assert_eq!(verify_code(RFC_SECRET, &next, time, 1, None), Some(current + 1));
+        assert_eq!(verify_code(RFC_SECRET, &two_ahead, time, 1, None), None);
+        assert_eq!(verify_code(RFC_SECRET, &two_ahead, time, 2, None), Some(current + 2));
+        assert_eq!(verify_code(RFC_SECRET, &next, time, 0, None), None);
+        assert_eq!(verify_code(RFC_SECRET, "12345", time, 1, None), None);
+        assert_eq!(verify_code(RFC_SECRET, "12a456", time, 1, None), None);
+    }
+
+    #[test]
+    fn used_codes_are_rejected() {
+        let time = 1111111111;
+        let current = time / TOTP_STEP;
+        let code = totp_at(RFC_SECRET, time);
+        let previous = totp_at(RFC_SECRET, time - TOTP_STEP);
+
+        assert_eq!(verify_code(RFC_SECRET, &code, time, 1, Some(current)), None);
+        // Nor any older one, once a newer code was accepted
+        assert_eq!(verify_code(RFC_SECRET, &previous, time, 1, Some(current)), None);
+    }
+
+    #[test]
+    fn module_rejects_replay() {
+        let dir = test_dir("replay");
+        let state = TotpState { secret: RFC_SECRET.to_vec(), last_counter: None, recovery: Vec::new() };
+        state.save(&dir, "alice").unwrap();
+        let totp = Totp::with_args(&[format!("dir={}", dir.display())]).with_clock(|| 1111111111);
+        let code = totp_at(RFC_SECRET, 1111111111);
+
+        assert!(matches!(totp.check("alice", &code), AuthResult::Success));
+        assert!(matches!(totp.check("alice", &code), AuthResult::Failure(_)));
+        let saved = TotpState::load(&dir, "alice").unwrap().unwrap();
+        assert_eq!(saved.last_counter, Some(1111111111 / TOTP_STEP));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn recovery_codes_work_once() {
+        let dir = test_dir("recovery");
+        let (state, codes) = TotpState::generate();
+        assert_eq!(codes.len(), RECOVERY_CODES);
+        state.save(&dir, "bob").unwrap();
+        let totp = Totp::with_args(&[format!("dir={}", dir.display())]).with_clock(|| 59);
+
+        assert!(matches!(totp.check("bob", &codes[3]), AuthResult::Success));
+        assert!(matches!(totp.check("bob", &codes[3]), AuthResult::Failure(_)));
+        // Dashes and case don't matter
+        assert!(matches!(totp.check("bob", &codes[4].replace('-', "")), AuthResult::Success));
+        assert_eq!(TotpState::load(&dir, "bob").unwrap().unwrap().recovery.len(), RECOVERY_CODES - 2);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn state_round_trip() {
+        let (mut state, _) = TotpState::generate();
+        state.last_counter = Some(42);
+        let parsed = TotpState::parse(&state.to_text()).unwrap();
+        assert_eq!(parsed.secret, state.secret);
+        assert_eq!(parsed.last_counter, Some(42));
+        assert_eq!(parsed.recovery, state.recovery);
+        assert!(TotpState::parse("last_counter 1\n").is_none());
+    }
+}
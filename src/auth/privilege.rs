@@ -0,0 +1,109 @@
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::fs::{DirBuilderExt, MetadataExt, OpenOptionsExt};
+use std::path::PathBuf;
+use chrono::Local;
+use crate::auth::modules::faillock::now_secs;
+use crate::auth::modules::passwd::PasswdEntry;
+
+pub const DEFAULT_AUTH_LOG_PATH: &str = "/var/log/auth.log";
+pub const DEFAULT_TIMESTAMP_DIR: &str = "/run/sudo/ts";
+/// PATH for commands run with someone else's privileges, never taken from the caller
+pub const SECURE_PATH: &str = "/bin:/sbin:/usr/bin:/usr/sbin";
+
+/// Variables that change how programs load or parse input, never passed on.
+fn is_unsafe_var(name: &str) -> bool {
+    name.starts_with("LD_") || matches!(name, "IFS" | "ENV" | "BASH_ENV" | "PATH" | "SHELLOPTS" | "PS4")
+}
+
+/// Environment for a command run as `user`: the variables describing `user` plus
+/// those of the caller's that are listed in `keep`.
+pub fn sanitized_env(keep: &[String], user: &PasswdEntry) -> Vec<(String, String)> {
+    let mut vars: Vec<(String, String)> = env::vars()
+        .filter(|(name, value)| keep.contains(name) && !is_unsafe_var(name) && !value.starts_with("()"))
+        .collect();
+    vars.extend([
+        ("HOME".to_string(), user.home.clone()),
+        ("USER".to_string(), user.username.clone()),
+        ("LOGNAME".to_string(), user.username.clone()),
+        ("SHELL".to_string(), user.shell.clone()),
+        ("PATH".to_string(), SECURE_PATH.to_string()),
+    ]);
+    vars
+}
+
+/// The caller's environment minus the unsafe variables, with the ones describing `user` replaced.
+pub fn filtered_env(user: &PasswdEntry) -> Vec<(String, String)> {
+    let keep: Vec<String> = env::vars()
+        .map(|(name, _)| name)
+        .filter(|name| !matches!(name.as_str(), "HOME" | "USER" | "LOGNAME" | "SHELL"))
+        .collect();
+    sanitized_env(&keep, user)
+}
+
+/// Append one line to the auth log, e.g. `sudo: alice : TTY=/dev/tty1 ; ...`.
+/// Logging must not stop the command, so failures are ignored.
+pub fn log_auth(service: &str, message: &str) {
+    let line = format!(
+        "{} {}[{}]: {}\n",
+        Local::now().format("%Y-%m-%d %H:%M:%S"),
+        service,
+        std::process::id(),
+        message
+    );
+    if let Ok(mut file) = OpenOptions::new().append(true).create(true).mode(0o600).open(DEFAULT_AUTH_LOG_PATH) {
+        let _ = file.write_all(line.as_bytes());
+    }
+}
+
+/// Remembers a successful authentication of one user on one terminal, so sudo
+/// doesn't ask again for a while. Bound to the login session, a new login on
+/// the same tty has to authenticate again.
+pub struct Timestamp {
+    path: PathBuf,
+}
+
+impl Timestamp {
+    /// `None` when stdin is not a terminal, nothing is remembered then.
+    pub fn for_tty(uid: u32) -> Option<Self> {
+        if unsafe { libc::isatty(0) } != 1 {
+            return None;
+        }
+        let tty = fs::metadata("/proc/self/fd/0").ok()?.rdev();
+        Some(Timestamp { path: PathBuf::from(DEFAULT_TIMESTAMP_DIR).join(format!("{}-{}", uid, tty)) })
+    }
+
+    fn session() -> i32 {
+        unsafe { libc::getsid(0) }
+    }
+
+    /// Written less than `timeout_secs` ago, by root, in this login session.
+    pub fn is_valid(&self, timeout_secs: u64) -> bool {
+        if !fs::metadata(&self.path).is_ok_and(|meta| meta.uid() == 0) {
+            return false;
+        }
+        let content = fs::read_to_string(&self.path).unwrap_or_default();
+        let mut fields = content.split_whitespace();
+        let time: u64 = match fields.next().and_then(|t| t.parse().ok()) {
+            Some(time) => time,
+            None => return false,
+        };
+        let session: Option<i32> = fields.next().and_then(|s| s.parse().ok());
+
+        let now = now_secs();
+        session == Some(Self::session()) && time <= now && now - time < timeout_secs
+    }
+
+    pub fn update(&self) -> io::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::DirBuilder::new().recursive(true).mode(0o700).create(dir)?;
+        }
+        let mut file = OpenOptions::new().write(true).create(true).truncate(true).mode(0o600).open(&self.path)?;
+        writeln!(file, "{} {}", now_secs(), Self::session())
+    }
+
+    pub fn remove(&self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
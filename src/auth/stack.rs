@@ -0,0 +1,449 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use crate::auth::{AuthModule, AuthContext, AuthResult};
+use crate::audit::{self, EventKind, Outcome};
+use crate::auth::modules::{account_expiry::AccountExpiry, auth_unix::AuthUnix, faillock::Faillock, rootok::RootOk, session_logger::SessionLogger, totp::Totp,
+    limits::Limits, runtime_dir::RuntimeDir};
+
+pub const DEFAULT_AUTH_DIR: &str = "/etc/auth.d";
+
+/// Used when neither `/etc/auth.d/<service>` nor `/etc/auth.d/other` exist, so a
+/// broken image still behaves like login did before stacks.
+const FALLBACK_STACK: &str = "\
+auth     requisite faillock
+auth     required  unix
+account  required  faillock
+account  required  unix
+account  required  expiry
+session  optional  logger
+session  required  unix
+";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Phase {
+    Auth,
+    Account,
+    Session,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Control {
+    /// Failure fails the stack, the rest of the phase still runs
+    Required,
+    /// Failure fails the stack right away
+    Requisite,
+    /// Success ends the phase when nothing required failed before it, failure is ignored
+    Sufficient,
+    /// Only counts when it is the only module of the phase
+    Optional,
+}
+
+pub type ModuleFactory = fn(&[String]) -> Box<dyn AuthModule>;
+
+/// Module names usable in stack files.
+pub struct ModuleRegistry {
+    factories: HashMap<String, ModuleFactory>,
+}
+
+impl ModuleRegistry {
+    pub fn new() -> Self {
+        ModuleRegistry { factories: HashMap::new() }
+    }
+
+    /// The modules that ship with Vantara.
+    pub fn builtin() -> Self {
+        let mut registry = Self::new();
+        registry.register("unix", |args| Box::new(AuthUnix::with_args(args)));
+        registry.register("faillock", |args| Box::new(Faillock::with_args(args)));
+        registry.register("expiry", |_| Box::new(AccountExpiry::new()));
+        registry.register("logger", |_| Box::new(SessionLogger::new()));
+        registry.register("rootok", |_| Box::new(RootOk::new()));
+        registry.register("totp", |args| Box::new(Totp::with_args(args)));
+        registry.register("limits", |args| Box::new(Limits::with_args(args)));
+        registry.register("rundir", |_| Box::new(RuntimeDir::new()));
+        registry
+    }
+
+    pub fn register(&mut self, name: &str, factory: ModuleFactory) {
+        self.factories.insert(name.to_string(), factory);
+    }
+
+    pub fn create(&self, name: &str, args: &[String]) -> Option<Box<dyn AuthModule>> {
+        self.factories.get(name).map(|factory| factory(args))
+    }
+}
+
+impl Default for ModuleRegistry {
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
+
+pub struct StackEntry {
+    pub phase: Phase,
+    pub control: Control,
+    pub name: String,
+    pub args: Vec<String>,
+    pub module: Box<dyn AuthModule>,
+}
+
+/// The auth policy of one service, e.g. `login` or `su`.
+///
+/// One module per line: `<auth|account|session> <control> <module> [args...]`,
+/// `#` starts a comment. Modules of a phase run in file order.
+pub struct AuthStack {
+    pub service: String,
+    pub entries: Vec<StackEntry>,
+}
+
+impl AuthStack {
+    pub fn parse(content: &str, source: &str, service: &str, registry: &ModuleRegistry) -> Result<Self, String> {
+        let mut entries = Vec::new();
+
+        for (lineno, line) in content.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let at = || format!("{}:{}", source, lineno + 1);
+
+            let mut parts = line.split_whitespace();
+            let phase = match parts.next() {
+                Some("auth") => Phase::Auth,
+                Some("account") => Phase::Account,
+                Some("session") => Phase::Session,
+                Some(other) => return Err(format!("{}: unknown type '{}'", at(), other)),
+                None => continue,
+            };
+            let control = match parts.next() {
+                Some("required") => Control::Required,
+                Some("requisite") => Control::Requisite,
+                Some("sufficient") => Control::Sufficient,
+                Some("optional") => Control::Optional,
+                Some(other) => return Err(format!("{}: unknown control '{}'", at(), other)),
+                None => return Err(format!("{}: missing control and module", at())),
+            };
+            let name = parts.next().ok_or_else(|| format!("{}: missing module", at()))?;
+            let args: Vec<String> = parts.map(|s| s.to_string()).collect();
+            let module = registry
+                .create(name, &args)
+                .ok_or_else(|| format!("{}: unknown module '{}'", at(), name))?;
+
+            entries.push(StackEntry { phase, control, name: name.to_string(), args, module });
+        }
+
+        Ok(AuthStack { service: service.to_string(), entries })
+    }
+
+    /// `<dir>/<service>`, falling back to `<dir>/other` and then the built-in stack.
+    pub fn load_from(dir: &Path, service: &str, registry: &ModuleRegistry) -> Result<Self, String> {
+        for candidate in [service, "other"] {
+            let path = dir.join(candidate);
+            if let Ok(content) = fs::read_to_string(&path) {
+                return Self::parse(&content, &path.display().to_string(), service, registry);
+            }
+        }
+        Self::parse(FALLBACK_STACK, "built-in stack", service, registry)
+    }
+
+    pub fn load(service: &str) -> Result<Self, String> {
+        Self::load_from(Path::new(DEFAULT_AUTH_DIR), service, &ModuleRegistry::builtin())
+    }
+
+    /// Run every module of `phase` and combine the results by control flag.
+    pub fn run_phase(&self, phase: Phase, ctx: &mut AuthContext) -> AuthResult {
+        self.run_phase_with(phase, ctx, |entry, ctx| match phase {
+            Phase::Auth => entry.module.auth(ctx),
+            Phase::Account => entry.module.account(ctx),
+            Phase::Session => entry.module.open_session(ctx),
+        })
+    }
+
+    fn run_phase_with<'s, F>(&'s self, phase: Phase, ctx: &mut AuthContext, mut call: F) -> AuthResult
+    where
+        F: FnMut(&'s StackEntry, &mut AuthContext) -> AuthResult,
+    {
+        let entries: Vec<&StackEntry> = self.entries.iter().filter(|e| e.phase == phase).collect();
+        if entries.is_empty() {
+            return AuthResult::Failure(format!("no {} modules configured for {}", format!("{:?}", phase).to_lowercase(), self.service));
+        }
+
+        let only_optional = entries.iter().all(|e| e.control == Control::Optional);
+        let mut failure: Option<String> = None;
+        let mut succeeded = false;
+
+        for entry in entries {
+            let result = call(entry, ctx);
+
+            match (entry.control, result) {
+                (Control::Required, AuthResult::Success) => succeeded = true,
+                (Control::Required, AuthResult::Failure(reason)) => {
+                    failure.get_or_insert(reason);
+                }
+                (Control::Requisite, AuthResult::Success) => succeeded = true,
+                (Control::Requisite, AuthResult::Failure(reason)) => {
+                    return AuthResult::Failure(failure.unwrap_or(reason));
+                }
+                (Control::Sufficient, AuthResult::Success) => {
+                    if failure.is_none() {
+                        return AuthResult::Success;
+                    }
+                }
+                (Control::Sufficient, AuthResult::Failure(_)) => {}
+                (Control::Optional, AuthResult::Success) => succeeded |= only_optional,
+                (Control::Optional, AuthResult::Failure(reason)) => {
+                    if only_optional {
+                        failure.get_or_insert(reason);
+                    }
+                }
+            }
+        }
+
+        match failure {
+            Some(reason) => AuthResult::Failure(reason),
+            None if succeeded => AuthResult::Success,
+            None => AuthResult::Failure("no module accepted the request".into()),
+        }
+    }
+
+    /// Auth phase, recorded in the audit log. On failure every auth module gets
+    /// `auth_failed`.
+    pub fn authenticate(&self, ctx: &mut AuthContext) -> AuthResult {
+        let result = self.run_phase(Phase::Auth, ctx);
+        match &result {
+            AuthResult::Success => audit::record(EventKind::Auth, Outcome::Success, &ctx.username, &self.service),
+            AuthResult::Failure(reason) => {
+                audit::record(EventKind::Auth, Outcome::Failure, &ctx.username, &format!("{}: {}", self.service, reason));
+                self.auth_failed(ctx);
+            }
+        }
+        result
+    }
+
+    fn auth_failed(&self, ctx: &mut AuthContext) {
+        for entry in self.entries.iter().filter(|e| e.phase == Phase::Auth) {
+            entry.module.auth_failed(ctx);
+        }
+    }
+
+    /// Auth and account phases, printing why a login was refused.
+    pub fn login(&self, ctx: &mut AuthContext) -> bool {
+        if let AuthResult::Failure(reason) = self.authenticate(ctx) {
+            println!("Auth failed: {}", reason);
+            return false;
+        }
+        if let AuthResult::Failure(reason) = self.run_phase(Phase::Account, ctx) {
+            println!("Account check failed: {}", reason);
+            return false;
+        }
+        true
+    }
+
+    /// Session phase. Modules that opened successfully are closed again in reverse
+    /// order, right away when the phase fails and otherwise when the returned
+    /// session is closed or dropped.
+    pub fn open_session<'a>(&'a self, ctx: &'a mut AuthContext) -> Result<Session<'a>, String> {
+        let mut opened = Vec::new();
+        let result = self.run_phase_with(Phase::Session, ctx, |entry, ctx| {
+            let result = entry.module.open_session(ctx);
+            if let AuthResult::Success = result {
+                opened.push(entry);
+            }
+            result
+        });
+        let session = Session { opened, ctx, closed: false };
+
+        match result {
+            AuthResult::Success => Ok(session),
+            AuthResult::Failure(reason) => Err(reason), // dropping `session` closes it
+        }
+    }
+}
+
+/// An open session of an `AuthStack`, see `AuthStack::open_session`.
+pub struct Session<'a> {
+    opened: Vec<&'a StackEntry>,
+    ctx: &'a mut AuthContext,
+    closed: bool,
+}
+
+impl Session<'_> {
+    pub fn context(&mut self) -> &mut AuthContext {
+        self.ctx
+    }
+
+    pub fn close(mut self) {
+        self.close_modules();
+    }
+
+    fn close_modules(&mut self) {
+        if self.closed {
+            return;
+        }
+        self.closed = true;
+        for entry in self.opened.iter().rev() {
+            if let AuthResult::Failure(reason) = entry.module.close_session(self.ctx) {
+                println!("Session close failed: {}", reason);
+            }
+        }
+    }
+}
+
+impl Drop for Session<'_> {
+    fn drop(&mut self) {
+        self.close_modules();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    thread_local! {
+        /// `<module>.<call>` for every call a stub gets, in order
+        static CALLS: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+    }
+
+    /// `stub <name> [fail]`: succeeds in every phase unless told to fail.
+    struct Stub {
+        name: String,
+        fail: bool,
+    }
+
+    impl Stub {
+        fn call(&self, what: &str) -> AuthResult {
+            CALLS.with(|c| c.borrow_mut().push(format!("{}.{}", self.name, what)));
+            if self.fail {
+                AuthResult::Failure(format!("{} failed", self.name))
+            } else {
+                AuthResult::Success
+            }
+        }
+    }
+
+    impl AuthModule for Stub {
+        fn auth(&self, _ctx: &mut AuthContext) -> AuthResult {
+            self.call("auth")
+        }
+        fn account(&self, _ctx: &mut AuthContext) -> AuthResult {
+            self.call("account")
+        }
+        fn open_session(&self, _ctx: &mut AuthContext) -> AuthResult {
+            self.call("open")
+        }
+        fn close_session(&self, _ctx: &mut AuthContext) -> AuthResult {
+            self.call("close")
+        }
+        fn auth_failed(&self, _ctx: &mut AuthContext) {
+            CALLS.with(|c| c.borrow_mut().push(format!("{}.auth_failed", self.name)));
+        }
+    }
+
+    fn stack(content: &str) -> AuthStack {
+        let mut registry = ModuleRegistry::new();
+        registry.register("stub", |args| {
+            Box::new(Stub { name: args[0].clone(), fail: args.get(1).is_some_and(|a| a == "fail") })
+        });
+        AuthStack::parse(content, "test", "test", &registry).unwrap()
+    }
+
+    fn ctx() -> AuthContext {
+        AuthContext { username: "alice".into(), password: String::new(), metadata: HashMap::new() }
+    }
+
+    fn calls() -> Vec<String> {
+        CALLS.with(|c| c.borrow_mut().drain(..).collect())
+    }
+
+    fn outcome(result: AuthResult) -> Result<(), String> {
+        match result {
+            AuthResult::Success => Ok(()),
+            AuthResult::Failure(reason) => Err(reason),
+        }
+    }
+
+    #[test]
+    fn control_flags() {
+        let cases: &[(&str, Result<(), &str>, &[&str])] = &[
+            ("auth required stub a\nauth required stub b", Ok(()), &["a.auth", "b.auth"]),
+            // required fails the stack, but the rest still runs
+            ("auth required stub a fail\nauth required stub b", Err("a failed"), &["a.auth", "b.auth"]),
+            // the first failure is the reason given
+            ("auth required stub a fail\nauth required stub b fail", Err("a failed"), &["a.auth", "b.auth"]),
+            // requisite stops the stack
+            ("auth requisite stub a fail\nauth required stub b", Err("a failed"), &["a.auth"]),
+            ("auth required stub a fail\nauth requisite stub b fail\nauth required stub c", Err("a failed"), &["a.auth", "b.auth"]),
+            // sufficient ends the phase on success
+            ("auth sufficient stub a\nauth required stub b fail", Ok(()), &["a.auth"]),
+            ("auth sufficient stub a fail\nauth required stub b", Ok(()), &["a.auth", "b.auth"]),
+            // but not after a required failure
+            ("auth required stub a fail\nauth sufficient stub b\nauth required stub c", Err("a failed"), &["a.auth", "b.auth", "c.auth"]),
+            // only a sufficient failure, nothing accepted
+            ("auth sufficient stub a fail", Err("no module accepted the request"), &["a.auth"]),
+            // an optional failure next to other modules doesn't count
+            ("auth optional stub a fail\nauth required stub b", Ok(()), &["a.auth", "b.auth"]),
+            // it does when it is alone
+            ("auth optional stub a fail", Err("a failed"), &["a.auth"]),
+            ("auth optional stub a", Ok(()), &["a.auth"]),
+            // other phases don't run
+            ("account required stub a fail\nauth required stub b", Ok(()), &["b.auth"]),
+            ("account required stub a", Err("no auth modules configured for test"), &[]),
+        ];
+
+        for (content, expected, expected_calls) in cases {
+            let stack = stack(content);
+            let result = outcome(stack.run_phase(Phase::Auth, &mut ctx()));
+            assert_eq!(result, expected.map_err(|e| e.to_string()), "{}", content);
+            assert_eq!(calls(), *expected_calls, "{}", content);
+        }
+    }
+
+    #[test]
+    fn auth_failed_reaches_every_auth_module() {
+        let stack = stack("auth requisite stub a fail\nauth optional stub b\naccount required stub c\nsession required stub d");
+        stack.auth_failed(&mut ctx());
+        assert_eq!(calls(), ["a.auth_failed", "b.auth_failed"]);
+    }
+
+    #[test]
+    fn sessions_close_in_reverse_order() {
+        let stack = stack("session required stub a\nsession optional stub b\nsession required stub c");
+        let mut ctx = ctx();
+        let session = stack.open_session(&mut ctx).unwrap();
+        assert_eq!(calls(), ["a.open", "b.open", "c.open"]);
+        session.close();
+        assert_eq!(calls(), ["c.close", "b.close", "a.close"]);
+    }
+
+    #[test]
+    fn failed_session_closes_what_opened() {
+        let stack = stack("session required stub a\nsession optional stub b fail\nsession requisite stub c fail\nsession required stub d");
+        let mut ctx = ctx();
+        assert_eq!(stack.open_session(&mut ctx).err().as_deref(), Some("c failed"));
+        // b failed to open so it isn't closed, d never ran
+        assert_eq!(calls(), ["a.open", "b.open", "c.open", "a.close"]);
+    }
+
+    #[test]
+    fn dropped_session_is_closed_once() {
+        let stack = stack("session required stub a\nsession required stub b");
+        let mut ctx = ctx();
+        {
+            let _session = stack.open_session(&mut ctx).unwrap();
+        }
+        assert_eq!(calls(), ["a.open", "b.open", "b.close", "a.close"]);
+    }
+
+    #[test]
+    fn parse_errors() {
+        let registry = ModuleRegistry::new();
+        let err = |content| AuthStack::parse(content, "f", "test", &registry).err().unwrap();
+        assert_eq!(err("bogus required x"), "f:1: unknown type 'bogus'");
+        assert_eq!(err("# comment\n\nauth maybe x"), "f:3: unknown control 'maybe'");
+        assert_eq!(err("auth"), "f:1: missing control and module");
+        assert_eq!(err("auth required"), "f:1: missing module");
+        assert_eq!(err("auth required nosuch"), "f:1: unknown module 'nosuch'");
+    }
+}
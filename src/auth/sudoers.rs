@@ -0,0 +1,577 @@
+use std::collections::HashMap;
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+
+pub const DEFAULT_SUDOERS_PATH: &str = "/etc/sudoers";
+
+/// Who a rule applies to.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Principal {
+    All,
+    User(String),
+    /// Written `%name`
+    Group(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CommandSpec {
+    All,
+    /// `args` of `None` allows any arguments, `Some(vec![])` (written `""`) none at all
+    Path { path: String, args: Option<Vec<String>> },
+}
+
+/// An entry of a list in a rule or alias. Of the entries that match, the last one
+/// decides, so `ALL, !/bin/sh` is every command but the shell.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Item<T> {
+    /// Written with a leading `!`
+    pub negated: bool,
+    pub value: T,
+}
+
+/// `<who> <host> = [(<runas>)] [NOPASSWD:] <command>[, <command>...]`
+#[derive(Debug, Clone)]
+pub struct Rule {
+    pub who: Vec<Item<Principal>>,
+    pub hosts: Vec<Item<String>>,
+    /// Users the commands may run as, `ALL` for anyone and root when not given
+    pub runas: Vec<Item<String>>,
+    pub nopasswd: bool,
+    pub commands: Vec<Item<CommandSpec>>,
+}
+
+/// `User_Alias`, `Host_Alias`, `Runas_Alias` and `Cmnd_Alias` definitions, expanded
+/// into the rules while parsing. An alias has to be defined before it is used.
+#[derive(Debug, Default)]
+struct Aliases {
+    users: HashMap<String, Vec<Item<Principal>>>,
+    hosts: HashMap<String, Vec<Item<String>>>,
+    runas: HashMap<String, Vec<Item<String>>>,
+    commands: HashMap<String, Vec<Item<CommandSpec>>>,
+}
+
+/// What `sudo` is asked to do, checked against the policy with `Sudoers::check`.
+pub struct Request<'a> {
+    pub user: &'a str,
+    /// Names of every group `user` is in
+    pub groups: &'a [String],
+    pub host: &'a str,
+    pub runas: &'a str,
+    /// Absolute path of the command
+    pub command: &'a str,
+    pub args: &'a [String],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Decision {
+    Allow { nopasswd: bool },
+    Deny,
+}
+
+#[derive(Debug, Clone)]
+pub struct Sudoers {
+    pub rules: Vec<Rule>,
+    /// Variables passed on to the command besides the ones describing the target user
+    pub env_keep: Vec<String>,
+    /// Minutes a successful authentication is remembered per tty, 0 asks every time
+    pub timestamp_timeout: i64,
+    pub passwd_tries: usize,
+}
+
+impl Default for Sudoers {
+    fn default() -> Self {
+        Sudoers {
+            rules: Vec::new(),
+            env_keep: ["TERM", "LANG", "LC_ALL", "TZ"].iter().map(|s| s.to_string()).collect(),
+            timestamp_timeout: 5,
+            passwd_tries: 3,
+        }
+    }
+}
+
+impl Principal {
+    fn parse(token: &str) -> Result<Self, String> {
+        Ok(match token {
+            "ALL" => Principal::All,
+            _ => match token.strip_prefix('%') {
+                Some(group) => Principal::Group(group.to_string()),
+                None => Principal::User(token.to_string()),
+            },
+        })
+    }
+
+    fn matches(&self, user: &str, groups: &[String]) -> bool {
+        match self {
+            Principal::All => true,
+            Principal::User(name) => name == user,
+            Principal::Group(group) => groups.iter().any(|g| g == group),
+        }
+    }
+}
+
+impl CommandSpec {
+    fn parse(spec: &str) -> Result<Self, String> {
+        if spec == "ALL" {
+            return Ok(CommandSpec::All);
+        }
+        let mut parts = spec.split_whitespace();
+        let path = parts.next().ok_or("empty command")?;
+        if !path.starts_with('/') {
+            return Err(format!("command '{}' is not an absolute path", path));
+        }
+        let args: Vec<String> = parts.map(String::from).collect();
+        let args = match args.as_slice() {
+            [] => None,
+            [empty] if empty == "\"\"" => Some(Vec::new()),
+            _ => Some(args),
+        };
+        Ok(CommandSpec::Path { path: path.to_string(), args })
+    }
+
+    fn matches(&self, command: &str, args: &[String]) -> bool {
+        match self {
+            CommandSpec::All => true,
+            CommandSpec::Path { path, args: allowed } => {
+                same_program(path, command) && allowed.as_ref().is_none_or(|allowed| allowed.as_slice() == args)
+            }
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            CommandSpec::All => "ALL".to_string(),
+            CommandSpec::Path { path, args: None } => path.clone(),
+            CommandSpec::Path { path, args: Some(args) } if args.is_empty() => format!("{} \"\"", path),
+            CommandSpec::Path { path, args: Some(args) } => format!("{} {}", path, args.join(" ")),
+        }
+    }
+}
+
+impl<T> Item<T> {
+    fn describe(&self, value: String) -> String {
+        if self.negated { format!("!{}", value) } else { value }
+    }
+}
+
+/// `Some(true)` when the last matching entry is a plain one, `Some(false)` when it
+/// is negated and `None` when nothing matches.
+fn list_matches<T>(items: &[Item<T>], matches: impl Fn(&T) -> bool) -> Option<bool> {
+    items.iter().rev().find(|item| matches(&item.value)).map(|item| !item.negated)
+}
+
+/// The same path, or two paths of one file: `/bin//sh`, `/bin/./sh` or a link to
+/// `/bin/sh` must not get past `!/bin/sh`.
+fn same_program(spec: &str, command: &str) -> bool {
+    if spec == command {
+        return true;
+    }
+    match (fs::metadata(spec), fs::metadata(command)) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+        _ => false,
+    }
+}
+
+/// Upper case letters, digits and `_`, starting with a letter. `ALL` is reserved.
+fn is_alias_name(name: &str) -> bool {
+    name != "ALL"
+        && name.starts_with(|c: char| c.is_ascii_uppercase())
+        && name.chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_')
+}
+
+/// `alice, bob web1` -> (`alice, bob`, `web1`)
+fn split_lists(text: &str) -> Option<(&str, &str)> {
+    let mut rest = text;
+    while let Some(start) = rest.find(char::is_whitespace) {
+        let before = rest[..start].trim_end();
+        let after = rest[start..].trim_start();
+        let offset = text.len() - rest.len();
+        if !before.ends_with(',') && !after.starts_with(',') {
+            return Some((text[..offset + start].trim(), after));
+        }
+        rest = after;
+    }
+    None
+}
+
+/// A comma separated list, with aliases of the list's kind replaced by their entries.
+fn parse_list<T: Clone>(
+    list: &str,
+    aliases: &HashMap<String, Vec<Item<T>>>,
+    parse: impl Fn(&str) -> Result<T, String>,
+) -> Result<Vec<Item<T>>, String> {
+    let mut items = Vec::new();
+    for token in list.split(',') {
+        let mut token = token.trim();
+        let mut negated = false;
+        while let Some(rest) = token.strip_prefix('!') {
+            negated = !negated;
+            token = rest.trim_start();
+        }
+        if token.is_empty() {
+            return Err("empty list entry".into());
+        }
+
+        match aliases.get(token) {
+            // `!ALIAS` flips every entry of the alias
+            Some(expansion) => items.extend(expansion.iter().map(|item| Item {
+                negated: item.negated != negated,
+                value: item.value.clone(),
+            })),
+            None if is_alias_name(token) => return Err(format!("undefined alias '{}'", token)),
+            None => items.push(Item { negated, value: parse(token)? }),
+        }
+    }
+    Ok(items)
+}
+
+impl Aliases {
+    /// `<NAME> = <list>` after one of the `*_Alias` keywords.
+    fn define(&mut self, kind: &str, definition: &str) -> Result<(), String> {
+        let (name, list) = definition.split_once('=').ok_or("missing '='")?;
+        let name = name.trim();
+        if !is_alias_name(name) {
+            return Err(format!("invalid alias name '{}'", name));
+        }
+        if self.users.contains_key(name) || self.hosts.contains_key(name)
+            || self.runas.contains_key(name) || self.commands.contains_key(name)
+        {
+            return Err(format!("alias '{}' is already defined", name));
+        }
+
+        match kind {
+            "User_Alias" => {
+                let items = parse_list(list, &self.users, Principal::parse)?;
+                self.users.insert(name.to_string(), items);
+            }
+            "Host_Alias" => {
+                let items = parse_list(list, &self.hosts, |h| Ok(h.to_string()))?;
+                self.hosts.insert(name.to_string(), items);
+            }
+            "Runas_Alias" => {
+                let items = parse_list(list, &self.runas, |u| Ok(u.to_string()))?;
+                self.runas.insert(name.to_string(), items);
+            }
+            _ => {
+                let items = parse_list(list, &self.commands, CommandSpec::parse)?;
+                self.commands.insert(name.to_string(), items);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Rule {
+    fn parse(line: &str, aliases: &Aliases) -> Result<Self, String> {
+        let (left, right) = line.split_once('=').ok_or("missing '='")?;
+
+        // Who, then the hosts: two lists separated by space not next to a comma
+        let left = left.trim();
+        let (who, hosts) = split_lists(left).ok_or("missing host")?;
+        let who = parse_list(who, &aliases.users, Principal::parse)?;
+        let hosts = parse_list(hosts, &aliases.hosts, |h| Ok(h.to_string()))?;
+
+        let mut right = right.trim();
+        let mut runas = vec![Item { negated: false, value: "root".to_string() }];
+        if let Some(rest) = right.strip_prefix('(') {
+            let (list, rest) = rest.split_once(')').ok_or("unterminated runas list")?;
+            if list.contains(':') {
+                return Err("runas groups are not supported".into());
+            }
+            runas = parse_list(list, &aliases.runas, |u| Ok(u.to_string()))?;
+            right = rest.trim_start();
+        }
+
+        let mut nopasswd = false;
+        loop {
+            if let Some(rest) = right.strip_prefix("NOPASSWD:") {
+                nopasswd = true;
+                right = rest.trim_start();
+            } else if let Some(rest) = right.strip_prefix("PASSWD:") {
+                nopasswd = false;
+                right = rest.trim_start();
+            } else {
+                break;
+            }
+        }
+
+        let commands = parse_list(right, &aliases.commands, CommandSpec::parse)?;
+
+        Ok(Rule { who, hosts, runas, nopasswd, commands })
+    }
+
+    fn applies_to(&self, user: &str, groups: &[String], host: &str) -> bool {
+        list_matches(&self.who, |p| p.matches(user, groups)) == Some(true)
+            && list_matches(&self.hosts, |h| h == "ALL" || h == host) == Some(true)
+    }
+
+    /// `None` when the rule says nothing about the request, otherwise whether it
+    /// allows it.
+    fn decide(&self, request: &Request) -> Option<bool> {
+        if !self.applies_to(request.user, request.groups, request.host)
+            || list_matches(&self.runas, |r| r == "ALL" || r == request.runas) != Some(true)
+        {
+            return None;
+        }
+        list_matches(&self.commands, |c| c.matches(request.command, request.args))
+    }
+
+    /// As `sudo -l` shows it, e.g. `(root) NOPASSWD: /bin/servicectl`.
+    pub fn describe(&self) -> String {
+        let runas: Vec<String> = self.runas.iter().map(|r| r.describe(r.value.clone())).collect();
+        let commands: Vec<String> = self.commands.iter().map(|c| c.describe(c.value.describe())).collect();
+        format!(
+            "({}) {}{}",
+            runas.join(", "),
+            if self.nopasswd { "NOPASSWD: " } else { "" },
+            commands.join(", ")
+        )
+    }
+}
+
+impl Sudoers {
+    pub fn parse(content: &str, source: &str) -> Result<Self, String> {
+        let mut sudoers = Sudoers::default();
+        let mut aliases = Aliases::default();
+
+        for (lineno, line) in content.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let at = |e: String| format!("{}:{}: {}", source, lineno + 1, e);
+
+            let (keyword, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            match keyword {
+                "Defaults" => sudoers.apply_default(rest.trim()).map_err(at)?,
+                "User_Alias" | "Host_Alias" | "Runas_Alias" | "Cmnd_Alias" => {
+                    aliases.define(keyword, rest).map_err(at)?;
+                }
+                _ => sudoers.rules.push(Rule::parse(line, &aliases).map_err(at)?),
+            }
+        }
+
+        Ok(sudoers)
+    }
+
+    /// `env_keep = "A B"`, `env_keep += "C"`, `timestamp_timeout = 5` or `passwd_tries = 3`.
+    fn apply_default(&mut self, setting: &str) -> Result<(), String> {
+        let (key, value) = setting.split_once('=').ok_or_else(|| format!("invalid Defaults '{}'", setting))?;
+        let (key, append) = match key.trim().strip_suffix('+') {
+            Some(key) => (key.trim(), true),
+            None => (key.trim(), false),
+        };
+        let value = value.trim().trim_matches('"');
+
+        match key {
+            "env_keep" => {
+                if !append {
+                    self.env_keep.clear();
+                }
+                self.env_keep.extend(value.split_whitespace().map(String::from));
+            }
+            "timestamp_timeout" => {
+                self.timestamp_timeout = value.parse().map_err(|_| format!("invalid timestamp_timeout '{}'", value))?;
+            }
+            "passwd_tries" => {
+                self.passwd_tries = value.parse().map_err(|_| format!("invalid passwd_tries '{}'", value))?;
+            }
+            _ => return Err(format!("unknown Defaults setting '{}'", key)),
+        }
+        Ok(())
+    }
+
+    /// Refuses a policy file that someone besides root could have written.
+    pub fn load_from(path: &Path) -> Result<Self, String> {
+        let meta = fs::metadata(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        if meta.uid() != 0 || meta.mode() & 0o022 != 0 {
+            return Err(format!("{} must be owned by root and not writable by others", path.display()));
+        }
+        let content = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Self::parse(&content, &path.display().to_string())
+    }
+
+    pub fn load() -> Result<Self, String> {
+        Self::load_from(Path::new(DEFAULT_SUDOERS_PATH))
+    }
+
+    /// The last matching rule decides, so later lines can narrow earlier ones.
+    pub fn check(&self, request: &Request) -> Decision {
+        self.rules
+            .iter()
+            .rev()
+            .find_map(|rule| rule.decide(request).map(|allowed| (rule, allowed)))
+            .map(|(rule, allowed)| match allowed {
+                true => Decision::Allow { nopasswd: rule.nopasswd },
+                false => Decision::Deny,
+            })
+            .unwrap_or(Decision::Deny)
+    }
+
+    /// Rules that apply to `user` on `host`, for `sudo -l`.
+    pub fn rules_for(&self, user: &str, groups: &[String], host: &str) -> Vec<&Rule> {
+        self.rules.iter().filter(|rule| rule.applies_to(user, groups, host)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(policy: &str, user: &str, groups: &[&str], host: &str, runas: &str, command: &str) -> Decision {
+        let sudoers = Sudoers::parse(policy, "sudoers").unwrap();
+        let groups: Vec<String> = groups.iter().map(|g| g.to_string()).collect();
+        let mut words = command.split_whitespace();
+        let command = words.next().unwrap();
+        let args: Vec<String> = words.map(String::from).collect();
+        sudoers.check(&Request { user, groups: &groups, host, runas, command, args: &args })
+    }
+
+    const ALLOW: Decision = Decision::Allow { nopasswd: false };
+    const NOPASSWD: Decision = Decision::Allow { nopasswd: true };
+
+    #[test]
+    fn users_and_groups() {
+        let policy = "alice ALL = ALL\n%wheel ALL = (ALL) ALL\n";
+        assert_eq!(check(policy, "alice", &[], "h", "root", "/bin/ls"), ALLOW);
+        // Without a runas list only root
+        assert_eq!(check(policy, "alice", &[], "h", "bob", "/bin/ls"), Decision::Deny);
+        assert_eq!(check(policy, "carol", &["users", "wheel"], "h", "bob", "/bin/ls"), ALLOW);
+        assert_eq!(check(policy, "carol", &["users"], "h", "root", "/bin/ls"), Decision::Deny);
+    }
+
+    #[test]
+    fn hosts() {
+        let policy = "alice web1, web2 = ALL\n";
+        assert_eq!(check(policy, "alice", &[], "web2", "root", "/bin/ls"), ALLOW);
+        assert_eq!(check(policy, "alice", &[], "db1", "root", "/bin/ls"), Decision::Deny);
+    }
+
+    #[test]
+    fn commands_and_arguments() {
+        let policy = "alice ALL = /bin/servicectl restart crond, /bin/date \"\", /bin/id\n";
+        assert_eq!(check(policy, "alice", &[], "h", "root", "/bin/servicectl restart crond"), ALLOW);
+        assert_eq!(check(policy, "alice", &[], "h", "root", "/bin/servicectl stop crond"), Decision::Deny);
+        assert_eq!(check(policy, "alice", &[], "h", "root", "/bin/date"), ALLOW);
+        assert_eq!(check(policy, "alice", &[], "h", "root", "/bin/date -s 10:00"), Decision::Deny);
+        assert_eq!(check(policy, "alice", &[], "h", "root", "/bin/id -u"), ALLOW);
+    }
+
+    #[test]
+    fn nopasswd() {
+        let policy = "alice ALL = NOPASSWD: /bin/id\nalice ALL = /bin/ls\nbob ALL = NOPASSWD: PASSWD: ALL\n";
+        assert_eq!(check(policy, "alice", &[], "h", "root", "/bin/id"), NOPASSWD);
+        assert_eq!(check(policy, "alice", &[], "h", "root", "/bin/ls"), ALLOW);
+        assert_eq!(check(policy, "bob", &[], "h", "root", "/bin/ls"), ALLOW);
+    }
+
+    #[test]
+    fn last_match_wins() {
+        let policy = "%wheel ALL = (ALL) ALL\nalice ALL = NOPASSWD: /bin/id\n";
+        assert_eq!(check(policy, "alice", &["wheel"], "h", "root", "/bin/id"), NOPASSWD);
+        assert_eq!(check(policy, "alice", &["wheel"], "h", "root", "/bin/ls"), ALLOW);
+
+        let policy = "alice ALL = NOPASSWD: /bin/id\n%wheel ALL = (ALL) ALL\n";
+        assert_eq!(check(policy, "alice", &["wheel"], "h", "root", "/bin/id"), ALLOW);
+    }
+
+    #[test]
+    fn negation() {
+        let policy = "ALL, !mallory ALL = ALL, !/bin/shell\n";
+        assert_eq!(check(policy, "alice", &[], "h", "root", "/bin/ls"), ALLOW);
+        assert_eq!(check(policy, "alice", &[], "h", "root", "/bin/shell"), Decision::Deny);
+        assert_eq!(check(policy, "mallory", &[], "h", "root", "/bin/ls"), Decision::Deny);
+
+        // A later negated command takes back what an earlier rule allowed
+        let policy = "alice ALL = ALL\nalice ALL = !/bin/passwd\n";
+        assert_eq!(check(policy, "alice", &[], "h", "root", "/bin/passwd"), Decision::Deny);
+        assert_eq!(check(policy, "alice", &[], "h", "root", "/bin/ls"), ALLOW);
+
+        let policy = "alice ALL = (ALL, !root) ALL\n";
+        assert_eq!(check(policy, "alice", &[], "h", "bob", "/bin/ls"), ALLOW);
+        assert_eq!(check(policy, "alice", &[], "h", "root", "/bin/ls"), Decision::Deny);
+    }
+
+    #[test]
+    fn negated_command_under_another_path() {
+        let dir = std::env::temp_dir().join(format!("vantara-sudoers-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let shell = dir.join("sh");
+        fs::write(&shell, "").unwrap();
+        let link = dir.join("link");
+        let _ = fs::remove_file(&link);
+        std::os::unix::fs::symlink(&shell, &link).unwrap();
+
+        let dir = dir.display().to_string();
+        let policy = format!("alice ALL = ALL, !{}/sh\n", dir);
+        for path in [format!("{}/sh", dir), format!("{}//sh", dir), format!("{}/./sh", dir), format!("{}/link", dir)] {
+            assert_eq!(check(&policy, "alice", &[], "h", "root", &path), Decision::Deny, "{}", path);
+        }
+        assert_eq!(check(&policy, "alice", &[], "h", "root", "/bin/ls"), ALLOW);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn aliases() {
+        let policy = "\
+User_Alias ADMINS = alice, %ops
+Host_Alias SERVERS = web1, db1
+Runas_Alias SERVICE = www, postgres
+Cmnd_Alias SHELLS = /bin/shell, /bin/sh
+Cmnd_Alias SERVICES = /bin/servicectl
+ADMINS SERVERS = (SERVICE) ALL, !SHELLS
+ADMINS SERVERS = NOPASSWD: SERVICES
+";
+        assert_eq!(check(policy, "alice", &[], "web1", "www", "/bin/ls"), ALLOW);
+        assert_eq!(check(policy, "bob", &["ops"], "db1", "postgres", "/bin/ls"), ALLOW);
+        assert_eq!(check(policy, "bob", &[], "db1", "postgres", "/bin/ls"), Decision::Deny);
+        assert_eq!(check(policy, "alice", &[], "laptop", "www", "/bin/ls"), Decision::Deny);
+        assert_eq!(check(policy, "alice", &[], "web1", "root", "/bin/ls"), Decision::Deny);
+        assert_eq!(check(policy, "alice", &[], "web1", "www", "/bin/sh"), Decision::Deny);
+        assert_eq!(check(policy, "alice", &[], "web1", "root", "/bin/servicectl status"), NOPASSWD);
+
+        // Negating an alias flips its entries
+        let policy = "User_Alias TRUSTED = ALL, !mallory\n!TRUSTED ALL = ALL\n";
+        assert_eq!(check(policy, "mallory", &[], "h", "root", "/bin/ls"), ALLOW);
+        assert_eq!(check(policy, "alice", &[], "h", "root", "/bin/ls"), Decision::Deny);
+    }
+
+    #[test]
+    fn defaults() {
+        let sudoers = Sudoers::parse(
+            "Defaults env_keep += \"EDITOR\"\nDefaults timestamp_timeout = 0\nDefaults passwd_tries = 5\n",
+            "sudoers",
+        ).unwrap();
+        assert!(sudoers.env_keep.iter().any(|v| v == "TERM"));
+        assert!(sudoers.env_keep.iter().any(|v| v == "EDITOR"));
+        assert_eq!(sudoers.timestamp_timeout, 0);
+        assert_eq!(sudoers.passwd_tries, 5);
+
+        let sudoers = Sudoers::parse("Defaults env_keep = \"PATH\"", "sudoers").unwrap();
+        assert_eq!(sudoers.env_keep, ["PATH"]);
+    }
+
+    #[test]
+    fn parse_errors() {
+        let error = |policy: &str| Sudoers::parse(policy, "sudoers").unwrap_err();
+
+        assert_eq!(error("# fine\nalice ALL ALL\n"), "sudoers:2: missing '='");
+        assert!(error("alice = ALL").contains("missing host"));
+        assert!(error("alice ALL = ls").contains("not an absolute path"));
+        assert!(error("alice ALL = (root ALL").contains("unterminated runas list"));
+        assert!(error("alice ALL = (root:wheel) ALL").contains("runas groups"));
+        assert!(error("alice ALL = /bin/ls,").contains("empty list entry"));
+        assert!(error("ADMINS ALL = ALL").contains("undefined alias 'ADMINS'"));
+        assert!(error("User_Alias admins = alice").contains("invalid alias name"));
+        assert!(error("User_Alias A = alice\nHost_Alias A = h").contains("already defined"));
+        assert!(error("Defaults lecture = always").contains("unknown Defaults setting"));
+        assert!(error("Defaults passwd_tries = many").contains("invalid passwd_tries"));
+    }
+
+    #[test]
+    fn rules_for_lists_what_applies() {
+        let sudoers = Sudoers::parse("alice ALL = NOPASSWD: /bin/id, !/bin/sh\n%wheel web1 = (ALL) ALL\n", "sudoers").unwrap();
+        let rules: Vec<String> = sudoers.rules_for("alice", &["wheel".to_string()], "db1").iter().map(|r| r.describe()).collect();
+        assert_eq!(rules, ["(root) NOPASSWD: /bin/id, !/bin/sh"]);
+    }
+}
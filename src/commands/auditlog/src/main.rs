@@ -0,0 +1,141 @@
+use chrono::{Local, NaiveDate, TimeZone};
+use std::env;
+use std::path::PathBuf;
+use std::process::exit;
+use vantara::audit::{read_verified, EventKind, Outcome, DEFAULT_AUDIT_LOG_PATH, GENESIS_HASH};
+use vantara::{package_name, print_version, safe_eprintln, safe_println};
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let mut path = PathBuf::from(DEFAULT_AUDIT_LOG_PATH);
+    let mut verify_only = false;
+    let mut head_only = false;
+    let mut kinds: Vec<EventKind> = Vec::new();
+    let mut user: Option<String> = None;
+    let mut failed_only = false;
+    let mut since: Option<i64> = None;
+    let mut limit: Option<usize> = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--help" => { print_usage(); exit(0); },
+            "--version" => { print_version!(); exit(0); },
+            "--verify" => verify_only = true,
+            "--head" => head_only = true,
+            "-f" | "--file" => path = PathBuf::from(required(&arg, args.next())),
+            "-t" | "--type" => {
+                let value = required(&arg, args.next());
+                for name in value.split(',') {
+                    match EventKind::parse(name) {
+                        Some(kind) => kinds.push(kind),
+                        None => fail_with(2, &format!("unknown event type '{}', one of: {}", name, kind_names())),
+                    }
+                }
+            }
+            "-u" | "--user" => user = Some(required(&arg, args.next())),
+            "--failed" => failed_only = true,
+            "-s" | "--since" => {
+                let value = required(&arg, args.next());
+                let day = NaiveDate::parse_from_str(&value, "%Y-%m-%d")
+                    .unwrap_or_else(|_| fail_with(2, &format!("invalid date '{}', expected YYYY-MM-DD", value)));
+                since = day
+                    .and_hms_opt(0, 0, 0)
+                    .and_then(|t| Local.from_local_datetime(&t).earliest())
+                    .map(|t| t.timestamp());
+            }
+            "-n" | "--limit" => {
+                limit = Some(required(&arg, args.next()).parse().unwrap_or_else(|_| fail_with(2, &format!("option {} requires a number", arg))))
+            }
+            _ => fail_with(2, &format!("unknown option '{}'", arg)),
+        }
+    }
+
+    // The log is readable by root only, this binary is setuid
+    if unsafe { libc::getuid() } != 0 {
+        fail_with(1, "only root may read the audit log");
+    }
+
+    let (events, chain_break) = read_verified(&path)
+        .unwrap_or_else(|e| fail_with(1, &format!("cannot read {}: {}", path.display(), e)));
+    let head = events.last().map(|e| e.hash.as_str()).unwrap_or(GENESIS_HASH);
+
+    if head_only {
+        safe_println(format_args!("{}", head));
+    } else if verify_only {
+        if chain_break.is_none() {
+            safe_println(format_args!("{}: {} records, chain intact", path.display(), events.len()));
+            safe_println(format_args!("head {}", head));
+        }
+    } else {
+        let selected: Vec<_> = events
+            .iter()
+            .filter(|e| kinds.is_empty() || kinds.contains(&e.kind))
+            .filter(|e| user.as_ref().is_none_or(|u| *u == e.user))
+            .filter(|e| !failed_only || e.outcome == Outcome::Failure)
+            .filter(|e| since.is_none_or(|s| e.time >= s))
+            .collect();
+        let skip = limit.map(|n| selected.len().saturating_sub(n)).unwrap_or(0);
+
+        for event in &selected[skip..] {
+            safe_println(format_args!(
+                "{:>6} {} {:<8} {:<8} {:<12} {:<8} {}",
+                event.seq,
+                format_time(event.time),
+                event.kind,
+                event.outcome.as_str(),
+                event.user,
+                if event.tty.is_empty() { "-" } else { event.tty.as_str() },
+                event.detail
+            ));
+        }
+    }
+
+    if let Some(chain_break) = chain_break {
+        safe_eprintln(format_args!(
+            "{}: {} line {}: {}, the log was altered after record {}",
+            package_name!(),
+            path.display(),
+            chain_break.line,
+            chain_break.reason,
+            events.len()
+        ));
+        exit(1);
+    }
+}
+
+fn format_time(time: i64) -> String {
+    Local
+        .timestamp_opt(time, 0)
+        .single()
+        .map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or_else(|| time.to_string())
+}
+
+fn kind_names() -> String {
+    EventKind::ALL.iter().map(EventKind::as_str).collect::<Vec<_>>().join(", ")
+}
+
+fn required(option: &str, value: Option<String>) -> String {
+    value.unwrap_or_else(|| fail_with(2, &format!("option {} requires an argument", option)))
+}
+
+fn fail_with(code: i32, message: &str) -> ! {
+    safe_eprintln(format_args!("{}: {}", package_name!(), message));
+    exit(code);
+}
+
+fn print_usage() {
+    safe_println(format_args!("Usage: {} [options]", package_name!()));
+    safe_println(format_args!("Show the audit log after checking its hash chain."));
+    safe_println(format_args!("Options:"));
+    safe_println(format_args!("     --verify                  Only check the chain, exit 1 when it is broken"));
+    safe_println(format_args!("     --head                    Print the hash of the last record, to keep elsewhere"));
+    safe_println(format_args!("     -f, --file FILE           Read FILE instead of {}", DEFAULT_AUDIT_LOG_PATH));
+    safe_println(format_args!("     -t, --type TYPE[,TYPE]    Only events of these types: {}", kind_names()));
+    safe_println(format_args!("     -u, --user USER           Only events of USER"));
+    safe_println(format_args!("     --failed                  Only failures"));
+    safe_println(format_args!("     -s, --since YYYY-MM-DD    Only events from that day on"));
+    safe_println(format_args!("     -n, --limit N             Only the last N matching events"));
+    safe_println(format_args!("     --help                    Show help"));
+    safe_println(format_args!("     --version                 Show version"));
+}
@@ -0,0 +1,145 @@
+use std::env;
+use std::process::exit;
+use vantara::nss::getpwuid;
+use vantara::auth::modules::shadow::{format_day, get_shadow_entry, modify_shadow_entry, parse_day, ShadowEntry};
+use vantara::{package_name, print_version, safe_eprintln, safe_println};
+
+enum Field {
+    LastChange,
+    Min,
+    Max,
+    Warn,
+    Inactive,
+    Expire,
+}
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let mut list = false;
+    let mut changes: Vec<(Field, Option<i64>)> = Vec::new();
+    let mut username: Option<String> = None;
+
+    while let Some(arg) = args.next() {
+        let field = match arg.as_str() {
+            "--help" => { print_usage(); exit(0); },
+            "--version" => { print_version!(); exit(0); },
+            "-l" | "--list" => { list = true; continue; },
+            "-d" | "--lastday" => Field::LastChange,
+            "-m" | "--mindays" => Field::Min,
+            "-M" | "--maxdays" => Field::Max,
+            "-W" | "--warndays" => Field::Warn,
+            "-I" | "--inactive" => Field::Inactive,
+            "-E" | "--expiredate" => Field::Expire,
+            _ if arg.starts_with('-') => {
+                safe_eprintln(format_args!("{}: unknown option '{}'", package_name!(), arg));
+                exit(2);
+            }
+            _ => {
+                username = Some(arg);
+                continue;
+            }
+        };
+
+        let value = match args.next() {
+            Some(value) => value,
+            None => {
+                safe_eprintln(format_args!("{}: option {} requires an argument", package_name!(), arg));
+                exit(2);
+            }
+        };
+        // -1 clears a field, which turns that check off
+        let day = if value == "-1" { None } else {
+            match parse_day(&value) {
+                Some(day) if day >= 0 => Some(day),
+                _ => {
+                    safe_eprintln(format_args!("{}: invalid value '{}' for {}", package_name!(), value, arg));
+                    exit(2);
+                }
+            }
+        };
+        changes.push((field, day));
+    }
+
+    let username = match username {
+        Some(name) => name,
+        None => {
+            print_usage();
+            exit(2);
+        }
+    };
+
+    let uid = unsafe { libc::getuid() };
+    let own_account = getpwuid(uid).is_some_and(|u| u.username == username);
+    if uid != 0 && !(list && changes.is_empty() && own_account) {
+        safe_eprintln(format_args!("{}: permission denied", package_name!()));
+        exit(1);
+    }
+
+    let mut entry = match get_shadow_entry(&username) {
+        Some(entry) => entry,
+        None => {
+            safe_eprintln(format_args!("{}: user '{}' has no shadow entry", package_name!(), username));
+            exit(1);
+        }
+    };
+
+    if changes.is_empty() {
+        print_ageing(&entry);
+        exit(0);
+    }
+
+    // Applied to the entry as it is under the lock, not the copy read above
+    let result = modify_shadow_entry(&username, |locked| {
+        for (field, day) in &changes {
+            match field {
+                Field::LastChange => locked.last_change = *day,
+                Field::Min => locked.min_days = *day,
+                Field::Max => locked.max_days = *day,
+                Field::Warn => locked.warn_days = *day,
+                Field::Inactive => locked.inactive_days = *day,
+                Field::Expire => locked.expire_date = *day,
+            }
+        }
+        entry = locked.clone();
+    });
+    if let Err(e) = result {
+        safe_eprintln(format_args!("{}: cannot update shadow file: {}", package_name!(), e));
+        exit(1);
+    }
+    if list {
+        print_ageing(&entry);
+    }
+}
+
+fn print_ageing(entry: &ShadowEntry) {
+    let never = || "never".to_string();
+    let last_change = match entry.last_change {
+        Some(0) => "password must be changed".to_string(),
+        Some(day) => format_day(day),
+        None => never(),
+    };
+    let number = |n: Option<i64>| n.map(|n| n.to_string()).unwrap_or_else(|| "-1".to_string());
+
+    safe_println(format_args!("{:<54}: {}", "Last password change", last_change));
+    safe_println(format_args!("{:<54}: {}", "Password expires", entry.password_expires().map(format_day).unwrap_or_else(never)));
+    safe_println(format_args!("{:<54}: {}", "Password inactive", entry.password_inactive().map(format_day).unwrap_or_else(never)));
+    safe_println(format_args!("{:<54}: {}", "Account expires", entry.expire_date.map(format_day).unwrap_or_else(never)));
+    safe_println(format_args!("{:<54}: {}", "Minimum number of days between password change", number(entry.min_days)));
+    safe_println(format_args!("{:<54}: {}", "Maximum number of days between password change", number(entry.max_days)));
+    safe_println(format_args!("{:<54}: {}", "Number of days of warning before password expires", number(entry.warn_days)));
+}
+
+fn print_usage() {
+    safe_println(format_args!("Usage: {} [options] USER", package_name!()));
+    safe_println(format_args!("Options:"));
+    safe_println(format_args!("     -l, --list                Show account ageing information"));
+    safe_println(format_args!("     -d, --lastday DAY         Set the last password change, 0 forces a change at next login"));
+    safe_println(format_args!("     -m, --mindays DAYS        Minimum days between password changes"));
+    safe_println(format_args!("     -M, --maxdays DAYS        Maximum days a password is valid"));
+    safe_println(format_args!("     -W, --warndays DAYS       Days of warning before the password expires"));
+    safe_println(format_args!("     -I, --inactive DAYS       Days after expiry the password can still be changed"));
+    safe_println(format_args!("     -E, --expiredate DAY      Day the account expires"));
+    safe_println(format_args!("     --help                    Show help"));
+    safe_println(format_args!("     --version                 Show version"));
+    safe_println(format_args!("DAY is YYYY-MM-DD or days since 1970-01-01, -1 removes a setting."));
+}
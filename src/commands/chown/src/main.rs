@@ -2,7 +2,7 @@ use std::env;
 use std::path::Path;
 use std::fs::{metadata, symlink_metadata};
 use nix::unistd::{chown, Gid, Uid};
-use users::{get_user_by_name, get_group_by_name};
+use vantara::nss::{getgrnam, getpwnam};
 use std::process::exit;
 use walkdir::WalkDir;
 use vantara::{print_version, safe_println, safe_eprintln, package_name};
@@ -85,8 +85,8 @@ fn parse_user_group(input: &str) -> (Option<Uid>, Option<Gid>) {
         _ => (None, None),
     };
 
-    let uid = user.and_then(|u| get_user_by_name(u).map(|u| Uid::from_raw(u.uid())));
-    let gid = group.and_then(|g| get_group_by_name(g).map(|g| Gid::from_raw(g.gid())));
+    let uid = user.and_then(|u| getpwnam(u).map(|u| Uid::from_raw(u.uid)));
+    let gid = group.and_then(|g| getgrnam(g).map(|g| Gid::from_raw(g.gid)));
     (uid, gid)
 }
 
@@ -0,0 +1,153 @@
+use std::env;
+use std::fs;
+use std::os::unix::process::CommandExt;
+use std::process::{exit, Child, Command, Stdio};
+use vantara::cron::scheduler::{reboot_entries, Scheduler, SystemClock};
+use vantara::cron::table::{load_all_tables, CronEntry, CronTable};
+use vantara::auth::modules::group::drop_privileges;
+use vantara::nss::{getgrouplist, getpwnam};
+use vantara::{get_system_timezone, package_name, print_version, safe_eprintln, safe_println};
+
+const DEFAULT_BOOT_ID_PATH: &str = "/proc/sys/kernel/random/boot_id";
+const DEFAULT_REBOOT_MARKER_PATH: &str = "/run/crond.boot_id";
+const DEFAULT_JOB_SHELL: &str = "/bin/shell";
+const DEFAULT_JOB_PATH: &str = "/bin:/usr/bin";
+
+struct RunningJob {
+    child: Child,
+    user: String,
+    command: String,
+}
+
+fn main() {
+    if let Some(arg) = env::args().nth(1) {
+        match arg.as_str() {
+            "--help" => { print_usage(); exit(0); },
+            "--version" => { print_version!(); exit(0); },
+            _ => {
+                safe_eprintln(format_args!("{}: unknown option '{}'", package_name!(), arg));
+                exit(1);
+            }
+        }
+    }
+
+    if unsafe { libc::getuid() } != 0 {
+        safe_eprintln(format_args!("{}: must be run as root", package_name!()));
+        exit(1);
+    }
+
+    let mut scheduler = Scheduler::new(SystemClock);
+    let mut running: Vec<RunningJob> = Vec::new();
+
+    let (tables, errors) = load_all_tables();
+    let mut last_errors = errors;
+    for e in &last_errors {
+        safe_eprintln(format_args!("[CRON] {}", e));
+    }
+    safe_println(format_args!("[CRON] Loaded {} tables", tables.len()));
+
+    if first_start_since_boot() {
+        for (table, entry) in reboot_entries(&tables) {
+            start_job(table, entry, &mut running);
+        }
+    }
+
+    loop {
+        std::thread::sleep(scheduler.until_next_minute());
+        reap_jobs(&mut running);
+
+        // Tables are re-read every minute, so crontab edits apply without a signal
+        let (tables, errors) = load_all_tables();
+        if errors != last_errors {
+            for e in &errors {
+                safe_eprintln(format_args!("[CRON] {}", e));
+            }
+            last_errors = errors;
+        }
+
+        let tz = get_system_timezone();
+        for (table, entry) in scheduler.due(&tables, &tz) {
+            start_job(table, entry, &mut running);
+        }
+    }
+}
+
+/// `@reboot` jobs run once per boot, not every time crond is restarted.
+fn first_start_since_boot() -> bool {
+    let boot_id = fs::read_to_string(DEFAULT_BOOT_ID_PATH).unwrap_or_default();
+    let seen = fs::read_to_string(DEFAULT_REBOOT_MARKER_PATH).unwrap_or_default();
+    if !boot_id.is_empty() && boot_id == seen {
+        return false;
+    }
+    let _ = fs::write(DEFAULT_REBOOT_MARKER_PATH, &boot_id);
+    true
+}
+
+/// Run the job through the shell with the owner's uid, gid and a fresh environment.
+fn start_job(table: &CronTable, entry: &CronEntry, running: &mut Vec<RunningJob>) {
+    let user = match getpwnam(&entry.user) {
+        Some(u) => u,
+        None => {
+            safe_eprintln(format_args!("[CRON] {}: unknown user '{}', job skipped", entry.origin, entry.user));
+            return;
+        }
+    };
+
+    let home = if fs::metadata(&user.home).is_ok() { user.home.clone() } else { "/".to_string() };
+    // Command::uid would clear the supplementary groups, set them like su and sudo do
+    let groups = getgrouplist(&user.username, user.gid);
+    let (uid, gid) = (user.uid, user.gid);
+
+    let mut command = Command::new(DEFAULT_JOB_SHELL);
+    command
+        .arg("-c")
+        .arg(&entry.command)
+        .env_clear()
+        .env("HOME", &user.home)
+        .env("USER", &user.username)
+        .env("LOGNAME", &user.username)
+        .env("SHELL", &user.shell)
+        .env("PATH", DEFAULT_JOB_PATH)
+        .envs(table.env.iter().map(|(k, v)| (k, v)))
+        .current_dir(home)
+        .stdin(Stdio::null());
+    unsafe {
+        command.pre_exec(move || drop_privileges(uid, gid, &groups));
+    }
+    let result = command.spawn();
+
+    match result {
+        Ok(child) => {
+            safe_println(format_args!("[CRON] ({}) CMD ({}) PID {}", user.username, entry.command, child.id()));
+            running.push(RunningJob {
+                child,
+                user: user.username,
+                command: entry.command.clone(),
+            });
+        }
+        Err(e) => {
+            safe_eprintln(format_args!("[CRON] ({}) failed to start '{}': {}", user.username, entry.command, e));
+        }
+    }
+}
+
+fn reap_jobs(running: &mut Vec<RunningJob>) {
+    running.retain_mut(|job| match job.child.try_wait() {
+        Ok(Some(status)) => {
+            if !status.success() {
+                safe_eprintln(format_args!("[CRON] ({}) CMD ({}) {}", job.user, job.command, status));
+            }
+            false
+        }
+        Ok(None) => true,
+        Err(_) => false,
+    });
+}
+
+fn print_usage() {
+    safe_println(format_args!("Usage: {}", package_name!()));
+    safe_println(format_args!("Runs jobs from /etc/crontab, /etc/cron.d/* and /var/spool/cron/crontabs/<user>."));
+    safe_println(format_args!("Options:"));
+    safe_println(format_args!("     --help          Show this help"));
+    safe_println(format_args!("     --version       Show version"));
+}
@@ -0,0 +1,262 @@
+use std::env;
+use std::ffi::CString;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Read, Write};
+use std::os::unix::fs::{MetadataExt, OpenOptionsExt, PermissionsExt};
+use std::os::unix::process::CommandExt;
+use std::path::Path;
+use std::process::{exit, Command};
+use vantara::nss::{getpwnam, getpwuid, PasswdEntry};
+use vantara::cron::table::{user_crontab_path, CronTable, DEFAULT_USER_CRONTAB_PATH};
+use vantara::{confirm, package_name, print_version, safe_eprintln, safe_print, safe_println};
+
+const EDITOR: &str = "edit";
+const NEW_CRONTAB_TEMPLATE: &str = "\
+# m h dom mon dow command
+# Example, run a backup every day at 02:30:
+# 30 2 * * * /bin/rtar -czf /tmp/backup.tar.gz /home
+";
+
+enum Action {
+    List,
+    Edit,
+    Remove,
+    Install(String),
+}
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let mut action = None;
+    let mut target_user: Option<String> = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--help" => { print_usage(); exit(0); },
+            "--version" => { print_version!(); exit(0); },
+            "-l" => action = Some(Action::List),
+            "-e" => action = Some(Action::Edit),
+            "-r" => action = Some(Action::Remove),
+            "-u" => match args.next() {
+                Some(user) => target_user = Some(user),
+                None => {
+                    safe_eprintln(format_args!("{}: option -u requires a user name", package_name!()));
+                    exit(1);
+                }
+            },
+            _ if arg.starts_with('-') && arg != "-" => {
+                safe_eprintln(format_args!("{}: unknown option '{}'", package_name!(), arg));
+                exit(1);
+            }
+            _ => action = Some(Action::Install(arg)),
+        }
+    }
+
+    let action = match action {
+        Some(a) => a,
+        None => {
+            print_usage();
+            exit(1);
+        }
+    };
+
+    let caller = match getpwuid(unsafe { libc::getuid() }) {
+        Some(user) => user,
+        None => {
+            safe_eprintln(format_args!("{}: cannot determine your user name", package_name!()));
+            exit(1);
+        }
+    };
+
+    let user = match target_user {
+        Some(name) if name != caller.username => {
+            if caller.uid != 0 {
+                safe_eprintln(format_args!("{}: only root may use -u", package_name!()));
+                exit(1);
+            }
+            getpwnam(&name).unwrap_or_else(|| {
+                safe_eprintln(format_args!("{}: user '{}' unknown", package_name!(), name));
+                exit(1);
+            })
+        }
+        _ => caller,
+    };
+
+    let path = user_crontab_path(&user.username);
+    let code = match action {
+        Action::List => list(&user, &path),
+        Action::Remove => remove(&user, &path),
+        Action::Edit => edit(&user, &path),
+        Action::Install(source) => install_from(&user, &path, &source),
+    };
+    exit(code);
+}
+
+fn list(user: &PasswdEntry, path: &str) -> i32 {
+    match fs::read_to_string(path) {
+        Ok(content) => {
+            safe_print(format_args!("{}", content));
+            0
+        }
+        Err(_) => {
+            safe_eprintln(format_args!("no crontab for {}", user.username));
+            1
+        }
+    }
+}
+
+fn remove(user: &PasswdEntry, path: &str) -> i32 {
+    match fs::remove_file(path) {
+        Ok(_) => 0,
+        Err(_) => {
+            safe_eprintln(format_args!("no crontab for {}", user.username));
+            1
+        }
+    }
+}
+
+/// Copy the crontab to a temp file owned by the user, let them edit it with their own
+/// privileges, and install it only once it parses.
+fn edit(user: &PasswdEntry, path: &str) -> i32 {
+    let original = fs::read_to_string(path).unwrap_or_else(|_| NEW_CRONTAB_TEMPLATE.to_string());
+    let tmp = format!("/tmp/crontab.{}", std::process::id());
+
+    let written = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(&tmp)
+        .and_then(|mut f| f.write_all(original.as_bytes()));
+    if let Err(e) = written {
+        safe_eprintln(format_args!("{}: cannot create {}: {}", package_name!(), tmp, e));
+        return 1;
+    }
+    let _ = std::os::unix::fs::chown(&tmp, Some(user.uid), Some(user.gid));
+
+    let code = loop {
+        let status = Command::new(EDITOR).arg(&tmp).uid(user.uid).gid(user.gid).status();
+        if let Err(e) = status {
+            safe_eprintln(format_args!("{}: failed to run {}: {}", package_name!(), EDITOR, e));
+            break 1;
+        }
+
+        let content = match read_back(&tmp, user) {
+            Ok(content) => content,
+            Err(e) => {
+                safe_eprintln(format_args!("{}: cannot read {}: {}", package_name!(), tmp, e));
+                break 1;
+            }
+        };
+        if content == original {
+            safe_println(format_args!("{}: no changes made to crontab", package_name!()));
+            break 0;
+        }
+
+        let (_, errors) = CronTable::parse(&content, "crontab", Some(&user.username));
+        if errors.is_empty() {
+            break install(path, &content, &user.username);
+        }
+
+        for e in &errors {
+            safe_eprintln(format_args!("{}", e));
+        }
+        if !confirm("Errors in crontab file. Edit again?") {
+            safe_eprintln(format_args!("{}: edits left in {}", package_name!(), tmp));
+            return 1;
+        }
+    };
+
+    let _ = fs::remove_file(&tmp);
+    code
+}
+
+/// The temp file belongs to the user by now, who may have swapped it for a link to
+/// something only root can read. Only a plain file of theirs is read back.
+fn read_back(tmp: &str, user: &PasswdEntry) -> io::Result<String> {
+    let mut file = OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NOFOLLOW | libc::O_NONBLOCK)
+        .open(tmp)?;
+    let meta = file.metadata()?;
+    if !meta.is_file() || meta.uid() != user.uid || meta.nlink() != 1 {
+        return Err(io::Error::new(io::ErrorKind::PermissionDenied, "not a file of yours"));
+    }
+
+    let mut content = String::new();
+    file.read_to_string(&mut content)?;
+    Ok(content)
+}
+
+fn install_from(user: &PasswdEntry, path: &str, source: &str) -> i32 {
+    let mut content = String::new();
+
+    let read = if source == "-" {
+        io::stdin().read_to_string(&mut content).map(|_| ())
+    } else {
+        // We may be running setuid, so check the file against the real uid
+        let c_path = CString::new(source).unwrap_or_default();
+        if unsafe { libc::access(c_path.as_ptr(), libc::R_OK) } != 0 {
+            safe_eprintln(format_args!("{}: cannot read {}", package_name!(), source));
+            return 1;
+        }
+        fs::read_to_string(source).map(|c| content = c)
+    };
+    if let Err(e) = read {
+        safe_eprintln(format_args!("{}: cannot read {}: {}", package_name!(), source, e));
+        return 1;
+    }
+
+    let (_, errors) = CronTable::parse(&content, source, Some(&user.username));
+    if !errors.is_empty() {
+        for e in &errors {
+            safe_eprintln(format_args!("{}", e));
+        }
+        safe_eprintln(format_args!("{}: errors in crontab file, can't install", package_name!()));
+        return 1;
+    }
+
+    install(path, &content, &user.username)
+}
+
+/// Write through a temp file and rename, so crond never reads half a crontab.
+fn install(path: &str, content: &str, username: &str) -> i32 {
+    if let Err(e) = fs::create_dir_all(DEFAULT_USER_CRONTAB_PATH) {
+        safe_eprintln(format_args!("{}: cannot create {}: {}", package_name!(), DEFAULT_USER_CRONTAB_PATH, e));
+        return 1;
+    }
+    let _ = fs::set_permissions(DEFAULT_USER_CRONTAB_PATH, fs::Permissions::from_mode(0o700));
+
+    let tmp = Path::new(DEFAULT_USER_CRONTAB_PATH).join(format!(".{}.tmp", username));
+    let result = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(&tmp)
+        .and_then(|mut f| f.write_all(content.as_bytes()))
+        .and_then(|_| fs::rename(&tmp, path));
+
+    match result {
+        Ok(_) => {
+            safe_println(format_args!("{}: installing new crontab", package_name!()));
+            0
+        }
+        Err(e) => {
+            let _ = fs::remove_file(&tmp);
+            safe_eprintln(format_args!("{}: cannot install crontab: {}", package_name!(), e));
+            1
+        }
+    }
+}
+
+fn print_usage() {
+    safe_println(format_args!("Usage: {} [-u USER] FILE", package_name!()));
+    safe_println(format_args!("       {} [-u USER] -l | -e | -r", package_name!()));
+    safe_println(format_args!("Options:"));
+    safe_println(format_args!("     FILE        Install FILE as the crontab, - reads stdin"));
+    safe_println(format_args!("     -l          Show the crontab"));
+    safe_println(format_args!("     -e          Edit the crontab with {}", EDITOR));
+    safe_println(format_args!("     -r          Remove the crontab"));
+    safe_println(format_args!("     -u USER     Work on USER's crontab (root only)"));
+    safe_println(format_args!("     --help      Show help"));
+    safe_println(format_args!("     --version   Show version"));
+}
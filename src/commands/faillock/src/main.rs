@@ -0,0 +1,93 @@
+use std::env;
+use std::path::PathBuf;
+use std::process::exit;
+use vantara::auth::modules::faillock::{format_time, now_secs, FaillockConfig, Tally};
+use vantara::nss::getpwnam;
+use vantara::{package_name, print_version, safe_eprintln, safe_println};
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let mut config = FaillockConfig::load();
+    let mut user: Option<String> = None;
+    let mut reset = false;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--help" => { print_usage(); exit(0); },
+            "--version" => { print_version!(); exit(0); },
+            "--reset" => reset = true,
+            "--user" | "--dir" => match args.next() {
+                Some(value) if arg == "--user" => user = Some(value),
+                Some(value) => config.dir = PathBuf::from(value),
+                None => {
+                    safe_eprintln(format_args!("{}: option {} requires an argument", package_name!(), arg));
+                    exit(1);
+                }
+            },
+            _ => {
+                safe_eprintln(format_args!("{}: unknown option '{}'", package_name!(), arg));
+                print_usage();
+                exit(1);
+            }
+        }
+    }
+
+    if unsafe { libc::getuid() } != 0 {
+        safe_eprintln(format_args!("{}: only root may read or reset the tally", package_name!()));
+        exit(1);
+    }
+
+    if let Some(name) = &user {
+        if getpwnam(name).is_none() {
+            safe_eprintln(format_args!("{}: user '{}' unknown", package_name!(), name));
+            exit(1);
+        }
+    }
+
+    let users = match user {
+        Some(name) => vec![name],
+        None => Tally::users(&config.dir),
+    };
+
+    if reset {
+        let mut code = 0;
+        for name in &users {
+            if let Err(e) = Tally::reset(&config.dir, name) {
+                safe_eprintln(format_args!("{}: cannot reset {}: {}", package_name!(), name, e));
+                code = 1;
+            }
+        }
+        exit(code);
+    }
+
+    let now = now_secs();
+    let window_start = now.saturating_sub(config.fail_interval);
+    for name in &users {
+        let tally = Tally::load(&config.dir, name);
+        let status = match tally.locked_until(&config, now) {
+            Some(0) => "locked until reset".to_string(),
+            Some(until) => format!("locked until {}", format_time(until)),
+            None => "not locked".to_string(),
+        };
+        safe_println(format_args!("{}: {} failures, {}", name, tally.records.len(), status));
+        if tally.records.is_empty() {
+            continue;
+        }
+        safe_println(format_args!("{:<20} {:<16} {}", "When", "Source", "Valid"));
+        for record in &tally.records {
+            let valid = if record.time >= window_start { "V" } else { "I" };
+            safe_println(format_args!("{:<20} {:<16} {}", format_time(record.time), record.source, valid));
+        }
+    }
+}
+
+fn print_usage() {
+    safe_println(format_args!("Usage: {} [--dir DIR] [--user USER] [--reset]", package_name!()));
+    safe_println(format_args!("Options:"));
+    safe_println(format_args!("     --user USER   Only show or reset USER's tally"));
+    safe_println(format_args!("     --reset       Clear the tally, which also unlocks the account"));
+    safe_println(format_args!("     --dir DIR     Tally directory, default from faillock.conf"));
+    safe_println(format_args!("     --help        Show help"));
+    safe_println(format_args!("     --version     Show version"));
+    safe_println(format_args!("Records marked V count towards the lock, I are outside fail_interval."));
+}
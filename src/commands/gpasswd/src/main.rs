@@ -0,0 +1,101 @@
+use std::env;
+use std::path::Path;
+use std::process::exit;
+use vantara::auth::modules::account_db::AccountDb;
+use vantara::{package_name, print_version, safe_eprintln, safe_println};
+
+enum Action {
+    Add(String),
+    Delete(String),
+    SetMembers(Vec<String>),
+}
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let mut action: Option<Action> = None;
+    let mut group: Option<String> = None;
+    let mut root = String::from("/");
+
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().unwrap_or_else(|| fail_with(2, &format!("option {} requires an argument", arg)));
+        match arg.as_str() {
+            "--help" => { print_usage(); exit(0); },
+            "--version" => { print_version!(); exit(0); },
+            "-a" | "--add" => action = Some(Action::Add(value())),
+            "-d" | "--delete" => action = Some(Action::Delete(value())),
+            "-M" | "--members" => {
+                action = Some(Action::SetMembers(value().split(',').filter(|m| !m.is_empty()).map(String::from).collect()))
+            }
+            "-R" | "--root" => root = value(),
+            _ if arg.starts_with('-') => {
+                safe_eprintln(format_args!("{}: unknown option '{}'", package_name!(), arg));
+                exit(2);
+            }
+            _ => group = Some(arg),
+        }
+    }
+
+    let (action, group) = match (action, group) {
+        (Some(action), Some(group)) => (action, group),
+        _ => {
+            print_usage();
+            exit(2);
+        }
+    };
+    if unsafe { libc::getuid() } != 0 {
+        fail_with(1, "permission denied");
+    }
+
+    let mut db = AccountDb::open_at(Path::new(&root)).unwrap_or_else(|e| fail_with(1, &e));
+    if db.group(&group).is_none() {
+        fail_with(3, &format!("group '{}' does not exist", group));
+    }
+    let users: Vec<&String> = match &action {
+        Action::Add(user) | Action::Delete(user) => vec![user],
+        Action::SetMembers(users) => users.iter().collect(),
+    };
+    if let Some(missing) = users.iter().find(|u| db.user(u).is_none()) {
+        fail_with(3, &format!("user '{}' does not exist", missing));
+    }
+
+    let entry = match db.group.find_mut(&group) {
+        Some(entry) => entry,
+        None => fail_with(3, &format!("group '{}' does not exist", group)),
+    };
+    match action {
+        Action::Add(user) => {
+            safe_println(format_args!("Adding user {} to group {}", user, group));
+            if !entry.members.contains(&user) {
+                entry.members.push(user);
+            }
+        }
+        Action::Delete(user) => {
+            if !entry.members.contains(&user) {
+                fail_with(3, &format!("user '{}' is not a member of '{}'", user, group));
+            }
+            safe_println(format_args!("Removing user {} from group {}", user, group));
+            entry.members.retain(|m| m != &user);
+        }
+        Action::SetMembers(mut users) => {
+            users.dedup();
+            entry.members = users;
+        }
+    }
+    db.commit().unwrap_or_else(|e| fail_with(10, &e));
+}
+
+fn fail_with(code: i32, message: &str) -> ! {
+    safe_eprintln(format_args!("{}: {}", package_name!(), message));
+    exit(code);
+}
+
+fn print_usage() {
+    safe_println(format_args!("Usage: {} [-R DIR] OPTION GROUP", package_name!()));
+    safe_println(format_args!("Options:"));
+    safe_println(format_args!("     -a, --add USER         Add USER to GROUP"));
+    safe_println(format_args!("     -d, --delete USER      Remove USER from GROUP"));
+    safe_println(format_args!("     -M, --members U1,U2    Set the member list of GROUP"));
+    safe_println(format_args!("     -R, --root DIR         Work on the system installed at DIR"));
+    safe_println(format_args!("     --help                 Show help"));
+    safe_println(format_args!("     --version              Show version"));
+}
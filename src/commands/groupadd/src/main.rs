@@ -0,0 +1,83 @@
+use std::env;
+use std::path::Path;
+use std::process::exit;
+use vantara::auth::modules::account_db::{is_valid_name, AccountDb};
+use vantara::auth::modules::group::GroupEntry;
+use vantara::{package_name, print_version, safe_eprintln, safe_println};
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let mut gid: Option<u32> = None;
+    let mut system = false;
+    let mut force = false;
+    let mut name: Option<String> = None;
+    let mut root = String::from("/");
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--help" => { print_usage(); exit(0); },
+            "--version" => { print_version!(); exit(0); },
+            "-g" | "--gid" => {
+                let value = args.next().unwrap_or_else(|| fail_with(2, "option -g requires an argument"));
+                gid = Some(value.parse().unwrap_or_else(|_| fail_with(3, &format!("invalid gid '{}'", value))));
+            }
+            "-r" | "--system" => system = true,
+            "-f" | "--force" => force = true,
+            "-R" | "--root" => root = args.next().unwrap_or_else(|| fail_with(2, "option -R requires an argument")),
+            _ if arg.starts_with('-') => {
+                safe_eprintln(format_args!("{}: unknown option '{}'", package_name!(), arg));
+                exit(2);
+            }
+            _ => name = Some(arg),
+        }
+    }
+
+    let name = match name {
+        Some(name) => name,
+        None => {
+            print_usage();
+            exit(2);
+        }
+    };
+    if !is_valid_name(&name) {
+        fail_with(3, &format!("invalid group name '{}'", name));
+    }
+    if unsafe { libc::getuid() } != 0 {
+        fail_with(1, "permission denied");
+    }
+
+    let mut db = AccountDb::open_at(Path::new(&root)).unwrap_or_else(|e| fail_with(1, &e));
+    if db.group(&name).is_some() {
+        // -f: the group being there already is fine
+        if force {
+            exit(0);
+        }
+        fail_with(9, &format!("group '{}' already exists", name));
+    }
+
+    let gid = match gid {
+        // -f: pick another gid when the requested one is taken
+        Some(gid) if db.group_by_gid(gid).is_some() && !force => fail_with(4, &format!("gid {} is not unique", gid)),
+        Some(gid) if db.group_by_gid(gid).is_none() => gid,
+        _ => db.next_gid(system, None).unwrap_or_else(|e| fail_with(4, &e)),
+    };
+
+    db.group.insert(GroupEntry { groupname: name, password: "x".to_string(), gid, members: Vec::new() });
+    db.commit().unwrap_or_else(|e| fail_with(10, &e));
+}
+
+fn fail_with(code: i32, message: &str) -> ! {
+    safe_eprintln(format_args!("{}: {}", package_name!(), message));
+    exit(code);
+}
+
+fn print_usage() {
+    safe_println(format_args!("Usage: {} [options] GROUP", package_name!()));
+    safe_println(format_args!("Options:"));
+    safe_println(format_args!("     -g, --gid GID      Group id, default the next free one"));
+    safe_println(format_args!("     -r, --system       Create a system group"));
+    safe_println(format_args!("     -f, --force        Succeed if the group exists, pick another gid if GID is taken"));
+    safe_println(format_args!("     -R, --root DIR     Work on the system installed at DIR"));
+    safe_println(format_args!("     --help             Show help"));
+    safe_println(format_args!("     --version          Show version"));
+}
@@ -0,0 +1,57 @@
+use std::env;
+use std::path::Path;
+use std::process::exit;
+use vantara::auth::modules::account_db::AccountDb;
+use vantara::{package_name, print_version, safe_eprintln, safe_println};
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let mut name: Option<String> = None;
+    let mut root = String::from("/");
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--help" => { print_usage(); exit(0); },
+            "--version" => { print_version!(); exit(0); },
+            "-R" | "--root" => root = args.next().unwrap_or_else(|| fail_with(2, "option -R requires an argument")),
+            _ if arg.starts_with('-') => {
+                safe_eprintln(format_args!("{}: unknown option '{}'", package_name!(), arg));
+                exit(2);
+            }
+            _ => name = Some(arg),
+        }
+    }
+
+    let name = match name {
+        Some(name) => name,
+        None => {
+            print_usage();
+            exit(2);
+        }
+    };
+    if unsafe { libc::getuid() } != 0 {
+        fail_with(1, "permission denied");
+    }
+
+    let mut db = AccountDb::open_at(Path::new(&root)).unwrap_or_else(|e| fail_with(1, &e));
+    let gid = db.group(&name).map(|g| g.gid).unwrap_or_else(|| fail_with(6, &format!("group '{}' does not exist", name)));
+    if let Some(user) = db.passwd.entries().find(|u| u.gid == gid) {
+        fail_with(8, &format!("cannot remove the primary group of user '{}'", user.username));
+    }
+
+    db.group.remove(&name);
+    db.commit().unwrap_or_else(|e| fail_with(10, &e));
+}
+
+fn fail_with(code: i32, message: &str) -> ! {
+    safe_eprintln(format_args!("{}: {}", package_name!(), message));
+    exit(code);
+}
+
+fn print_usage() {
+    safe_println(format_args!("Usage: {} [options] GROUP", package_name!()));
+    safe_println(format_args!("Options:"));
+    safe_println(format_args!("     -R, --root DIR     Work on the system installed at DIR"));
+    safe_println(format_args!("     --help             Show help"));
+    safe_println(format_args!("     --version          Show version"));
+}
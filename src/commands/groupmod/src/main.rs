@@ -0,0 +1,84 @@
+use std::env;
+use std::path::Path;
+use std::process::exit;
+use vantara::auth::modules::account_db::{is_valid_name, AccountDb};
+use vantara::{package_name, print_version, safe_eprintln, safe_println};
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let mut new_gid: Option<u32> = None;
+    let mut new_name: Option<String> = None;
+    let mut name: Option<String> = None;
+    let mut root = String::from("/");
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--help" => { print_usage(); exit(0); },
+            "--version" => { print_version!(); exit(0); },
+            "-g" | "--gid" => {
+                let value = args.next().unwrap_or_else(|| fail_with(2, "option -g requires an argument"));
+                new_gid = Some(value.parse().unwrap_or_else(|_| fail_with(3, &format!("invalid gid '{}'", value))));
+            }
+            "-n" | "--new-name" => new_name = Some(args.next().unwrap_or_else(|| fail_with(2, "option -n requires an argument"))),
+            "-R" | "--root" => root = args.next().unwrap_or_else(|| fail_with(2, "option -R requires an argument")),
+            _ if arg.starts_with('-') => {
+                safe_eprintln(format_args!("{}: unknown option '{}'", package_name!(), arg));
+                exit(2);
+            }
+            _ => name = Some(arg),
+        }
+    }
+
+    let name = match name {
+        Some(name) => name,
+        None => {
+            print_usage();
+            exit(2);
+        }
+    };
+    if unsafe { libc::getuid() } != 0 {
+        fail_with(1, "permission denied");
+    }
+
+    let mut db = AccountDb::open_at(Path::new(&root)).unwrap_or_else(|e| fail_with(1, &e));
+    let old_gid = db.group(&name).map(|g| g.gid).unwrap_or_else(|| fail_with(6, &format!("group '{}' does not exist", name)));
+
+    if let Some(new_name) = &new_name {
+        if !is_valid_name(new_name) {
+            fail_with(3, &format!("invalid group name '{}'", new_name));
+        }
+        if db.group(new_name).is_some() {
+            fail_with(9, &format!("group '{}' already exists", new_name));
+        }
+    }
+    if let Some(gid) = new_gid {
+        if db.group_by_gid(gid).is_some_and(|g| g.groupname != name) {
+            fail_with(4, &format!("gid {} is not unique", gid));
+        }
+        // Users with this primary group move along
+        for user in db.passwd.entries_mut().filter(|u| u.gid == old_gid) {
+            user.gid = gid;
+        }
+    }
+
+    if let Some(group) = db.group.find_mut(&name) {
+        if let Some(gid) = new_gid { group.gid = gid; }
+        if let Some(new_name) = new_name { group.groupname = new_name; }
+    }
+    db.commit().unwrap_or_else(|e| fail_with(10, &e));
+}
+
+fn fail_with(code: i32, message: &str) -> ! {
+    safe_eprintln(format_args!("{}: {}", package_name!(), message));
+    exit(code);
+}
+
+fn print_usage() {
+    safe_println(format_args!("Usage: {} [options] GROUP", package_name!()));
+    safe_println(format_args!("Options:"));
+    safe_println(format_args!("     -g, --gid GID          New group id, primary groups of users follow"));
+    safe_println(format_args!("     -n, --new-name NAME    New group name"));
+    safe_println(format_args!("     -R, --root DIR         Work on the system installed at DIR"));
+    safe_println(format_args!("     --help                 Show help"));
+    safe_println(format_args!("     --version              Show version"));
+}
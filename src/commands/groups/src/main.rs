@@ -0,0 +1,58 @@
+use std::env;
+use std::process::exit;
+use vantara::auth::modules::group::current_gids;
+use vantara::nss::{getgrgid, getgrouplist, getpwnam};
+use vantara::{package_name, print_version, safe_eprintln, safe_println};
+
+fn main() {
+    let mut users: Vec<String> = Vec::new();
+
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--help" => { print_usage(); exit(0); },
+            "--version" => { print_version!(); exit(0); },
+            _ if arg.starts_with('-') => {
+                safe_eprintln(format_args!("{}: unknown option '{}'", package_name!(), arg));
+                exit(2);
+            }
+            _ => users.push(arg),
+        }
+    }
+
+    if users.is_empty() {
+        let egid = unsafe { libc::getegid() };
+        let mut gids = current_gids();
+        if !gids.contains(&egid) {
+            gids.insert(0, egid);
+        }
+        safe_println(format_args!("{}", group_names(&gids)));
+        return;
+    }
+
+    let mut status = 0;
+    for name in &users {
+        match getpwnam(name) {
+            Some(user) => safe_println(format_args!("{} : {}", name, group_names(&getgrouplist(name, user.gid)))),
+            None => {
+                safe_eprintln(format_args!("{}: '{}': no such user", package_name!(), name));
+                status = 1;
+            }
+        }
+    }
+    exit(status);
+}
+
+fn group_names(gids: &[u32]) -> String {
+    gids.iter()
+        .map(|gid| getgrgid(*gid).map(|g| g.groupname).unwrap_or_else(|| gid.to_string()))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn print_usage() {
+    safe_println(format_args!("Usage: {} [USER...]", package_name!()));
+    safe_println(format_args!("Print the groups each USER is in, or those of the current process."));
+    safe_println(format_args!("Options:"));
+    safe_println(format_args!("     --help                    Show help"));
+    safe_println(format_args!("     --version                 Show version"));
+}
@@ -0,0 +1,83 @@
+use std::env;
+use std::process::exit;
+use vantara::host::{kernel_hostname, read_os_release, set_hostname, static_hostname, OS_NAME};
+use vantara::{package_name, print_version, safe_eprintln, safe_println};
+
+fn main() {
+    let mut positional: Vec<String> = Vec::new();
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--help" => { print_usage(); exit(0); },
+            "--version" => { print_version!(); exit(0); },
+            _ if arg.starts_with('-') => fail_with(2, &format!("unknown option '{}'", arg)),
+            _ => positional.push(arg),
+        }
+    }
+
+    let args: Vec<&str> = positional.iter().map(String::as_str).collect();
+    match args.as_slice() {
+        [] | ["status"] => print_status(),
+        ["hostname"] => safe_println(format_args!("{}", kernel_hostname())),
+        ["hostname" | "set-hostname", name] => change_hostname(name),
+        ["set-hostname"] => fail_with(2, "set-hostname needs a name"),
+        ["status" | "hostname" | "set-hostname", ..] => fail_with(2, "too many arguments"),
+        [command, ..] => fail_with(2, &format!("unknown command '{}'", command)),
+    }
+}
+
+fn change_hostname(name: &str) {
+    // The binary is setuid, only root itself may rename the machine
+    if unsafe { libc::getuid() } != 0 {
+        fail_with(1, "only root may change the hostname");
+    }
+    if let Err(e) = set_hostname(name) {
+        fail_with(1, &e);
+    }
+}
+
+fn print_status() {
+    let transient = kernel_hostname();
+    let release = read_os_release();
+    let os = release.get("PRETTY_NAME").or_else(|| release.get("NAME")).map(String::as_str).unwrap_or(OS_NAME);
+    let mut uts: libc::utsname = unsafe { std::mem::zeroed() };
+    let (kernel, machine) = if unsafe { libc::uname(&mut uts) } == 0 {
+        let field = |f: &[libc::c_char]| unsafe { std::ffi::CStr::from_ptr(f.as_ptr()) }.to_string_lossy().into_owned();
+        (format!("{} {}", field(&uts.sysname), field(&uts.release)), field(&uts.machine))
+    } else {
+        (String::from("unknown"), String::from("unknown"))
+    };
+
+    match static_hostname() {
+        Some(name) => {
+            safe_println(format_args!("   Static hostname: {}", name));
+            // Differs until the next boot when /etc/hostname was edited by hand
+            if name != transient {
+                safe_println(format_args!("Transient hostname: {}", transient));
+            }
+        }
+        None => {
+            safe_println(format_args!("   Static hostname: (unset)"));
+            safe_println(format_args!("Transient hostname: {}", transient));
+        }
+    }
+    safe_println(format_args!("  Operating System: {}", os));
+    safe_println(format_args!("            Kernel: {}", kernel));
+    safe_println(format_args!("      Architecture: {}", machine));
+}
+
+fn fail_with(code: i32, message: &str) -> ! {
+    safe_eprintln(format_args!("{}: {}", package_name!(), message));
+    exit(code);
+}
+
+fn print_usage() {
+    safe_println(format_args!("Usage: {} [COMMAND]", package_name!()));
+    safe_println(format_args!("Show or change the host name."));
+    safe_println(format_args!("Commands:"));
+    safe_println(format_args!("     status                    Show the host name and operating system (default)"));
+    safe_println(format_args!("     hostname [NAME]           Print the host name, or set it to NAME"));
+    safe_println(format_args!("     set-hostname NAME         Set the host name in /etc/hostname, /etc/hosts and the kernel"));
+    safe_println(format_args!("Options:"));
+    safe_println(format_args!("     --help                    Show help"));
+    safe_println(format_args!("     --version                 Show version"));
+}
@@ -0,0 +1,118 @@
+use std::env;
+use std::process::exit;
+use vantara::auth::modules::group::current_gids;
+use vantara::nss::{getgrgid, getgrouplist, getpwnam, getpwuid};
+use vantara::{package_name, print_version, safe_eprintln, safe_println};
+
+#[derive(PartialEq)]
+enum Show {
+    All,
+    User,
+    Group,
+    Groups,
+}
+
+fn main() {
+    let mut show = Show::All;
+    let mut names = false;
+    let mut real = false;
+    let mut username: Option<String> = None;
+
+    // Short options can be combined, e.g. `-un`
+    let args = env::args().skip(1).flat_map(|arg| {
+        if arg.len() > 2 && arg.starts_with('-') && !arg.starts_with("--") {
+            arg[1..].chars().map(|c| format!("-{}", c)).collect()
+        } else {
+            vec![arg]
+        }
+    });
+
+    for arg in args {
+        match arg.as_str() {
+            "--help" => { print_usage(); exit(0); },
+            "--version" => { print_version!(); exit(0); },
+            "-u" | "--user" => show = Show::User,
+            "-g" | "--group" => show = Show::Group,
+            "-G" | "--groups" => show = Show::Groups,
+            "-n" | "--name" => names = true,
+            "-r" | "--real" => real = true,
+            _ if arg.starts_with('-') => {
+                safe_eprintln(format_args!("{}: unknown option '{}'", package_name!(), arg));
+                exit(2);
+            }
+            _ => username = Some(arg),
+        }
+    }
+
+    if show == Show::All && (names || real) {
+        safe_eprintln(format_args!("{}: -n and -r only work with -u, -g or -G", package_name!()));
+        exit(2);
+    }
+
+    // (real uid, effective uid, real gid, effective gid, groups)
+    let (uid, euid, gid, egid, groups) = match &username {
+        Some(name) => match getpwnam(name) {
+            Some(user) => (user.uid, user.uid, user.gid, user.gid, getgrouplist(&user.username, user.gid)),
+            None => {
+                safe_eprintln(format_args!("{}: '{}': no such user", package_name!(), name));
+                exit(1);
+            }
+        },
+        None => unsafe {
+            let egid = libc::getegid();
+            let mut groups = current_gids();
+            if !groups.contains(&egid) {
+                groups.insert(0, egid);
+            }
+            (libc::getuid(), libc::geteuid(), libc::getgid(), egid, groups)
+        },
+    };
+
+    let user_name = |uid: u32| getpwuid(uid).map(|u| u.username);
+    let group_name = |gid: u32| getgrgid(gid).map(|g| g.groupname);
+    let single = |id: u32, name: Option<String>| if names { name.unwrap_or_else(|| id.to_string()) } else { id.to_string() };
+    let labelled = |id: u32, name: Option<String>| match name {
+        Some(name) => format!("{}({})", id, name),
+        None => id.to_string(),
+    };
+
+    match show {
+        Show::User => {
+            let id = if real { uid } else { euid };
+            safe_println(format_args!("{}", single(id, user_name(id))));
+        }
+        Show::Group => {
+            let id = if real { gid } else { egid };
+            safe_println(format_args!("{}", single(id, group_name(id))));
+        }
+        Show::Groups => {
+            let list: Vec<String> = groups.iter().map(|g| single(*g, group_name(*g))).collect();
+            safe_println(format_args!("{}", list.join(" ")));
+        }
+        Show::All => {
+            let mut line = format!("uid={} gid={}", labelled(uid, user_name(uid)), labelled(gid, group_name(gid)));
+            if euid != uid {
+                line.push_str(&format!(" euid={}", labelled(euid, user_name(euid))));
+            }
+            if egid != gid {
+                line.push_str(&format!(" egid={}", labelled(egid, group_name(egid))));
+            }
+            let list: Vec<String> = groups.iter().map(|g| labelled(*g, group_name(*g))).collect();
+            line.push_str(&format!(" groups={}", list.join(",")));
+            safe_println(format_args!("{}", line));
+        }
+    }
+}
+
+fn print_usage() {
+    safe_println(format_args!("Usage: {} [options] [USER]", package_name!()));
+    safe_println(format_args!("Print user and group IDs of USER, or of the current process."));
+    safe_println(format_args!("Options:"));
+    safe_println(format_args!("     -u, --user                Print only the effective user ID"));
+    safe_println(format_args!("     -g, --group               Print only the effective group ID"));
+    safe_println(format_args!("     -G, --groups              Print all group IDs"));
+    safe_println(format_args!("     -n, --name                Print names instead of numbers, with -u, -g or -G"));
+    safe_println(format_args!("     -r, --real                Print the real ID instead of the effective one, with -u or -g"));
+    safe_println(format_args!("     --help                    Show help"));
+    safe_println(format_args!("     --version                 Show version"));
+}
@@ -0,0 +1,114 @@
+use chrono::{Local, TimeZone};
+use std::collections::HashMap;
+use std::env;
+use std::process::exit;
+use vantara::auth::modules::session_log::{active_sessions, session_history, RecordKind, SessionRecord};
+use vantara::{package_name, print_version, safe_eprintln, safe_println};
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let mut limit: Option<usize> = None;
+    let mut users: Vec<String> = Vec::new();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--help" => { print_usage(); exit(0); },
+            "--version" => { print_version!(); exit(0); },
+            "-n" | "--limit" => {
+                limit = match args.next().and_then(|n| n.parse().ok()) {
+                    Some(n) => Some(n),
+                    None => {
+                        safe_eprintln(format_args!("{}: option {} requires a number", package_name!(), arg));
+                        exit(2);
+                    }
+                }
+            }
+            _ if arg.starts_with('-') => {
+                safe_eprintln(format_args!("{}: unknown option '{}'", package_name!(), arg));
+                exit(2);
+            }
+            _ => users.push(arg),
+        }
+    }
+
+    let history = session_history();
+    let active = active_sessions();
+    let mut printed = 0;
+
+    // Walking backwards, logouts are seen before the logins they end. Both are
+    // written by the same login process, so pid and tty pair them up
+    let mut logouts: HashMap<(u32, String), i64> = HashMap::new();
+    let mut next_boundary: Option<&SessionRecord> = None;
+
+    for record in history.iter().rev() {
+        if limit.is_some_and(|n| printed >= n) {
+            break;
+        }
+
+        let line = match record.kind {
+            RecordKind::Logout => {
+                logouts.insert((record.pid, record.tty.clone()), record.time);
+                continue;
+            }
+            RecordKind::Boot | RecordKind::Shutdown => {
+                logouts.clear();
+                next_boundary = Some(record);
+                let (user, what) = if record.kind == RecordKind::Boot { ("reboot", "system boot") } else { ("shutdown", "system down") };
+                if !users.is_empty() && !users.iter().any(|u| u == user) {
+                    continue;
+                }
+                format!("{:<8} {:<12} {:<16} {}", user, what, record.host, format_time(record.time))
+            }
+            RecordKind::Login => {
+                let end = match logouts.remove(&(record.pid, record.tty.clone())) {
+                    Some(time) => format!("- {}  ({})", format_clock(time), format_duration(time - record.time)),
+                    None => match next_boundary {
+                        Some(b) if b.kind == RecordKind::Shutdown => format!("- down   ({})", format_duration(b.time - record.time)),
+                        Some(b) => format!("- crash  ({})", format_duration(b.time - record.time)),
+                        None if active.iter().any(|s| s.pid == record.pid && s.tty == record.tty) => "  still logged in".to_string(),
+                        None => "  gone - no logout".to_string(),
+                    },
+                };
+                if !users.is_empty() && !users.contains(&record.user) {
+                    continue;
+                }
+                format!("{:<8} {:<12} {:<16} {} {}", record.user, record.tty, record.host, format_time(record.time), end)
+            }
+            RecordKind::Empty => continue,
+        };
+
+        safe_println(format_args!("{}", line));
+        printed += 1;
+    }
+
+    let begins = history.first().map(|r| r.time).unwrap_or_else(|| Local::now().timestamp());
+    safe_println(format_args!(""));
+    safe_println(format_args!("wtmp begins {}", format_time(begins)));
+}
+
+fn format_time(time: i64) -> String {
+    Local.timestamp_opt(time, 0).single().map(|t| t.format("%a %b %e %H:%M").to_string()).unwrap_or_default()
+}
+
+fn format_clock(time: i64) -> String {
+    Local.timestamp_opt(time, 0).single().map(|t| t.format("%H:%M").to_string()).unwrap_or_default()
+}
+
+fn format_duration(secs: i64) -> String {
+    let secs = secs.max(0);
+    let (days, hours, minutes) = (secs / 86400, (secs % 86400) / 3600, (secs % 3600) / 60);
+    if days > 0 {
+        format!("{}+{:02}:{:02}", days, hours, minutes)
+    } else {
+        format!("{:02}:{:02}", hours, minutes)
+    }
+}
+
+fn print_usage() {
+    safe_println(format_args!("Usage: {} [options] [USER...]", package_name!()));
+    safe_println(format_args!("Show past logins, newest first, with boots and shutdowns in between."));
+    safe_println(format_args!("Options:"));
+    safe_println(format_args!("     -n, --limit N             Show at most N lines"));
+    safe_println(format_args!("     --help                    Show help"));
+    safe_println(format_args!("     --version                 Show version"));
+}
@@ -0,0 +1,61 @@
+use std::env;
+use std::process::exit;
+use vantara::nss::{getpwnam, passwd_entries};
+use vantara::auth::modules::session_log::{format_login_time, get_lastlog};
+use vantara::{package_name, print_version, safe_eprintln, safe_println};
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let mut username: Option<String> = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--help" => { print_usage(); exit(0); },
+            "--version" => { print_version!(); exit(0); },
+            "-u" | "--user" => {
+                username = match args.next() {
+                    Some(name) => Some(name),
+                    None => {
+                        safe_eprintln(format_args!("{}: option {} requires an argument", package_name!(), arg));
+                        exit(2);
+                    }
+                }
+            }
+            _ => {
+                safe_eprintln(format_args!("{}: unknown option '{}'", package_name!(), arg));
+                exit(2);
+            }
+        }
+    }
+
+    let users = match username {
+        Some(name) => match getpwnam(&name) {
+            Some(user) => vec![user],
+            None => {
+                safe_eprintln(format_args!("{}: user '{}' does not exist", package_name!(), name));
+                exit(1);
+            }
+        },
+        None => passwd_entries().to_vec(),
+    };
+
+    safe_println(format_args!("{:<16} {:<8} {:<16} {}", "Username", "Port", "From", "Latest"));
+    for user in users {
+        match get_lastlog(user.uid) {
+            Some(last) => safe_println(format_args!(
+                "{:<16} {:<8} {:<16} {}",
+                user.username, last.tty, last.host, format_login_time(last.time)
+            )),
+            None => safe_println(format_args!("{:<16} {:<8} {:<16} {}", user.username, "", "", "**Never logged in**")),
+        }
+    }
+}
+
+fn print_usage() {
+    safe_println(format_args!("Usage: {} [options]", package_name!()));
+    safe_println(format_args!("Show the most recent login of every user."));
+    safe_println(format_args!("Options:"));
+    safe_println(format_args!("     -u, --user USER           Show only USER"));
+    safe_println(format_args!("     --help                    Show help"));
+    safe_println(format_args!("     --version                 Show version"));
+}
@@ -1,26 +1,76 @@
-use vantara::auth::modules::session_log::log_logout;
-use vantara::auth::{self, run_login, AuthContext};
-use vantara::auth::modules::{auth_unix::AuthUnix, session_logger::SessionLogger, account_expiry::AccountExpiry};
-use vantara::auth::modules::passwd::get_passwd_entry;
+use vantara::auth::AuthContext;
+use vantara::auth::stack::AuthStack;
+use vantara::auth::modules::group::drop_privileges;
+use vantara::nss::{getgrouplist, getpwnam};
+use vantara::auth::modules::login_defs::LoginDefs;
+use vantara::auth::modules::login_env::{
+    expand_issue, login_environment, login_umask, nologin_message, TtyOwnership, DEFAULT_ISSUE_PATH, DEFAULT_MOTD_PATH,
+};
+use vantara::auth::modules::session_log::{get_last_login, get_tty};
+use std::fs;
 use std::io::{self, stdin, Write};
-use std::process::Command;
-use vantara::{safe_print, safe_println, safe_eprintln, read_password};
+use std::os::unix::process::CommandExt;
+use std::path::Path;
+use std::process::{exit, Command};
+use std::sync::OnceLock;
+use vantara::{safe_print, safe_println, safe_eprintln, read_password, tr};
 
+/// Terminal settings from before the first prompt, put back when the timeout
+/// hits while echo is off for the password.
+static SAVED_TERMIOS: OnceLock<libc::termios> = OnceLock::new();
+/// Translated up front, the signal handler can't allocate
+static TIMEOUT_MESSAGE: OnceLock<String> = OnceLock::new();
+
+extern "C" fn login_timed_out(_: libc::c_int) {
+    unsafe {
+        if let Some(term) = SAVED_TERMIOS.get() {
+            libc::tcsetattr(0, libc::TCSANOW, term);
+        }
+        if let Some(message) = TIMEOUT_MESSAGE.get() {
+            libc::write(2, message.as_ptr().cast(), message.len());
+        }
+        libc::_exit(1);
+    }
+}
+
+/// One login per process: init starts login again once it exits, after the
+/// session, a timeout or too many failed attempts.
 fn main() {
-    let modules: Vec<Box<dyn auth::AuthModule>> = vec![
-        Box::new(AuthUnix::new()),
-        Box::new(SessionLogger::new()),
-        Box::new(AccountExpiry::new()),
-    ];
-
-    loop {
-        safe_print(format_args!("Username: "));
+    let stack = match AuthStack::load("login") {
+        Ok(stack) => stack,
+        Err(e) => {
+            safe_eprintln(format_args!("login: {}", e));
+            exit(1);
+        }
+    };
+    let defs = LoginDefs::load();
+    let retries = defs.get_num("LOGIN_RETRIES", 5).max(1);
+    let timeout = defs.get_num("LOGIN_TIMEOUT", 60).max(0) as u32;
+    let tty = get_tty();
+
+    let _ = TIMEOUT_MESSAGE.set(format!("\n{}\n", tr!("login.timeout")));
+    unsafe {
+        let mut term: libc::termios = std::mem::zeroed();
+        if libc::tcgetattr(0, &mut term) == 0 {
+            let _ = SAVED_TERMIOS.set(term);
+        }
+        libc::signal(libc::SIGALRM, login_timed_out as *const () as libc::sighandler_t);
+        libc::alarm(timeout);
+    }
+
+    if let Ok(issue) = fs::read_to_string(DEFAULT_ISSUE_PATH) {
+        safe_print(format_args!("{}", expand_issue(&issue, &tty)));
+    }
+
+    let mut authenticated = None;
+    for _ in 0..retries {
+        safe_print(format_args!("{} ", tr!("login.username")));
         let _ = io::stdout().flush(); // Ensure the prompt is printed immediately
         let mut username = String::new();
         stdin().read_line(&mut username).unwrap();
         let username = username.trim(); // Remove any trailing newline or spaces
 
-        safe_print(format_args!("Password: "));
+        safe_print(format_args!("{} ", tr!("login.password")));
         let _ = io::stdout().flush(); // Ensure the prompt is printed immediately
         let password = read_password();
 
@@ -30,18 +80,105 @@ fn main() {
             metadata: std::collections::HashMap::new(),
         };
 
-        if run_login(&modules, &mut ctx) {
-            if let Some(user) = get_passwd_entry(&ctx.username) {
-                let _ = Command::new(&user.shell)
-                    .spawn()
-                    .unwrap()
-                    .wait();
-            } else {
-                safe_eprintln(format_args!("User entry not found"));
-            }
-        } else {
-            safe_println(format_args!("Please try again"));
-        }
-        log_logout(&ctx.username);
+        if stack.login(&mut ctx) {
+            authenticated = Some(ctx);
+            break;
+        }
+        safe_println(format_args!("{}", tr!("login.incorrect")));
+    }
+    let mut ctx = match authenticated {
+        Some(ctx) => ctx,
+        None => {
+            safe_println(format_args!("{}", tr!("login.tries", n = retries)));
+            exit(1);
+        }
+    };
+    unsafe {
+        libc::alarm(0);
+    }
+
+    let user = match getpwnam(&ctx.username) {
+        Some(user) => user,
+        None => {
+            safe_eprintln(format_args!("{}", tr!("login.no_entry")));
+            exit(1);
+        }
+    };
+
+    if user.uid != 0 {
+        if let Some(message) = nologin_message() {
+            safe_println(format_args!("{}", message));
+            exit(1);
+        }
+    }
+
+    if let Some(last) = get_last_login(&ctx.username) {
+        safe_println(format_args!("{}", tr!("login.last", last = last)));
+    }
+    if let Ok(motd) = fs::read_to_string(DEFAULT_MOTD_PATH) {
+        safe_print(format_args!("{}", motd));
+    }
+
+    let tty_owner = match TtyOwnership::take(&tty, &user, &defs) {
+        Ok(owner) => Some(owner),
+        Err(e) if tty.starts_with("/dev/") => {
+            safe_eprintln(format_args!("login: {}", tr!("login.tty_owner", tty = tty, error = e)));
+            None
+        }
+        Err(_) => None, // not started on a terminal
+    };
+
+    let mut session = match stack.open_session(&mut ctx) {
+        Ok(session) => session,
+        Err(reason) => {
+            safe_println(format_args!("{}", tr!("login.session_failed", reason = reason)));
+            exit(1);
+        }
+    };
+
+    let home = if Path::new(&user.home).is_dir() {
+        user.home.clone()
+    } else {
+        safe_println(format_args!("{}", tr!("login.no_home", home = user.home)));
+        "/".to_string()
+    };
+    let mut env = login_environment(&user, &defs);
+    for (key, value) in env.iter_mut() {
+        if key == "HOME" {
+            *value = home.clone();
+        }
+    }
+    // e.g. XDG_RUNTIME_DIR from the session modules
+    for (key, value) in session.context().session_env() {
+        env.retain(|(k, _)| *k != key);
+        env.push((key, value));
+    }
+
+    // The shell gets the user's credentials, login stays root so the session
+    // can be closed however the shell ends
+    let groups = getgrouplist(&user.username, user.gid);
+    let (uid, gid) = (user.uid, user.gid);
+    let umask = login_umask(&defs);
+    let shell_name = Path::new(&user.shell).file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    let mut shell = Command::new(&user.shell);
+    shell.arg0(format!("-{}", shell_name)) // a leading '-' marks a login shell
+        .env_clear()
+        .envs(env)
+        .current_dir(&home);
+    unsafe {
+        shell.pre_exec(move || {
+            libc::umask(umask as libc::mode_t);
+            drop_privileges(uid, gid, &groups)
+        });
+    }
+    match shell.spawn() {
+        Ok(mut child) => {
+            let _ = child.wait();
+        }
+        Err(e) => safe_eprintln(format_args!("{}", tr!("login.shell_failed", shell = user.shell, error = e))),
+    }
+    session.close();
+    if let Some(owner) = tty_owner {
+        owner.restore();
     }
 }
@@ -5,7 +5,7 @@ use std::path::Path;
 use std::time::{UNIX_EPOCH};
 use chrono::{DateTime, Local};
 use std::process::{exit};
-use users::{get_user_by_uid, get_group_by_gid};
+use vantara::nss::{group_name, user_name};
 use vantara::{package_name, safe_println, safe_eprintln, safe_print, print_version, get_system_timezone};
 use libc;
 use std::os::unix::fs::MetadataExt;
@@ -193,13 +193,8 @@ fn print_metadata(entry: &DirEntry, meta: &Metadata, long_format: bool) {
         let uid = meta.uid();
         let gid = meta.gid();
 
-        let user = get_user_by_uid(uid)
-            .map(|u| u.name().to_string_lossy().into_owned())
-            .unwrap_or(uid.to_string());
-
-        let group = get_group_by_gid(gid)
-            .map(|g| g.name().to_string_lossy().into_owned())
-            .unwrap_or(gid.to_string());
+        let user = user_name(uid);
+        let group = group_name(gid);
 
         safe_println(format_args!(
             "{} {:>8} {:<8} {:<8} {} \x1b[38;2;{};{};{}m{}{}\x1b[0m{}",
@@ -0,0 +1,215 @@
+use std::collections::HashMap;
+use std::env;
+use std::io::{self, Write};
+use std::process::exit;
+use vantara::audit::{self, EventKind, Outcome};
+use vantara::auth::{AuthContext, AuthResult};
+use vantara::auth::stack::AuthStack;
+use vantara::auth::modules::account_db::AccountDb;
+use vantara::nss::{getpwuid, user_name};
+use vantara::auth::modules::pwquality::PasswordPolicy;
+use vantara::auth::modules::shadow::{change_shadow_password, format_day, get_shadow_entry, today_days};
+use vantara::{package_name, print_version, read_password, safe_eprintln, safe_print, safe_println};
+
+/// Attempts at choosing an acceptable new password.
+const CHANGE_ATTEMPTS: usize = 3;
+
+#[derive(PartialEq)]
+enum Action {
+    Change,
+    Lock,
+    Unlock,
+    Delete,
+    Expire,
+    Status,
+}
+
+fn main() {
+    let mut action = Action::Change;
+    let mut username: Option<String> = None;
+
+    for arg in env::args().skip(1) {
+        let next = match arg.as_str() {
+            "--help" => { print_usage(); exit(0); },
+            "--version" => { print_version!(); exit(0); },
+            "-l" | "--lock" => Action::Lock,
+            "-u" | "--unlock" => Action::Unlock,
+            "-d" | "--delete" => Action::Delete,
+            "-e" | "--expire" => Action::Expire,
+            "-S" | "--status" => Action::Status,
+            _ if arg.starts_with('-') => {
+                safe_eprintln(format_args!("{}: unknown option '{}'", package_name!(), arg));
+                exit(2);
+            }
+            _ => {
+                username = Some(arg);
+                continue;
+            }
+        };
+        if action != Action::Change {
+            fail_with(2, "only one of -l, -u, -d, -e and -S can be given");
+        }
+        action = next;
+    }
+
+    let uid = unsafe { libc::getuid() };
+    let caller = match getpwuid(uid) {
+        Some(user) => user.username,
+        None => fail_with(1, &format!("cannot determine your user name (uid {})", uid)),
+    };
+    let username = username.unwrap_or_else(|| caller.clone());
+
+    if uid != 0 && (username != caller || !matches!(action, Action::Change | Action::Status)) {
+        fail_with(1, "permission denied");
+    }
+
+    match action {
+        Action::Change => change_password(&username, uid == 0),
+        Action::Status => print_status(&username),
+        _ => modify_entry(&username, action),
+    }
+}
+
+fn change_password(username: &str, is_root: bool) {
+    let entry = get_shadow_entry(username)
+        .unwrap_or_else(|| fail_with(6, &format!("user '{}' has no shadow entry", username)));
+
+    safe_println(format_args!("Changing password for {}.", username));
+
+    // Root may reset any password, everyone else proves they know the current one
+    let mut old_password = None;
+    if !is_root {
+        let stack = AuthStack::load("passwd").unwrap_or_else(|e| fail_with(3, &e));
+        safe_print(format_args!("Current password: "));
+        let _ = io::stdout().flush();
+        let mut ctx = AuthContext {
+            username: username.to_string(),
+            password: read_password(),
+            metadata: HashMap::new(),
+        };
+        if let AuthResult::Failure(reason) = stack.authenticate(&mut ctx) {
+            fail_with(1, &format!("Authentication token manipulation error: {}", reason));
+        }
+
+        if let (Some(last), Some(min)) = (entry.last_change, entry.min_days) {
+            if last > 0 && today_days() < last + min {
+                fail_with(1, "You must wait longer to change your password");
+            }
+        }
+        old_password = Some(ctx.password);
+    }
+
+    let policy = PasswordPolicy::load();
+    for _ in 0..CHANGE_ATTEMPTS {
+        safe_print(format_args!("New password: "));
+        let _ = io::stdout().flush();
+        let password = read_password();
+        if password.is_empty() {
+            safe_println(format_args!("No password supplied"));
+            continue;
+        }
+
+        // Root gets a warning and may insist, users have to pick another password
+        if let Err(reason) = policy.check(&password, old_password.as_deref(), username) {
+            safe_println(format_args!("BAD PASSWORD: {}", reason));
+            if !is_root {
+                continue;
+            }
+        }
+
+        safe_print(format_args!("Retype new password: "));
+        let _ = io::stdout().flush();
+        if read_password() != password {
+            safe_println(format_args!("Sorry, passwords do not match"));
+            continue;
+        }
+
+        if let Err(e) = change_shadow_password(username, &password) {
+            fail_with(3, &format!("cannot update shadow file: {}", e));
+        }
+        audit_change(username, Outcome::Success, "password changed");
+        safe_println(format_args!("passwd: password updated successfully"));
+        return;
+    }
+
+    audit_change(username, Outcome::Failure, "no acceptable new password");
+    fail_with(3, "Have exhausted maximum number of retries for service");
+}
+
+fn modify_entry(username: &str, action: Action) {
+    let mut db = AccountDb::open().unwrap_or_else(|e| fail_with(8, &e));
+    let entry = match db.shadow.find_mut(username) {
+        Some(entry) => entry,
+        None => fail_with(6, &format!("user '{}' has no shadow entry", username)),
+    };
+
+    let message = match action {
+        Action::Lock => {
+            entry.lock();
+            "Locking password"
+        }
+        Action::Unlock => {
+            if let Err(e) = entry.unlock() {
+                fail_with(3, &e);
+            }
+            "Unlocking password"
+        }
+        Action::Delete => {
+            entry.password.clear();
+            "Removing password"
+        }
+        Action::Expire => {
+            entry.last_change = Some(0);
+            "Expiring password"
+        }
+        _ => unreachable!(),
+    };
+
+    if let Err(e) = db.commit() {
+        fail_with(3, &format!("cannot update shadow file: {}", e));
+    }
+    audit_change(username, Outcome::Success, message);
+    safe_println(format_args!("{} for user {}.", message, username));
+}
+
+/// Audit records name the caller, the account they changed goes in the detail.
+fn audit_change(username: &str, outcome: Outcome, what: &str) {
+    let caller = user_name(unsafe { libc::getuid() });
+    audit::record(EventKind::Passwd, outcome, &caller, &format!("{} for {}", what, username));
+}
+
+fn print_status(username: &str) {
+    let entry = get_shadow_entry(username)
+        .unwrap_or_else(|| fail_with(6, &format!("user '{}' has no shadow entry", username)));
+    let number = |n: Option<i64>| n.map(|n| n.to_string()).unwrap_or_else(|| "-1".to_string());
+
+    safe_println(format_args!(
+        "{} {} {} {} {} {} {}",
+        entry.username,
+        entry.status(),
+        entry.last_change.map(format_day).unwrap_or_else(|| "-".to_string()),
+        number(entry.min_days),
+        number(entry.max_days),
+        number(entry.warn_days),
+        number(entry.inactive_days),
+    ));
+}
+
+fn fail_with(code: i32, message: &str) -> ! {
+    safe_eprintln(format_args!("{}: {}", package_name!(), message));
+    exit(code);
+}
+
+fn print_usage() {
+    safe_println(format_args!("Usage: {} [options] [LOGIN]", package_name!()));
+    safe_println(format_args!("Changes the password of LOGIN, or your own when no LOGIN is given."));
+    safe_println(format_args!("Options:"));
+    safe_println(format_args!("     -l, --lock                Lock the password of LOGIN"));
+    safe_println(format_args!("     -u, --unlock              Unlock the password of LOGIN"));
+    safe_println(format_args!("     -d, --delete              Delete the password of LOGIN, making it empty"));
+    safe_println(format_args!("     -e, --expire              Force LOGIN to change the password at next login"));
+    safe_println(format_args!("     -S, --status              Show the password status of LOGIN"));
+    safe_println(format_args!("     --help                    Show help"));
+    safe_println(format_args!("     --version                 Show version"));
+    safe_println(format_args!("Only root can change other users' passwords or use -l, -u, -d and -e."));
+}
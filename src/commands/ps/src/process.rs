@@ -1,4 +1,4 @@
-use users::get_user_by_uid;
+use vantara::nss::user_name;
 use crate::args::Options;
 use procfs::CurrentSI;
 use rayon::prelude::*;
@@ -50,9 +50,7 @@ pub fn get_processes(args: &Options) -> Vec<ProcInfo> {
                 }
             }
 
-            let user = get_user_by_uid(uid)
-                .map(|u| u.name().to_string_lossy().into_owned())
-                .unwrap_or(uid.to_string());
+            let user = user_name(uid);
 
             let cpu_before = stat.utime + stat.stime;
             let stat_after = p.stat().ok()?;
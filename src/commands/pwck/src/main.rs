@@ -0,0 +1,78 @@
+use std::env;
+use std::path::Path;
+use std::process::exit;
+use vantara::auth::modules::account_check::check_users;
+use vantara::auth::modules::account_db::AccountDb;
+use vantara::{package_name, print_version, safe_eprintln, safe_println};
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let mut fix = false;
+    let mut root = String::from("/");
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--help" => { print_usage(); exit(0); },
+            "--version" => { print_version!(); exit(0); },
+            "--fix" => fix = true,
+            "-r" | "--read-only" => fix = false,
+            "-R" | "--root" => root = args.next().unwrap_or_else(|| {
+                safe_eprintln(format_args!("{}: option {} requires an argument", package_name!(), arg));
+                exit(2);
+            }),
+            _ => {
+                safe_eprintln(format_args!("{}: unknown option '{}'", package_name!(), arg));
+                exit(2);
+            }
+        }
+    }
+
+    // shadow is read too, so even a read-only check is for root
+    if unsafe { libc::getuid() } != 0 {
+        safe_eprintln(format_args!("{}: permission denied", package_name!()));
+        exit(1);
+    }
+
+    let mut db = match AccountDb::open_at(Path::new(&root)) {
+        Ok(db) => db,
+        Err(e) => {
+            safe_eprintln(format_args!("{}: {}", package_name!(), e));
+            exit(4);
+        }
+    };
+
+    let found = check_users(&mut db, fix);
+    let mut remaining = 0;
+    for finding in &found.items {
+        if fix && finding.fixable {
+            safe_println(format_args!("{} (fixed)", finding.message));
+        } else {
+            safe_println(format_args!("{}", finding.message));
+            remaining += 1;
+        }
+    }
+
+    if fix {
+        if let Err(e) = db.commit() {
+            safe_eprintln(format_args!("{}: {}", package_name!(), e));
+            exit(5);
+        }
+    }
+    if remaining > 0 {
+        if !fix && found.items.iter().any(|f| f.fixable) {
+            safe_println(format_args!("{}: run with --fix to repair what can be repaired", package_name!()));
+        }
+        exit(2);
+    }
+}
+
+fn print_usage() {
+    safe_println(format_args!("Usage: {} [options]", package_name!()));
+    safe_println(format_args!("Check /etc/passwd and /etc/shadow for damaged, duplicate and inconsistent entries."));
+    safe_println(format_args!("Options:"));
+    safe_println(format_args!("     --fix                     Repair what can be repaired, rewriting the files atomically"));
+    safe_println(format_args!("     -r, --read-only           Only report, the default"));
+    safe_println(format_args!("     -R, --root DIR            Check the system installed at DIR"));
+    safe_println(format_args!("     --help                    Show help"));
+    safe_println(format_args!("     --version                 Show version"));
+}
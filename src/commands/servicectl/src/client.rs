@@ -0,0 +1,60 @@
+use std::io::{Read, Write};
+use std::net::Shutdown;
+use std::os::unix::net::UnixStream;
+use vantara::systemd::manager::{CALLER_PREFIX, DEFAULT_SOCKET_PATH};
+
+pub type Properties = Vec<(String, String)>;
+
+/// Send one command to init's control socket and return the whole reply.
+pub fn request(command: &str) -> Result<String, String> {
+    let mut stream = UnixStream::connect(DEFAULT_SOCKET_PATH)
+        .map_err(|e| format!("failed to connect to init at {}: {}", DEFAULT_SOCKET_PATH, e))?;
+
+    // When servicectl is installed setuid, init sees root on the socket. Say who
+    // ran it, init only takes that from root and refuses changes for anyone else
+    let uid = unsafe { libc::getuid() };
+    stream
+        .write_all(format!("{}{} {}", CALLER_PREFIX, uid, command).as_bytes())
+        .map_err(|e| format!("failed to write to socket: {}", e))?;
+    let _ = stream.shutdown(Shutdown::Write);
+
+    let mut response = String::new();
+    stream
+        .read_to_string(&mut response)
+        .map_err(|e| format!("failed to read from socket: {}", e))?;
+    Ok(response)
+}
+
+pub fn is_not_found(response: &str) -> bool {
+    response.starts_with("Service '") && response.trim_end().ends_with("not found")
+}
+
+/// Parse the `key=value` blocks returned by `show`, one block per service.
+pub fn parse_properties(response: &str) -> Vec<Properties> {
+    let mut blocks = Vec::new();
+    let mut current = Properties::new();
+
+    for line in response.lines() {
+        if line.trim().is_empty() {
+            if !current.is_empty() {
+                blocks.push(std::mem::take(&mut current));
+            }
+            continue;
+        }
+        if let Some((key, value)) = line.split_once('=') {
+            current.push((key.to_string(), value.to_string()));
+        }
+    }
+    if !current.is_empty() {
+        blocks.push(current);
+    }
+    blocks
+}
+
+pub fn get<'a>(props: &'a Properties, key: &str) -> &'a str {
+    props
+        .iter()
+        .find(|(k, _)| k == key)
+        .map(|(_, v)| v.as_str())
+        .unwrap_or("")
+}
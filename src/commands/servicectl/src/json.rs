@@ -0,0 +1,52 @@
+use crate::client::Properties;
+
+const NUMERIC_KEYS: [&str; 4] = ["pid", "restarts", "active_since", "inactive_since"];
+const BOOLEAN_KEYS: [&str; 3] = ["enabled", "active", "changed"];
+
+pub fn escape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len() + 2);
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn value(key: &str, value: &str) -> String {
+    if value.is_empty() {
+        "null".to_string()
+    } else if NUMERIC_KEYS.contains(&key) && value.parse::<i64>().is_ok() {
+        value.to_string()
+    } else if BOOLEAN_KEYS.contains(&key) {
+        matches!(value, "yes" | "true").to_string()
+    } else {
+        escape(value)
+    }
+}
+
+pub fn object(props: &Properties) -> String {
+    let fields: Vec<String> = props
+        .iter()
+        .map(|(k, v)| format!("{}:{}", escape(k), value(k, v)))
+        .collect();
+    format!("{{{}}}", fields.join(","))
+}
+
+pub fn array(items: &[Properties]) -> String {
+    let objects: Vec<String> = items.iter().map(object).collect();
+    format!("[{}]", objects.join(","))
+}
+
+pub fn string_array(items: &[String]) -> String {
+    let values: Vec<String> = items.iter().map(|s| escape(s)).collect();
+    format!("[{}]", values.join(","))
+}
@@ -0,0 +1,533 @@
+mod client;
+mod json;
+
+use std::env;
+use std::fs::{self, File};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::PathBuf;
+use std::process::{exit, Command};
+use client::{get, is_not_found, parse_properties, request, Properties};
+use vantara::systemd::preset::{Preset, PresetAction};
+use vantara::systemd::unit_files::UnitDirs;
+use vantara::{package_name, print_version, safe_eprintln, safe_print, safe_println};
+
+// Exit codes, so scripts can tell the cases apart
+const EXIT_OK: i32 = 0;
+const EXIT_FAILURE: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+const EXIT_INACTIVE: i32 = 3;
+const EXIT_NOT_FOUND: i32 = 4;
+const EXIT_NO_MANAGER: i32 = 5;
+
+const DEFAULT_LOG_LINES: usize = 20;
+const EDITOR_PATH: &str = "/bin/edit";
+
+struct Options {
+    json: bool,
+    quiet: bool,
+    follow: bool,
+    lines: usize,
+    root: Option<PathBuf>,
+    preset_file: Option<PathBuf>,
+}
+
+fn main() {
+    let mut options = Options {
+        json: false,
+        quiet: false,
+        follow: false,
+        lines: DEFAULT_LOG_LINES,
+        root: None,
+        preset_file: None,
+    };
+    let mut positional: Vec<String> = Vec::new();
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--help" => { print_usage(); exit(EXIT_OK); },
+            "--version" => { print_version!(); exit(EXIT_OK); },
+            "--json" => options.json = true,
+            "--quiet" => options.quiet = true,
+            "--follow" => options.follow = true,
+            "--root" => options.root = Some(PathBuf::from(args.next().unwrap_or_else(|| usage_error("--root needs a directory")))),
+            "--lines" => options.lines = parse_lines(args.next()),
+            "--preset-file" => options.preset_file = Some(PathBuf::from(args.next().unwrap_or_else(|| usage_error("--preset-file needs a file")))),
+            _ if arg.starts_with("--root=") => options.root = Some(PathBuf::from(&arg["--root=".len()..])),
+            _ if arg.starts_with("--preset-file=") => options.preset_file = Some(PathBuf::from(&arg["--preset-file=".len()..])),
+            _ if arg.starts_with("--") => usage_error(&format!("unknown option '{}'", arg)),
+            _ if arg.starts_with('-') && arg.len() > 1 => {
+                for c in arg.chars().skip(1) {
+                    match c {
+                        'q' => options.quiet = true,
+                        'f' => options.follow = true,
+                        'n' => options.lines = parse_lines(args.next()),
+                        _ => usage_error(&format!("unknown flag -{}", c)),
+                    }
+                }
+            }
+            _ => positional.push(arg),
+        }
+    }
+
+    let command = match positional.first() {
+        Some(c) => c.as_str(),
+        None => usage_error("no command given"),
+    };
+    let name = positional.get(1).map(|s| s.as_str());
+
+    // servicectl may be installed setuid root, anything beyond looking needs a real root
+    if options.root.is_some() {
+        require_root("--root");
+    }
+    if command == "preset" {
+        require_root(command);
+        exit(cmd_preset(&positional[1..], &options));
+    }
+
+    let code = match &options.root {
+        Some(root) => run_offline(command, name, &UnitDirs::with_root(root), &options),
+        None => run_online(command, name, &options),
+    };
+    exit(code);
+}
+
+fn run_online(command: &str, name: Option<&str>, options: &Options) -> i32 {
+    match command {
+        "list" => cmd_list(options),
+        "status" => cmd_status(require_name(command, name), options),
+        "start" | "stop" | "restart" | "enable" | "disable" => {
+            require_root(command);
+            cmd_action(command, require_name(command, name), options)
+        }
+        "reload" => {
+            require_root(command);
+            cmd_reload(name, options)
+        }
+        "history" => {
+            let n = require_name(command, name);
+            show(n);
+            safe_print(format_args!("{}", request_or_exit(&format!("history {}", n))));
+            EXIT_OK
+        }
+        "logs" => {
+            let props = show(require_name(command, name));
+            print_log(&PathBuf::from(get(&props, "log_path")), options)
+        }
+        "cat" => {
+            let props = show(require_name(command, name));
+            cat_file(&PathBuf::from(get(&props, "loaded_path")))
+        }
+        "edit" => {
+            require_root(command);
+            let props = show(require_name(command, name));
+            let code = edit_file(&PathBuf::from(get(&props, "loaded_path")));
+            if code == EXIT_OK {
+                // Pick up the new definition straight away
+                request_or_exit("reload");
+            }
+            code
+        }
+        "is-active" => {
+            let props = show(require_name(command, name));
+            let state = get(&props, "state");
+            report_check(name.unwrap(), "active", state == "running", state, options);
+            if state == "running" { EXIT_OK } else { EXIT_INACTIVE }
+        }
+        "reexec" => {
+            require_root(command);
+            let reply = request_or_exit("reexec");
+            report_action("init", command, reply.trim(), options);
+            if reply.starts_with("Re-exec failed") || reply.starts_with("Failed") { EXIT_FAILURE } else { EXIT_OK }
+        }
+        "poweroff" | "reboot" => {
+            require_root(command);
+            let reply = request_or_exit(command);
+            report_action("init", command, reply.trim(), options);
+            if reply.starts_with("Failed") { EXIT_FAILURE } else { EXIT_OK }
+        }
+        "is-enabled" => {
+            let props = show(require_name(command, name));
+            let enabled = get(&props, "enabled") == "yes";
+            report_check(name.unwrap(), "enabled", enabled, if enabled { "enabled" } else { "disabled" }, options);
+            if enabled { EXIT_OK } else { EXIT_FAILURE }
+        }
+        _ => usage_error(&format!("unknown command '{}'", command)),
+    }
+}
+
+/// Commands that only touch files, used with `--root` on an image that isn't running.
+fn run_offline(command: &str, name: Option<&str>, dirs: &UnitDirs, options: &Options) -> i32 {
+    if !matches!(command, "list") {
+        if let Some(n) = name {
+            if !dirs.exists(n) {
+                safe_eprintln(format_args!("{}: service '{}' not found in {}", package_name!(), n, dirs.available.display()));
+                return EXIT_NOT_FOUND;
+            }
+        }
+    }
+
+    match command {
+        "list" => {
+            let services: Vec<Properties> = dirs
+                .list()
+                .into_iter()
+                .map(|n| {
+                    let enabled = if dirs.is_enabled(&n) { "yes" } else { "no" };
+                    vec![
+                        ("name".to_string(), n.clone()),
+                        ("enabled".to_string(), enabled.to_string()),
+                        ("loaded_path".to_string(), dirs.unit_path(&n).display().to_string()),
+                    ]
+                })
+                .collect();
+
+            if options.json {
+                safe_println(format_args!("{}", json::array(&services)));
+            } else {
+                for svc in &services {
+                    safe_println(format_args!(
+                        "{:<24} {}",
+                        get(svc, "name"),
+                        if get(svc, "enabled") == "yes" { "enabled" } else { "disabled" }
+                    ));
+                }
+            }
+            EXIT_OK
+        }
+        "enable" | "disable" => {
+            let n = require_name(command, name);
+            let result = if command == "enable" { dirs.enable(n) } else { dirs.disable(n) };
+            match result {
+                Ok(changed) => {
+                    let message = match (command, changed) {
+                        ("enable", true) => format!("Enabled {}", n),
+                        ("enable", false) => format!("Service '{}' already enabled", n),
+                        (_, true) => format!("Disabled {}", n),
+                        (_, false) => format!("Service '{}' not enabled", n),
+                    };
+                    report_action(n, command, &message, options);
+                    EXIT_OK
+                }
+                Err(e) => {
+                    safe_eprintln(format_args!("{}: {}", package_name!(), e));
+                    EXIT_FAILURE
+                }
+            }
+        }
+        "is-enabled" => {
+            let n = require_name(command, name);
+            let enabled = dirs.is_enabled(n);
+            report_check(n, "enabled", enabled, if enabled { "enabled" } else { "disabled" }, options);
+            if enabled { EXIT_OK } else { EXIT_FAILURE }
+        }
+        "cat" => cat_file(&dirs.unit_path(require_name(command, name))),
+        "edit" => edit_file(&dirs.unit_path(require_name(command, name))),
+        "logs" => print_log(&dirs.log_path(require_name(command, name)), options),
+        "status" | "history" | "start" | "stop" | "restart" | "reload" | "is-active" | "reexec" | "poweroff" | "reboot" => {
+            safe_eprintln(format_args!("{}: '{}' needs a running init and can't be used with --root", package_name!(), command));
+            EXIT_USAGE
+        }
+        _ => usage_error(&format!("unknown command '{}'", command)),
+    }
+}
+
+/// Apply the preset to the image given with `--root`, or to this system.
+fn cmd_preset(names: &[String], options: &Options) -> i32 {
+    let root = options.root.clone().unwrap_or_else(|| PathBuf::from("/"));
+    let dirs = UnitDirs::with_root(&root);
+
+    let preset = match &options.preset_file {
+        Some(file) => Preset::from_file(file),
+        None => Preset::for_root(&root),
+    };
+    let changes = match preset.and_then(|p| p.apply(&dirs, names)) {
+        Ok(changes) => changes,
+        Err(e) => {
+            safe_eprintln(format_args!("{}: {}", package_name!(), e));
+            return EXIT_FAILURE;
+        }
+    };
+
+    let reports: Vec<Properties> = changes
+        .iter()
+        .map(|c| {
+            vec![
+                ("name".to_string(), c.name.clone()),
+                ("action".to_string(), if c.action == PresetAction::Enable { "enable" } else { "disable" }.to_string()),
+                ("changed".to_string(), c.changed.to_string()),
+            ]
+        })
+        .collect();
+
+    if options.json {
+        safe_println(format_args!("{}", json::array(&reports)));
+    } else if !options.quiet {
+        for change in &changes {
+            let verb = if change.action == PresetAction::Enable { "Enabled" } else { "Disabled" };
+            if change.changed {
+                safe_println(format_args!("{} {}", verb, change.name));
+            } else {
+                safe_println(format_args!("{} {} (unchanged)", verb, change.name));
+            }
+        }
+    }
+
+    // Let a running init see the new enabled flags
+    if options.root.is_none() && changes.iter().any(|c| c.changed) {
+        request_or_exit("reload");
+    }
+    EXIT_OK
+}
+
+fn cmd_list(options: &Options) -> i32 {
+    let services = parse_properties(&request_or_exit("show"));
+
+    if options.json {
+        safe_println(format_args!("{}", json::array(&services)));
+        return EXIT_OK;
+    }
+
+    safe_println(format_args!("{:<24} {:<9} {:<9} {}", "SERVICE", "ENABLED", "STATE", "PID"));
+    for svc in &services {
+        safe_println(format_args!(
+            "{:<24} {:<9} {:<9} {}",
+            get(svc, "name"),
+            get(svc, "enabled"),
+            get(svc, "state"),
+            get(svc, "pid")
+        ));
+    }
+    EXIT_OK
+}
+
+fn cmd_status(name: &str, options: &Options) -> i32 {
+    let props = show(name);
+
+    if options.json {
+        safe_println(format_args!("{}", json::object(&props)));
+    } else {
+        safe_print(format_args!("{}", request_or_exit(&format!("status {}", name))));
+    }
+
+    if get(&props, "state") == "running" { EXIT_OK } else { EXIT_INACTIVE }
+}
+
+fn cmd_action(command: &str, name: &str, options: &Options) -> i32 {
+    show(name);
+    let reply = request_or_exit(&format!("{} {}", command, name));
+    report_action(name, command, reply.trim(), options);
+    if reply.starts_with("Failed") {
+        return EXIT_FAILURE;
+    }
+
+    // Give start/restart a result scripts can rely on
+    if matches!(command, "start" | "restart") && get(&show(name), "state") != "running" {
+        safe_eprintln(format_args!("{}: {} is not running after {}", package_name!(), name, command));
+        return EXIT_FAILURE;
+    }
+    EXIT_OK
+}
+
+fn cmd_reload(name: Option<&str>, options: &Options) -> i32 {
+    let reply = match name {
+        Some(n) => {
+            show(n);
+            request_or_exit(&format!("reload {}", n))
+        }
+        None => request_or_exit("reload"),
+    };
+    report_action(name.unwrap_or(""), "reload", reply.trim(), options);
+
+    if reply.starts_with("Failed") { EXIT_FAILURE } else { EXIT_OK }
+}
+
+fn show(name: &str) -> Properties {
+    let reply = request_or_exit(&format!("show {}", name));
+    if is_not_found(&reply) {
+        safe_eprintln(format_args!("{}: service '{}' not found", package_name!(), name));
+        exit(EXIT_NOT_FOUND);
+    }
+    parse_properties(&reply).into_iter().next().unwrap_or_default()
+}
+
+fn request_or_exit(command: &str) -> String {
+    request(command).unwrap_or_else(|e| {
+        safe_eprintln(format_args!("{}: {}", package_name!(), e));
+        exit(EXIT_NO_MANAGER);
+    })
+}
+
+fn report_action(name: &str, action: &str, message: &str, options: &Options) {
+    if options.json {
+        let props = vec![
+            ("name".to_string(), name.to_string()),
+            ("action".to_string(), action.to_string()),
+            ("message".to_string(), message.to_string()),
+        ];
+        safe_println(format_args!("{}", json::object(&props)));
+    } else if !options.quiet {
+        safe_println(format_args!("{}", message));
+    }
+}
+
+fn report_check(name: &str, key: &str, value: bool, text: &str, options: &Options) {
+    if options.json {
+        let props = vec![
+            ("name".to_string(), name.to_string()),
+            (key.to_string(), value.to_string()),
+        ];
+        safe_println(format_args!("{}", json::object(&props)));
+    } else if !options.quiet {
+        safe_println(format_args!("{}", text));
+    }
+}
+
+fn cat_file(path: &PathBuf) -> i32 {
+    drop_to_caller();
+    match fs::read_to_string(path) {
+        Ok(content) => {
+            safe_println(format_args!("# {}", path.display()));
+            safe_print(format_args!("{}", content));
+            EXIT_OK
+        }
+        Err(e) => {
+            safe_eprintln(format_args!("{}: cannot read {}: {}", package_name!(), path.display(), e));
+            EXIT_FAILURE
+        }
+    }
+}
+
+fn edit_file(path: &PathBuf) -> i32 {
+    drop_to_caller();
+    match Command::new(EDITOR_PATH).arg(path).status() {
+        Ok(status) if status.success() => EXIT_OK,
+        Ok(_) => EXIT_FAILURE,
+        Err(e) => {
+            safe_eprintln(format_args!("{}: failed to run {}: {}", package_name!(), EDITOR_PATH, e));
+            EXIT_FAILURE
+        }
+    }
+}
+
+fn print_log(path: &PathBuf, options: &Options) -> i32 {
+    drop_to_caller();
+    let content = match fs::read_to_string(path) {
+        Ok(c) => c,
+        Err(e) => {
+            safe_eprintln(format_args!("{}: cannot read {}: {}", package_name!(), path.display(), e));
+            return EXIT_FAILURE;
+        }
+    };
+
+    let lines: Vec<String> = content.lines().map(|l| l.to_string()).collect();
+    let tail = &lines[lines.len().saturating_sub(options.lines)..];
+
+    if options.json {
+        safe_println(format_args!("{}", json::string_array(tail)));
+        return EXIT_OK;
+    }
+
+    for line in tail {
+        safe_println(format_args!("{}", line));
+    }
+
+    if options.follow {
+        follow_file(path, content.len() as u64);
+    }
+    EXIT_OK
+}
+
+fn follow_file(path: &PathBuf, mut offset: u64) {
+    loop {
+        std::thread::sleep(std::time::Duration::from_millis(500));
+
+        let mut file = match File::open(path) {
+            Ok(f) => f,
+            Err(_) => continue,
+        };
+        let len = file.metadata().map(|m| m.len()).unwrap_or(0);
+        if len < offset {
+            // Log was truncated, start over
+            offset = 0;
+        }
+        if len == offset {
+            continue;
+        }
+
+        let mut chunk = String::new();
+        if file.seek(SeekFrom::Start(offset)).is_ok() && file.read_to_string(&mut chunk).is_ok() {
+            safe_print(format_args!("{}", chunk));
+            offset = len;
+        }
+    }
+}
+
+fn require_root(what: &str) {
+    if unsafe { libc::getuid() } != 0 {
+        safe_eprintln(format_args!("{}: {} needs root", package_name!(), what));
+        exit(EXIT_FAILURE);
+    }
+}
+
+/// Files are read and edited with the rights of whoever ran servicectl, not the
+/// setuid owner. Init has been asked already, the socket isn't needed after this.
+fn drop_to_caller() {
+    let dropped = unsafe { libc::setgid(libc::getgid()) == 0 && libc::setuid(libc::getuid()) == 0 };
+    if !dropped {
+        safe_eprintln(format_args!("{}: cannot drop privileges: {}", package_name!(), std::io::Error::last_os_error()));
+        exit(EXIT_FAILURE);
+    }
+}
+
+fn require_name<'a>(command: &str, name: Option<&'a str>) -> &'a str {
+    name.unwrap_or_else(|| usage_error(&format!("'{}' needs a service name", command)))
+}
+
+fn parse_lines(value: Option<String>) -> usize {
+    value
+        .and_then(|v| v.parse().ok())
+        .unwrap_or_else(|| usage_error("-n needs a number"))
+}
+
+fn usage_error(message: &str) -> ! {
+    safe_eprintln(format_args!("{}: {}", package_name!(), message));
+    safe_eprintln(format_args!("Try '{} --help' for more information.", package_name!()));
+    exit(EXIT_USAGE);
+}
+
+fn print_usage() {
+    safe_println(format_args!("Usage: {} [OPTIONS] COMMAND [SERVICE]", package_name!()));
+    safe_println(format_args!("Commands:"));
+    safe_println(format_args!("     list                List all services"));
+    safe_println(format_args!("     status SERVICE      Show state, processes, last exit and recent logs"));
+    safe_println(format_args!("     history SERVICE     Show the last runs of a service and how they ended"));
+    safe_println(format_args!("     start SERVICE       Start a service"));
+    safe_println(format_args!("     stop SERVICE        Stop a service"));
+    safe_println(format_args!("     restart SERVICE     Restart a service"));
+    safe_println(format_args!("     reload [SERVICE]    Send SIGHUP to SERVICE, or re-read all service files"));
+    safe_println(format_args!("     enable SERVICE      Start SERVICE at boot"));
+    safe_println(format_args!("     disable SERVICE     Don't start SERVICE at boot"));
+    safe_println(format_args!("     logs SERVICE        Show the service log"));
+    safe_println(format_args!("     cat SERVICE         Print the service file"));
+    safe_println(format_args!("     edit SERVICE        Open the service file in edit"));
+    safe_println(format_args!("     is-active SERVICE   Exit 0 if SERVICE is running"));
+    safe_println(format_args!("     is-enabled SERVICE  Exit 0 if SERVICE is enabled"));
+    safe_println(format_args!("     preset [SERVICE]..  Enable/disable services as listed in the preset files"));
+    safe_println(format_args!("     reexec              Restart init from /sbin/init, keeping services running"));
+    safe_println(format_args!("     poweroff            Stop all services and power off"));
+    safe_println(format_args!("     reboot              Stop all services and reboot"));
+    safe_println(format_args!("Options:"));
+    safe_println(format_args!("     --json              Print machine readable output"));
+    safe_println(format_args!("     --root DIR          Work on the image at DIR instead of the running system"));
+    safe_println(format_args!("     --preset-file FILE  Use FILE instead of DIR/etc/service/preset/*.preset"));
+    safe_println(format_args!("     -n, --lines N       Number of log lines to show (default {})", DEFAULT_LOG_LINES));
+    safe_println(format_args!("     -f, --follow        Keep printing new log lines"));
+    safe_println(format_args!("     -q, --quiet         Only set the exit code"));
+    safe_println(format_args!("     --help              Show this help"));
+    safe_println(format_args!("     --version           Show version"));
+    safe_println(format_args!("Only root may change services, run preset or use --root."));
+    safe_println(format_args!("Exit codes:"));
+    safe_println(format_args!("     0 success, 1 failure or not enabled, 2 usage error, 3 not running,"));
+    safe_println(format_args!("     4 no such service, 5 init not reachable"));
+}
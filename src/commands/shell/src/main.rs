@@ -14,12 +14,9 @@ use std::io::{BufRead, BufReader, Write};
 use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
 use vantara::{safe_println, safe_eprintln};
+use vantara::host::short_hostname;
 use std::fmt::Write as _;
 use std::process::{exit};
-use std::os::unix::net::UnixStream;
-use std::io::Read;
-
-const DEFAULT_SOCKET_PATH: &str = "/run/systemd.sock";
 
 struct DirCompleter;
 
@@ -87,13 +84,24 @@ impl Hinter for DirCompleter {
 }
 
 fn main() {
+    // Non-interactive: `shell -c "command | command"`, used by crond and scripts
+    let args: Vec<String> = env::args().collect();
+    if args.len() >= 3 && args[1] == "-c" {
+        if env::var("PATH").is_err() {
+            env::set_var("PATH", "/bin:/usr/bin:/sbin:/usr/sbin");
+        }
+        exit(run_pipeline_command(&args[2]));
+    }
+
     let username = std::env::var("USER").unwrap_or("user".to_string());
     let home_dir = std::env::var("HOME").unwrap_or("/".to_string());
     let profile_path = format!("{}/.profile", home_dir);
     let _aliases = load_profile(&profile_path);
 
-    //Defaulting binary PATH
-    env::set_var("PATH", "/bin:/usr/bin:/sbin:/usr/sbin");
+    //Defaulting binary PATH, login sets one depending on the user
+    if env::var("PATH").is_err() {
+        env::set_var("PATH", "/bin:/usr/bin:/sbin:/usr/sbin");
+    }
 
     let config = Config::builder()
         .completion_type(CompletionType::List)
@@ -112,11 +120,13 @@ fn main() {
         let mut prompt = String::with_capacity(128);
         let _ = write!(
             &mut prompt,
-            "\x1b[38;2;0;255;0m{}@host\x1b[0m:\x1b[38;2;0;119;255m{}\x1b[0m$ ",
+            "\x1b[38;2;0;255;0m{}@{}\x1b[0m:\x1b[38;2;0;119;255m{}\x1b[0m$ ",
             username,
+            short_hostname(), // read every time, hostnamectl may have changed it
             get_display_path(&curr_dir)
         );
 
+        lock_when_idle(&prompt);
         match rl.readline(&prompt) {
             Ok(line) => {
 
@@ -160,30 +170,16 @@ fn main() {
                         let second = parts.next();
 
                         match (first, second) {
-                            (Some("list"), None) => {
-                                run_service_command("list", None);
-                            },
-                            (Some(name), Some("enable")) => {
-                                run_service_command("enable", Some(name));
+                            (Some(cmd @ ("list" | "reexec")), None) => {
+                                run_servicectl(&[cmd]);
                             },
-                            (Some(name), Some("disable")) => {
-                                run_service_command("disable", Some(name));
-                            },
-                            (Some(name), Some("status")) => {
-                                run_service_command("status", Some(name));
-                            },
-                            (Some(name), Some("start")) => {
-                                run_service_command("start", Some(name));
-                            },
-                            (Some(name), Some("stop")) => {
-                                run_service_command("stop", Some(name));
-                            },
-                            (Some(name), Some("restart")) => {
-                                run_service_command("restart", Some(name));
+                            (Some(name), Some(action @ ("status" | "history" | "start" | "stop" | "restart" | "reload"
+                                | "enable" | "disable" | "logs" | "cat" | "edit"))) => {
+                                run_servicectl(&[action, name]);
                             },
                             _ => {
                                 safe_eprintln(format_args!(
-                                    "Usage:\n  service list\n  service <name> <status|start|stop|restart|enable|disable>"
+                                    "Usage:\n  service list\n  service reexec\n  service <name> <status|history|start|stop|restart|reload|enable|disable|logs|cat|edit>"
                                 ));
                             }
                         }
@@ -223,11 +219,12 @@ fn main() {
     }
 }
 
-fn run_pipeline_command(input: &str) {
+/// Runs the pipeline and returns the exit code of its last command.
+fn run_pipeline_command(input: &str) -> i32 {
     let commands: Vec<&str> = input.trim().split('|').map(str::trim).collect();
 
     if commands.is_empty() {
-        return;
+        return 0;
     }
 
     let mut previous_stdout = None;
@@ -269,48 +266,66 @@ fn run_pipeline_command(input: &str) {
             }
             Err(_) => {
                 safe_eprintln(format_args!("Command '{}' not found", cmd));
-                return;
+                return 127;
             }
         }
 
     }
 
+    let mut code = 0;
     for mut child in children {
-        let _ = child.wait();
+        code = child.wait().ok().and_then(|s| s.code()).unwrap_or(1);
     }
+    code
 }
 
-fn run_service_command(cmd: &str, name: Option<&str>) {
-    let full_command = match name {
-        Some(n) => format!("{} {}", cmd, n),
-        None => cmd.to_string(),
+/// With VLOCK_TIMEOUT set, runs vlock whenever the prompt waits that many
+/// seconds for the first key of a command.
+fn lock_when_idle(prompt: &str) {
+    let timeout = match env::var("VLOCK_TIMEOUT").ok().and_then(|v| v.parse::<i32>().ok()) {
+        Some(secs) if secs > 0 => secs.saturating_mul(1000),
+        _ => return,
     };
 
-    match UnixStream::connect(DEFAULT_SOCKET_PATH) {
-        Ok(mut stream) => {
-            if let Err(e) = stream.write_all(full_command.as_bytes()) {
-                safe_eprintln(format_args!("Failed to write to socket: {}", e));
-                return;
+    unsafe {
+        let mut term: libc::termios = std::mem::zeroed();
+        if libc::tcgetattr(0, &mut term) != 0 {
+            return;
+        }
+        // Wake up on the first key without taking it, readline reads it after
+        let saved = term;
+        term.c_lflag &= !(libc::ICANON | libc::ECHO);
+        term.c_cc[libc::VMIN] = 1;
+        term.c_cc[libc::VTIME] = 0;
+        libc::tcsetattr(0, libc::TCSANOW, &term);
+
+        loop {
+            print!("{}", prompt);
+            std::io::stdout().flush().unwrap();
+            let mut fds = libc::pollfd { fd: 0, events: libc::POLLIN, revents: 0 };
+            if libc::poll(&mut fds, 1, timeout) != 0 {
+                break;
             }
-
-            let mut buffer = [0u8; 1024];
-            let mut response = String::new();
-            loop {
-                match stream.read(&mut buffer) {
-                    Ok(0) => break,
-                    Ok(n) => response.push_str(&String::from_utf8_lossy(&buffer[..n])),
-                    Err(e) => {
-                        safe_eprintln(format_args!("Failed to read from socket: {}", e));
-                        break;
-                    }
-                }
+            libc::tcsetattr(0, libc::TCSANOW, &saved);
+            println!();
+            if let Err(e) = Command::new("vlock").status() {
+                safe_eprintln(format_args!("sh: cannot run vlock: {}", e));
+                break;
             }
-
-            println!("{}", response);
-        }
-        Err(e) => {
-            safe_eprintln(format_args!("Failed to connect to minisystemd: {}", e));
+            libc::tcsetattr(0, libc::TCSANOW, &term);
         }
+
+        libc::tcsetattr(0, libc::TCSANOW, &saved);
+        // readline draws the prompt again
+        print!("\r\x1B[K");
+        std::io::stdout().flush().unwrap();
+    }
+}
+
+fn run_servicectl(args: &[&str]) {
+    match Command::new("servicectl").args(args).status() {
+        Ok(_) => {},
+        Err(e) => safe_eprintln(format_args!("Failed to run servicectl: {}", e)),
     }
 }
 
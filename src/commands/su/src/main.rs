@@ -0,0 +1,137 @@
+use std::collections::HashMap;
+use std::env;
+use std::io::{self, Write};
+use std::os::unix::process::{CommandExt, ExitStatusExt};
+use std::path::Path;
+use std::process::{exit, Command};
+use vantara::audit::{self, EventKind, Outcome};
+use vantara::auth::AuthContext;
+use vantara::auth::stack::AuthStack;
+use vantara::auth::privilege::{filtered_env, log_auth, sanitized_env};
+use vantara::auth::modules::group::drop_privileges;
+use vantara::nss::{getgrouplist, getpwnam, getpwuid};
+use vantara::auth::modules::session_log::get_tty;
+use vantara::{package_name, print_version, read_password, safe_eprintln, safe_print, safe_println};
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let mut login = false;
+    let mut command: Option<String> = None;
+    let mut shell: Option<String> = None;
+    let mut target = String::from("root");
+
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().unwrap_or_else(|| fail_with(2, &format!("option {} requires an argument", arg)));
+        match arg.as_str() {
+            "--help" => { print_usage(); exit(0); },
+            "--version" => { print_version!(); exit(0); },
+            "-" | "-l" | "--login" => login = true,
+            "-c" | "--command" => command = Some(value()),
+            "-s" | "--shell" => shell = Some(value()),
+            _ if arg.starts_with('-') => {
+                safe_eprintln(format_args!("{}: unknown option '{}'", package_name!(), arg));
+                exit(2);
+            }
+            _ => target = arg,
+        }
+    }
+
+    let uid = unsafe { libc::getuid() };
+    let caller = getpwuid(uid).map(|u| u.username).unwrap_or_else(|| uid.to_string());
+    let user = getpwnam(&target)
+        .unwrap_or_else(|| fail_with(1, &format!("user {} does not exist", target)));
+
+    let stack = AuthStack::load("su").unwrap_or_else(|e| fail_with(1, &e));
+    let password = if uid == 0 {
+        String::new()
+    } else {
+        safe_print(format_args!("Password: "));
+        let _ = io::stdout().flush();
+        read_password()
+    };
+    let mut ctx = AuthContext {
+        username: user.username.clone(),
+        password,
+        metadata: HashMap::new(),
+    };
+
+    let tty = get_tty();
+    if !stack.login(&mut ctx) {
+        log_auth("su", &format!("FAILED su for {} by {} on {}", user.username, caller, tty));
+        audit::record(EventKind::Su, Outcome::Failure, &caller, &format!("to {}", user.username));
+        fail_with(1, "Authentication failure");
+    }
+    log_auth("su", &format!("(to {}) {} on {}", user.username, caller, tty));
+    audit::record(EventKind::Su, Outcome::Success, &caller, &format!("to {}", user.username));
+
+    let mut session = match stack.open_session(&mut ctx) {
+        Ok(session) => session,
+        Err(reason) => fail_with(1, &format!("cannot open session: {}", reason)),
+    };
+
+    let shell = shell.unwrap_or_else(|| user.shell.clone());
+    let mut env = if login {
+        sanitized_env(&["TERM".to_string()], &user)
+    } else {
+        filtered_env(&user)
+    };
+    // e.g. XDG_RUNTIME_DIR from the session modules
+    for (key, value) in session.context().session_env() {
+        env.retain(|(k, _)| *k != key);
+        env.push((key, value));
+    }
+
+    let mut child = Command::new(&shell);
+    child.env_clear().envs(env);
+    if login {
+        // A leading '-' in argv[0] tells the shell it is a login shell
+        let name = Path::new(&shell).file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+        child.arg0(format!("-{}", name)).current_dir(&user.home);
+    }
+    if let Some(command) = command {
+        child.args(["-c", &command]);
+    }
+
+    // su stays as root and waits, so the session is closed however the shell ends
+    let groups = getgrouplist(&user.username, user.gid);
+    let (target_uid, target_gid) = (user.uid, user.gid);
+    unsafe {
+        child.pre_exec(move || drop_privileges(target_uid, target_gid, &groups));
+    }
+    let status = match child.spawn() {
+        Ok(mut child) => {
+            // ^C and ^\ are for the shell, not for us
+            unsafe {
+                libc::signal(libc::SIGINT, libc::SIG_IGN);
+                libc::signal(libc::SIGQUIT, libc::SIG_IGN);
+            }
+            child.wait().ok()
+        }
+        Err(e) => {
+            safe_eprintln(format_args!("{}: failed to execute {}: {}", package_name!(), shell, e));
+            None
+        }
+    };
+    session.close();
+
+    exit(match status {
+        Some(status) => status.code().unwrap_or_else(|| 128 + status.signal().unwrap_or(0)),
+        None => 1,
+    });
+}
+
+fn fail_with(code: i32, message: &str) -> ! {
+    safe_eprintln(format_args!("{}: {}", package_name!(), message));
+    exit(code);
+}
+
+fn print_usage() {
+    safe_println(format_args!("Usage: {} [options] [-] [USER]", package_name!()));
+    safe_println(format_args!("Run a shell as USER, root by default, after asking for USER's password."));
+    safe_println(format_args!("Options:"));
+    safe_println(format_args!("     -, -l, --login            Start a login shell with a clean environment in USER's home"));
+    safe_println(format_args!("     -c, --command CMD         Run CMD with the shell instead of an interactive shell"));
+    safe_println(format_args!("     -s, --shell SHELL         Run SHELL instead of USER's login shell"));
+    safe_println(format_args!("     --help                    Show help"));
+    safe_println(format_args!("     --version                 Show version"));
+}
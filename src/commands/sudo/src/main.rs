@@ -0,0 +1,248 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{exit, Command};
+use vantara::audit::{self, EventKind, Outcome};
+use vantara::auth::{AuthContext, AuthResult};
+use vantara::auth::stack::{AuthStack, Phase};
+use vantara::auth::sudoers::{Decision, Request, Sudoers};
+use vantara::auth::privilege::{log_auth, sanitized_env, Timestamp, SECURE_PATH};
+use vantara::auth::modules::group::drop_privileges;
+use vantara::host::kernel_hostname;
+use vantara::nss::{getgrgid, getgrouplist, getpwnam, getpwuid};
+use vantara::auth::modules::session_log::get_tty;
+use vantara::{package_name, print_version, read_password, safe_eprintln, safe_print, safe_println};
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let mut runas = String::from("root");
+    let mut list = false;
+    let mut validate = false;
+    let mut invalidate = false;
+    let mut remove = false;
+    let mut command: Vec<String> = Vec::new();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--help" => { print_usage(); exit(0); },
+            "--version" => { print_version!(); exit(0); },
+            "-u" | "--user" => runas = args.next().unwrap_or_else(|| fail_with(2, "option -u requires an argument")),
+            "-l" | "--list" => list = true,
+            "-v" | "--validate" => validate = true,
+            "-k" | "--reset-timestamp" => invalidate = true,
+            "-K" | "--remove-timestamp" => remove = true,
+            "--" => {
+                command.extend(args.by_ref());
+            }
+            _ if arg.starts_with('-') => {
+                safe_eprintln(format_args!("{}: unknown option '{}'", package_name!(), arg));
+                exit(2);
+            }
+            _ => {
+                command.push(arg);
+                command.extend(args.by_ref());
+            }
+        }
+    }
+
+    let uid = unsafe { libc::getuid() };
+    let caller = getpwuid(uid)
+        .unwrap_or_else(|| fail_with(1, &format!("you do not exist in the passwd database (uid {})", uid)));
+    let timestamp = Timestamp::for_tty(uid);
+
+    if remove || invalidate {
+        if let Some(timestamp) = &timestamp {
+            timestamp.remove();
+        }
+        if remove || command.is_empty() {
+            exit(0);
+        }
+    }
+
+    let policy = Sudoers::load().unwrap_or_else(|e| fail_with(1, &e));
+    let groups: Vec<String> = getgrouplist(&caller.username, caller.gid)
+        .into_iter()
+        .filter_map(|gid| getgrgid(gid).map(|g| g.groupname))
+        .collect();
+    let host = kernel_hostname();
+
+    if list {
+        let rules = policy.rules_for(&caller.username, &groups, &host);
+        if rules.is_empty() {
+            fail_with(1, &format!("{} is not allowed to run sudo on {}", caller.username, host));
+        }
+        safe_println(format_args!("User {} may run the following commands on {}:", caller.username, host));
+        for rule in rules {
+            safe_println(format_args!("    {}", rule.describe()));
+        }
+        exit(0);
+    }
+
+    if validate {
+        if policy.rules_for(&caller.username, &groups, &host).is_empty() {
+            fail_with(1, &format!("{} is not allowed to run sudo on {}", caller.username, host));
+        }
+        authenticate(&caller.username, &policy, timestamp.as_ref(), "validate");
+        check_account(&caller.username, "validate");
+        exit(0);
+    }
+
+    if command.is_empty() {
+        print_usage();
+        exit(2);
+    }
+
+    let target = getpwnam(&runas).unwrap_or_else(|| fail_with(1, &format!("unknown user {}", runas)));
+    let path = resolve_command(&command[0])
+        .unwrap_or_else(|| fail_with(1, &format!("{}: command not found", command[0])));
+    let path = path.to_string_lossy().into_owned();
+    let args = &command[1..];
+
+    let cwd = env::current_dir().map(|d| d.display().to_string()).unwrap_or_default();
+    let details = format!(
+        "TTY={} ; PWD={} ; USER={} ; COMMAND={}",
+        get_tty(),
+        cwd,
+        target.username,
+        std::iter::once(path.as_str()).chain(args.iter().map(String::as_str)).collect::<Vec<_>>().join(" ")
+    );
+
+    let request = Request {
+        user: &caller.username,
+        groups: &groups,
+        host: &host,
+        runas: &target.username,
+        command: &path,
+        args,
+    };
+    match policy.check(&request) {
+        Decision::Deny => {
+            log_auth("sudo", &format!("{} : command not allowed ; {}", caller.username, details));
+            audit::record(EventKind::Sudo, Outcome::Failure, &caller.username, &format!("command not allowed ; {}", details));
+            fail_with(1, &format!(
+                "{} is not allowed to run '{}' as {} on {}",
+                caller.username, path, target.username, host
+            ));
+        }
+        Decision::Allow { nopasswd } => {
+            if !nopasswd && uid != 0 {
+                authenticate(&caller.username, &policy, timestamp.as_ref(), &details);
+            }
+        }
+    }
+    // Also with NOPASSWD or a recent timestamp, so expired or locked accounts can't use sudo
+    check_account(&caller.username, &details);
+    log_auth("sudo", &format!("{} : {}", caller.username, details));
+    audit::record(EventKind::Sudo, Outcome::Success, &caller.username, &details);
+
+    let groups = getgrouplist(&target.username, target.gid);
+    let (target_uid, target_gid) = (target.uid, target.gid);
+    let mut child = Command::new(&path);
+    // The name as typed, for programs that look at how they were called
+    child.arg0(&command[0]).args(args).env_clear().envs(sanitized_env(&policy.env_keep, &target));
+    unsafe {
+        child.pre_exec(move || drop_privileges(target_uid, target_gid, &groups));
+    }
+    let err = child.exec();
+    fail_with(1, &format!("unable to execute {}: {}", path, err));
+}
+
+/// Ask for the caller's own password unless they authenticated on this tty
+/// recently, exits when they can't.
+fn authenticate(username: &str, policy: &Sudoers, timestamp: Option<&Timestamp>, details: &str) {
+    let timeout = match policy.timestamp_timeout {
+        t if t < 0 => u64::MAX,
+        t => t as u64 * 60,
+    };
+    if timestamp.is_some_and(|ts| ts.is_valid(timeout)) {
+        return;
+    }
+
+    let stack = AuthStack::load("sudo").unwrap_or_else(|e| fail_with(1, &e));
+    let mut ctx = AuthContext {
+        username: username.to_string(),
+        password: String::new(),
+        metadata: HashMap::new(),
+    };
+
+    let mut authenticated = false;
+    for attempt in 0..policy.passwd_tries {
+        if attempt > 0 {
+            safe_println(format_args!("Sorry, try again."));
+        }
+        safe_print(format_args!("[sudo] password for {}: ", username));
+        let _ = io::stdout().flush();
+        ctx.password = read_password();
+        if let AuthResult::Success = stack.authenticate(&mut ctx) {
+            authenticated = true;
+            break;
+        }
+    }
+    if !authenticated {
+        log_auth("sudo", &format!("{} : {} incorrect password attempts ; {}", username, policy.passwd_tries, details));
+        audit::record(EventKind::Sudo, Outcome::Failure, username, &format!("{} incorrect password attempts ; {}", policy.passwd_tries, details));
+        fail_with(1, &format!("{} incorrect password attempts", policy.passwd_tries));
+    }
+
+    if let Some(timestamp) = timestamp.filter(|_| timeout > 0) {
+        let _ = timestamp.update();
+    }
+}
+
+/// Account phase of the sudo stack for the caller, exits when their account may not
+/// be used.
+fn check_account(username: &str, details: &str) {
+    let stack = AuthStack::load("sudo").unwrap_or_else(|e| fail_with(1, &e));
+    let mut ctx = AuthContext {
+        username: username.to_string(),
+        password: String::new(),
+        metadata: HashMap::new(),
+    };
+    if let AuthResult::Failure(reason) = stack.run_phase(Phase::Account, &mut ctx) {
+        log_auth("sudo", &format!("{} : account validation failure ; {}", username, details));
+        audit::record(EventKind::Sudo, Outcome::Failure, username, &format!("account validation failure ; {}", details));
+        fail_with(1, &format!("account validation failure: {}", reason));
+    }
+}
+
+/// Canonical path of `name`, searching the caller's PATH and then the secure path.
+/// Links, `.` and `//` are resolved so the policy sees the program that runs.
+fn resolve_command(name: &str) -> Option<PathBuf> {
+    let is_executable = |path: &Path| fs::metadata(path).is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0);
+
+    if name.contains('/') {
+        let path = env::current_dir().ok()?.join(name);
+        return Some(path).filter(|p| is_executable(p)).and_then(|p| fs::canonicalize(p).ok());
+    }
+
+    let search = env::var("PATH").unwrap_or_default();
+    search
+        .split(':')
+        .chain(SECURE_PATH.split(':'))
+        .filter(|dir| dir.starts_with('/'))
+        .map(|dir| Path::new(dir).join(name))
+        .find(|path| is_executable(path))
+        .and_then(|path| fs::canonicalize(path).ok())
+}
+
+fn fail_with(code: i32, message: &str) -> ! {
+    safe_eprintln(format_args!("{}: {}", package_name!(), message));
+    exit(code);
+}
+
+fn print_usage() {
+    safe_println(format_args!("Usage: {} [options] COMMAND [ARGS...]", package_name!()));
+    safe_println(format_args!("Run COMMAND as another user, as allowed by /etc/sudoers."));
+    safe_println(format_args!("Options:"));
+    safe_println(format_args!("     -u, --user USER           Run COMMAND as USER instead of root"));
+    safe_println(format_args!("     -l, --list                List the commands you may run"));
+    safe_println(format_args!("     -v, --validate            Authenticate and extend the timeout without running anything"));
+    safe_println(format_args!("     -k, --reset-timestamp     Ask for the password next time, or now when COMMAND is given"));
+    safe_println(format_args!("     -K, --remove-timestamp    Remove the remembered authentication and exit"));
+    safe_println(format_args!("     --help                    Show help"));
+    safe_println(format_args!("     --version                 Show version"));
+}
@@ -0,0 +1,174 @@
+use std::env;
+use std::io::{self, stdin, Write};
+use std::path::Path;
+use std::process::exit;
+use qrcode::QrCode;
+use qrcode::render::unicode::Dense1x2;
+use vantara::auth::modules::faillock::now_secs;
+use vantara::auth::modules::totp::{base32_encode, otpauth_uri, verify_code, TotpState, DEFAULT_TOTP_DIR, DEFAULT_WINDOW};
+use vantara::host::kernel_hostname;
+use vantara::nss::{getpwnam, getpwuid};
+use vantara::{package_name, print_version, read_password, safe_eprintln, safe_print, safe_println};
+
+const ISSUER: &str = "Vantara";
+/// Attempts at typing the first code from the app.
+const CONFIRM_ATTEMPTS: usize = 3;
+
+#[derive(PartialEq)]
+enum Action {
+    Enroll,
+    Remove,
+    Status,
+}
+
+fn main() {
+    let mut action = Action::Enroll;
+    let mut username: Option<String> = None;
+
+    for arg in env::args().skip(1) {
+        let next = match arg.as_str() {
+            "--help" => { print_usage(); exit(0); },
+            "--version" => { print_version!(); exit(0); },
+            "-r" | "--remove" => Action::Remove,
+            "-S" | "--status" => Action::Status,
+            _ if arg.starts_with('-') => {
+                safe_eprintln(format_args!("{}: unknown option '{}'", package_name!(), arg));
+                exit(2);
+            }
+            _ => {
+                username = Some(arg);
+                continue;
+            }
+        };
+        if action != Action::Enroll {
+            fail_with(2, "only one of -r and -S can be given");
+        }
+        action = next;
+    }
+
+    let uid = unsafe { libc::getuid() };
+    let caller = match getpwuid(uid) {
+        Some(user) => user.username,
+        None => fail_with(1, &format!("cannot determine your user name (uid {})", uid)),
+    };
+    let username = username.unwrap_or_else(|| caller.clone());
+    if uid != 0 && username != caller {
+        fail_with(1, "permission denied");
+    }
+    if getpwnam(&username).is_none() {
+        fail_with(1, &format!("user '{}' does not exist", username));
+    }
+
+    let dir = Path::new(DEFAULT_TOTP_DIR);
+    let existing = TotpState::load(dir, &username).unwrap_or_else(|e| fail_with(1, &e.to_string()));
+
+    match action {
+        Action::Status => match existing {
+            Some(state) => safe_println(format_args!(
+                "{}: enrolled, {} recovery codes left",
+                username,
+                state.recovery.len()
+            )),
+            None => safe_println(format_args!("{}: not enrolled", username)),
+        },
+        Action::Remove => {
+            if existing.is_none() {
+                fail_with(1, &format!("{} is not enrolled", username));
+            }
+            if uid != 0 {
+                prove_current(dir, &username);
+            }
+            TotpState::remove(dir, &username).unwrap_or_else(|e| fail_with(1, &e.to_string()));
+            safe_println(format_args!("Two-factor authentication removed for {}.", username));
+        }
+        Action::Enroll => {
+            // Someone who got hold of an open session must not swap the second factor
+            if existing.is_some() && uid != 0 {
+                prove_current(dir, &username);
+            }
+            enroll(dir, &username);
+        }
+    }
+}
+
+/// Asks for a code or recovery code of the current enrollment, exits when wrong.
+fn prove_current(dir: &Path, username: &str) {
+    safe_print(format_args!("Current verification code: "));
+    let _ = io::stdout().flush();
+    let input = read_password();
+
+    // Read again under the lock, a login may have used a code meanwhile
+    let _lock = TotpState::lock(dir, username).unwrap_or_else(|e| fail_with(1, &e.to_string()));
+    let mut state = match TotpState::load(dir, username) {
+        Ok(Some(state)) => state,
+        Ok(None) => fail_with(1, &format!("{} is not enrolled", username)),
+        Err(e) => fail_with(1, &e.to_string()),
+    };
+    if !state.verify(&input, now_secs(), DEFAULT_WINDOW) {
+        fail_with(1, "invalid verification code");
+    }
+    // The code is spent even if nothing else changes
+    let _ = state.save(dir, username);
+}
+
+fn enroll(dir: &Path, username: &str) {
+    let (mut state, recovery_codes) = TotpState::generate();
+    let host = kernel_hostname();
+    let uri = otpauth_uri(ISSUER, &format!("{}@{}", username, host), &state.secret);
+
+    safe_println(format_args!("Scan this code with an authenticator app:"));
+    match QrCode::new(uri.as_bytes()) {
+        // Drawn light on dark, for the usual terminal with a dark background
+        Ok(code) => safe_println(format_args!(
+            "{}",
+            code.render::<Dense1x2>().dark_color(Dense1x2::Light).light_color(Dense1x2::Dark).quiet_zone(true).build()
+        )),
+        Err(e) => safe_eprintln(format_args!("{}: cannot draw QR code: {}", package_name!(), e)),
+    }
+    safe_println(format_args!("Or add it by hand:"));
+    safe_println(format_args!("  {}", uri));
+    safe_println(format_args!("  secret: {}", base32_encode(&state.secret)));
+    safe_println(format_args!(""));
+
+    let mut confirmed = false;
+    for _ in 0..CONFIRM_ATTEMPTS {
+        safe_print(format_args!("Enter the code shown by the app: "));
+        let _ = io::stdout().flush();
+        let mut code = String::new();
+        if stdin().read_line(&mut code).unwrap_or(0) == 0 {
+            break;
+        }
+        // Only a TOTP code proves the app is set up, not a recovery code
+        if let Some(counter) = verify_code(&state.secret, code.trim(), now_secs(), DEFAULT_WINDOW, None) {
+            state.last_counter = Some(counter);
+            confirmed = true;
+            break;
+        }
+        safe_println(format_args!("That code is not valid, check the clock of the device."));
+    }
+    if !confirmed {
+        fail_with(1, "enrollment cancelled, nothing was saved");
+    }
+
+    state.save(dir, username).unwrap_or_else(|e| fail_with(1, &format!("cannot save secret: {}", e)));
+    safe_println(format_args!("Two-factor authentication is on for {}.", username));
+    safe_println(format_args!("Recovery codes, each works once when the app is not at hand:"));
+    for code in recovery_codes {
+        safe_println(format_args!("  {}", code));
+    }
+}
+
+fn fail_with(code: i32, message: &str) -> ! {
+    safe_eprintln(format_args!("{}: {}", package_name!(), message));
+    exit(code);
+}
+
+fn print_usage() {
+    safe_println(format_args!("Usage: {} [options] [USER]", package_name!()));
+    safe_println(format_args!("Set up time based one-time codes for USER, or yourself."));
+    safe_println(format_args!("Options:"));
+    safe_println(format_args!("     -r, --remove              Turn two-factor authentication off"));
+    safe_println(format_args!("     -S, --status              Show whether USER is enrolled"));
+    safe_println(format_args!("     --help                    Show help"));
+    safe_println(format_args!("     --version                 Show version"));
+}
@@ -2,6 +2,7 @@ use libc::utsname;
 use std::env;
 use std::ffi::CStr;
 use vantara::{safe_eprintln, safe_println, package_name, print_version};
+use vantara::host::{read_os_release, OS_NAME};
 use std::process::exit;
 
 fn main() {
@@ -33,6 +34,7 @@ fn main() {
                         'r' => output.push(release.clone()),
                         'v' => output.push(version.clone()),
                         'm' | 'p' | 'i' => output.push(machine.clone()),
+                        'o' => output.push(os_name()),
                         'a' => {
                             output = vec![
                                 sysname.clone(),
@@ -40,6 +42,7 @@ fn main() {
                                 release.clone(),
                                 version.clone(),
                                 machine.clone(),
+                                os_name(),
                             ];
                             break;
                         }
@@ -75,6 +78,11 @@ fn get_uname() -> Result<(String, String, String, String, String), String> {
     }
 }
 
+/// NAME from /etc/os-release, where GNU uname prints "GNU/Linux"
+fn os_name() -> String {
+    read_os_release().remove("NAME").unwrap_or_else(|| OS_NAME.to_string())
+}
+
 fn print_usage() {
     safe_println(format_args!("Usage: {} -[OPTIONS]", package_name!()));
     safe_println(format_args!("     s           Print kernel name"));
@@ -82,5 +90,6 @@ fn print_usage() {
     safe_println(format_args!("     r           Print kernel release"));
     safe_println(format_args!("     v           Print kernel version"));
     safe_println(format_args!("     m           Print machine hardware name"));
+    safe_println(format_args!("     o           Print operating system name"));
     safe_println(format_args!("     a           Print all the information above"));
 }
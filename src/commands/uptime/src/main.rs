@@ -3,8 +3,8 @@ use sysinfo::System;
 use std::env;
 use std::fs;
 use std::process::exit;
-use vantara::{safe_println, safe_eprintln, package_name, print_version};
-use std::collections::HashSet;
+use vantara::{safe_println, safe_eprintln, package_name, print_version, tr, tr_count};
+use vantara::auth::modules::session_log::active_sessions;
 
 const DEFAULT_LOADAVG_PATH: &str = "/proc/loadavg";
 
@@ -58,7 +58,7 @@ fn format_uptime(seconds: u64) -> String {
     let minutes = (seconds % 3600) / 60;
 
     if days > 0 {
-        format!("{} days, {:02}:{:02}", days, hours, minutes)
+        format!("{}, {:02}:{:02}", tr_count!("uptime.days", days), hours, minutes)
     } else {
         format!("{:02}:{:02}", hours, minutes)
     }
@@ -71,50 +71,25 @@ fn pretty_uptime(seconds: u64) -> String {
 
     let mut parts = Vec::new();
     if days > 0 {
-        parts.push(format!("{} day{}", days, if days > 1 { "s" } else { "" }));
+        parts.push(tr_count!("uptime.days", days));
     }
     if hours > 0 {
-        parts.push(format!("{} hour{}", hours, if hours > 1 { "s" } else { "" }));
+        parts.push(tr_count!("uptime.hours", hours));
     }
     if minutes > 0 {
-        parts.push(format!("{} minute{}", minutes, if minutes > 1 { "s" } else { "" }));
+        parts.push(tr_count!("uptime.minutes", minutes));
     }
 
     if parts.is_empty() {
-        parts.push("less than a minute".to_string());
+        parts.push(tr!("uptime.under_a_minute"));
     }
 
-    format!("up {}", parts.join(", "))
+    tr!("uptime.pretty", uptime = parts.join(", "))
 }
 
+/// Open login sessions, the same ones `who` lists.
 fn get_user_count() -> usize {
-    let mut users = HashSet::new();
-
-    if let Ok(entries) = fs::read_dir("/proc") {
-        for entry in entries.flatten() {
-            let path = entry.path();
-            if path.is_dir() {
-                if let Some(file_name) = path.file_name().and_then(|f| f.to_str()) {
-                    if file_name.chars().all(|c| c.is_digit(10)) {
-                        let status_path = path.join("status");
-                        if let Ok(content) = fs::read_to_string(status_path) {
-                            for line in content.lines() {
-                                if line.starts_with("Uid:") {
-                                    let uid_str = line.split_whitespace().nth(1).unwrap_or("0");
-                                    if let Ok(uid) = uid_str.parse::<u32>() {
-                                        users.insert(uid);
-                                    }
-                                    break;
-                                }
-                            }
-                        }
-                    }
-                }
-            }
-        }
-    }
-
-    users.len()
+    active_sessions().len()
 }
 
 fn print_pretty(uptime_secs: u64) {
@@ -124,7 +99,7 @@ fn print_pretty(uptime_secs: u64) {
 fn print_since(uptime_secs: u64) {
     let now = Local::now();
     let boot_time = now - Duration::seconds(uptime_secs as i64);
-    safe_println(format_args!("System boot time: {}", boot_time.format("%Y-%m-%d %H:%M:%S")));
+    safe_println(format_args!("{}", tr!("uptime.boot_time", time = boot_time.format("%Y-%m-%d %H:%M:%S"))));
 }
 
 fn print_default(uptime_secs: u64) {
@@ -141,13 +116,14 @@ fn print_default(uptime_secs: u64) {
     );
 
     safe_println(format_args!(
-        "{} up {},  {} users,  load average: {:.2}, {:.2}, {:.2}",
-        now,
-        uptime,
-        user_count,
-        one,
-        five,
-        fifteen
+        "{}",
+        tr!(
+            "uptime.summary",
+            now = now,
+            uptime = uptime,
+            users = tr_count!("uptime.users", user_count),
+            load = format!("{:.2}, {:.2}, {:.2}", one, five, fifteen)
+        )
     ));
 }
 
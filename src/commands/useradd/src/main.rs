@@ -0,0 +1,132 @@
+use std::env;
+use std::path::Path;
+use std::process::exit;
+use vantara::auth::modules::account_db::{AccountDb, NewUser};
+use vantara::auth::modules::homedir::{create_home, DEFAULT_SKEL_PATH};
+use vantara::auth::modules::shadow::parse_day;
+use vantara::{package_name, print_version, safe_eprintln, safe_println};
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let mut new = NewUser::default();
+    let mut create_home_dir: Option<bool> = None;
+    let mut skel: Option<String> = None;
+    let mut expire: Option<i64> = None;
+    let mut inactive: Option<i64> = None;
+    let mut root = String::from("/");
+
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().unwrap_or_else(|| {
+            safe_eprintln(format_args!("{}: option {} requires an argument", package_name!(), arg));
+            exit(2);
+        });
+        match arg.as_str() {
+            "--help" => { print_usage(); exit(0); },
+            "--version" => { print_version!(); exit(0); },
+            "-c" | "--comment" => new.comment = value(),
+            "-d" | "--home-dir" => new.home = Some(value()),
+            "-g" | "--gid" => new.group = Some(value()),
+            "-G" | "--groups" => new.groups = value().split(',').filter(|g| !g.is_empty()).map(String::from).collect(),
+            "-s" | "--shell" => new.shell = Some(value()),
+            "-u" | "--uid" => new.uid = Some(parse_number(&value())),
+            "-k" | "--skel" => skel = Some(value()),
+            "-e" | "--expiredate" => expire = Some(parse_date(&value())),
+            "-f" | "--inactive" => inactive = Some(parse_number(&value()) as i64),
+            "-R" | "--root" => root = value(),
+            "-m" | "--create-home" => create_home_dir = Some(true),
+            "-M" | "--no-create-home" => create_home_dir = Some(false),
+            "-r" | "--system" => new.system = true,
+            _ if arg.starts_with('-') => {
+                safe_eprintln(format_args!("{}: unknown option '{}'", package_name!(), arg));
+                exit(2);
+            }
+            _ if new.name.is_empty() => new.name = arg,
+            _ => {
+                print_usage();
+                exit(2);
+            }
+        }
+    }
+
+    if new.name.is_empty() {
+        print_usage();
+        exit(2);
+    }
+    if unsafe { libc::getuid() } != 0 {
+        safe_eprintln(format_args!("{}: permission denied", package_name!()));
+        exit(1);
+    }
+
+    let mut db = match AccountDb::open_at(Path::new(&root)) {
+        Ok(db) => db,
+        Err(e) => fail(&e),
+    };
+
+    let user = match db.add_user(&new) {
+        Ok(user) => user,
+        Err(e) => fail(&e),
+    };
+    if let Some(entry) = db.shadow.find_mut(&user.username) {
+        entry.expire_date = expire.filter(|d| *d >= 0);
+        entry.inactive_days = inactive.filter(|d| *d >= 0);
+    }
+
+    let create = create_home_dir.unwrap_or(!new.system && db.defs.get_bool("CREATE_HOME", true));
+    let home = db.path(&user.home);
+    let skel = db.path(skel.as_deref().unwrap_or(DEFAULT_SKEL_PATH));
+    let mode = u32::from_str_radix(db.defs.get("HOME_MODE").unwrap_or("0700"), 8).unwrap_or(0o700);
+
+    if let Err(e) = db.commit() {
+        fail(&e);
+    }
+
+    if create {
+        if let Err(e) = create_home(&home, &skel, user.uid, user.gid, mode) {
+            safe_eprintln(format_args!("{}: {}", package_name!(), e));
+            exit(12);
+        }
+    }
+}
+
+fn fail(message: &str) -> ! {
+    safe_eprintln(format_args!("{}: {}", package_name!(), message));
+    exit(1);
+}
+
+fn parse_number(value: &str) -> u32 {
+    value.parse().unwrap_or_else(|_| {
+        safe_eprintln(format_args!("{}: invalid number '{}'", package_name!(), value));
+        exit(3);
+    })
+}
+
+fn parse_date(value: &str) -> i64 {
+    if value == "-1" || value.is_empty() {
+        return -1;
+    }
+    parse_day(value).unwrap_or_else(|| {
+        safe_eprintln(format_args!("{}: invalid date '{}'", package_name!(), value));
+        exit(3);
+    })
+}
+
+fn print_usage() {
+    safe_println(format_args!("Usage: {} [options] LOGIN", package_name!()));
+    safe_println(format_args!("Options:"));
+    safe_println(format_args!("     -c, --comment TEXT        Full name or other comment"));
+    safe_println(format_args!("     -d, --home-dir DIR        Home directory, default HOME/LOGIN from login.defs"));
+    safe_println(format_args!("     -g, --gid GROUP           Primary group, default a new group named LOGIN"));
+    safe_println(format_args!("     -G, --groups G1,G2        Supplementary groups"));
+    safe_println(format_args!("     -s, --shell SHELL         Login shell"));
+    safe_println(format_args!("     -u, --uid UID             User id, default the next free one"));
+    safe_println(format_args!("     -m, --create-home         Create the home directory from the skeleton"));
+    safe_println(format_args!("     -M, --no-create-home      Don't create the home directory"));
+    safe_println(format_args!("     -k, --skel DIR            Skeleton directory, default {}", DEFAULT_SKEL_PATH));
+    safe_println(format_args!("     -r, --system              Create a system account"));
+    safe_println(format_args!("     -e, --expiredate DATE     Account expiry, YYYY-MM-DD"));
+    safe_println(format_args!("     -f, --inactive DAYS       Days after password expiry until the account is disabled"));
+    safe_println(format_args!("     -R, --root DIR            Work on the system installed at DIR"));
+    safe_println(format_args!("     --help                    Show help"));
+    safe_println(format_args!("     --version                 Show version"));
+    safe_println(format_args!("The account is locked until a password is set with passwd."));
+}
@@ -0,0 +1,103 @@
+use std::env;
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+use std::process::exit;
+use vantara::auth::modules::account_db::AccountDb;
+use vantara::auth::modules::faillock::{Tally, DEFAULT_TALLY_DIR};
+use vantara::auth::modules::homedir::remove_home;
+use vantara::cron::table::user_crontab_path;
+use vantara::{package_name, print_version, safe_eprintln, safe_println};
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let mut remove = false;
+    let mut force = false;
+    let mut username: Option<String> = None;
+    let mut root = String::from("/");
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--help" => { print_usage(); exit(0); },
+            "--version" => { print_version!(); exit(0); },
+            "-r" | "--remove" => remove = true,
+            "-f" | "--force" => force = true,
+            "-R" | "--root" => root = args.next().unwrap_or_else(|| fail_with(2, "option -R requires an argument")),
+            _ if arg.starts_with('-') => {
+                safe_eprintln(format_args!("{}: unknown option '{}'", package_name!(), arg));
+                exit(2);
+            }
+            _ => username = Some(arg),
+        }
+    }
+
+    let username = match username {
+        Some(name) => name,
+        None => {
+            print_usage();
+            exit(2);
+        }
+    };
+    if unsafe { libc::getuid() } != 0 {
+        fail_with(1, "permission denied");
+    }
+
+    let mut db = AccountDb::open_at(Path::new(&root)).unwrap_or_else(|e| fail_with(1, &e));
+    let user = db.passwd.remove(&username).unwrap_or_else(|| fail_with(6, &format!("user '{}' does not exist", username)));
+
+    if root == "/" && !force && has_processes(user.uid) {
+        fail_with(8, &format!("user '{}' is currently running processes, use -f to delete anyway", username));
+    }
+
+    db.shadow.remove(&username);
+    for group in db.group.entries_mut() {
+        group.members.retain(|m| m != &username);
+    }
+
+    // The user's own group goes too, unless someone else still relies on it
+    let private_group = db.group(&username).filter(|g| g.gid == user.gid && g.members.is_empty()).is_some();
+    if private_group && !db.passwd.entries().any(|u| u.gid == user.gid) {
+        db.group.remove(&username);
+    }
+
+    let home = db.path(&user.home);
+    let crontab = db.path(&user_crontab_path(&username));
+    let tally_dir = db.path(DEFAULT_TALLY_DIR);
+    db.commit().unwrap_or_else(|e| fail_with(10, &e));
+
+    let _ = fs::remove_file(crontab);
+    let _ = Tally::reset(&tally_dir, &username);
+
+    if remove {
+        if let Err(e) = remove_home(&home, user.uid) {
+            fail_with(12, &e);
+        }
+    }
+}
+
+/// Any process whose real uid is `uid`.
+fn has_processes(uid: u32) -> bool {
+    let entries = match fs::read_dir("/proc") {
+        Ok(entries) => entries,
+        Err(_) => return false,
+    };
+    entries
+        .flatten()
+        .filter(|e| e.file_name().to_str().is_some_and(|n| n.chars().all(|c| c.is_ascii_digit())))
+        .any(|e| fs::metadata(e.path()).map(|m| m.uid() == uid).unwrap_or(false))
+}
+
+fn fail_with(code: i32, message: &str) -> ! {
+    safe_eprintln(format_args!("{}: {}", package_name!(), message));
+    exit(code);
+}
+
+fn print_usage() {
+    safe_println(format_args!("Usage: {} [options] LOGIN", package_name!()));
+    safe_println(format_args!("Options:"));
+    safe_println(format_args!("     -r, --remove       Remove the home directory as well"));
+    safe_println(format_args!("     -f, --force        Delete even while the user has running processes"));
+    safe_println(format_args!("     -R, --root DIR     Work on the system installed at DIR"));
+    safe_println(format_args!("     --help             Show help"));
+    safe_println(format_args!("     --version          Show version"));
+}
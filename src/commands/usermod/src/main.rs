@@ -0,0 +1,207 @@
+use std::env;
+use std::path::Path;
+use std::process::exit;
+use vantara::auth::modules::account_db::{is_valid_field, is_valid_name, AccountDb};
+use vantara::auth::modules::homedir::{chown_tree, move_home};
+use vantara::auth::modules::shadow::parse_day;
+use vantara::{package_name, print_version, safe_eprintln, safe_println};
+
+#[derive(Default)]
+struct Changes {
+    comment: Option<String>,
+    home: Option<String>,
+    move_home: bool,
+    group: Option<String>,
+    groups: Option<Vec<String>>,
+    append: bool,
+    new_name: Option<String>,
+    shell: Option<String>,
+    uid: Option<u32>,
+    lock: bool,
+    unlock: bool,
+    expire: Option<i64>,
+    inactive: Option<i64>,
+}
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let mut changes = Changes::default();
+    let mut username: Option<String> = None;
+    let mut root = String::from("/");
+
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().unwrap_or_else(|| {
+            safe_eprintln(format_args!("{}: option {} requires an argument", package_name!(), arg));
+            exit(2);
+        });
+        match arg.as_str() {
+            "--help" => { print_usage(); exit(0); },
+            "--version" => { print_version!(); exit(0); },
+            "-c" | "--comment" => changes.comment = Some(value()),
+            "-d" | "--home" => changes.home = Some(value()),
+            "-m" | "--move-home" => changes.move_home = true,
+            "-g" | "--gid" => changes.group = Some(value()),
+            "-G" | "--groups" => changes.groups = Some(value().split(',').filter(|g| !g.is_empty()).map(String::from).collect()),
+            "-a" | "--append" => changes.append = true,
+            "-l" | "--login" => changes.new_name = Some(value()),
+            "-s" | "--shell" => changes.shell = Some(value()),
+            "-u" | "--uid" => changes.uid = Some(value().parse().unwrap_or_else(|_| fail_with(3, "invalid uid"))),
+            "-L" | "--lock" => changes.lock = true,
+            "-U" | "--unlock" => changes.unlock = true,
+            "-e" | "--expiredate" => {
+                let v = value();
+                changes.expire = Some(if v == "-1" || v.is_empty() { -1 } else {
+                    parse_day(&v).unwrap_or_else(|| fail_with(3, &format!("invalid date '{}'", v)))
+                });
+            }
+            "-f" | "--inactive" => changes.inactive = Some(value().parse().unwrap_or_else(|_| fail_with(3, "invalid number of days"))),
+            "-R" | "--root" => root = value(),
+            _ if arg.starts_with('-') => {
+                safe_eprintln(format_args!("{}: unknown option '{}'", package_name!(), arg));
+                exit(2);
+            }
+            _ => username = Some(arg),
+        }
+    }
+
+    let username = match username {
+        Some(name) => name,
+        None => {
+            print_usage();
+            exit(2);
+        }
+    };
+    if changes.lock && changes.unlock {
+        fail_with(2, "-L and -U can't be used together");
+    }
+    if changes.append && changes.groups.is_none() {
+        fail_with(2, "-a only works together with -G");
+    }
+    if unsafe { libc::getuid() } != 0 {
+        fail_with(1, "permission denied");
+    }
+
+    let mut db = AccountDb::open_at(Path::new(&root)).unwrap_or_else(|e| fail_with(1, &e));
+    let old = db.user(&username).cloned().unwrap_or_else(|| fail_with(6, &format!("user '{}' does not exist", username)));
+
+    for field in [&changes.comment, &changes.home, &changes.shell].into_iter().flatten() {
+        if !is_valid_field(field) {
+            fail_with(3, &format!("invalid field '{}'", field));
+        }
+    }
+    if let Some(uid) = changes.uid {
+        if db.user_by_uid(uid).is_some_and(|u| u.username != username) {
+            fail_with(4, &format!("uid {} is not unique", uid));
+        }
+    }
+    if let Some(new_name) = &changes.new_name {
+        if !is_valid_name(new_name) {
+            fail_with(3, &format!("invalid user name '{}'", new_name));
+        }
+        if db.user(new_name).is_some() {
+            fail_with(9, &format!("user '{}' already exists", new_name));
+        }
+    }
+    let gid = changes.group.as_ref().map(|g| {
+        db.resolve_group(g).map(|g| g.gid).unwrap_or_else(|| fail_with(6, &format!("group '{}' does not exist", g)))
+    });
+    if let Some(groups) = &changes.groups {
+        if let Some(missing) = groups.iter().find(|g| db.resolve_group(g).is_none()) {
+            fail_with(6, &format!("group '{}' does not exist", missing));
+        }
+    }
+
+    // Supplementary groups, by name
+    if let Some(groups) = &changes.groups {
+        let wanted: Vec<String> = groups.iter().filter_map(|g| db.resolve_group(g)).map(|g| g.groupname.clone()).collect();
+        for group in db.group.entries_mut() {
+            let listed = wanted.contains(&group.groupname);
+            let member = group.members.contains(&username);
+            if listed && !member {
+                group.members.push(username.clone());
+            } else if !listed && member && !changes.append {
+                group.members.retain(|m| m != &username);
+            }
+        }
+    }
+
+    if let Some(shadow) = db.shadow.find_mut(&username) {
+        if changes.lock {
+            shadow.lock();
+        }
+        if changes.unlock {
+            if let Err(e) = shadow.unlock() {
+                fail_with(1, &e);
+            }
+        }
+        if let Some(expire) = changes.expire {
+            shadow.expire_date = Some(expire).filter(|d| *d >= 0);
+        }
+        if let Some(inactive) = changes.inactive {
+            shadow.inactive_days = Some(inactive).filter(|d| *d >= 0);
+        }
+        if let Some(new_name) = &changes.new_name {
+            shadow.username = new_name.clone();
+        }
+    } else if changes.lock || changes.unlock || changes.expire.is_some() || changes.inactive.is_some() {
+        fail_with(1, &format!("user '{}' has no shadow entry", username));
+    }
+
+    if let Some(new_name) = &changes.new_name {
+        for group in db.group.entries_mut() {
+            for member in group.members.iter_mut().filter(|m| **m == username) {
+                *member = new_name.clone();
+            }
+        }
+    }
+
+    let user = db.passwd.find_mut(&username).unwrap_or_else(|| fail_with(6, "user vanished"));
+    if let Some(comment) = changes.comment { user.fullname = comment; }
+    if let Some(home) = &changes.home { user.home = home.clone(); }
+    if let Some(shell) = changes.shell { user.shell = shell; }
+    if let Some(uid) = changes.uid { user.uid = uid; }
+    if let Some(gid) = gid { user.gid = gid; }
+    if let Some(new_name) = changes.new_name { user.username = new_name; }
+    let user = user.clone();
+
+    let old_home = db.path(&old.home);
+    let new_home = db.path(&user.home);
+    db.commit().unwrap_or_else(|e| fail_with(1, &e));
+
+    if changes.move_home && changes.home.is_some() && old_home != new_home && old_home.exists() {
+        if let Err(e) = move_home(&old_home, &new_home) {
+            fail_with(12, &e);
+        }
+    }
+    if user.uid != old.uid && new_home.exists() {
+        if let Err(e) = chown_tree(&new_home, old.uid, user.uid, None) {
+            fail_with(12, &format!("cannot change owner of {}: {}", new_home.display(), e));
+        }
+    }
+}
+
+fn fail_with(code: i32, message: &str) -> ! {
+    safe_eprintln(format_args!("{}: {}", package_name!(), message));
+    exit(code);
+}
+
+fn print_usage() {
+    safe_println(format_args!("Usage: {} [options] LOGIN", package_name!()));
+    safe_println(format_args!("Options:"));
+    safe_println(format_args!("     -c, --comment TEXT        New comment"));
+    safe_println(format_args!("     -d, --home DIR            New home directory"));
+    safe_println(format_args!("     -m, --move-home           Move the old home directory to DIR (with -d)"));
+    safe_println(format_args!("     -g, --gid GROUP           New primary group"));
+    safe_println(format_args!("     -G, --groups G1,G2        Supplementary groups, replacing the current list"));
+    safe_println(format_args!("     -a, --append              Add to the groups given with -G instead"));
+    safe_println(format_args!("     -l, --login NAME          New login name"));
+    safe_println(format_args!("     -s, --shell SHELL         New login shell"));
+    safe_println(format_args!("     -u, --uid UID             New user id, files in the home directory follow"));
+    safe_println(format_args!("     -L, --lock                Lock the password"));
+    safe_println(format_args!("     -U, --unlock              Unlock the password"));
+    safe_println(format_args!("     -e, --expiredate DATE     Account expiry, YYYY-MM-DD, -1 removes it"));
+    safe_println(format_args!("     -f, --inactive DAYS       Days after password expiry until the account is disabled"));
+    safe_println(format_args!("     -R, --root DIR            Work on the system installed at DIR"));
+    safe_println(format_args!("     --help                    Show help"));
+    safe_println(format_args!("     --version                 Show version"));
+}
@@ -0,0 +1,208 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::os::unix::fs::MetadataExt;
+use std::os::unix::io::AsRawFd;
+use std::process::exit;
+use std::thread::sleep;
+use std::time::Duration;
+use vantara::auth::{AuthContext, AuthResult};
+use vantara::auth::stack::{AuthStack, Phase};
+use vantara::auth::privilege::log_auth;
+use vantara::auth::modules::faillock::now_secs;
+use vantara::auth::modules::session_log::get_tty;
+use vantara::nss::getpwuid;
+use vantara::{package_name, print_version, read_password, safe_eprintln, safe_print, safe_println};
+
+/// From linux/vt.h, stop and allow switching to other virtual consoles.
+const VT_LOCKSWITCH: libc::c_ulong = 0x560B;
+const VT_UNLOCKSWITCH: libc::c_ulong = 0x560C;
+
+/// Keeps the console from switching away until dropped.
+struct ConsoleLock {
+    file: std::fs::File,
+}
+
+impl ConsoleLock {
+    fn take(tty: &str) -> io::Result<Self> {
+        let file = OpenOptions::new().read(true).write(true).open(tty)?;
+        if unsafe { libc::ioctl(file.as_raw_fd(), VT_LOCKSWITCH as _) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(ConsoleLock { file })
+    }
+}
+
+impl Drop for ConsoleLock {
+    fn drop(&mut self) {
+        unsafe {
+            libc::ioctl(self.file.as_raw_fd(), VT_UNLOCKSWITCH as _);
+        }
+    }
+}
+
+fn main() {
+    let mut all = false;
+
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--help" => { print_usage(); exit(0); },
+            "--version" => { print_version!(); exit(0); },
+            "-a" | "--all" => all = true,
+            "-c" | "--current" => all = false,
+            _ => {
+                safe_eprintln(format_args!("{}: unknown option '{}'", package_name!(), arg));
+                exit(2);
+            }
+        }
+    }
+
+    let uid = unsafe { libc::getuid() };
+    let user = getpwuid(uid)
+        .unwrap_or_else(|| fail_with(1, &format!("you do not exist in the passwd database (uid {})", uid)));
+    let tty = get_tty();
+    if !tty.starts_with("/dev/") {
+        fail_with(1, "stdin is not a terminal");
+    }
+    let tty_name = tty.trim_start_matches("/dev/").to_string();
+
+    // Locking every console is only for whoever sits at this one, login hands
+    // the console to the user for the session
+    let console_lock = if all {
+        let is_vt = tty_name.strip_prefix("tty").is_some_and(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()));
+        if !is_vt {
+            fail_with(1, "-a only works on a virtual console");
+        }
+        let owner = std::fs::metadata(&tty).map(|m| m.uid()).unwrap_or(0);
+        if uid != 0 && owner != uid {
+            fail_with(1, &format!("{} does not own {}", user.username, tty));
+        }
+        Some(ConsoleLock::take(&tty).unwrap_or_else(|e| fail_with(1, &format!("cannot lock console switching: {}", e))))
+    } else {
+        None
+    };
+
+    let stack = AuthStack::load("vlock").unwrap_or_else(|e| fail_with(1, &e));
+    let saved = lock_terminal().unwrap_or_else(|e| fail_with(1, &format!("cannot set up {}: {}", tty, e)));
+    let locked_at = now_secs();
+    log_auth("vlock", &format!("{} locked {}", user.username, if all { "all consoles" } else { tty_name.as_str() }));
+
+    // root can unlock too, unless it is root's own lock
+    let mut unlockers = vec![user.username.clone()];
+    if uid != 0 {
+        unlockers.push("root".to_string());
+    }
+
+    loop {
+        safe_print(format_args!("\x1B[2J\x1B[1;1H"));
+        if all {
+            safe_println(format_args!("All consoles are locked by {}.", user.username));
+        } else {
+            safe_println(format_args!("This terminal ({}) is locked by {}.", tty_name, user.username));
+        }
+        safe_println(format_args!("It can be unlocked by {}.", unlockers.join(" or ")));
+        safe_println(format_args!(""));
+        wait_for_enter(locked_at);
+
+        for name in &unlockers {
+            safe_print(format_args!("{}'s password: ", name));
+            let _ = io::stdout().flush();
+            let mut ctx = AuthContext {
+                username: name.clone(),
+                password: read_password(),
+                metadata: HashMap::new(),
+            };
+
+            let result = match stack.authenticate(&mut ctx) {
+                AuthResult::Success => stack.run_phase(Phase::Account, &mut ctx),
+                failure => failure,
+            };
+            match result {
+                AuthResult::Success => {
+                    unlock_terminal(&saved);
+                    drop(console_lock);
+                    log_auth("vlock", &format!("{} unlocked {} as {}", user.username, tty_name, name));
+                    safe_println(format_args!("Unlocked after {}.", format_elapsed(now_secs() - locked_at)));
+                    exit(0);
+                }
+                AuthResult::Failure(reason) => {
+                    log_auth("vlock", &format!("failed unlock of {} as {}: {}", tty_name, name, reason));
+                    safe_println(format_args!("{}", reason));
+                }
+            }
+        }
+        sleep(Duration::from_secs(2));
+    }
+}
+
+/// Echo and the keys that send signals (^C, ^Z, ^\) are turned off, the
+/// signals themselves are ignored in case they come from elsewhere. Returns the
+/// settings to put back.
+fn lock_terminal() -> io::Result<libc::termios> {
+    unsafe {
+        let mut term: libc::termios = std::mem::zeroed();
+        if libc::tcgetattr(0, &mut term) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let saved = term;
+        term.c_lflag &= !(libc::ISIG | libc::ECHO);
+        term.c_iflag &= !libc::IXON;
+        if libc::tcsetattr(0, libc::TCSANOW, &term) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        for signal in [libc::SIGINT, libc::SIGQUIT, libc::SIGTSTP, libc::SIGTTIN, libc::SIGTTOU, libc::SIGHUP, libc::SIGTERM] {
+            libc::signal(signal, libc::SIG_IGN);
+        }
+        Ok(saved)
+    }
+}
+
+fn unlock_terminal(saved: &libc::termios) {
+    unsafe {
+        libc::tcsetattr(0, libc::TCSANOW, saved);
+    }
+}
+
+/// Shows how long the terminal has been locked until a line is entered.
+fn wait_for_enter(locked_at: u64) {
+    let mut fds = libc::pollfd { fd: 0, events: libc::POLLIN, revents: 0 };
+    loop {
+        safe_print(format_args!("\rLocked for {}, press Enter to unlock. ", format_elapsed(now_secs() - locked_at)));
+        let _ = io::stdout().flush();
+        if unsafe { libc::poll(&mut fds, 1, 1000) } <= 0 {
+            continue;
+        }
+        let mut buf = [0u8; 256];
+        let read = unsafe { libc::read(0, buf.as_mut_ptr().cast(), buf.len()) };
+        if read > 0 {
+            safe_println(format_args!(""));
+            return;
+        }
+        // Nobody can type on a terminal that hung up, the session is gone with it
+        if fds.revents & libc::POLLHUP != 0 {
+            exit(1);
+        }
+        sleep(Duration::from_secs(1));
+    }
+}
+
+/// e.g. `01:05:09`
+fn format_elapsed(secs: u64) -> String {
+    format!("{:02}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+fn fail_with(code: i32, message: &str) -> ! {
+    safe_eprintln(format_args!("{}: {}", package_name!(), message));
+    exit(code);
+}
+
+fn print_usage() {
+    safe_println(format_args!("Usage: {} [options]", package_name!()));
+    safe_println(format_args!("Lock the terminal until your password, or root's, is entered."));
+    safe_println(format_args!("Options:"));
+    safe_println(format_args!("     -c, --current             Lock only this terminal (default)"));
+    safe_println(format_args!("     -a, --all                 Also stop switching to other virtual consoles"));
+    safe_println(format_args!("     --help                    Show help"));
+    safe_println(format_args!("     --version                 Show version"));
+}
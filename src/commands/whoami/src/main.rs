@@ -1,8 +1,16 @@
-use std::env;
+use std::process::exit;
+use vantara::nss::getpwuid;
+use vantara::{package_name, safe_eprintln, safe_println, tr};
 
 fn main() {
-    match env::var("USER").or_else(|_| env::var("USERNAME")) {
-        Ok(user) => println!("{}", user),
-        Err(_) => eprintln!("Tak dapat kenal pasti nama pengguna."),
+    // The real uid: the binaries are installed setuid root, so the effective one
+    // is always 0. sudo sets both, `sudo whoami` still says root.
+    let uid = unsafe { libc::getuid() };
+    match getpwuid(uid) {
+        Some(user) => safe_println(format_args!("{}", user.username)),
+        None => {
+            safe_eprintln(format_args!("{}: {}", package_name!(), tr!("whoami.unknown", uid = uid)));
+            exit(1);
+        }
     }
-}
\ No newline at end of file
+}
@@ -36,7 +36,7 @@ pub fn show_boot_banner() {
        \_/ \__,_|_| |_|\__\__,_|_|  \__,_|
                Operating System           
     "#));
-    safe_println(format_args!("          Welcome to the VanOS {}        \n", env!("CARGO_PKG_VERSION")));
+    safe_println(format_args!("          {}        \n", crate::tr!("banner.welcome", os = crate::host::OS_NAME, version = crate::host::OS_VERSION)));
 }
 
 pub fn safe_println(args: std::fmt::Arguments) {
@@ -0,0 +1,273 @@
+use chrono::{DateTime, Datelike, Duration, TimeZone, Timelike};
+
+const MONTH_NAMES: [&str; 12] = ["jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec"];
+const DAY_NAMES: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+/// Longest search `next_after` does before giving up (e.g. `0 0 30 2 *` never fires).
+const MAX_SEARCH_DAYS: i64 = 366 * 5;
+
+/// When a crontab line fires.
+#[derive(Debug, Clone, PartialEq)]
+pub enum When {
+    /// `@reboot`, once when crond starts after boot
+    Reboot,
+    At(Schedule),
+}
+
+/// The five time fields as bit sets.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Schedule {
+    minutes: u64,       // bits 0..59
+    hours: u32,         // bits 0..23
+    days_of_month: u32, // bits 1..31
+    months: u16,        // bits 1..12
+    days_of_week: u8,   // bits 0..6, Sunday is 0
+    dom_restricted: bool,
+    dow_restricted: bool,
+}
+
+impl When {
+    /// Parse the schedule at the start of a crontab line. Returns the schedule and
+    /// the rest of the line.
+    pub fn parse_prefix(line: &str) -> Result<(When, &str), String> {
+        let line = line.trim_start();
+
+        if let Some(rest) = line.strip_prefix('@') {
+            let (word, rest) = split_word(rest);
+            let expr = match word {
+                "reboot" => return Ok((When::Reboot, rest)),
+                "yearly" | "annually" => "0 0 1 1 *",
+                "monthly" => "0 0 1 * *",
+                "weekly" => "0 0 * * 0",
+                "daily" | "midnight" => "0 0 * * *",
+                "hourly" => "0 * * * *",
+                _ => return Err(format!("unknown macro '@{}'", word)),
+            };
+            return Ok((When::At(Schedule::parse(expr)?), rest));
+        }
+
+        let mut rest = line;
+        let mut fields = Vec::with_capacity(5);
+        for _ in 0..5 {
+            let (word, next) = split_word(rest);
+            if word.is_empty() {
+                return Err("expected five time fields".into());
+            }
+            fields.push(word);
+            rest = next;
+        }
+
+        Ok((When::At(Schedule::parse(&fields.join(" "))?), rest))
+    }
+}
+
+impl Schedule {
+    /// Parse `minute hour day-of-month month day-of-week`.
+    pub fn parse(expr: &str) -> Result<Self, String> {
+        let fields: Vec<&str> = expr.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err(format!("expected five time fields, got {}", fields.len()));
+        }
+
+        let minutes = parse_field(fields[0], 0, 59, &[])?;
+        let hours = parse_field(fields[1], 0, 23, &[])?;
+        let days_of_month = parse_field(fields[2], 1, 31, &[])?;
+        let months = parse_field(fields[3], 1, 12, &MONTH_NAMES)?;
+        // 7 is accepted as Sunday as well
+        let mut days_of_week = parse_field(fields[4], 0, 7, &DAY_NAMES)?;
+        if days_of_week & (1 << 7) != 0 {
+            days_of_week = (days_of_week | 1) & !(1 << 7);
+        }
+
+        Ok(Schedule {
+            minutes,
+            hours: hours as u32,
+            days_of_month: days_of_month as u32,
+            months: months as u16,
+            days_of_week: days_of_week as u8,
+            dom_restricted: !fields[2].starts_with('*'),
+            dow_restricted: !fields[4].starts_with('*'),
+        })
+    }
+
+    /// Whether the schedule fires in the minute containing `time`, in `time`'s zone.
+    pub fn matches<Tz: TimeZone>(&self, time: &DateTime<Tz>) -> bool {
+        self.minutes & (1 << time.minute()) != 0
+            && self.hours & (1 << time.hour()) != 0
+            && self.months & (1 << time.month()) != 0
+            && self.day_matches(time)
+    }
+
+    /// First minute strictly after `time` that the schedule fires in.
+    pub fn next_after<Tz: TimeZone>(&self, time: &DateTime<Tz>) -> Option<DateTime<Tz>> {
+        let tz = time.timezone();
+        // Durations rather than with_second/with_minute, those fail on local times
+        // that happen twice when the clocks go back
+        let start = time.clone() - Duration::seconds(time.second().into())
+            - Duration::nanoseconds(time.nanosecond().into()) + Duration::minutes(1);
+        let limit = start.clone() + Duration::days(MAX_SEARCH_DAYS);
+        let mut candidate = start;
+
+        while candidate < limit {
+            if self.months & (1 << candidate.month()) == 0 || !self.day_matches(&candidate) {
+                // Jump to the next local midnight
+                let next_day = candidate.date_naive().succ_opt()?.and_hms_opt(0, 0, 0)?;
+                candidate = tz.from_local_datetime(&next_day).earliest()
+                    .unwrap_or_else(|| candidate.clone() + Duration::hours(1));
+                continue;
+            }
+            if self.hours & (1 << candidate.hour()) == 0 {
+                candidate = candidate.clone() - Duration::minutes(candidate.minute().into()) + Duration::hours(1);
+                continue;
+            }
+            if self.minutes & (1 << candidate.minute()) != 0 {
+                return Some(candidate);
+            }
+            candidate += Duration::minutes(1);
+        }
+        None
+    }
+
+    /// Standard cron rule: when both day fields are restricted, either may match.
+    fn day_matches<Tz: TimeZone>(&self, time: &DateTime<Tz>) -> bool {
+        let dom = self.days_of_month & (1 << time.day()) != 0;
+        let dow = self.days_of_week & (1 << time.weekday().num_days_from_sunday()) != 0;
+
+        match (self.dom_restricted, self.dow_restricted) {
+            (true, true) => dom || dow,
+            (true, false) => dom,
+            (false, true) => dow,
+            (false, false) => true,
+        }
+    }
+}
+
+fn split_word(s: &str) -> (&str, &str) {
+    let s = s.trim_start();
+    match s.find(char::is_whitespace) {
+        Some(i) => (&s[..i], &s[i..]),
+        None => (s, ""),
+    }
+}
+
+/// One field: `*`, `5`, `1-5`, `*/15`, `1-30/5`, `mon,wed` or a comma list of those.
+fn parse_field(field: &str, min: u32, max: u32, names: &[&str]) -> Result<u64, String> {
+    let mut bits = 0u64;
+
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((r, s)) => {
+                let step: u32 = s.parse().map_err(|_| format!("invalid step '{}'", s))?;
+                if step == 0 {
+                    return Err(format!("step can't be zero in '{}'", part));
+                }
+                (r, step)
+            }
+            None => (part, 1),
+        };
+
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((a, b)) = range.split_once('-') {
+            (parse_value(a, min, max, names)?, parse_value(b, min, max, names)?)
+        } else {
+            let value = parse_value(range, min, max, names)?;
+            // `5/10` means from 5 to the end in steps of 10
+            (value, if part.contains('/') { max } else { value })
+        };
+
+        if start > end {
+            return Err(format!("invalid range '{}'", range));
+        }
+
+        let mut v = start;
+        while v <= end {
+            bits |= 1 << v;
+            v += step;
+        }
+    }
+
+    Ok(bits)
+}
+
+fn parse_value(value: &str, min: u32, max: u32, names: &[&str]) -> Result<u32, String> {
+    let lower = value.to_ascii_lowercase();
+    if let Some(pos) = names.iter().position(|n| *n == lower) {
+        // Month names start at 1, day names at 0
+        return Ok(pos as u32 + min);
+    }
+
+    let n: u32 = value.parse().map_err(|_| format!("invalid value '{}'", value))?;
+    if n < min || n > max {
+        return Err(format!("value {} out of range {}-{}", n, min, max));
+    }
+    Ok(n)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use chrono_tz::Europe::Berlin;
+
+    fn utc(s: &str) -> DateTime<Utc> {
+        s.parse().unwrap()
+    }
+
+    fn next(expr: &str, after: &str) -> Option<DateTime<Utc>> {
+        Schedule::parse(expr).unwrap().next_after(&utc(after))
+    }
+
+    #[test]
+    fn day_fields_are_or_when_both_restricted() {
+        // 2026-10-18 is a Sunday
+        assert_eq!(next("0 0 13 * fri", "2026-10-18T12:00:00Z"), Some(utc("2026-10-23T00:00:00Z")));
+        assert_eq!(next("0 0 19 * fri", "2026-10-18T12:00:00Z"), Some(utc("2026-10-19T00:00:00Z")));
+        // Only one restricted: that one alone decides
+        assert_eq!(next("0 0 13 * *", "2026-10-18T12:00:00Z"), Some(utc("2026-11-13T00:00:00Z")));
+        assert_eq!(next("0 0 * * 5", "2026-10-18T12:00:00Z"), Some(utc("2026-10-23T00:00:00Z")));
+        assert_eq!(next("0 0 * * 7", "2026-10-18T12:00:00Z"), Some(utc("2026-10-25T00:00:00Z")));
+    }
+
+    #[test]
+    fn month_ends() {
+        assert_eq!(next("0 0 31 * *", "2026-01-31T00:00:00Z"), Some(utc("2026-03-31T00:00:00Z")));
+        assert_eq!(next("0 0 29 2 *", "2026-01-01T00:00:00Z"), Some(utc("2028-02-29T00:00:00Z")));
+        assert_eq!(next("59 23 31 dec *", "2026-12-31T23:59:00Z"), Some(utc("2027-12-31T23:59:00Z")));
+        assert_eq!(next("0 0 30 2 *", "2026-01-01T00:00:00Z"), None);
+    }
+
+    #[test]
+    fn next_is_strictly_after() {
+        assert_eq!(next("*/15 * * * *", "2026-10-18T10:15:00Z"), Some(utc("2026-10-18T10:30:00Z")));
+        assert_eq!(next("*/15 * * * *", "2026-10-18T10:14:59Z"), Some(utc("2026-10-18T10:15:00Z")));
+    }
+
+    #[test]
+    fn daylight_saving_changes() {
+        let berlin = |s: &str| utc(s).with_timezone(&Berlin);
+        let hourly = Schedule::parse("0 * * * *").unwrap();
+
+        // 2026-03-29 02:00 CET jumps to 03:00 CEST: 01:00 is followed by 03:00
+        assert_eq!(hourly.next_after(&berlin("2026-03-29T00:00:00Z")), Some(berlin("2026-03-29T01:00:00Z")));
+        // A time that doesn't exist that day is skipped
+        let at_half_two = Schedule::parse("30 2 * * *").unwrap();
+        assert_eq!(at_half_two.next_after(&berlin("2026-03-28T23:00:00Z")), Some(berlin("2026-03-30T00:30:00Z")));
+
+        // 2026-10-25 03:00 CEST goes back to 02:00 CET: 02:00 CEST is followed by 02:00 CET
+        assert_eq!(hourly.next_after(&berlin("2026-10-25T00:00:00Z")), Some(berlin("2026-10-25T01:00:00Z")));
+        // The day is 25 hours long, the next 01:00 is still local time
+        let at_one = Schedule::parse("0 1 * * *").unwrap();
+        assert_eq!(at_one.next_after(&berlin("2026-10-24T23:30:00Z")), Some(berlin("2026-10-26T00:00:00Z")));
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(Schedule::parse("* * * *").is_err());
+        assert!(Schedule::parse("60 * * * *").is_err());
+        assert!(Schedule::parse("*/0 * * * *").is_err());
+        assert!(Schedule::parse("5-1 * * * *").is_err());
+        assert!(When::parse_prefix("@sometimes true").is_err());
+        assert_eq!(When::parse_prefix("@reboot echo hi").unwrap().0, When::Reboot);
+    }
+}
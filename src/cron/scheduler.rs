@@ -0,0 +1,192 @@
+use chrono::{DateTime, Duration, TimeZone, Timelike, Utc};
+use crate::cron::schedule::When;
+use crate::cron::table::{CronEntry, CronTable};
+
+/// After a bigger forward jump (suspend, clock set) missed minutes aren't replayed.
+const MAX_CATCH_UP_MINUTES: i64 = 60;
+
+/// Source of the current time, so the scheduler can be driven by a fake clock.
+pub trait Clock {
+    fn now(&self) -> DateTime<Utc>;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+pub struct Scheduler<C: Clock> {
+    pub clock: C,
+    /// Last minute whose jobs were handed out
+    last_minute: DateTime<Utc>,
+}
+
+impl<C: Clock> Scheduler<C> {
+    pub fn new(clock: C) -> Self {
+        let last_minute = truncate_to_minute(clock.now());
+        Scheduler { clock, last_minute }
+    }
+
+    /// Minutes that started since the previous call, as local times in `tz`.
+    pub fn elapsed_minutes<Tz: TimeZone>(&mut self, tz: &Tz) -> Vec<DateTime<Tz>> {
+        let now = truncate_to_minute(self.clock.now());
+
+        if now <= self.last_minute {
+            // Clock went backwards: wait until it passes the last minute handled
+            // instead of running the same jobs twice.
+            return Vec::new();
+        }
+
+        let mut first = self.last_minute + Duration::minutes(1);
+        if (now - first).num_minutes() >= MAX_CATCH_UP_MINUTES {
+            first = now;
+        }
+        self.last_minute = now;
+
+        let mut minutes = Vec::new();
+        let mut minute = first;
+        while minute <= now {
+            minutes.push(minute.with_timezone(tz));
+            minute += Duration::minutes(1);
+        }
+        minutes
+    }
+
+    /// Entries due in the minutes that passed since the previous call.
+    pub fn due<'a, Tz: TimeZone>(&mut self, tables: &'a [CronTable], tz: &Tz) -> Vec<(&'a CronTable, &'a CronEntry)> {
+        let minutes = self.elapsed_minutes(tz);
+        let mut due = Vec::new();
+
+        for table in tables {
+            for entry in &table.entries {
+                if let When::At(schedule) = &entry.when {
+                    if minutes.iter().any(|m| schedule.matches(m)) {
+                        due.push((table, entry));
+                    }
+                }
+            }
+        }
+        due
+    }
+
+    /// How long to sleep until the next minute starts.
+    pub fn until_next_minute(&self) -> std::time::Duration {
+        let now = self.clock.now();
+        let next = truncate_to_minute(now) + Duration::minutes(1);
+        (next - now).to_std().unwrap_or(std::time::Duration::from_secs(1))
+    }
+}
+
+pub fn reboot_entries(tables: &[CronTable]) -> Vec<(&CronTable, &CronEntry)> {
+    tables
+        .iter()
+        .flat_map(|t| t.entries.iter().map(move |e| (t, e)))
+        .filter(|(_, e)| e.when == When::Reboot)
+        .collect()
+}
+
+fn truncate_to_minute(time: DateTime<Utc>) -> DateTime<Utc> {
+    time.with_second(0).and_then(|t| t.with_nanosecond(0)).unwrap_or(time)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    struct FixedClock(Cell<DateTime<Utc>>);
+
+    impl FixedClock {
+        fn set(&self, time: &str) {
+            self.0.set(time.parse().unwrap());
+        }
+    }
+
+    impl Clock for FixedClock {
+        fn now(&self) -> DateTime<Utc> {
+            self.0.get()
+        }
+    }
+
+    fn scheduler(time: &str) -> Scheduler<FixedClock> {
+        Scheduler::new(FixedClock(Cell::new(time.parse().unwrap())))
+    }
+
+    fn tables() -> Vec<CronTable> {
+        let content = "*/5 * * * * root five\n0 * * * * root hourly\n@reboot root boot\n";
+        let (table, errors) = CronTable::parse(content, "crontab", None);
+        assert!(errors.is_empty());
+        vec![table]
+    }
+
+    fn due_commands(scheduler: &mut Scheduler<FixedClock>, tables: &[CronTable]) -> Vec<String> {
+        scheduler.due(tables, &Utc).iter().map(|(_, e)| e.command.clone()).collect()
+    }
+
+    #[test]
+    fn due_once_per_minute() {
+        let tables = tables();
+        let mut scheduler = scheduler("2026-10-18T09:59:30Z");
+
+        assert!(due_commands(&mut scheduler, &tables).is_empty());
+        scheduler.clock.set("2026-10-18T10:00:01Z");
+        assert_eq!(due_commands(&mut scheduler, &tables), ["five", "hourly"]);
+        // Same minute again: nothing new
+        scheduler.clock.set("2026-10-18T10:00:40Z");
+        assert!(due_commands(&mut scheduler, &tables).is_empty());
+        scheduler.clock.set("2026-10-18T10:01:00Z");
+        assert!(due_commands(&mut scheduler, &tables).is_empty());
+    }
+
+    #[test]
+    fn missed_minutes_are_caught_up() {
+        let tables = tables();
+        let mut scheduler = scheduler("2026-10-18T09:58:00Z");
+
+        scheduler.clock.set("2026-10-18T10:07:00Z");
+        assert_eq!(scheduler.elapsed_minutes(&Utc).len(), 9);
+        scheduler.clock.set("2026-10-18T10:20:00Z");
+        // 10:10, 10:15 and 10:20, each entry only once
+        assert_eq!(due_commands(&mut scheduler, &tables), ["five"]);
+    }
+
+    #[test]
+    fn big_jumps_only_run_the_current_minute() {
+        let tables = tables();
+        let mut scheduler = scheduler("2026-10-18T08:00:00Z");
+
+        scheduler.clock.set("2026-10-18T10:01:00Z");
+        assert!(due_commands(&mut scheduler, &tables).is_empty());
+        scheduler.clock.set("2026-10-18T12:00:00Z");
+        assert_eq!(due_commands(&mut scheduler, &tables), ["five", "hourly"]);
+    }
+
+    #[test]
+    fn clock_going_back_does_not_repeat_jobs() {
+        let tables = tables();
+        let mut scheduler = scheduler("2026-10-18T10:00:00Z");
+
+        scheduler.clock.set("2026-10-18T09:30:00Z");
+        assert!(due_commands(&mut scheduler, &tables).is_empty());
+        scheduler.clock.set("2026-10-18T10:00:30Z");
+        assert!(due_commands(&mut scheduler, &tables).is_empty());
+        scheduler.clock.set("2026-10-18T10:05:00Z");
+        assert_eq!(due_commands(&mut scheduler, &tables), ["five"]);
+    }
+
+    #[test]
+    fn until_next_minute() {
+        let scheduler = scheduler("2026-10-18T10:00:45Z");
+        assert_eq!(scheduler.until_next_minute(), std::time::Duration::from_secs(15));
+    }
+
+    #[test]
+    fn reboot_entries_are_separate() {
+        let tables = tables();
+        let reboot: Vec<&str> = reboot_entries(&tables).iter().map(|(_, e)| e.command.as_str()).collect();
+        assert_eq!(reboot, ["boot"]);
+    }
+}
@@ -0,0 +1,179 @@
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+use crate::nss::getpwnam;
+use crate::cron::schedule::When;
+
+pub const DEFAULT_SYSTEM_CRONTAB: &str = "/etc/crontab";
+pub const DEFAULT_CRON_D_PATH: &str = "/etc/cron.d";
+pub const DEFAULT_USER_CRONTAB_PATH: &str = "/var/spool/cron/crontabs";
+
+#[derive(Debug, Clone)]
+pub struct CronEntry {
+    pub when: When,
+    pub user: String,
+    pub command: String,
+    /// `file:line`, for log messages
+    pub origin: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CronTable {
+    /// `NAME=value` lines, applied to every job of the table
+    pub env: Vec<(String, String)>,
+    pub entries: Vec<CronEntry>,
+}
+
+impl CronTable {
+    /// Parse a crontab. System tables (`owner == None`) carry a user column after
+    /// the schedule, per-user tables don't.
+    /// Bad lines are returned as errors next to whatever did parse.
+    pub fn parse(content: &str, source: &str, owner: Option<&str>) -> (CronTable, Vec<String>) {
+        let mut table = CronTable::default();
+        let mut errors = Vec::new();
+
+        for (lineno, raw) in content.lines().enumerate() {
+            let line = raw.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let origin = format!("{}:{}", source, lineno + 1);
+
+            if let Some((name, value)) = parse_env_line(line) {
+                table.env.push((name, value));
+                continue;
+            }
+
+            let (when, rest) = match When::parse_prefix(line) {
+                Ok(parsed) => parsed,
+                Err(e) => {
+                    errors.push(format!("{}: {}", origin, e));
+                    continue;
+                }
+            };
+
+            let (user, command) = match owner {
+                Some(owner) => (owner.to_string(), rest.trim()),
+                None => {
+                    let rest = rest.trim_start();
+                    match rest.split_once(char::is_whitespace) {
+                        Some((user, command)) => (user.to_string(), command.trim()),
+                        None => {
+                            errors.push(format!("{}: missing user or command", origin));
+                            continue;
+                        }
+                    }
+                }
+            };
+
+            if command.is_empty() {
+                errors.push(format!("{}: missing command", origin));
+                continue;
+            }
+
+            table.entries.push(CronEntry {
+                when,
+                user,
+                command: command.to_string(),
+                origin,
+            });
+        }
+
+        (table, errors)
+    }
+
+    pub fn from_file(path: &Path, owner: Option<&str>) -> Result<(CronTable, Vec<String>), String> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        Ok(Self::parse(&content, &path.display().to_string(), owner))
+    }
+}
+
+/// `NAME=value` or `NAME = "value"`; a schedule line never has `=` in its first word.
+fn parse_env_line(line: &str) -> Option<(String, String)> {
+    let (name, value) = line.split_once('=')?;
+    let name = name.trim();
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return None;
+    }
+    if name.chars().next().is_some_and(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    let value = value.trim();
+    let value = value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .or_else(|| value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')))
+        .unwrap_or(value);
+    Some((name.to_string(), value.to_string()))
+}
+
+/// Tables root has to own and only root may write, so nobody can schedule jobs as root.
+pub fn is_trusted_file(path: &Path) -> bool {
+    match fs::metadata(path) {
+        Ok(meta) => meta.is_file() && meta.uid() == 0 && meta.mode() & 0o022 == 0,
+        Err(_) => false,
+    }
+}
+
+pub fn user_crontab_path(username: &str) -> String {
+    format!("{}/{}", DEFAULT_USER_CRONTAB_PATH, username)
+}
+
+/// Read `/etc/crontab`, `/etc/cron.d/*` and every user crontab. Files that fail the
+/// ownership checks are skipped and reported in the error list.
+pub fn load_all_tables() -> (Vec<CronTable>, Vec<String>) {
+    let mut tables = Vec::new();
+    let mut errors = Vec::new();
+
+    let mut system_files = vec![Path::new(DEFAULT_SYSTEM_CRONTAB).to_path_buf()];
+    if let Ok(entries) = fs::read_dir(DEFAULT_CRON_D_PATH) {
+        let mut files: Vec<_> = entries
+            .flatten()
+            .map(|e| e.path())
+            // Skip editor backups and package manager leftovers like foo~ or foo.bak
+            .filter(|p| p.file_name().and_then(|n| n.to_str()).is_some_and(|n| !n.contains('.') && !n.ends_with('~')))
+            .collect();
+        files.sort();
+        system_files.extend(files);
+    }
+
+    for path in system_files {
+        if !path.exists() {
+            continue;
+        }
+        if !is_trusted_file(&path) {
+            errors.push(format!("{}: ignored, must be owned by root and not group/world writable", path.display()));
+            continue;
+        }
+        load_into(&path, None, &mut tables, &mut errors);
+    }
+
+    if let Ok(entries) = fs::read_dir(DEFAULT_USER_CRONTAB_PATH) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let username = match path.file_name().and_then(|n| n.to_str()) {
+                Some(n) if !n.starts_with('.') => n.to_string(),
+                _ => continue,
+            };
+            if getpwnam(&username).is_none() {
+                errors.push(format!("{}: ignored, no such user '{}'", path.display(), username));
+                continue;
+            }
+            load_into(&path, Some(&username), &mut tables, &mut errors);
+        }
+    }
+
+    (tables, errors)
+}
+
+fn load_into(path: &Path, owner: Option<&str>, tables: &mut Vec<CronTable>, errors: &mut Vec<String>) {
+    match CronTable::from_file(path, owner) {
+        Ok((table, errs)) => {
+            tables.push(table);
+            errors.extend(errs);
+        }
+        Err(e) => errors.push(e),
+    }
+}
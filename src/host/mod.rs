@@ -0,0 +1,172 @@
+//! Host name and operating system identity.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+
+pub const DEFAULT_HOSTNAME_PATH: &str = "/etc/hostname";
+pub const DEFAULT_HOSTS_PATH: &str = "/etc/hosts";
+pub const DEFAULT_OS_RELEASE_PATH: &str = "/etc/os-release";
+/// Used until a host name is set
+pub const DEFAULT_HOSTNAME: &str = "vantara";
+/// Where /etc/hosts maps the host's own name, as on Debian
+const HOSTS_SELF_ADDRESS: &str = "127.0.1.1";
+
+pub const OS_NAME: &str = "VanOS";
+pub const OS_ID: &str = "vanos";
+pub const OS_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Longest name sethostname(2) takes
+const HOST_NAME_MAX: usize = 64;
+
+/// Labels of letters, digits and `-`, separated by dots. At most 64 characters,
+/// the kernel's limit, rather than the 253 DNS allows.
+pub fn is_valid_hostname(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= HOST_NAME_MAX
+        && name.split('.').all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
+}
+
+/// Name of the running kernel, what `uname -n` shows.
+pub fn kernel_hostname() -> String {
+    fs::read_to_string("/proc/sys/kernel/hostname")
+        .map(|h| h.trim().to_string())
+        .unwrap_or_else(|_| "localhost".to_string())
+}
+
+/// The name kept in /etc/hostname, `None` when it is missing or invalid.
+pub fn static_hostname() -> Option<String> {
+    let content = fs::read_to_string(DEFAULT_HOSTNAME_PATH).ok()?;
+    let name = content.lines().map(str::trim).find(|l| !l.is_empty() && !l.starts_with('#'))?;
+    is_valid_hostname(name).then(|| name.to_string())
+}
+
+/// Host name up to the first dot, for prompts.
+pub fn short_hostname() -> String {
+    let name = kernel_hostname();
+    name.split('.').next().unwrap_or(&name).to_string()
+}
+
+pub fn set_kernel_hostname(name: &str) -> io::Result<()> {
+    if unsafe { libc::sethostname(name.as_ptr() as *const libc::c_char, name.len()) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// /etc/hosts with the `127.0.1.1` line pointing at `name`, the rest kept.
+fn hosts_with(content: &str, name: &str) -> String {
+    let short = name.split('.').next().unwrap_or(name);
+    let own = if short == name { format!("{}\t{}", HOSTS_SELF_ADDRESS, name) } else { format!("{}\t{} {}", HOSTS_SELF_ADDRESS, name, short) };
+
+    let mut lines: Vec<String> = Vec::new();
+    let mut replaced = false;
+    for line in content.lines() {
+        if line.split_whitespace().next() == Some(HOSTS_SELF_ADDRESS) {
+            if !replaced {
+                lines.push(own.clone());
+                replaced = true;
+            }
+        } else {
+            lines.push(line.to_string());
+        }
+    }
+    if lines.is_empty() {
+        lines.push("127.0.0.1\tlocalhost".into());
+        lines.push("::1\t\tlocalhost ip6-localhost ip6-loopback".into());
+    }
+    if !replaced {
+        // Right after the localhost lines, where resolvers look first
+        let at = lines.iter().position(|l| l.split_whitespace().next() == Some("127.0.0.1")).map_or(0, |i| i + 1);
+        lines.insert(at, own);
+    }
+    lines.join("\n") + "\n"
+}
+
+/// Validate `name`, keep it in /etc/hostname and /etc/hosts and give it to
+/// the kernel.
+pub fn set_hostname(name: &str) -> Result<(), String> {
+    if !is_valid_hostname(name) {
+        return Err(format!("Invalid hostname: {}", name));
+    }
+    fs::write(DEFAULT_HOSTNAME_PATH, format!("{}\n", name))
+        .map_err(|e| format!("Failed to write to '{}': {}", DEFAULT_HOSTNAME_PATH, e))?;
+
+    let hosts = fs::read_to_string(DEFAULT_HOSTS_PATH).unwrap_or_default();
+    fs::write(DEFAULT_HOSTS_PATH, hosts_with(&hosts, name))
+        .map_err(|e| format!("Failed to write to '{}': {}", DEFAULT_HOSTS_PATH, e))?;
+
+    set_kernel_hostname(name).map_err(|e| format!("Failed to set hostname: {}", e))
+}
+
+/// Called by init: the kernel gets the name from /etc/hostname, or the default
+/// when none is set yet.
+pub fn apply_boot_hostname() -> io::Result<String> {
+    let name = static_hostname().unwrap_or_else(|| DEFAULT_HOSTNAME.to_string());
+    set_kernel_hostname(&name)?;
+    Ok(name)
+}
+
+/// /etc/os-release for this build.
+pub fn os_release_text() -> String {
+    format!(
+        "NAME=\"{name}\"\nID={id}\nVERSION=\"{version}\"\nVERSION_ID={version}\nPRETTY_NAME=\"{name} {version}\"\n",
+        name = OS_NAME,
+        id = OS_ID,
+        version = OS_VERSION
+    )
+}
+
+/// Called by init, so the file always matches the installed version.
+pub fn write_os_release() -> io::Result<()> {
+    let text = os_release_text();
+    if fs::read_to_string(DEFAULT_OS_RELEASE_PATH).is_ok_and(|current| current == text) {
+        return Ok(());
+    }
+    fs::write(DEFAULT_OS_RELEASE_PATH, text)
+}
+
+/// Fields of /etc/os-release, quotes removed.
+pub fn read_os_release() -> HashMap<String, String> {
+    fs::read_to_string(DEFAULT_OS_RELEASE_PATH)
+        .unwrap_or_else(|_| os_release_text())
+        .lines()
+        .filter(|l| !l.trim_start().starts_with('#'))
+        .filter_map(|l| l.split_once('='))
+        .map(|(key, value)| (key.trim().to_string(), value.trim().trim_matches(|c| c == '"' || c == '\'').to_string()))
+        .collect()
+}
+
+/// `PRETTY_NAME` of os-release, e.g. "VanOS 0.1.0".
+pub fn os_pretty_name() -> String {
+    let release = read_os_release();
+    release
+        .get("PRETTY_NAME")
+        .or_else(|| release.get("NAME"))
+        .cloned()
+        .unwrap_or_else(|| OS_NAME.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hostname_validity() {
+        assert!(is_valid_hostname("vantara"));
+        assert!(is_valid_hostname("web-1.example.com"));
+        assert!(is_valid_hostname(&format!("{}.{}", "a".repeat(30), "b".repeat(33))));
+        // Fine for DNS, too long for the kernel
+        assert!(!is_valid_hostname(&format!("{}.{}", "a".repeat(40), "b".repeat(40))));
+        assert!(!is_valid_hostname(""));
+        assert!(!is_valid_hostname("-web"));
+        assert!(!is_valid_hostname("web..example"));
+        assert!(!is_valid_hostname("web_1"));
+    }
+}
@@ -0,0 +1,268 @@
+//! Translated user-facing messages.
+//!
+//! Each language has a catalog, `<language>.cat` next to this file, built
+//! into the binaries. One message per line:
+//!
+//! ```text
+//! # comment
+//! login.incorrect = Login incorrect
+//! login.tries = Maximum number of tries exceeded ({n})
+//! uptime.users = {n} user|{n} users
+//! ```
+//!
+//! `{name}` is replaced by the argument of that name. Messages that depend on
+//! a count list their plural forms separated by `|`, the language picks one.
+//! A key missing from a catalog falls back to English, then to the key itself.
+
+use std::collections::HashMap;
+use std::env;
+use std::fmt::Display;
+use std::fs;
+use std::sync::{Mutex, OnceLock};
+
+/// `LANG=...` written by firstboot, used when the environment has no locale
+pub const DEFAULT_LOCALE_CONF_PATH: &str = "/etc/locale.conf";
+pub const DEFAULT_LANGUAGE: &str = "en";
+
+pub struct Catalog {
+    /// ISO 639-1 code, as in `ms_MY.UTF-8`
+    pub language: &'static str,
+    /// Name of the language in that language, for menus
+    pub name: &'static str,
+    /// Full locale written to locale.conf when this language is chosen
+    pub locale: &'static str,
+    /// Which plural form to use for a count
+    plural: fn(u64) -> usize,
+    source: &'static str,
+    messages: OnceLock<HashMap<&'static str, &'static str>>,
+}
+
+impl Catalog {
+    fn messages(&self) -> &HashMap<&'static str, &'static str> {
+        self.messages.get_or_init(|| {
+            self.source
+                .lines()
+                .map(str::trim)
+                .filter(|l| !l.is_empty() && !l.starts_with('#'))
+                .filter_map(|l| l.split_once('='))
+                .map(|(key, text)| (key.trim(), text.trim()))
+                .collect()
+        })
+    }
+
+    pub fn get(&self, key: &str) -> Option<&'static str> {
+        self.messages().get(key).copied()
+    }
+}
+
+static EN: Catalog = Catalog {
+    language: "en",
+    name: "English",
+    locale: "en_US.UTF-8",
+    plural: |n| if n == 1 { 0 } else { 1 },
+    source: include_str!("en.cat"),
+    messages: OnceLock::new(),
+};
+
+static MS: Catalog = Catalog {
+    language: "ms",
+    name: "Bahasa Melayu",
+    locale: "ms_MY.UTF-8",
+    // Malay nouns don't change with the count
+    plural: |_| 0,
+    source: include_str!("ms.cat"),
+    messages: OnceLock::new(),
+};
+
+pub static CATALOGS: [&Catalog; 2] = [&EN, &MS];
+
+/// Set by `set_language`, otherwise worked out from the environment once
+static SELECTED: Mutex<Option<&'static Catalog>> = Mutex::new(None);
+
+pub fn catalog(language: &str) -> Option<&'static Catalog> {
+    CATALOGS.iter().copied().find(|c| c.language == language)
+}
+
+/// `ms_MY.UTF-8` -> `ms`. `C` and `POSIX` mean English.
+pub fn language_of(locale: &str) -> &str {
+    let language = locale.split(['_', '.', '@']).next().unwrap_or(locale);
+    match language {
+        "C" | "POSIX" | "" => DEFAULT_LANGUAGE,
+        language => language,
+    }
+}
+
+/// `LANG` from /etc/locale.conf.
+pub fn system_locale() -> Option<String> {
+    let content = fs::read_to_string(DEFAULT_LOCALE_CONF_PATH).ok()?;
+    content
+        .lines()
+        .filter_map(|l| l.trim().strip_prefix("LANG="))
+        .map(|v| v.trim_matches('"').to_string())
+        .find(|v| !v.is_empty())
+}
+
+/// The first of LC_ALL, LC_MESSAGES and LANG that is set, else /etc/locale.conf.
+/// English when we have no catalog for that language.
+fn detect() -> &'static Catalog {
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|var| env::var(var).ok().filter(|v| !v.is_empty()))
+        .chain(system_locale())
+        .next()
+        .and_then(|locale| catalog(language_of(&locale)))
+        .unwrap_or(&EN)
+}
+
+pub fn current() -> &'static Catalog {
+    let mut selected = SELECTED.lock().unwrap_or_else(|e| e.into_inner());
+    selected.get_or_insert_with(detect)
+}
+
+/// Use `language` from now on in this process, for a choice made at runtime.
+pub fn set_language(language: &str) -> Result<(), String> {
+    let catalog = catalog(language).ok_or_else(|| format!("no messages for language '{}'", language))?;
+    *SELECTED.lock().unwrap_or_else(|e| e.into_inner()) = Some(catalog);
+    Ok(())
+}
+
+/// Write /etc/locale.conf so logins and services get `language` too.
+pub fn save_language(language: &str) -> Result<(), String> {
+    let catalog = catalog(language).ok_or_else(|| format!("no messages for language '{}'", language))?;
+    fs::write(DEFAULT_LOCALE_CONF_PATH, format!("LANG={}\n", catalog.locale))
+        .map_err(|e| format!("Failed to write to '{}': {}", DEFAULT_LOCALE_CONF_PATH, e))
+}
+
+fn lookup(key: &str) -> (&'static Catalog, Option<&'static str>) {
+    let catalog = current();
+    match catalog.get(key) {
+        Some(text) => (catalog, Some(text)),
+        None => (&EN, EN.get(key)),
+    }
+}
+
+/// Replace each `{name}` in `template` by its argument, `{{` and `}}` are
+/// literal braces. Unknown names are left as they are.
+pub fn format(template: &str, args: &[(&str, &dyn Display)]) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(pos) = rest.find(['{', '}']) {
+        out.push_str(&rest[..pos]);
+        let tail = &rest[pos..];
+        if tail.starts_with("{{") || tail.starts_with("}}") {
+            out.push_str(&tail[..1]);
+            rest = &tail[2..];
+            continue;
+        }
+        let end = match tail.find('}').filter(|_| tail.starts_with('{')) {
+            Some(end) => end,
+            None => {
+                out.push_str(&tail[..1]);
+                rest = &tail[1..];
+                continue;
+            }
+        };
+        let name = &tail[1..end];
+        match args.iter().find(|(n, _)| *n == name) {
+            Some((_, value)) => out.push_str(&value.to_string()),
+            None => out.push_str(&tail[..=end]),
+        }
+        rest = &tail[end + 1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// The message `key` in the current language.
+pub fn tr(key: &str, args: &[(&str, &dyn Display)]) -> String {
+    match lookup(key) {
+        (_, Some(text)) => format(text, args),
+        (_, None) => key.to_string(),
+    }
+}
+
+/// Like `tr`, picking the plural form for `n`, which is also argument `{n}`.
+pub fn tr_count(key: &str, n: u64, args: &[(&str, &dyn Display)]) -> String {
+    let (catalog, text) = match lookup(key) {
+        (catalog, Some(text)) => (catalog, text),
+        (_, None) => return key.to_string(),
+    };
+    let forms: Vec<&str> = text.split('|').map(str::trim).collect();
+    let form = forms[(catalog.plural)(n).min(forms.len() - 1)];
+
+    let mut all: Vec<(&str, &dyn Display)> = vec![("n", &n)];
+    all.extend_from_slice(args);
+    format(form, &all)
+}
+
+/// `tr!("key")` or `tr!("key", name = value, ...)`: the message in the current
+/// language with its arguments filled in.
+#[macro_export]
+macro_rules! tr {
+    ($key:expr $(, $name:ident = $value:expr)* $(,)?) => {
+        $crate::i18n::tr($key, &[$((stringify!($name), &$value as &dyn std::fmt::Display)),*])
+    };
+}
+
+/// `tr_count!("key", n)` or `tr_count!("key", n, name = value, ...)`, for
+/// messages with plural forms.
+#[macro_export]
+macro_rules! tr_count {
+    ($key:expr, $n:expr $(, $name:ident = $value:expr)* $(,)?) => {
+        $crate::i18n::tr_count($key, $n as u64, &[$((stringify!($name), &$value as &dyn std::fmt::Display)),*])
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_substitutes_arguments() {
+        let n = 3;
+        assert_eq!(format("{n} of {total}", &[("n", &n), ("total", &"10")]), "3 of 10");
+        assert_eq!(format("{a}{a}", &[("a", &'x')]), "xx");
+        assert_eq!(format("{{literal}} }} {{", &[]), "{literal} } {");
+        assert_eq!(format("{unknown} stays", &[("n", &n)]), "{unknown} stays");
+        assert_eq!(format("open { and close }", &[]), "open { and close }");
+        assert_eq!(format("no arguments", &[("n", &n)]), "no arguments");
+    }
+
+    #[test]
+    fn language_of_locales() {
+        assert_eq!(language_of("ms_MY.UTF-8"), "ms");
+        assert_eq!(language_of("en_US"), "en");
+        assert_eq!(language_of("de@euro"), "de");
+        assert_eq!(language_of("C"), DEFAULT_LANGUAGE);
+        assert_eq!(language_of("POSIX"), DEFAULT_LANGUAGE);
+    }
+
+    #[test]
+    fn catalogs_have_the_same_keys() {
+        for catalog in CATALOGS {
+            for key in EN.messages().keys() {
+                assert!(catalog.get(key).is_some(), "{} has no '{}'", catalog.language, key);
+            }
+            assert_eq!(catalog.messages().len(), EN.messages().len(), "{} has keys English lacks", catalog.language);
+        }
+    }
+
+    // One test, the selected language is shared by the whole process
+    #[test]
+    fn plural_forms_follow_the_language() {
+        set_language("en").unwrap();
+        assert_eq!(tr_count!("uptime.users", 1), "1 user");
+        assert_eq!(tr_count!("uptime.users", 0), "0 users");
+        assert_eq!(tr_count!("uptime.days", 2), "2 days");
+        assert_eq!(tr!("no.such.key"), "no.such.key");
+        assert_eq!(tr_count!("no.such.key", 2), "no.such.key");
+
+        set_language("ms").unwrap();
+        assert_eq!(tr_count!("uptime.users", 1), "1 pengguna");
+        assert_eq!(tr_count!("uptime.users", 5), "5 pengguna");
+
+        assert!(set_language("xx").is_err());
+        assert_eq!(current().language, "ms");
+        set_language("en").unwrap();
+    }
+}
@@ -1,39 +1,100 @@
 use std::fs::{create_dir_all, File};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::ffi::CString;
 use std::ptr;
 use std::process::Command;
+use std::time::{Duration, Instant};
 use libc;
-use vantara::{safe_println, safe_eprintln, show_boot_banner};
+use vantara::{safe_println, safe_eprintln, show_boot_banner, tr};
 use vantara::systemd::manager::ServiceManager;
 use vantara::common::clear_screen;
+use vantara::auth::modules::session_log::log_boot;
+use vantara::host::{apply_boot_hostname, write_os_release};
+
+/// A login that ends sooner than this didn't serve anyone
+const MIN_LOGIN_RUN: Duration = Duration::from_secs(2);
+const MAX_LOGIN_RESTART_DELAY: Duration = Duration::from_secs(60);
 
 fn main() {
+    // Started again by a running init through `reexec`: pick up where it left off
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() == 3 && args[1] == "--deserialize" {
+        resume_after_reexec(&args[2]);
+        return;
+    }
+
     clear_screen();
     safe_println(format_args!("[BOOT] INIT Start"));
 
     create_directories_and_dev_nodes();
     mount_all_filesystems();
+    if let Err(e) = log_boot() {
+        safe_eprintln(format_args!("[ERR] Failed to record boot: {}", e));
+    }
+    if let Err(e) = apply_boot_hostname() {
+        safe_eprintln(format_args!("[ERR] Failed to set hostname: {}", e));
+    }
+    if let Err(e) = write_os_release() {
+        safe_eprintln(format_args!("[ERR] Failed to write /etc/os-release: {}", e));
+    }
     clear_screen();
     vantara::setup::setup_firstboot();
-    load_enable_services();
+    let manager = load_enable_services();
+
+    // Reap in the background so service exits are recorded while the console is in use
+    let reaper_manager = Arc::clone(&manager);
+    let reaper = std::thread::spawn(move || reap_loop(&reaper_manager));
+
     clear_screen();
     show_boot_banner();
 
-    if let Err(_e) = spawn_gui() {
-        spawn_cli();
+    if let Err(_e) = spawn_gui(&manager) {
+        spawn_cli(&manager);
     }
 
+    let _ = reaper.join();
+}
+
+fn reap_loop(manager: &Arc<Mutex<ServiceManager>>) -> ! {
     loop {
-        ServiceManager::reap_children(); // collect all zombie process
+        ServiceManager::reap_children(manager); // collect all zombie process
         std::thread::sleep(std::time::Duration::from_millis(500));
     }
 }
 
+fn resume_after_reexec(state_path: &str) {
+    safe_println(format_args!("[INIT] Resuming from {}", state_path));
+
+    // Filesystems and firstboot are already done by the previous init, only
+    // supervision and the console need to be picked up again.
+    let manager = match ServiceManager::restore(state_path) {
+        Ok(manager) => manager,
+        Err(e) => {
+            // Don't start enabled services again, they are most likely still running
+            safe_eprintln(format_args!("[ERR] Failed to restore service state: {}", e));
+            let manager = ServiceManager::new();
+            ServiceManager::load_services(Arc::clone(&manager));
+            manager
+        }
+    };
+
+    let reaper_manager = Arc::clone(&manager);
+    let reaper = std::thread::spawn(move || reap_loop(&reaper_manager));
+
+    // The login of the previous init is still on the console, start a new one after it
+    let console_pid = manager.lock().unwrap().console_pid;
+    if let Some(pid) = console_pid {
+        wait_console(&manager, pid);
+    }
+    spawn_cli(&manager);
+
+    let _ = reaper.join();
+}
+
 fn create_directories_and_dev_nodes() {
     let dirs = [
         "/dev", "/dev/pts", "/proc", "/sys", "/mnt",
-        "/run", "/usr", "/etc/service/available", "/etc/service/enabled"
+        "/run", "/usr", "/var/log", "/etc/service/available", "/etc/service/enabled"
     ];
 
     for dir in dirs {
@@ -80,7 +141,7 @@ fn mount_fs(fstype: &str, target: &str, source_opt: Option<&str>) {
     }
 }
 
-fn load_enable_services() {
+fn load_enable_services() -> Arc<Mutex<ServiceManager>> {
     let manager = ServiceManager::new();
     ServiceManager::load_services(Arc::clone(&manager));
 
@@ -88,35 +149,88 @@ fn load_enable_services() {
         let mut m = manager.lock().unwrap();
         m.start_enabled_services();
     }
+
+    manager
+}
+
+/// Wait for the console child, then hand reaping back to the reaper. `None` when
+/// it was not our child.
+fn wait_console(manager: &Arc<Mutex<ServiceManager>>, pid: libc::pid_t) -> Option<libc::c_int> {
+    let mut status = 0;
+    let result = loop {
+        let result = unsafe { libc::waitpid(pid, &mut status, 0) };
+        if result >= 0 || std::io::Error::last_os_error().raw_os_error() != Some(libc::EINTR) {
+            break result;
+        }
+    };
+    manager.lock().unwrap().console_pid = None;
+    (result == pid).then_some(status)
 }
 
-fn spawn_cli() {
+/// Like a getty: start login again every time it exits, login handles a single
+/// login and exits after the session, a timeout or too many failed attempts.
+/// When login keeps ending right away the restarts slow down, so a broken login
+/// doesn't spin on the console.
+fn spawn_cli(manager: &Arc<Mutex<ServiceManager>>) {
     let path = CString::new("/bin/login").unwrap();
     let arg0 = CString::new("login").unwrap();
     let args = vec![arg0.as_ptr(), ptr::null()];
     let envp = vec![ptr::null()];
+    let mut delay = Duration::ZERO;
+
+    loop {
+        let started = Instant::now();
+        // The pid is recorded before the reaper can look, so it never takes login's exit
+        let pid = {
+            let mut m = manager.lock().unwrap();
+            let pid = unsafe { libc::fork() };
+            if pid == 0 {
+                unsafe {
+                    libc::setsid(); // Buat session baru (jadi pemilik terminal)
+                    libc::execve(path.as_ptr(), args.as_ptr(), envp.as_ptr());
+                    libc::_exit(127); // hanya dipanggil kalau execve gagal
+                }
+            }
+            if pid > 0 {
+                m.console_pid = Some(pid);
+            }
+            pid
+        };
+        if pid < 0 {
+            safe_eprintln(format_args!("[ERR] {}", tr!("init.login_fork")));
+            return;
+        }
+
+        let status = wait_console(manager, pid); // Tunggu login tamat
+        if status.is_some_and(|s| libc::WIFEXITED(s) && libc::WEXITSTATUS(s) == 127) {
+            safe_eprintln(format_args!("[ERR] {}", tr!("init.login_exec")));
+            return;
+        }
 
-    unsafe {
-        let pid = libc::fork();
-        if pid == 0 {
-            libc::setsid(); // Buat session baru (jadi pemilik terminal)
-            libc::execve(path.as_ptr(), args.as_ptr(), envp.as_ptr());
-            libc::_exit(1); // hanya dipanggil kalau execve gagal
-        } else if pid > 0 {
-            libc::waitpid(pid, ptr::null_mut(), 0); // Tunggu login tamat
+        if started.elapsed() < MIN_LOGIN_RUN {
+            delay = (delay * 2).clamp(Duration::from_secs(1), MAX_LOGIN_RESTART_DELAY);
+            std::thread::sleep(delay);
         } else {
-            safe_eprintln(format_args!("[ERR] Failed to fork login process."));
+            delay = Duration::ZERO;
         }
     }
 }
 
-fn spawn_gui() -> Result<(), String> {
-    match Command::new("/usr/bin/vantara_de")
-        .spawn()
-        .and_then(|mut child| child.wait())
-    {
+fn spawn_gui(manager: &Arc<Mutex<ServiceManager>>) -> Result<(), String> {
+    let child = {
+        let mut m = manager.lock().unwrap();
+        let child = Command::new("/usr/bin/vantara_de").spawn();
+        if let Ok(child) = &child {
+            m.console_pid = Some(child.id() as libc::pid_t);
+        }
+        child
+    };
+    let result = child.and_then(|mut child| child.wait());
+    manager.lock().unwrap().console_pid = None;
+
+    match result {
         Ok(status) if status.success() => Ok(()),
-        Ok(status) => Err(format!("Keluar dengan kod: {}", status.code().unwrap_or(-1))),
-        Err(e) => Err(format!("Gagal spawn DE: {e}")),
+        Ok(status) => Err(tr!("init.gui_exit", code = status.code().unwrap_or(-1))),
+        Err(e) => Err(tr!("init.gui_failed", error = e)),
     }
 }
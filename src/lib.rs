@@ -1,5 +1,10 @@
+pub mod audit;
 pub mod auth;
 pub mod common;
+pub mod cron;
+pub mod host;
+pub mod i18n;
+pub mod nss;
 pub mod setup;
 pub mod systemd;
 
@@ -0,0 +1,56 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use crate::auth::modules::group::{GroupEntry, DEFAULT_GROUP_FILE};
+use crate::auth::modules::passwd::{PasswdEntry, DEFAULT_PASSWD_FILE};
+
+/// Identifies one version of a file, a change means it has to be read again.
+pub type Stamp = Option<(SystemTime, u64)>;
+
+/// Reads /etc/passwd and /etc/group below `root`.
+#[derive(Debug, Clone)]
+pub struct FilesBackend {
+    root: PathBuf,
+}
+
+impl FilesBackend {
+    pub fn new(root: &Path) -> Self {
+        FilesBackend { root: root.to_path_buf() }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn path(&self, file: &str) -> PathBuf {
+        self.root.join(file.trim_start_matches('/'))
+    }
+
+    pub fn passwd_stamp(&self) -> Stamp {
+        stamp(&self.path(DEFAULT_PASSWD_FILE))
+    }
+
+    pub fn group_stamp(&self) -> Stamp {
+        stamp(&self.path(DEFAULT_GROUP_FILE))
+    }
+
+    /// Lines that don't parse are skipped, a missing file has no entries.
+    pub fn passwd(&self) -> Vec<PasswdEntry> {
+        read_entries(&self.path(DEFAULT_PASSWD_FILE), PasswdEntry::parse)
+    }
+
+    pub fn group(&self) -> Vec<GroupEntry> {
+        read_entries(&self.path(DEFAULT_GROUP_FILE), GroupEntry::parse)
+    }
+}
+
+fn stamp(path: &Path) -> Stamp {
+    let meta = fs::metadata(path).ok()?;
+    Some((meta.modified().ok()?, meta.len()))
+}
+
+fn read_entries<T>(path: &Path, parse: fn(&str) -> Option<T>) -> Vec<T> {
+    fs::read_to_string(path)
+        .map(|content| content.lines().filter_map(parse).collect())
+        .unwrap_or_default()
+}
@@ -0,0 +1,191 @@
+//! User and group lookups, named after their libc counterparts.
+//!
+//! Entries come from the files backend and are cached for the life of the
+//! process. A cached file is read again once its mtime or size changes, so
+//! long running programs like crond see account changes.
+
+pub mod files;
+
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use crate::nss::files::{FilesBackend, Stamp};
+
+pub use crate::auth::modules::group::GroupEntry;
+pub use crate::auth::modules::passwd::PasswdEntry;
+
+struct Cached<T> {
+    stamp: Stamp,
+    entries: Arc<Vec<T>>,
+}
+
+struct Database {
+    backend: FilesBackend,
+    passwd: Option<Cached<PasswdEntry>>,
+    group: Option<Cached<GroupEntry>>,
+}
+
+static DATABASE: Mutex<Option<Database>> = Mutex::new(None);
+
+fn with_database<R>(f: impl FnOnce(&mut Database) -> R) -> R {
+    let mut guard = DATABASE.lock().unwrap_or_else(|e| e.into_inner());
+    let db = guard.get_or_insert_with(|| Database {
+        backend: FilesBackend::new(Path::new("/")),
+        passwd: None,
+        group: None,
+    });
+    f(db)
+}
+
+/// Look everything up below `root` instead of `/`, e.g. an image being set up
+/// or a test fixture. Drops whatever was cached.
+pub fn set_root(root: &Path) {
+    with_database(|db| {
+        db.backend = FilesBackend::new(root);
+        db.passwd = None;
+        db.group = None;
+    });
+}
+
+/// Forget cached entries, for callers that just wrote the files themselves.
+pub fn invalidate() {
+    with_database(|db| {
+        db.passwd = None;
+        db.group = None;
+    });
+}
+
+/// Every passwd entry, in file order (getpwent).
+pub fn passwd_entries() -> Arc<Vec<PasswdEntry>> {
+    with_database(|db| {
+        let stamp = db.backend.passwd_stamp();
+        match &db.passwd {
+            Some(cached) if cached.stamp.is_some() && cached.stamp == stamp => Arc::clone(&cached.entries),
+            _ => {
+                let entries = Arc::new(db.backend.passwd());
+                db.passwd = Some(Cached { stamp, entries: Arc::clone(&entries) });
+                entries
+            }
+        }
+    })
+}
+
+/// Every group entry, in file order (getgrent).
+pub fn group_entries() -> Arc<Vec<GroupEntry>> {
+    with_database(|db| {
+        let stamp = db.backend.group_stamp();
+        match &db.group {
+            Some(cached) if cached.stamp.is_some() && cached.stamp == stamp => Arc::clone(&cached.entries),
+            _ => {
+                let entries = Arc::new(db.backend.group());
+                db.group = Some(Cached { stamp, entries: Arc::clone(&entries) });
+                entries
+            }
+        }
+    })
+}
+
+pub fn getpwnam(name: &str) -> Option<PasswdEntry> {
+    passwd_entries().iter().find(|u| u.username == name).cloned()
+}
+
+pub fn getpwuid(uid: u32) -> Option<PasswdEntry> {
+    passwd_entries().iter().find(|u| u.uid == uid).cloned()
+}
+
+pub fn getgrnam(name: &str) -> Option<GroupEntry> {
+    group_entries().iter().find(|g| g.groupname == name).cloned()
+}
+
+pub fn getgrgid(gid: u32) -> Option<GroupEntry> {
+    group_entries().iter().find(|g| g.gid == gid).cloned()
+}
+
+/// The primary group followed by every group listing `username` as a member,
+/// like initgroups(3) would set them up.
+pub fn getgrouplist(username: &str, primary_gid: u32) -> Vec<u32> {
+    let mut gids = vec![primary_gid];
+    for group in group_entries().iter() {
+        if group.members.iter().any(|m| m == username) && !gids.contains(&group.gid) {
+            gids.push(group.gid);
+        }
+    }
+    gids
+}
+
+/// Name of `uid`, or the number when it has no passwd entry.
+pub fn user_name(uid: u32) -> String {
+    getpwuid(uid).map(|u| u.username).unwrap_or_else(|| uid.to_string())
+}
+
+/// Name of `gid`, or the number when it has no group entry.
+pub fn group_name(gid: u32) -> String {
+    getgrgid(gid).map(|g| g.groupname).unwrap_or_else(|| gid.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    const PASSWD: &str = "\
+root:x:0:0:root:/root:/bin/shell
+
+# a comment, then a malformed line and one with a bad uid
+broken:x:1000
+bad:x:abc:100::/home/bad:/bin/shell
+alice:x:1000:100:Alice:/home/alice:/bin/shell
+bob:x:1001:1001::/home/bob:/bin/shell
+";
+
+    const GROUP: &str = "\
+root:x:0:
+users:x:100:
+wheel:x:10:alice,,bob
+broken:x:11
+audio:x:12:bob
+bob:x:1001:
+";
+
+    // The database is shared by the whole process, so everything that moves its
+    // root is in this one test
+    #[test]
+    fn lookups_below_a_root() {
+        let root = std::env::temp_dir().join(format!("vantara-nss-{}", std::process::id()));
+        fs::create_dir_all(root.join("etc")).unwrap();
+        fs::write(root.join("etc/passwd"), PASSWD).unwrap();
+        fs::write(root.join("etc/group"), GROUP).unwrap();
+        set_root(&root);
+
+        let names: Vec<String> = passwd_entries().iter().map(|u| u.username.clone()).collect();
+        assert_eq!(names, ["root", "alice", "bob"]);
+        assert_eq!(getpwnam("alice").map(|u| (u.uid, u.gid, u.home)), Some((1000, 100, "/home/alice".into())));
+        assert_eq!(getpwuid(1001).map(|u| u.username), Some("bob".into()));
+        assert!(getpwnam("broken").is_none());
+        assert!(getpwnam("bad").is_none());
+        assert!(getpwuid(4242).is_none());
+
+        assert_eq!(getgrnam("wheel").map(|g| (g.gid, g.members)), Some((10, vec!["alice".into(), "bob".into()])));
+        assert_eq!(getgrgid(12).map(|g| g.groupname), Some("audio".into()));
+        assert!(getgrnam("broken").is_none());
+        assert!(getgrnam("root").is_some_and(|g| g.members.is_empty()));
+
+        assert_eq!(getgrouplist("bob", 1001), [1001, 10, 12]);
+        assert_eq!(getgrouplist("alice", 100), [100, 10]);
+        // The primary group isn't repeated when it also lists the user
+        assert_eq!(getgrouplist("alice", 10), [10]);
+        assert_eq!(getgrouplist("nobody", 65534), [65534]);
+
+        assert_eq!(user_name(1000), "alice");
+        assert_eq!(user_name(4242), "4242");
+        assert_eq!(group_name(4242), "4242");
+
+        // A changed file is read again
+        fs::write(root.join("etc/passwd"), format!("{}carol:x:1002:100::/home/carol:/bin/shell\n", PASSWD)).unwrap();
+        assert_eq!(getpwnam("carol").map(|u| u.uid), Some(1002));
+        fs::remove_file(root.join("etc/group")).unwrap();
+        assert!(getgrnam("wheel").is_none());
+
+        set_root(Path::new("/"));
+        let _ = fs::remove_dir_all(&root);
+    }
+}
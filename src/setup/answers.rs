@@ -0,0 +1,334 @@
+use std::fs;
+use std::net::IpAddr;
+use std::path::Path;
+use crate::auth::modules::account_db::{is_valid_field, is_valid_name, AccountDb};
+use crate::host::is_valid_hostname;
+use crate::i18n::catalog;
+use crate::setup::modules::keymap::is_valid_keymap;
+use crate::setup::modules::network::{is_valid_interface, Addressing, NetworkConfig};
+use crate::setup::modules::timezone::zoneinfo_path;
+use crate::systemd::unit_files::UnitDirs;
+
+pub const DEFAULT_ANSWER_FILE_PATH: &str = "/etc/firstboot.conf";
+/// `firstboot=PATH` on the kernel command line points to another answer file
+const CMDLINE_KEY: &str = "firstboot=";
+
+#[derive(Debug, Clone, Default)]
+pub struct AnswerUser {
+    pub name: String,
+    /// Shadow hash, `!` for a locked account
+    pub password_hash: String,
+    pub groups: Vec<String>,
+    pub comment: String,
+}
+
+/// Answers for firstboot, one `key = value` per line:
+///
+/// ```text
+/// language = ms                           # en or ms
+/// timezone = Asia/Kuala_Lumpur
+/// hostname = vantara
+/// keymap = us
+/// root_user = root
+/// root_password = $6$...
+/// user = alice:$6$...:wheel,audio:Alice   # name:hash[:groups[:comment]], repeatable
+/// service = crond                         # repeatable
+/// interface = eth0
+/// address = 10.0.2.15/24                  # or dhcp
+/// gateway = 10.0.2.2
+/// dns = 10.0.2.3                          # repeatable
+/// ```
+///
+/// Passwords are only accepted as hashes. Anything left out is asked for on
+/// the console.
+#[derive(Debug, Default)]
+pub struct AnswerFile {
+    pub language: Option<String>,
+    pub timezone: Option<String>,
+    pub hostname: Option<String>,
+    pub keymap: Option<String>,
+    pub root_user: Option<String>,
+    pub root_password: Option<String>,
+    pub users: Vec<AnswerUser>,
+    pub services: Vec<String>,
+    pub network: Option<NetworkConfig>,
+}
+
+fn is_password_hash(value: &str) -> bool {
+    value == "!" || value.starts_with("$6$") || value.starts_with("$argon2id$")
+}
+
+impl AnswerFile {
+    /// Syntax only, what the values refer to is checked by `validate`.
+    pub fn parse(content: &str, source: &str) -> Result<Self, Vec<String>> {
+        let mut answers = AnswerFile::default();
+        let mut errors = Vec::new();
+        let (mut interface, mut address, mut gateway, mut dns) = (None, None, None, Vec::new());
+
+        for (lineno, line) in content.lines().enumerate() {
+            // Hashes hold `$` and `=` but never ` #`, so comments need the space before them
+            let line = line.split(" #").next().unwrap_or("").trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut error = |message: String| errors.push(format!("{}:{}: {}", source, lineno + 1, message));
+
+            let (key, value) = match line.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim().to_string()),
+                None => {
+                    error(format!("expected key = value, got '{}'", line));
+                    continue;
+                }
+            };
+            match key {
+                "language" => answers.language = Some(value),
+                "timezone" => answers.timezone = Some(value),
+                "hostname" => answers.hostname = Some(value),
+                "keymap" => answers.keymap = Some(value),
+                "root_user" => answers.root_user = Some(value),
+                "root_password" => answers.root_password = Some(value),
+                "user" => {
+                    let fields: Vec<&str> = value.splitn(4, ':').collect();
+                    if fields.len() < 2 {
+                        error(format!("user needs at least name:hash, got '{}'", value));
+                        continue;
+                    }
+                    answers.users.push(AnswerUser {
+                        name: fields[0].to_string(),
+                        password_hash: fields[1].to_string(),
+                        groups: fields.get(2).map(|g| g.split(',').filter(|g| !g.is_empty()).map(String::from).collect()).unwrap_or_default(),
+                        comment: fields.get(3).unwrap_or(&"").to_string(),
+                    });
+                }
+                "service" => answers.services.push(value.trim_end_matches(".service").to_string()),
+                "interface" => interface = Some(value),
+                "address" => match Addressing::parse(&value) {
+                    Ok(parsed) => address = Some(parsed),
+                    Err(e) => error(e),
+                },
+                "gateway" => match value.parse() {
+                    Ok(ip) => gateway = Some(ip),
+                    Err(_) => error(format!("invalid gateway '{}'", value)),
+                },
+                "dns" => match value.parse::<IpAddr>() {
+                    Ok(ip) => dns.push(ip),
+                    Err(_) => error(format!("invalid name server '{}'", value)),
+                },
+                _ => error(format!("unknown key '{}'", key)),
+            }
+        }
+
+        match (interface, address) {
+            (Some(interface), Some(addressing)) => answers.network = Some(NetworkConfig { interface, addressing, gateway, dns }),
+            (None, None) if gateway.is_none() && dns.is_empty() => {}
+            _ => errors.push(format!("{}: network settings need both interface and address", source)),
+        }
+
+        if errors.is_empty() {
+            Ok(answers)
+        } else {
+            Err(errors)
+        }
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, Vec<String>> {
+        let content = fs::read_to_string(path).map_err(|e| vec![format!("Failed to read {}: {}", path.display(), e)])?;
+        Self::parse(&content, &path.display().to_string())
+    }
+
+    /// Check every answer against the system before anything is applied.
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
+
+        if self.language.as_ref().is_some_and(|l| catalog(l).is_none()) {
+            errors.push(format!("unknown language '{}'", self.language.as_deref().unwrap_or_default()));
+        }
+        if let Some(timezone) = &self.timezone {
+            if let Err(e) = zoneinfo_path(timezone) {
+                errors.push(e);
+            }
+        }
+        if self.hostname.as_ref().is_some_and(|h| !is_valid_hostname(h)) {
+            errors.push(format!("invalid hostname '{}'", self.hostname.as_deref().unwrap_or_default()));
+        }
+        if self.keymap.as_ref().is_some_and(|k| !is_valid_keymap(k)) {
+            errors.push(format!("invalid keyboard layout '{}'", self.keymap.as_deref().unwrap_or_default()));
+        }
+        if self.root_user.as_ref().is_some_and(|u| !is_valid_name(u)) {
+            errors.push(format!("invalid root user name '{}'", self.root_user.as_deref().unwrap_or_default()));
+        }
+        if self.root_password.as_ref().is_some_and(|p| !is_password_hash(p) || p == "!") {
+            errors.push("root_password must be a $6$ or $argon2id$ hash".into());
+        }
+
+        let db = AccountDb::open();
+        let mut names: Vec<&str> = self.root_user.iter().map(String::as_str).collect();
+        for user in &self.users {
+            if !is_valid_name(&user.name) {
+                errors.push(format!("invalid user name '{}'", user.name));
+            }
+            if names.contains(&user.name.as_str()) {
+                errors.push(format!("user '{}' is given twice", user.name));
+            }
+            names.push(&user.name);
+            if !is_password_hash(&user.password_hash) {
+                errors.push(format!("user '{}': password must be a $6$ or $argon2id$ hash, or !", user.name));
+            }
+            if !is_valid_field(&user.comment) {
+                errors.push(format!("user '{}': invalid comment", user.name));
+            }
+            if let Ok(db) = &db {
+                for group in user.groups.iter().filter(|g| db.resolve_group(g).is_none()) {
+                    errors.push(format!("user '{}': group '{}' does not exist", user.name, group));
+                }
+            }
+        }
+        if let Err(e) = &db {
+            if !self.users.is_empty() {
+                errors.push(e.clone());
+            }
+        }
+
+        let dirs = UnitDirs::system();
+        for service in self.services.iter().filter(|s| !dirs.exists(s)) {
+            errors.push(format!("service '{}' does not exist", service));
+        }
+
+        if let Some(network) = &self.network {
+            if !is_valid_interface(&network.interface) {
+                errors.push(format!("invalid interface name '{}'", network.interface));
+            }
+            if network.addressing == Addressing::Dhcp && network.gateway.is_some() {
+                errors.push("gateway is only used with a static address".into());
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+/// The answer file to use: `firstboot=PATH` from the kernel command line, else
+/// /etc/firstboot.conf when it exists.
+pub fn find_answer_file() -> Option<String> {
+    let cmdline = fs::read_to_string("/proc/cmdline").unwrap_or_default();
+    if let Some(path) = cmdline.split_whitespace().find_map(|arg| arg.strip_prefix(CMDLINE_KEY)) {
+        return Some(path.to_string());
+    }
+    Path::new(DEFAULT_ANSWER_FILE_PATH).exists().then(|| DEFAULT_ANSWER_FILE_PATH.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "\
+# firstboot answers
+language = ms
+hostname = vantara   # short name
+root_password = $6$salt$hash=with=equals
+user = alice:$6$abc:wheel,audio:Alice Tan
+user = bob:!
+service = crond.service
+interface = eth0
+address = 10.0.2.15/24
+gateway = 10.0.2.2
+dns = 10.0.2.3
+dns = 1.1.1.1
+";
+
+    #[test]
+    fn parse_sample() {
+        let answers = AnswerFile::parse(SAMPLE, "test").unwrap();
+        assert_eq!(answers.language.as_deref(), Some("ms"));
+        assert_eq!(answers.hostname.as_deref(), Some("vantara"));
+        assert_eq!(answers.root_password.as_deref(), Some("$6$salt$hash=with=equals"));
+        assert!(answers.timezone.is_none());
+
+        assert_eq!(answers.users.len(), 2);
+        assert_eq!(answers.users[0].name, "alice");
+        assert_eq!(answers.users[0].password_hash, "$6$abc");
+        assert_eq!(answers.users[0].groups, ["wheel", "audio"]);
+        assert_eq!(answers.users[0].comment, "Alice Tan");
+        assert_eq!(answers.users[1].password_hash, "!");
+        assert!(answers.users[1].groups.is_empty());
+
+        assert_eq!(answers.services, ["crond"]);
+        let network = answers.network.unwrap();
+        assert_eq!(network.interface, "eth0");
+        assert_eq!(network.addressing, Addressing::Static("10.0.2.15".parse().unwrap(), 24));
+        assert_eq!(network.gateway, Some("10.0.2.2".parse().unwrap()));
+        assert_eq!(network.dns.len(), 2);
+    }
+
+    #[test]
+    fn parse_errors_name_the_line() {
+        let errors = AnswerFile::parse("hostname = a\ncolour = blue\nuser = carol\ndns = nowhere\njunk\n", "answers").unwrap_err();
+        assert_eq!(
+            errors,
+            [
+                "answers:2: unknown key 'colour'",
+                "answers:3: user needs at least name:hash, got 'carol'",
+                "answers:4: invalid name server 'nowhere'",
+                "answers:5: expected key = value, got 'junk'",
+            ]
+        );
+    }
+
+    #[test]
+    fn network_needs_interface_and_address() {
+        assert!(AnswerFile::parse("interface = eth0\n", "t").is_err());
+        assert!(AnswerFile::parse("address = dhcp\n", "t").is_err());
+        let answers = AnswerFile::parse("interface = eth0\naddress = dhcp\n", "t").unwrap();
+        assert_eq!(answers.network.unwrap().addressing, Addressing::Dhcp);
+        assert!(AnswerFile::parse("", "t").unwrap().network.is_none());
+    }
+
+    #[test]
+    fn validate_checks_values() {
+        assert!(AnswerFile::default().validate().is_ok());
+
+        let answers = AnswerFile {
+            language: Some("xx".into()),
+            hostname: Some("-bad-".into()),
+            keymap: Some("us/../../x".into()),
+            root_password: Some("!".into()),
+            network: Some(NetworkConfig {
+                interface: "eth/0".into(),
+                addressing: Addressing::Dhcp,
+                gateway: Some("10.0.2.2".parse().unwrap()),
+                dns: Vec::new(),
+            }),
+            ..AnswerFile::default()
+        };
+        let errors = answers.validate().unwrap_err();
+        assert_eq!(
+            errors,
+            [
+                "unknown language 'xx'",
+                "invalid hostname '-bad-'",
+                "invalid keyboard layout 'us/../../x'",
+                "root_password must be a $6$ or $argon2id$ hash",
+                "invalid interface name 'eth/0'",
+                "gateway is only used with a static address",
+            ]
+        );
+    }
+
+    #[test]
+    fn validate_checks_users() {
+        let user = |name: &str, hash: &str| AnswerUser { name: name.into(), password_hash: hash.into(), ..AnswerUser::default() };
+        let answers = AnswerFile {
+            root_user: Some("admin".into()),
+            users: vec![user("admin", "!"), user("Bad Name", "$6$x"), user("carol", "plaintext")],
+            ..AnswerFile::default()
+        };
+        let errors = answers.validate().unwrap_err();
+        assert!(errors.contains(&"user 'admin' is given twice".to_string()));
+        assert!(errors.contains(&"invalid user name 'Bad Name'".to_string()));
+        assert!(errors.contains(&"user 'carol': password must be a $6$ or $argon2id$ hash, or !".to_string()));
+    }
+}
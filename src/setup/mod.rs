@@ -1,4 +1,5 @@
 pub mod setup;
+pub mod answers;
 pub mod modules;
 
 pub use setup::*;
@@ -0,0 +1,23 @@
+use std::io::{self, Write};
+use crate::host::{set_hostname, DEFAULT_HOSTNAME};
+use crate::common::{safe_print, safe_eprintln, safe_println};
+use crate::tr;
+
+pub fn set_hostname_interactive() {
+    loop {
+        safe_print(format_args!("{} ", tr!("setup.hostname.prompt", default = DEFAULT_HOSTNAME)));
+        io::stdout().flush().unwrap();
+
+        let mut name = String::new();
+        io::stdin().read_line(&mut name).unwrap();
+        let name = if name.trim().is_empty() { DEFAULT_HOSTNAME } else { name.trim() };
+
+        match set_hostname(name) {
+            Ok(_) => {
+                safe_println(format_args!("{}", tr!("setup.hostname.set", hostname = name)));
+                break;
+            }
+            Err(e) => safe_eprintln(format_args!("{}", e)),
+        }
+    }
+}
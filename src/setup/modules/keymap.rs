@@ -0,0 +1,39 @@
+use std::fs;
+use std::io::{self, Write};
+use crate::common::{safe_print, safe_eprintln, safe_println};
+use crate::tr;
+
+/// Console keyboard layout, `KEYMAP=<name>`
+pub const DEFAULT_VCONSOLE_PATH: &str = "/etc/vconsole.conf";
+pub const DEFAULT_KEYMAP: &str = "us";
+
+/// Keymap names such as `us`, `de-latin1` or `fr_CH`.
+pub fn is_valid_keymap(name: &str) -> bool {
+    !name.is_empty() && name.len() <= 64 && name.chars().all(|c| c.is_ascii_alphanumeric() || "-_.".contains(c))
+}
+
+pub fn set_keymap(name: &str) -> Result<(), String> {
+    if !is_valid_keymap(name) {
+        return Err(tr!("setup.keymap.invalid", keymap = name));
+    }
+    fs::write(DEFAULT_VCONSOLE_PATH, format!("KEYMAP={}\n", name))
+        .map_err(|e| format!("Failed to write to '{}': {}", DEFAULT_VCONSOLE_PATH, e))?;
+    safe_println(format_args!("{}", tr!("setup.keymap.set", keymap = name)));
+    Ok(())
+}
+
+pub fn set_keymap_interactive() {
+    loop {
+        safe_print(format_args!("{} ", tr!("setup.keymap.prompt", default = DEFAULT_KEYMAP)));
+        io::stdout().flush().unwrap();
+
+        let mut name = String::new();
+        io::stdin().read_line(&mut name).unwrap();
+        let name = if name.trim().is_empty() { DEFAULT_KEYMAP } else { name.trim() };
+
+        match set_keymap(name) {
+            Ok(_) => break,
+            Err(e) => safe_eprintln(format_args!("{}", e)),
+        }
+    }
+}
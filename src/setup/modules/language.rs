@@ -0,0 +1,30 @@
+use std::io::{self, Write};
+use crate::i18n::{catalog, save_language, set_language, CATALOGS, DEFAULT_LANGUAGE};
+use crate::common::{safe_print, safe_eprintln, safe_println};
+use crate::tr;
+
+/// Use `language` for the rest of firstboot and keep it in /etc/locale.conf.
+pub fn choose_language(language: &str) -> Result<(), String> {
+    let chosen = catalog(language).ok_or_else(|| tr!("setup.language.invalid", language = language))?;
+    set_language(language)?;
+    save_language(language)?;
+    safe_println(format_args!("{}", tr!("setup.language.set", name = chosen.name)));
+    Ok(())
+}
+
+pub fn choose_language_interactive() {
+    let choices: Vec<String> = CATALOGS.iter().map(|c| format!("{} = {}", c.language, c.name)).collect();
+    loop {
+        safe_print(format_args!("{} ", tr!("setup.language.prompt", choices = choices.join(", "), default = DEFAULT_LANGUAGE)));
+        io::stdout().flush().unwrap();
+
+        let mut language = String::new();
+        io::stdin().read_line(&mut language).unwrap();
+        let language = if language.trim().is_empty() { DEFAULT_LANGUAGE } else { language.trim() };
+
+        match choose_language(language) {
+            Ok(_) => break,
+            Err(e) => safe_eprintln(format_args!("{}", e)),
+        }
+    }
+}
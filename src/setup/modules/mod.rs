@@ -1,2 +1,6 @@
 pub mod timezone;
-pub mod rootuser;
\ No newline at end of file
+pub mod rootuser;
+pub mod hostname;
+pub mod keymap;
+pub mod network;
+pub mod language;
\ No newline at end of file
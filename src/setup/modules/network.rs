@@ -0,0 +1,126 @@
+use std::fs;
+use std::io::{self, Write};
+use std::net::{IpAddr, Ipv4Addr};
+use crate::common::{safe_print, safe_eprintln, safe_println};
+use crate::tr;
+
+pub const DEFAULT_NETWORK_PATH: &str = "/etc/network.conf";
+pub const DEFAULT_RESOLV_PATH: &str = "/etc/resolv.conf";
+pub const DEFAULT_INTERFACE: &str = "eth0";
+/// Typed at the interface prompt to leave the network unconfigured
+const SKIP_NETWORK: &str = "none";
+
+/// Linux interface names: at most 15 bytes, no `/` and no whitespace.
+pub fn is_valid_interface(name: &str) -> bool {
+    !name.is_empty() && name.len() <= 15 && !name.contains('/') && !name.contains(char::is_whitespace)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Addressing {
+    Dhcp,
+    /// Address and prefix length
+    Static(Ipv4Addr, u8),
+}
+
+impl Addressing {
+    /// `dhcp` or `ADDRESS/PREFIX`.
+    pub fn parse(value: &str) -> Result<Self, String> {
+        if value == "dhcp" {
+            return Ok(Addressing::Dhcp);
+        }
+        let (address, prefix) = value
+            .split_once('/')
+            .ok_or_else(|| format!("address '{}' needs a prefix length, e.g. 10.0.2.15/24", value))?;
+        let address = address.parse().map_err(|_| format!("invalid address '{}'", address))?;
+        let prefix = prefix.parse().ok().filter(|p| *p <= 32).ok_or_else(|| format!("invalid prefix length '{}'", prefix))?;
+        Ok(Addressing::Static(address, prefix))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NetworkConfig {
+    pub interface: String,
+    pub addressing: Addressing,
+    pub gateway: Option<Ipv4Addr>,
+    pub dns: Vec<IpAddr>,
+}
+
+impl NetworkConfig {
+    fn to_text(&self) -> String {
+        let mut text = format!("INTERFACE={}\n", self.interface);
+        match self.addressing {
+            Addressing::Dhcp => text.push_str("ADDRESS=dhcp\n"),
+            Addressing::Static(address, prefix) => text.push_str(&format!("ADDRESS={}/{}\n", address, prefix)),
+        }
+        if let Some(gateway) = self.gateway {
+            text.push_str(&format!("GATEWAY={}\n", gateway));
+        }
+        text
+    }
+}
+
+/// Write the interface settings to /etc/network.conf and the name servers to
+/// /etc/resolv.conf.
+pub fn set_network(config: &NetworkConfig) -> Result<(), String> {
+    fs::write(DEFAULT_NETWORK_PATH, config.to_text())
+        .map_err(|e| format!("Failed to write to '{}': {}", DEFAULT_NETWORK_PATH, e))?;
+    if !config.dns.is_empty() {
+        let resolv: String = config.dns.iter().map(|ip| format!("nameserver {}\n", ip)).collect();
+        fs::write(DEFAULT_RESOLV_PATH, resolv).map_err(|e| format!("Failed to write to '{}': {}", DEFAULT_RESOLV_PATH, e))?;
+    }
+    safe_println(format_args!("{}", tr!("setup.network.set", interface = config.interface)));
+    Ok(())
+}
+
+fn prompt(message: &str) -> String {
+    safe_print(format_args!("{} ", message));
+    io::stdout().flush().unwrap();
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer).unwrap();
+    answer.trim().to_string()
+}
+
+/// Ask until `parse` accepts the answer, an empty answer is `parse("")`.
+fn prompt_until<T>(message: &str, parse: impl Fn(&str) -> Result<T, String>) -> T {
+    loop {
+        match parse(&prompt(message)) {
+            Ok(value) => return value,
+            Err(e) => safe_eprintln(format_args!("{}", e)),
+        }
+    }
+}
+
+pub fn set_network_interactive() {
+    let interface = prompt_until(&tr!("setup.network.interface", default = DEFAULT_INTERFACE, skip = SKIP_NETWORK), |answer| {
+        match answer {
+            "" => Ok(Some(DEFAULT_INTERFACE.to_string())),
+            SKIP_NETWORK => Ok(None),
+            name if is_valid_interface(name) => Ok(Some(name.to_string())),
+            name => Err(tr!("setup.network.invalid_interface", interface = name)),
+        }
+    });
+    let Some(interface) = interface else {
+        return;
+    };
+
+    let addressing = prompt_until(&tr!("setup.network.address"), |answer| {
+        Addressing::parse(if answer.is_empty() { "dhcp" } else { answer })
+    });
+    let gateway = match addressing {
+        Addressing::Dhcp => None,
+        Addressing::Static(..) => prompt_until(&tr!("setup.network.gateway"), |answer| match answer {
+            "" => Ok(None),
+            _ => answer.parse().map(Some).map_err(|_| format!("invalid gateway '{}'", answer)),
+        }),
+    };
+    let dns = prompt_until(&tr!("setup.network.dns"), |answer| {
+        answer
+            .split_whitespace()
+            .map(|ip| ip.parse().map_err(|_| format!("invalid name server '{}'", ip)))
+            .collect::<Result<Vec<IpAddr>, String>>()
+    });
+
+    if let Err(e) = set_network(&NetworkConfig { interface, addressing, gateway, dns }) {
+        safe_eprintln(format_args!("{}", e));
+    }
+}
@@ -1,24 +1,46 @@
 use std::io::{self, Write};
-use crate::common::{safe_print, read_password, safe_println};
+use crate::auth::modules::account_db::{AccountDb, NewUser};
+use crate::auth::modules::shadow::{hash_password, HashMethod};
+use crate::common::{safe_print, read_password, safe_println, safe_eprintln};
+use crate::tr;
 
-pub fn add_root_user() {
-    safe_print(format_args!("Set Username (default: root): "));
-    io::stdout().flush().unwrap(); // Ensure the prompt is printed immediately
-    let mut username = String::new();
-    io::stdin().read_line(&mut username).unwrap(); 
-    let username = if username.trim().is_empty() { "root".into() } else { username.trim().to_string() };
+/// Create the uid 0 account `username` with an already hashed password.
+pub fn create_root_user(username: &str, hash: &str) -> Result<(), String> {
+    let mut db = AccountDb::open()?;
+    db.add_user(&NewUser {
+        name: username.to_string(),
+        uid: Some(0),
+        comment: "Administrator".into(),
+        home: Some("/root".into()),
+        password_hash: Some(hash.to_string()),
+        ..Default::default()
+    })?;
+    db.commit()?;
+    std::fs::create_dir_all("/root").map_err(|e| e.to_string())
+}
+
+/// Asks for the password, and for the name unless `username` is given already.
+pub fn add_root_user(username: Option<&str>) {
+    let username = match username {
+        Some(username) => username.to_string(),
+        None => {
+            safe_print(format_args!("{} ", tr!("setup.root.username")));
+            io::stdout().flush().unwrap(); // Ensure the prompt is printed immediately
+            let mut username = String::new();
+            io::stdin().read_line(&mut username).unwrap();
+            if username.trim().is_empty() { "root".into() } else { username.trim().to_string() }
+        }
+    };
 
-    safe_print(format_args!("Set {} Password: ", &username));
+    safe_print(format_args!("{} ", tr!("setup.root.password", user = username)));
     io::stdout().flush().unwrap(); // Ensure the prompt is printed immediately
     let password = read_password();
 
-    let uid = 0;
-    let gid = 0;
-
-    let _ = crate::auth::modules::group::add_user_to_group_file(&username, gid);
-    let _ = crate::auth::modules::passwd::add_user_to_passwd_file(&username, "Administrator", uid, gid);
-    let _ = crate::auth::modules::shadow::add_user_to_shadow_file(&username, &password);
+    let result = hash_password(&password, &HashMethod::configured()).and_then(|hash| create_root_user(&username, &hash));
 
-    safe_println(format_args!("{} account had been successfuly created. Proceed to login now", username));
+    match result {
+        Ok(_) => safe_println(format_args!("{}", tr!("setup.root.created", user = username))),
+        Err(e) => safe_eprintln(format_args!("{}", tr!("setup.root.failed", user = username, error = e))),
+    }
     println!();
-}
\ No newline at end of file
+}
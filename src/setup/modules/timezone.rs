@@ -2,46 +2,52 @@ use std::os::unix::fs as unix_fs;
 use std::fs;
 use std::io::{self, Write};
 use crate::common::{safe_print, safe_eprintln, safe_println};
+use crate::tr;
 
 const DEFAULT_LOCALTIME_PATH: &str = "/etc/localtime";
 const DEFAULT_TIMEZONE_PATH: &str = "/etc/timezone";
+const DEFAULT_ZONEINFO_PATH: &str = "/usr/share/zoneinfo";
+
+/// Zone file of `Region/City`, error when the name is malformed or unknown.
+pub fn zoneinfo_path(timezone: &str) -> Result<String, String> {
+    let parts: Vec<&str> = timezone.splitn(2, '/').collect();
+    if parts.len() != 2 || parts.iter().any(|p| p.is_empty() || p.contains("..")) {
+        return Err(tr!("setup.timezone.invalid", timezone = timezone));
+    }
+    let zoneinfo_path = format!("{}/{}/{}", DEFAULT_ZONEINFO_PATH, parts[0], parts[1]);
+    if fs::metadata(&zoneinfo_path).is_err() {
+        return Err(tr!("setup.timezone.missing", path = zoneinfo_path));
+    }
+    Ok(zoneinfo_path)
+}
+
+pub fn set_timezone(timezone: &str) -> Result<(), String> {
+    let zoneinfo_path = zoneinfo_path(timezone)?;
+
+    // Padam symlink lama kalau ada
+    let _ = fs::remove_file(DEFAULT_LOCALTIME_PATH);
+
+    // Buat symlink baru
+    unix_fs::symlink(&zoneinfo_path, DEFAULT_LOCALTIME_PATH).map_err(|e| tr!("setup.timezone.failed", error = e))?;
+    if let Err(e) = fs::write(DEFAULT_TIMEZONE_PATH, format!("{}\n", timezone)) {
+        safe_eprintln(format_args!("Failed to write to '{}': {}", DEFAULT_TIMEZONE_PATH, e));
+    }
+    safe_println(format_args!("{}", tr!("setup.timezone.set", timezone = timezone)));
+    Ok(())
+}
 
 pub fn set_timezone_interactive() {
     loop {
-        safe_print(format_args!("Set timezone (e.g., Asia/Kuala_Lumpur): "));
+        safe_print(format_args!("{} ", tr!("setup.timezone.prompt")));
         io::stdout().flush().unwrap();
 
         let mut timezone_str = String::new();
         io::stdin().read_line(&mut timezone_str).unwrap();
         let timezone_str = timezone_str.trim(); // buang \n dan space
 
-        let parts: Vec<&str> = timezone_str.splitn(2, '/').collect();
-        if parts.len() == 2 {
-            let region = parts[0];
-            let city = parts[1];
-            let zoneinfo_path = format!("/usr/share/zoneinfo/{}/{}", region, city);
-
-            if !fs::metadata(&zoneinfo_path).is_ok() {
-                safe_println(format_args!("Time zone not exist: {}", zoneinfo_path));
-                continue;
-            }
-
-            // Padam symlink lama kalau ada
-            let _ = fs::remove_file(DEFAULT_LOCALTIME_PATH);
-
-            // Buat symlink baru
-            match unix_fs::symlink(&zoneinfo_path, DEFAULT_LOCALTIME_PATH) {
-                Ok(_) => {
-                    if let Err(e) = fs::write(DEFAULT_TIMEZONE_PATH, format!("{}\n", timezone_str)) {
-                        safe_eprintln(format_args!("Failed to write to '{}': {}", DEFAULT_TIMEZONE_PATH, e));
-                    }
-                    safe_println(format_args!("Successfully set time zone to '{}'", timezone_str));
-                    break; 
-                },
-                Err(e) => safe_eprintln(format_args!("Failed to set time zone: {}", e)),
-            }
-        } else {
-            safe_println(format_args!("Invalid time zone format: {}", timezone_str));
+        match set_timezone(timezone_str) {
+            Ok(_) => break,
+            Err(e) => safe_println(format_args!("{}", e)),
         }
     }
-}
\ No newline at end of file
+}
@@ -1,27 +1,200 @@
 use std::path::Path;
-use std::fs;
-use crate::setup::modules::timezone::set_timezone_interactive;
-use crate::setup::modules::rootuser::add_root_user;
-use crate::common::show_boot_banner;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use crate::setup::answers::{find_answer_file, AnswerFile};
+use crate::setup::modules::timezone::{set_timezone, set_timezone_interactive};
+use crate::setup::modules::rootuser::{add_root_user, create_root_user};
+use crate::setup::modules::hostname::set_hostname_interactive;
+use crate::setup::modules::keymap::{set_keymap, set_keymap_interactive};
+use crate::setup::modules::network::{set_network, set_network_interactive};
+use crate::setup::modules::language::{choose_language, choose_language_interactive};
+use crate::auth::modules::account_db::{AccountDb, NewUser};
+use crate::auth::modules::homedir::{create_home, DEFAULT_SKEL_PATH};
+use crate::systemd::unit_files::UnitDirs;
+use crate::host::set_hostname;
+use crate::common::{show_boot_banner, safe_eprintln, safe_println};
+use crate::tr;
 
 const DEFAULT_FIRSTBOOT_PATH: &str = "/etc/.firstboot";
 
-pub fn setup_firstboot() {
-    if Path::new(DEFAULT_FIRSTBOOT_PATH).exists() {
-        show_boot_banner();
+/// Steps in the order they run, the language first so the others are asked in it.
+/// The marker file lists the ones already done, so a reboot half way through
+/// carries on with the next one.
+const STEPS: [&str; 8] = ["language", "timezone", "hostname", "keymap", "network", "root", "users", "services"];
+
+fn completed_steps() -> Vec<String> {
+    fs::read_to_string(DEFAULT_FIRSTBOOT_PATH)
+        .unwrap_or_default()
+        .lines()
+        .map(|l| l.trim().to_string())
+        .collect()
+}
+
+fn mark_completed(step: &str) {
+    let result = OpenOptions::new()
+        .append(true)
+        .open(DEFAULT_FIRSTBOOT_PATH)
+        .and_then(|mut file| writeln!(file, "{}", step).and_then(|_| file.sync_all()));
+    if let Err(e) = result {
+        safe_eprintln(format_args!("[FIRSTBOOT] {}", tr!("setup.step.record_failed", step = step, error = e)));
+    }
+}
+
+/// The answers to use, or none when there is no answer file or it is invalid.
+/// Nothing from an invalid file is applied.
+fn load_answers() -> AnswerFile {
+    let path = match find_answer_file() {
+        Some(path) => path,
+        None => return AnswerFile::default(),
+    };
+    match AnswerFile::from_file(Path::new(&path)).and_then(|answers| answers.validate().map(|_| answers)) {
+        Ok(answers) => {
+            safe_println(format_args!("[FIRSTBOOT] {}", tr!("setup.answers.using", path = path)));
+            answers
+        }
+        Err(errors) => {
+            for error in errors {
+                safe_eprintln(format_args!("[FIRSTBOOT] {}", error));
+            }
+            safe_eprintln(format_args!("[FIRSTBOOT] {}", tr!("setup.answers.ignored", path = path)));
+            AnswerFile::default()
+        }
+    }
+}
+
+/// Create the users of the answer file, skipping the ones that already exist.
+fn add_users(answers: &AnswerFile) -> Result<(), String> {
+    let mut db = AccountDb::open()?;
+    let mut entries = Vec::new();
+    for user in &answers.users {
+        match db.user(&user.name) {
+            Some(existing) => entries.push(existing.clone()),
+            None => entries.push(db.add_user(&NewUser {
+                name: user.name.clone(),
+                groups: user.groups.clone(),
+                comment: user.comment.clone(),
+                password_hash: Some(user.password_hash.clone()),
+                ..Default::default()
+            })?),
+        }
+    }
+    let mode = u32::from_str_radix(db.defs.get("HOME_MODE").unwrap_or("0700"), 8).unwrap_or(0o700);
+    db.commit()?;
 
-        set_timezone_interactive();
-        println!();
+    // Users made by an earlier run that stopped half way may still lack a home
+    for user in entries {
+        let home = Path::new(&user.home);
+        if !home.exists() {
+            create_home(home, Path::new(DEFAULT_SKEL_PATH), user.uid, user.gid, mode)?;
+            safe_println(format_args!("{}", tr!("setup.user.created", user = user.username)));
+        }
+    }
+    Ok(())
+}
 
-        //TODO: keyboard layout
+/// The uid 0 account, from the answer file when it has a password hash for it.
+/// A `root_user` without a password only saves asking for the name.
+fn add_root(answers: &AnswerFile) -> Result<(), String> {
+    let hash = match &answers.root_password {
+        Some(hash) => hash,
+        None => {
+            add_root_user(answers.root_user.as_deref());
+            return Ok(());
+        }
+    };
+    let username = answers.root_user.as_deref().unwrap_or("root");
+    if AccountDb::open()?.user(username).is_some() {
+        return Ok(());
+    }
+    create_root_user(username, hash)?;
+    safe_println(format_args!("{}", tr!("setup.user.created", user = username)));
+    Ok(())
+}
+
+fn enable_services(answers: &AnswerFile) -> Result<(), String> {
+    let dirs = UnitDirs::system();
+    for service in &answers.services {
+        if dirs.enable(service)? {
+            safe_println(format_args!("{}", tr!("setup.service.enabled", service = service)));
+        }
+    }
+    Ok(())
+}
 
-        //TODO: set hostname
+/// Run one step, from the answer file when it has a value for it and on the
+/// console otherwise. Returns false when the step failed.
+fn run_step(step: &str, answers: &AnswerFile) -> bool {
+    let result = match step {
+        "language" => match &answers.language {
+            Some(language) => choose_language(language),
+            None => {
+                choose_language_interactive();
+                Ok(())
+            }
+        },
+        "timezone" => match &answers.timezone {
+            Some(timezone) => set_timezone(timezone),
+            None => {
+                set_timezone_interactive();
+                Ok(())
+            }
+        },
+        "hostname" => match &answers.hostname {
+            Some(hostname) => set_hostname(hostname)
+                .inspect(|_| safe_println(format_args!("{}", tr!("setup.hostname.set", hostname = hostname)))),
+            None => {
+                set_hostname_interactive();
+                Ok(())
+            }
+        },
+        "keymap" => match &answers.keymap {
+            Some(keymap) => set_keymap(keymap),
+            None => {
+                set_keymap_interactive();
+                Ok(())
+            }
+        },
+        "network" => match &answers.network {
+            Some(network) => set_network(network),
+            None => {
+                set_network_interactive();
+                Ok(())
+            }
+        },
+        "root" => add_root(answers),
+        "users" => add_users(answers),
+        "services" => enable_services(answers),
+        _ => Ok(()),
+    };
+    match result {
+        Ok(_) => true,
+        Err(e) => {
+            safe_eprintln(format_args!("[FIRSTBOOT] {}", tr!("setup.step.failed", step = step, error = e)));
+            false
+        }
+    }
+}
+
+pub fn setup_firstboot() {
+    if Path::new(DEFAULT_FIRSTBOOT_PATH).exists() {
+        show_boot_banner();
 
-        //TODO: network config
+        let answers = load_answers();
+        let done = completed_steps();
+        let mut failed = false;
 
-        //TODO: add user root
-        add_root_user();
+        for step in STEPS.iter().filter(|s| !done.iter().any(|d| d == *s)) {
+            if run_step(step, &answers) {
+                mark_completed(step);
+            } else {
+                failed = true;
+            }
+            println!();
+        }
 
-        let _ = fs::remove_file(DEFAULT_FIRSTBOOT_PATH);
+        // Failed steps are tried again on the next boot
+        if !failed {
+            let _ = fs::remove_file(DEFAULT_FIRSTBOOT_PATH);
+        }
     }
 }
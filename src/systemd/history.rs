@@ -0,0 +1,123 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::time::SystemTime;
+use nix::sys::wait::WaitStatus;
+
+/// How many finished runs each service remembers.
+pub const MAX_RUN_HISTORY: usize = 10;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RunExit {
+    Code(i32),
+    Signal(String),
+    /// Process was gone before init could collect its status.
+    Unknown,
+}
+
+#[derive(Debug, Clone)]
+pub struct RunRecord {
+    pub pid: i32,
+    pub start_time: SystemTime,
+    pub end_time: Option<SystemTime>,
+    pub exit: Option<RunExit>,
+    pub reason: String,
+    /// Set when the run was ended through `stop`, so its exit isn't a failure.
+    pub stop_requested: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RunHistory {
+    pub runs: VecDeque<RunRecord>,
+}
+
+impl RunExit {
+    pub fn from_wait_status(status: WaitStatus) -> Option<Self> {
+        match status {
+            WaitStatus::Exited(_, code) => Some(RunExit::Code(code)),
+            WaitStatus::Signaled(_, sig, _) => Some(RunExit::Signal(sig.as_str().to_string())),
+            _ => None,
+        }
+    }
+
+    pub fn is_success(&self) -> bool {
+        matches!(self, RunExit::Code(0))
+    }
+
+    pub fn serialize(&self) -> String {
+        match self {
+            RunExit::Code(code) => format!("code:{}", code),
+            RunExit::Signal(sig) => format!("signal:{}", sig),
+            RunExit::Unknown => "unknown".to_string(),
+        }
+    }
+
+    pub fn deserialize(value: &str) -> Option<Self> {
+        match value.split_once(':') {
+            Some(("code", code)) => code.parse().ok().map(RunExit::Code),
+            Some(("signal", sig)) => Some(RunExit::Signal(sig.to_string())),
+            _ if value == "unknown" => Some(RunExit::Unknown),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for RunExit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RunExit::Code(code) => write!(f, "exited with code {}", code),
+            RunExit::Signal(sig) => write!(f, "killed by signal {}", sig),
+            RunExit::Unknown => write!(f, "exit status unknown"),
+        }
+    }
+}
+
+impl RunRecord {
+    pub fn failed(&self) -> bool {
+        match &self.exit {
+            Some(RunExit::Unknown) | None => false,
+            Some(exit) => !self.stop_requested && !exit.is_success(),
+        }
+    }
+}
+
+impl RunHistory {
+    pub fn push(&mut self, record: RunRecord) {
+        self.runs.push_back(record);
+        while self.runs.len() > MAX_RUN_HISTORY {
+            self.runs.pop_front();
+        }
+    }
+
+    /// Close the open run for `pid`. Returns false when no such run is open.
+    pub fn finish(&mut self, pid: i32, exit: RunExit, end_time: SystemTime) -> bool {
+        match self.runs.iter_mut().rev().find(|r| r.pid == pid && r.exit.is_none()) {
+            Some(record) => {
+                record.end_time = Some(end_time);
+                record.exit = Some(exit);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Fill in the real exit status of a run that was closed as `Unknown`.
+    pub fn resolve(&mut self, pid: i32, exit: RunExit) -> bool {
+        match self.runs.iter_mut().rev().find(|r| r.pid == pid && r.exit == Some(RunExit::Unknown)) {
+            Some(record) => {
+                record.exit = Some(exit);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn mark_stop_requested(&mut self, pid: i32) {
+        if let Some(record) = self.runs.iter_mut().rev().find(|r| r.pid == pid && r.exit.is_none()) {
+            record.stop_requested = true;
+        }
+    }
+
+    pub fn last_finished(&self) -> Option<&RunRecord> {
+        self.runs.iter().rev().find(|r| r.exit.is_some())
+    }
+}
@@ -1,26 +1,41 @@
+use crate::systemd::history::RunExit;
 use crate::systemd::service::Service;
+use crate::systemd::unit_files::UnitDirs;
+use crate::systemd::state::{ManagerState, ServiceState, DEFAULT_STATE_PATH};
 use std::collections::HashMap;
+use std::ffi::CString;
 use std::fs;
 use std::path::{Path, PathBuf};
-use std::os::unix::net::UnixListener;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
 use std::io::{Read, Write};
 use std::sync::{Mutex, Arc};
 use std::thread;
 use std::os::unix::fs::PermissionsExt;
+use crate::audit::{self, EventKind, Outcome};
+use crate::auth::modules::session_log::log_shutdown;
 use crate::common::{safe_eprintln, safe_println};
+use crate::nss::user_name;
 
 pub const DEFAULT_SERVICE_AVAILABLE_PATH: &str = "/etc/service/available";
 pub const DEFAULT_SERVICE_ENABLED_PATH: &str = "/etc/service/enabled";
-const DEFAULT_SOCKET_PATH: &str = "/run/systemd.sock";
+pub const DEFAULT_SOCKET_PATH: &str = "/run/systemd.sock";
+const DEFAULT_INIT_PATH: &str = "/sbin/init";
 
 pub struct ServiceManager {
     pub services: HashMap<String, Service>,
+    /// Child that init waits for itself, login or the desktop. `reap_children`
+    /// leaves it to that waiter.
+    pub console_pid: Option<i32>,
+    listener_fd: Option<RawFd>,
 }
 
 impl ServiceManager {
     pub fn new() -> Arc<Mutex<Self>> {
         Arc::new(Mutex::new(ServiceManager {
             services: HashMap::new(),
+            console_pid: None,
+            listener_fd: None,
         }))
     }
 
@@ -38,31 +53,74 @@ impl ServiceManager {
         // Load services
         {
             let mut manager = self_arc.lock().unwrap();
-            let entries = fs::read_dir(DEFAULT_SERVICE_AVAILABLE_PATH)
-                .unwrap_or_else(|_| panic!("[INIT] Cannot open {}", DEFAULT_SERVICE_AVAILABLE_PATH));
+            manager.load_service_files();
+        }
 
-            for entry in entries {
-                let path = match entry {
-                    Ok(e) => e.path(),
-                    Err(e) => {
-                        safe_eprintln(format_args!("[INIT] Error reading entry: {}", e));
-                        continue;
-                    }
-                };
+        Self::spawn_ipc_thread(self_arc, listener);
+    }
 
-                if path.extension().map_or(false, |e| e == "service") {
-                    match Service::from_file(&path) {
-                        Ok(service) => {
-                            safe_println(format_args!("[INIT] Loading config for service {}", service.name));
-                            manager.services.insert(service.name.clone(), service);
-                        }
-                        Err(err) => {
-                            safe_eprintln(format_args!("[INIT] Error loading {}: {}", path.display(), err));
-                        }
+    /// Rebuild the manager from a state file written by `reexec`.
+    /// Service definitions are re-read from disk, runtime state is laid on top.
+    pub fn restore(state_path: &str) -> Result<Arc<Mutex<Self>>, String> {
+        let state = ManagerState::read_from(state_path)?;
+        let _ = fs::remove_file(state_path);
+
+        let fd = state.listener_fd.ok_or("State file has no listener_fd")?;
+        let listener = unsafe { UnixListener::from_raw_fd(fd) };
+        set_cloexec(fd, true);
+
+        let self_arc = Self::new();
+        {
+            let mut manager = self_arc.lock().unwrap();
+            manager.load_service_files();
+            manager.console_pid = state.console_pid;
+
+            for svc_state in &state.services {
+                match manager.services.get_mut(&svc_state.name) {
+                    Some(service) => svc_state.apply_to(service),
+                    None => safe_eprintln(format_args!(
+                        "[INIT] Service {} no longer available, PID {:?} left unsupervised",
+                        svc_state.name, svc_state.pid
+                    )),
+                }
+            }
+        }
+
+        safe_println(format_args!("[INIT] Restored {} services after re-exec", state.services.len()));
+        Self::spawn_ipc_thread(Arc::clone(&self_arc), listener);
+
+        Ok(self_arc)
+    }
+
+    fn load_service_files(&mut self) {
+        let entries = fs::read_dir(DEFAULT_SERVICE_AVAILABLE_PATH)
+            .unwrap_or_else(|_| panic!("[INIT] Cannot open {}", DEFAULT_SERVICE_AVAILABLE_PATH));
+
+        for entry in entries {
+            let path = match entry {
+                Ok(e) => e.path(),
+                Err(e) => {
+                    safe_eprintln(format_args!("[INIT] Error reading entry: {}", e));
+                    continue;
+                }
+            };
+
+            if path.extension().is_some_and(|e| e == "service") {
+                match Service::from_file(&path) {
+                    Ok(service) => {
+                        safe_println(format_args!("[INIT] Loading config for service {}", service.name));
+                        self.services.insert(service.name.clone(), service);
+                    }
+                    Err(err) => {
+                        safe_eprintln(format_args!("[INIT] Error loading {}: {}", path.display(), err));
                     }
                 }
             }
         }
+    }
+
+    fn spawn_ipc_thread(self_arc: Arc<Mutex<Self>>, listener: UnixListener) {
+        self_arc.lock().unwrap().listener_fd = Some(listener.as_raw_fd());
 
         // IPC thread
         let listener_arc = Arc::new(listener);
@@ -74,13 +132,44 @@ impl ServiceManager {
                     Ok(mut socket) => {
                         let mut buffer = [0u8; 512];
                         if let Ok(n) = socket.read(&mut buffer) {
-                            let input = String::from_utf8_lossy(&buffer[..n]).to_string();
-
-                            let response = {
+                            let request = String::from_utf8_lossy(&buffer[..n]).to_string();
+                            let (claimed, input) = split_caller(&request);
+                            let caller = caller_uid(&socket, claimed);
+
+                            let command = input.split_whitespace().next().unwrap_or("");
+                            if PRIVILEGED_COMMANDS.contains(&command) && caller != Some(0) {
+                                audit_command(caller, input, false);
+                                socket.write_all(format!("Failed to {}: permission denied\n", command).as_bytes()).ok();
+                                continue;
+                            }
+
+                            if input.trim() == "reexec" {
+                                let manager = sm_clone.lock().unwrap();
+                                // Only returns when the state could not be saved or execv failed
+                                let err = manager.reexec(|| {
+                                    socket.write_all(b"Re-executing init\n").ok();
+                                });
+                                socket.write_all(format!("Re-exec failed: {}\n", err).as_bytes()).ok();
+                                continue;
+                            }
+
+                            if let command @ ("poweroff" | "reboot") = input.trim() {
                                 let mut manager = sm_clone.lock().unwrap();
-                                manager.handle_command(&input)
+                                socket.write_all(b"Shutting down\n").ok();
+                                drop(socket);
+                                // Only returns when the kernel refused, nothing is left running by then
+                                let err = manager.shutdown(command == "reboot");
+                                safe_eprintln(format_args!("[INIT] {} failed: {}", command, err));
+                                continue;
+                            }
+
+                            let result = {
+                                let mut manager = sm_clone.lock().unwrap();
+                                manager.handle_command(input)
                             };
+                            audit_command(caller, input, result.is_ok());
 
+                            let response = result.unwrap_or_else(|e| e);
                             socket.write_all(response.as_bytes()).ok();
                         }
                     }
@@ -92,71 +181,193 @@ impl ServiceManager {
         });
     }
 
-    fn handle_command(&mut self, input: &str) -> String {
+    /// Serialize the supervisor state and replace the running init with the binary
+    /// at `/sbin/init`. `before_exec` runs once the state is safely on disk.
+    fn reexec<F: FnOnce()>(&self, before_exec: F) -> String {
+        let state = ManagerState {
+            listener_fd: self.listener_fd,
+            console_pid: self.console_pid,
+            services: self.services.values().map(ServiceState::from_service).collect(),
+        };
+
+        if let Err(e) = state.write_to(DEFAULT_STATE_PATH) {
+            return e;
+        }
+
+        // The listening socket has to survive execv
+        if let Some(fd) = self.listener_fd {
+            set_cloexec(fd, false);
+        }
+
+        safe_println(format_args!("[INIT] Re-executing {}", DEFAULT_INIT_PATH));
+        before_exec();
+
+        let path = CString::new(DEFAULT_INIT_PATH).unwrap();
+        let args = [
+            CString::new("init").unwrap(),
+            CString::new("--deserialize").unwrap(),
+            CString::new(DEFAULT_STATE_PATH).unwrap(),
+        ];
+        let err = nix::unistd::execv(&path, &args).unwrap_err();
+
+        if let Some(fd) = self.listener_fd {
+            set_cloexec(fd, true);
+        }
+        let _ = fs::remove_file(DEFAULT_STATE_PATH);
+        format!("execv {}: {}", DEFAULT_INIT_PATH, err)
+    }
+
+    /// Stop every service, record a clean shutdown for `last` and power off or
+    /// reboot. Returns the error when the kernel refused.
+    fn shutdown(&mut self, reboot: bool) -> String {
+        safe_println(format_args!("[INIT] Stopping all services"));
+        for service in self.services.values_mut() {
+            service.stop();
+        }
+
+        if let Err(e) = log_shutdown() {
+            safe_eprintln(format_args!("[INIT] Failed to record shutdown: {}", e));
+        }
+        let how = if reboot { libc::RB_AUTOBOOT } else { libc::RB_POWER_OFF };
+        unsafe {
+            libc::sync();
+            libc::reboot(how);
+        }
+        std::io::Error::last_os_error().to_string()
+    }
+
+    /// The reply to a control command, `Err` when the command failed.
+    fn handle_command(&mut self, input: &str) -> Result<String, String> {
         let parts: Vec<&str> = input.trim().split_whitespace().collect();
         if parts.is_empty() {
-            return "Invalid command\n".into();
+            return Err("Invalid command\n".into());
+        }
+
+        if let Some(name) = parts.get(1) {
+            if !self.services.contains_key(*name) {
+                return Err(format!("Service '{}' not found\n", name));
+            }
         }
 
         match parts[0] {
             "start" => {
                 if let Some(name) = parts.get(1) {
-                    self.start_service(name);
-                    format!("Started {}\n", name)
+                    self.start_service(name, "manual start")
+                        .map(|_| format!("Started {}\n", name))
+                        .map_err(|e| format!("Failed to start {}: {}\n", name, e))
                 } else {
-                    "start <service> required\n".into()
+                    Err("start <service> required\n".into())
                 }
             }
             "stop" => {
                 if let Some(name) = parts.get(1) {
                     self.stop_service(name);
-                    format!("Stopped {}\n", name)
+                    Ok(format!("Stopped {}\n", name))
                 } else {
-                    "stop <service> required\n".into()
+                    Err("stop <service> required\n".into())
                 }
             }
             "restart" => {
                 if let Some(name) = parts.get(1) {
                     self.stop_service(name);
-                    self.start_service(name);
-                    format!("Restarted {}\n", name)
+                    let result = self.start_service(name, "manual restart");
+                    if let Some(service) = self.services.get_mut(*name) {
+                        service.restarts += 1;
+                    }
+                    result
+                        .map(|_| format!("Restarted {}\n", name))
+                        .map_err(|e| format!("Failed to restart {}: {}\n", name, e))
+                } else {
+                    Err("restart <service> required\n".into())
+                }
+            }
+            "reload" => {
+                if let Some(name) = parts.get(1) {
+                    match self.services[*name].reload() {
+                        Ok(_) => Ok(format!("Reloaded {}\n", name)),
+                        Err(e) => Err(format!("Failed to reload {}: {}\n", name, e)),
+                    }
                 } else {
-                    "restart <service> required\n".into()
+                    Ok(self.reload_service_files())
                 }
             }
             "enable" => {
                 if let Some(name) = parts.get(1) {
-                    self.enable_service(name);
-                    format!("Enabled {}\n", name)
+                    self.enable_service(name)
+                        .map(|_| format!("Enabled {}\n", name))
+                        .map_err(|e| format!("Failed to enable {}: {}\n", name, e))
                 } else {
-                    "enable <service> required\n".into()
+                    Err("enable <service> required\n".into())
                 }
             }
             "disable" => {
                 if let Some(name) = parts.get(1) {
-                    self.disable_service(name);
-                    format!("Disabled {}\n", name)
+                    self.disable_service(name)
+                        .map(|_| format!("Disabled {}\n", name))
+                        .map_err(|e| format!("Failed to disable {}: {}\n", name, e))
                 } else {
-                    "disable <service> required\n".into()
+                    Err("disable <service> required\n".into())
                 }
             }
             "status" => {
                 if let Some(name) = parts.get(1) {
-                    self.status_service(name);
-                    format!("")
+                    Ok(self.status_service(name))
+                } else {
+                    Err("status <service> required\n".into())
+                }
+            }
+            "history" => {
+                if let Some(name) = parts.get(1) {
+                    Ok(self.services[*name].history_report())
                 } else {
-                    "status <service> required\n".into()
+                    Err("history <service> required\n".into())
                 }
             }
-            "list" => self.list_services(),
-            _ => "Unknown command\n".into(),
+            "show" => {
+                // key=value blocks separated by a blank line, meant for servicectl
+                Ok(match parts.get(1) {
+                    Some(name) => self.services[*name].properties(),
+                    None => {
+                        let mut names: Vec<&String> = self.services.keys().collect();
+                        names.sort();
+                        names.iter().map(|n| self.services[*n].properties()).collect::<Vec<_>>().join("\n")
+                    }
+                })
+            }
+            "list" => Ok(self.list_services()),
+            _ => Err("Unknown command\n".into()),
         }
     }
 
+    /// Re-read unit files from disk, keeping runtime state of services that are still defined.
+    fn reload_service_files(&mut self) -> String {
+        let mut previous = std::mem::take(&mut self.services);
+        self.load_service_files();
+
+        for (name, service) in self.services.iter_mut() {
+            if let Some(old) = previous.remove(name) {
+                let enabled = service.enabled;
+                ServiceState::from_service(&old).apply_to(service);
+                service.enabled = enabled;
+            }
+        }
+
+        // Unit file removed while running: keep supervising until it stops
+        for (name, old) in previous {
+            if old.pid.is_some() {
+                self.services.insert(name, old);
+            }
+        }
+
+        format!("Reloaded {} service definitions\n", self.services.len())
+    }
+
     pub fn start_enabled_services(&mut self) {
         for svc in Self::read_enabled_services(DEFAULT_SERVICE_ENABLED_PATH) {
             safe_println(format_args!("[INIT] Starting service {}", svc.name));
-            self.start_service(&svc.name);
+            if let Err(e) = self.start_service(&svc.name, "enabled at boot") {
+                safe_eprintln(format_args!("[INIT] Failed to start {}: {}", svc.name, e));
+            }
         }
     }
 
@@ -215,11 +426,10 @@ impl ServiceManager {
         services
     }
 
-    fn start_service(&mut self, name: &str) {
-        if let Some(service) = self.services.get_mut(name) {
-            service.start();
-        } else {
-            safe_eprintln(format_args!("Service '{}' not found", name));
+    fn start_service(&mut self, name: &str, reason: &str) -> Result<(), String> {
+        match self.services.get_mut(name) {
+            Some(service) => service.start(reason),
+            None => Err(format!("Service '{}' not found", name)),
         }
     }
 
@@ -231,24 +441,17 @@ impl ServiceManager {
         }
     }
 
-    fn enable_service(&mut self, name: &str) {
-        if let Some(service) = self.services.get_mut(name) {
-            let source = format!("{}/{}.service", DEFAULT_SERVICE_AVAILABLE_PATH, name);
-            let target = format!("{}/{}.service", DEFAULT_SERVICE_ENABLED_PATH, name);
-
-            service.enable(&source, &target);
-        } else {
-            safe_eprintln(format_args!("Service '{}' not found", name));
+    fn enable_service(&mut self, name: &str) -> Result<(), String> {
+        match self.services.get_mut(name) {
+            Some(service) => service.enable(&UnitDirs::system()),
+            None => Err(format!("Service '{}' not found", name)),
         }
     }
 
-    fn disable_service(&mut self, name: &str) {
-        if let Some(service) = self.services.get_mut(name) {
-            let target = format!("{}/{}.service", DEFAULT_SERVICE_ENABLED_PATH, name);
-
-            service.disable(&target);
-        } else {
-            safe_eprintln(format_args!("Service '{}' not found", name));
+    fn disable_service(&mut self, name: &str) -> Result<(), String> {
+        match self.services.get_mut(name) {
+            Some(service) => service.disable(&UnitDirs::system()),
+            None => Err(format!("Service '{}' not found", name)),
         }
     }
 
@@ -269,29 +472,146 @@ impl ServiceManager {
         output
     }
 
-    fn status_service(&mut self, name: &str) {
-        if let Some(service) = self.services.get_mut(name) {
-            service.status();
-        } else {
-            safe_eprintln(format_args!("Service '{}' not found", name));
+    fn status_service(&self, name: &str) -> String {
+        match self.services.get(name) {
+            Some(service) => service.status(),
+            None => format!("Service '{}' not found\n", name),
         }
     }
 
-    pub fn reap_children() {
-        use nix::sys::wait::{waitpid, WaitPidFlag, WaitStatus};
+    /// Collect exited children and close the run of whichever service owned them.
+    pub fn reap_children(self_arc: &Arc<Mutex<Self>>) {
+        use nix::sys::wait::{waitid, waitpid, Id, WaitPidFlag, WaitStatus};
         loop {
-            match waitpid(None, Some(WaitPidFlag::WNOHANG)) {
-                Ok(WaitStatus::Exited(pid, status)) => {
-                    println!("[REAP] Process {} exited with {}", pid, status);
-                }
-                Ok(WaitStatus::Signaled(pid, sig, _)) => {
-                    println!("[REAP] Process {} killed by signal {}", pid, sig);
-                }
-                Ok(WaitStatus::StillAlive) => break,
-                Ok(_) => {}
+            // Look before reaping, the console child is waited for elsewhere
+            let flags = WaitPidFlag::WEXITED | WaitPidFlag::WNOHANG | WaitPidFlag::WNOWAIT;
+            let pid = match waitid(Id::All, flags) {
+                Ok(status) => match status.pid() {
+                    Some(pid) => pid,
+                    None => break,
+                },
                 Err(_) => break,
+            };
+            if self_arc.lock().unwrap().console_pid == Some(pid.as_raw()) {
+                break;
+            }
+
+            let status = match waitpid(pid, Some(WaitPidFlag::WNOHANG)) {
+                Ok(WaitStatus::StillAlive) | Err(_) => break,
+                Ok(status) => status,
+            };
+
+            match status {
+                WaitStatus::Exited(pid, code) => println!("[REAP] Process {} exited with {}", pid, code),
+                WaitStatus::Signaled(pid, sig, _) => println!("[REAP] Process {} killed by signal {}", pid, sig),
+                _ => continue,
+            }
+
+            if let Some(exit) = RunExit::from_wait_status(status) {
+                let mut manager = self_arc.lock().unwrap();
+                let owner = manager.services.values_mut()
+                    .find(|s| s.pid == Some(pid) || s.history.runs.iter().any(|r| r.pid == pid.as_raw()));
+                if let Some(service) = owner {
+                    service.record_exit(pid, exit);
+                }
             }
         }
     }
+}
 
+fn set_cloexec(fd: RawFd, enabled: bool) {
+    unsafe {
+        let flags = libc::fcntl(fd, libc::F_GETFD);
+        if flags < 0 {
+            return;
+        }
+        let flags = if enabled { flags | libc::FD_CLOEXEC } else { flags & !libc::FD_CLOEXEC };
+        libc::fcntl(fd, libc::F_SETFD, flags);
+    }
+}
+
+/// `as=<uid> ` in front of a command: the user a setuid servicectl runs for.
+pub const CALLER_PREFIX: &str = "as=";
+
+/// Commands that change what runs, only root may send them.
+const PRIVILEGED_COMMANDS: [&str; 9] = ["start", "stop", "restart", "enable", "disable", "reload", "reexec", "poweroff", "reboot"];
+
+/// The claimed caller and the command itself.
+fn split_caller(request: &str) -> (Option<u32>, &str) {
+    let claimed = request.strip_prefix(CALLER_PREFIX).and_then(|rest| rest.split_once(' '));
+    match claimed.and_then(|(uid, command)| Some((uid.parse().ok()?, command))) {
+        Some((uid, command)) => (Some(uid), command),
+        None => (None, request),
+    }
+}
+
+/// Who sent a request. SO_PEERCRED has the effective uid, root for a setuid
+/// servicectl. Only root may say who it acts for, anyone else is taken as who
+/// they are.
+fn caller_uid(socket: &UnixStream, claimed: Option<u32>) -> Option<u32> {
+    let mut cred: libc::ucred = unsafe { std::mem::zeroed() };
+    let mut len = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+    let found = unsafe {
+        libc::getsockopt(socket.as_raw_fd(), libc::SOL_SOCKET, libc::SO_PEERCRED, (&mut cred as *mut libc::ucred).cast(), &mut len)
+    } == 0;
+    match (found, claimed) {
+        (true, Some(uid)) if cred.uid == 0 => Some(uid),
+        (true, _) => Some(cred.uid),
+        (false, _) => None,
+    }
+}
+
+/// Commands that change what runs are audited, along with who asked.
+fn audit_command(caller: Option<u32>, input: &str, succeeded: bool) {
+    let mut parts = input.split_whitespace();
+    let (action, name) = match (parts.next(), parts.next()) {
+        (Some(action @ ("start" | "stop" | "restart" | "enable" | "disable")), Some(name)) => (action, name),
+        _ => return,
+    };
+
+    let user = caller.map(user_name).unwrap_or_else(|| "unknown".to_string());
+    let outcome = if succeeded { Outcome::Success } else { Outcome::Failure };
+    audit::record(EventKind::Service, outcome, &user, &format!("{} {}", action, name));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nix::errno::Errno;
+    use nix::sys::wait::{waitid, waitpid, Id, WaitPidFlag, WaitStatus};
+    use nix::unistd::{fork, ForkResult, Pid};
+
+    /// A child that has exited but not been reaped yet.
+    fn zombie(code: i32) -> Pid {
+        let child = match unsafe { fork() }.unwrap() {
+            ForkResult::Child => unsafe { libc::_exit(code) },
+            ForkResult::Parent { child } => child,
+        };
+        waitid(Id::Pid(child), WaitPidFlag::WEXITED | WaitPidFlag::WNOWAIT).unwrap();
+        child
+    }
+
+    #[test]
+    fn caller_prefix() {
+        assert_eq!(split_caller("as=1000 start sshd"), (Some(1000), "start sshd"));
+        assert_eq!(split_caller("start sshd"), (None, "start sshd"));
+        // Not a uid, the whole request is the command
+        assert_eq!(split_caller("as=bob start sshd"), (None, "as=bob start sshd"));
+        assert_eq!(split_caller("as=0"), (None, "as=0"));
+    }
+
+    // One test, so no other test's reaper runs while a child is waiting
+    #[test]
+    fn reaper_leaves_the_console_child() {
+        let manager = ServiceManager::new();
+
+        let other = zombie(3);
+        ServiceManager::reap_children(&manager);
+        assert_eq!(waitpid(other, Some(WaitPidFlag::WNOHANG)), Err(Errno::ECHILD));
+
+        let console = zombie(7);
+        manager.lock().unwrap().console_pid = Some(console.as_raw());
+        ServiceManager::reap_children(&manager);
+        assert_eq!(waitpid(console, None), Ok(WaitStatus::Exited(console, 7)));
+    }
 }
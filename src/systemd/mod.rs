@@ -1,2 +1,7 @@
+pub mod history;
 pub mod manager;
+pub mod preset;
+pub mod procinfo;
 pub mod service;
+pub mod state;
+pub mod unit_files;
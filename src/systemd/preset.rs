@@ -0,0 +1,123 @@
+use std::fs;
+use std::path::Path;
+use glob::Pattern;
+use crate::systemd::unit_files::UnitDirs;
+
+pub const DEFAULT_PRESET_PATH: &str = "/etc/service/preset";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PresetAction {
+    Enable,
+    Disable,
+}
+
+#[derive(Debug)]
+pub struct PresetRule {
+    pub action: PresetAction,
+    pub pattern: Pattern,
+}
+
+/// Which services an image should have enabled by default.
+///
+/// One rule per line, `enable <glob>` or `disable <glob>`, `#` starts a comment.
+/// The first rule matching a service wins, services no rule matches are left alone.
+#[derive(Debug, Default)]
+pub struct Preset {
+    pub rules: Vec<PresetRule>,
+}
+
+pub struct PresetChange {
+    pub name: String,
+    pub action: PresetAction,
+    pub changed: bool,
+}
+
+impl Preset {
+    pub fn parse(content: &str, source: &str) -> Result<Self, String> {
+        let mut rules = Vec::new();
+
+        for (lineno, line) in content.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let mut parts = line.split_whitespace();
+            let action = match parts.next() {
+                Some("enable") => PresetAction::Enable,
+                Some("disable") => PresetAction::Disable,
+                Some(other) => return Err(format!("{}:{}: unknown action '{}'", source, lineno + 1, other)),
+                None => continue,
+            };
+            let pattern = parts
+                .next()
+                .ok_or_else(|| format!("{}:{}: missing service name", source, lineno + 1))?;
+            if parts.next().is_some() {
+                return Err(format!("{}:{}: trailing text after '{}'", source, lineno + 1, pattern));
+            }
+
+            let pattern = Pattern::new(pattern.trim_end_matches(".service"))
+                .map_err(|e| format!("{}:{}: invalid pattern: {}", source, lineno + 1, e))?;
+            rules.push(PresetRule { action, pattern });
+        }
+
+        Ok(Preset { rules })
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        Self::parse(&content, &path.display().to_string())
+    }
+
+    /// All `*.preset` files in `dir`, in name order, as one rule list.
+    pub fn from_dir(dir: &Path) -> Result<Self, String> {
+        let mut files: Vec<_> = match fs::read_dir(dir) {
+            Ok(entries) => entries
+                .flatten()
+                .map(|e| e.path())
+                .filter(|p| p.extension().is_some_and(|e| e == "preset"))
+                .collect(),
+            Err(_) => return Ok(Preset::default()),
+        };
+        files.sort();
+
+        let mut preset = Preset::default();
+        for file in files {
+            preset.rules.extend(Self::from_file(&file)?.rules);
+        }
+        Ok(preset)
+    }
+
+    /// Presets shipped inside an image root, i.e. `<root>/etc/service/preset/*.preset`.
+    pub fn for_root(root: &Path) -> Result<Self, String> {
+        Self::from_dir(&root.join(DEFAULT_PRESET_PATH.trim_start_matches('/')))
+    }
+
+    pub fn lookup(&self, name: &str) -> Option<PresetAction> {
+        self.rules
+            .iter()
+            .find(|rule| rule.pattern.matches(name))
+            .map(|rule| rule.action)
+    }
+
+    /// Enable or disable `names` (every available service when empty) under `dirs`.
+    pub fn apply(&self, dirs: &UnitDirs, names: &[String]) -> Result<Vec<PresetChange>, String> {
+        let names = if names.is_empty() { dirs.list() } else { names.to_vec() };
+        let mut changes = Vec::new();
+
+        for name in names {
+            let action = match self.lookup(&name) {
+                Some(action) => action,
+                None => continue,
+            };
+            let changed = match action {
+                PresetAction::Enable => dirs.enable(&name)?,
+                PresetAction::Disable => dirs.disable(&name)?,
+            };
+            changes.push(PresetChange { name, action, changed });
+        }
+
+        Ok(changes)
+    }
+}
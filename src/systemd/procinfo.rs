@@ -0,0 +1,78 @@
+use std::collections::HashMap;
+use std::fs;
+
+pub struct ProcessNode {
+    pub pid: i32,
+    pub comm: String,
+    pub depth: usize,
+}
+
+/// (pid, ppid, comm) from /proc/<pid>/stat
+fn read_stat(pid: i32) -> Option<(i32, String)> {
+    let stat = fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    // comm can contain spaces, it sits between the first '(' and the last ')'
+    let open = stat.find('(')?;
+    let close = stat.rfind(')')?;
+    let comm = stat[open + 1..close].to_string();
+    let ppid = stat[close + 1..].split_whitespace().nth(1)?.parse().ok()?;
+    Some((ppid, comm))
+}
+
+/// Main process followed by all of its descendants, depth-first.
+pub fn process_tree(root: i32) -> Vec<ProcessNode> {
+    let mut children: HashMap<i32, Vec<i32>> = HashMap::new();
+    let mut comms: HashMap<i32, String> = HashMap::new();
+
+    if let Ok(entries) = fs::read_dir("/proc") {
+        for entry in entries.flatten() {
+            let pid = match entry.file_name().to_str().and_then(|s| s.parse::<i32>().ok()) {
+                Some(pid) => pid,
+                None => continue,
+            };
+            if let Some((ppid, comm)) = read_stat(pid) {
+                children.entry(ppid).or_default().push(pid);
+                comms.insert(pid, comm);
+            }
+        }
+    }
+
+    let mut tree = Vec::new();
+    if !comms.contains_key(&root) {
+        return tree;
+    }
+
+    let mut stack = vec![(root, 0)];
+    while let Some((pid, depth)) = stack.pop() {
+        tree.push(ProcessNode {
+            pid,
+            comm: comms.get(&pid).cloned().unwrap_or_default(),
+            depth,
+        });
+        if let Some(kids) = children.get(&pid) {
+            for kid in kids.iter().rev() {
+                stack.push((*kid, depth + 1));
+            }
+        }
+    }
+    tree
+}
+
+/// Resident memory of a process in kB.
+pub fn rss_kb(pid: i32) -> Option<u64> {
+    let status = fs::read_to_string(format!("/proc/{}/status", pid)).ok()?;
+    status
+        .lines()
+        .find(|l| l.starts_with("VmRSS:"))
+        .and_then(|l| l.split_whitespace().nth(1))
+        .and_then(|v| v.parse().ok())
+}
+
+pub fn format_kb(kb: u64) -> String {
+    if kb >= 1024 * 1024 {
+        format!("{:.1}G", kb as f64 / (1024.0 * 1024.0))
+    } else if kb >= 1024 {
+        format!("{:.1}M", kb as f64 / 1024.0)
+    } else {
+        format!("{}K", kb)
+    }
+}
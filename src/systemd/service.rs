@@ -1,14 +1,19 @@
-use std::fs;
+use std::fs::{self, OpenOptions};
 use std::path::Path;
 use std::ffi::CString;
 use nix::unistd::{fork, ForkResult, execv, Pid, setsid};
-use std::os::unix::fs::symlink;
-use nix::sys::wait::{waitpid, WaitPidFlag};
+use std::os::unix::io::AsRawFd;
+use nix::sys::wait::{waitpid, WaitPidFlag, WaitStatus};
 use std::time::SystemTime;
 use chrono::{DateTime, Local};
-use crate::systemd::manager::{DEFAULT_SERVICE_ENABLED_PATH};
+use crate::systemd::unit_files::UnitDirs;
+use crate::systemd::history::{RunExit, RunHistory, RunRecord};
+use crate::systemd::procinfo::{process_tree, rss_kb, format_kb};
 use crate::common::{safe_eprintln, safe_println, get_system_timezone};
 
+pub const DEFAULT_SERVICE_LOG_PATH: &str = "/var/log/service";
+const STATUS_LOG_LINES: usize = 5;
+
 #[derive(Debug)]
 #[derive(Clone)]
 pub struct Service {
@@ -21,6 +26,9 @@ pub struct Service {
     pub pid: Option<Pid>,
     pub start_time: Option<SystemTime>,
     pub stop_time: Option<SystemTime>,
+    pub restarts: u32,
+    pub loaded_mtime: Option<SystemTime>,
+    pub history: RunHistory,
 }
 
 impl Service {
@@ -33,6 +41,7 @@ impl Service {
         let mut description = String::new();
         let mut service_type = String::new();
         let loaded_path = format!("{}", path.display());
+        let loaded_mtime = fs::metadata(path).and_then(|m| m.modified()).ok();
 
         for line in content.lines() {
             if line.starts_with("Name=") {
@@ -55,7 +64,7 @@ impl Service {
 
 
 
-        let enabled = Path::new(&format!("{}/{}.service", DEFAULT_SERVICE_ENABLED_PATH, name)).exists();
+        let enabled = UnitDirs::system().is_enabled(&name);
 
         Ok(Service {
             loaded_path,
@@ -67,19 +76,63 @@ impl Service {
             pid: None,
             start_time: None,
             stop_time: None,
+            restarts: 0,
+            loaded_mtime,
+            history: RunHistory::default(),
         })
     }
 
-    pub fn start(&mut self) {
+    pub fn log_path(&self) -> String {
+        format!("{}/{}.log", DEFAULT_SERVICE_LOG_PATH, self.name)
+    }
+
+    /// Fork and exec the service. `reason` is kept in the run history.
+    /// Fails when the fork fails or the program can't be executed.
+    pub fn start(&mut self, reason: &str) -> Result<(), String> {
+        // Open the log before forking so the child only has to dup2
+        let _ = fs::create_dir_all(DEFAULT_SERVICE_LOG_PATH);
+        let log = OpenOptions::new().create(true).append(true).open(self.log_path());
+
+        // Closed by a successful exec, otherwise the child sends its errno through it
+        let mut exec_pipe = [0; 2];
+        if unsafe { libc::pipe2(exec_pipe.as_mut_ptr(), libc::O_CLOEXEC) } != 0 {
+            return Err(format!("pipe: {}", std::io::Error::last_os_error()));
+        }
+        let (exec_read, exec_write) = (exec_pipe[0], exec_pipe[1]);
+
         match unsafe { fork() } {
             Ok(ForkResult::Parent { child }) => {
+                let mut errno = [0u8; 4];
+                let read = unsafe {
+                    libc::close(exec_write);
+                    let n = libc::read(exec_read, errno.as_mut_ptr().cast(), errno.len());
+                    libc::close(exec_read);
+                    n
+                };
+
+                let now = SystemTime::now();
+                self.pid = Some(child);
+                self.start_time = Some(now);
+                self.stop_time = None;
+                self.history.push(RunRecord {
+                    pid: child.as_raw(),
+                    start_time: now,
+                    end_time: None,
+                    exit: None,
+                    reason: reason.to_string(),
+                    stop_requested: false,
+                });
+
+                // The child exits right away, the reaper records it like any other run
+                if read == errno.len() as isize {
+                    let error = std::io::Error::from_raw_os_error(i32::from_ne_bytes(errno));
+                    return Err(format!("cannot execute {}: {}", self.exec, error));
+                }
                 safe_println(format_args!(
                     "[INIT] Started service {} with PID {}",
                     self.name, child
                 ));
-                self.pid = Some(child);
-                self.start_time = Some(SystemTime::now());
-                self.stop_time = None;
+                Ok(())
             }
             Ok(ForkResult::Child) => {
                 let exec_path = CString::new(self.exec.clone()).unwrap();
@@ -88,102 +141,254 @@ impl Service {
 
                 let _ = setsid();
 
-                execv(&exec_path, &args).unwrap_or_else(|e| {
-                    safe_eprintln(format_args!("[INIT] Failed to exec {:?}: {}", self.exec, e));
-                    std::process::exit(1);
-                });
+                if let Ok(file) = &log {
+                    unsafe {
+                        libc::dup2(file.as_raw_fd(), 1);
+                        libc::dup2(file.as_raw_fd(), 2);
+                    }
+                }
+
+                let err = execv(&exec_path, &args).unwrap_err();
+                unsafe {
+                    let errno = (err as i32).to_ne_bytes();
+                    libc::write(exec_write, errno.as_ptr().cast(), errno.len());
+                }
+                safe_eprintln(format_args!("[INIT] Failed to exec {:?}: {}", self.exec, err));
+                std::process::exit(1);
             }
             Err(err) => {
+                unsafe {
+                    libc::close(exec_read);
+                    libc::close(exec_write);
+                }
                 safe_eprintln(format_args!(
                     "[INIT] Failed to create service for {}: {}",
                     self.name, err
                 ));
+                Err(format!("fork: {}", err))
             }
         }
     }
 
+    /// Close the current run once its main process is gone.
+    pub fn record_exit(&mut self, pid: Pid, exit: RunExit) {
+        let now = SystemTime::now();
+        if !self.history.finish(pid.as_raw(), exit.clone(), now) {
+            // Already closed by stop() before the reaper got to it
+            self.history.resolve(pid.as_raw(), exit);
+        }
+
+        if self.pid == Some(pid) {
+            self.pid = None;
+            self.start_time = None;
+            self.stop_time = Some(now);
+        }
+    }
+
     pub fn stop(&mut self) {
         if let Some(pid) = self.pid {
+            self.history.mark_stop_requested(pid.as_raw());
+
             // Hantar SIGTERM
             let _ = nix::sys::signal::kill(pid, nix::sys::signal::Signal::SIGTERM);
-            let _ = waitpid(pid, Some(WaitPidFlag::WNOHANG)); // Cuba "clean up"
 
             // Tunggu proses mati (loop ringkas)
             for _ in 0..10 {
                 std::thread::sleep(std::time::Duration::from_millis(200));
-                if nix::sys::signal::kill(pid, None).is_err() {
-                    // Proses dah mati
-                    self.pid = None;
-                    self.stop_time = Some(SystemTime::now());
-                    self.start_time = None;
-                    safe_println(format_args!("Stopped service {}", self.name));
-                    return;
+                match waitpid(pid, Some(WaitPidFlag::WNOHANG)) {
+                    Ok(WaitStatus::StillAlive) => continue,
+                    Ok(status) => {
+                        if let Some(exit) = RunExit::from_wait_status(status) {
+                            self.record_exit(pid, exit);
+                            safe_println(format_args!("Stopped service {}", self.name));
+                            return;
+                        }
+                    }
+                    Err(_) => {
+                        // Proses dah mati, init's reaper collected it first
+                        self.record_exit(pid, RunExit::Unknown);
+                        safe_println(format_args!("Stopped service {}", self.name));
+                        return;
+                    }
                 }
             }
 
             // Paksa kill
             let _ = nix::sys::signal::kill(pid, nix::sys::signal::Signal::SIGKILL);
-            let _ = waitpid(pid, None);
-            self.pid = None;
+            let exit = waitpid(pid, None).ok().and_then(RunExit::from_wait_status).unwrap_or(RunExit::Unknown);
+            self.record_exit(pid, exit);
             safe_println(format_args!("Force-stopped service {}", self.name));
         } else {
             safe_println(format_args!("Service '{}' not running", self.name));
         }
     }
 
-    pub fn enable(&mut self, source: &str, target: &str) {
-        if Path::new(&target).exists() {
-            safe_println(format_args!("Service '{}' already enabled", self.name));
-            return;
+    pub fn enable(&mut self, dirs: &UnitDirs) -> Result<(), String> {
+        match dirs.enable(&self.name)? {
+            true => safe_println(format_args!("Enabling service '{}'", self.name)),
+            false => safe_println(format_args!("Service '{}' already enabled", self.name)),
         }
+        self.enabled = true;
+        Ok(())
+    }
 
-        match symlink(&source, &target) {
-            Ok(_) => { self.enabled = true; safe_println(format_args!("Enabling service '{}'", self.name)) },
-            Err(e) => safe_eprintln(format_args!("Failed to enable '{}': {}", self.name, e)),
+    pub fn disable(&mut self, dirs: &UnitDirs) -> Result<(), String> {
+        match dirs.disable(&self.name)? {
+            true => safe_println(format_args!("Disabling service '{}'", self.name)),
+            false => safe_println(format_args!("Service '{}' not enabled", self.name)),
         }
+        self.enabled = false;
+        Ok(())
     }
 
-    pub fn disable(&mut self, target: &str) {
-        if !Path::new(&target).exists() {
-            safe_println(format_args!("Service '{}' not enabled", self.name));
-            return;
+    /// Send SIGHUP so the service can re-read its own configuration.
+    pub fn reload(&self) -> Result<(), String> {
+        match self.pid {
+            Some(pid) if self.is_running() => nix::sys::signal::kill(pid, nix::sys::signal::Signal::SIGHUP)
+                .map_err(|e| e.to_string()),
+            _ => Err("not running".into()),
         }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.pid.is_some_and(|pid| nix::sys::signal::kill(pid, None).is_ok())
+    }
 
-        match fs::remove_file(&target) {
-            Ok(_) => { self.enabled = false; safe_println(format_args!("Disabling service '{}'", self.name)) },
-            Err(e) => safe_eprintln(format_args!("Failed to disable '{}': {}", self.name, e)),
+    pub fn state(&self) -> &'static str {
+        if self.is_running() {
+            "running"
+        } else if self.history.last_finished().is_some_and(|run| run.failed()) {
+            "failed"
+        } else {
+            "stopped"
         }
     }
 
-    pub fn status(&mut self) {
-        let tz = get_system_timezone();
+    /// Machine readable `key=value` lines used by the `show` control command.
+    pub fn properties(&self) -> String {
+        let epoch = |time: Option<SystemTime>| {
+            time.and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                .map(|d| d.as_secs().to_string())
+                .unwrap_or_default()
+        };
+        let last_exit = self.history.last_finished()
+            .and_then(|run| run.exit.as_ref())
+            .map(|exit| exit.serialize())
+            .unwrap_or_default();
 
-        safe_println(format_args!("      Loaded at: {}", self.loaded_path));
-        safe_println(format_args!("   Service Name: {}", self.name));
-        let running = if let Some(pid) = self.pid {
-            let is_alive = nix::sys::signal::kill(pid, None).is_ok();
-            safe_println(format_args!("            PID: {}", pid));
-            is_alive
-        } else {
-            safe_println(format_args!("            PID: None"));
-            false
+        let mut out = String::new();
+        out += &format!("name={}\n", self.name);
+        out += &format!("description={}\n", self.description);
+        out += &format!("loaded_path={}\n", self.loaded_path);
+        out += &format!("state={}\n", self.state());
+        out += &format!("enabled={}\n", if self.enabled { "yes" } else { "no" });
+        out += &format!("pid={}\n", if self.is_running() { self.pid.map(|p| p.to_string()).unwrap_or_default() } else { String::new() });
+        out += &format!("active_since={}\n", epoch(self.start_time));
+        out += &format!("inactive_since={}\n", epoch(self.stop_time));
+        out += &format!("restarts={}\n", self.restarts);
+        out += &format!("last_exit={}\n", last_exit);
+        out += &format!("log_path={}\n", self.log_path());
+        out
+    }
+
+    pub fn status(&self) -> String {
+        let tz = get_system_timezone();
+        let fmt_time = |time: SystemTime| {
+            let dt: DateTime<Local> = DateTime::from(time);
+            dt.with_timezone(&tz).format("%Y-%m-%d %H:%M:%S %Z").to_string()
         };
+        let mut out = String::new();
 
-        safe_println(format_args!("        Enabled: {}", if self.enabled { "Yes" } else { "No" }));
-        safe_println(format_args!("        Running: {}", if running { "Yes" } else { "No" }));
+        out += &format!("      Loaded at: {}\n", self.loaded_path);
+        if let Some(changed) = self.unit_changed_since_load() {
+            out += &format!("                 Warning: unit file changed on disk at {}, restart to apply\n", fmt_time(changed));
+        }
+        out += &format!("   Service Name: {}\n", self.name);
+        if !self.description.is_empty() {
+            out += &format!("    Description: {}\n", self.description);
+        }
+
+        let running = self.is_running();
+        let state = self.state();
+
+        out += &format!("        Enabled: {}\n", if self.enabled { "Yes" } else { "No" });
+        out += &format!("          State: {}\n", state);
+        out += &format!("       Restarts: {}\n", self.restarts);
 
         if running {
+            let pid = self.pid.unwrap().as_raw();
             if let Some(time) = self.start_time {
-                let dt: DateTime<Local> = DateTime::from(time);
-                let localtime = dt.with_timezone(&tz);
-                safe_println(format_args!("   Active since: {}", localtime.format("%Y-%m-%d %H:%M:%S %Z")));
+                out += &format!("   Active since: {}\n", fmt_time(time));
+            }
+            out += &format!("       Main PID: {}\n", pid);
+
+            let tree = process_tree(pid);
+            let memory: u64 = tree.iter().filter_map(|p| rss_kb(p.pid)).sum();
+            out += &format!("         Memory: {}\n", format_kb(memory));
+            out += &format!("          Tasks: {}\n", tree.len());
+            for node in &tree {
+                out += &format!("                 {}{} {}\n", "  ".repeat(node.depth), node.pid, node.comm);
             }
         } else {
+            out += "       Main PID: None\n";
             if let Some(time) = self.stop_time {
-                let dt: DateTime<Local> = DateTime::from(time);
-                let localtime = dt.with_timezone(&tz);
-                safe_println(format_args!(" Inactive since: {}", localtime.format("%Y-%m-%d %H:%M:%S %Z")));
+                out += &format!(" Inactive since: {}\n", fmt_time(time));
+            }
+        }
+
+        if let Some(run) = self.history.last_finished() {
+            out += &format!(
+                "      Last exit: {} (PID {}, started by {})\n",
+                run.exit.as_ref().unwrap(),
+                run.pid,
+                run.reason
+            );
+        }
+
+        let lines = self.tail_log(STATUS_LOG_LINES);
+        if !lines.is_empty() {
+            out += &format!("\nLast {} log lines from {}:\n", lines.len(), self.log_path());
+            for line in lines {
+                out += &format!("  {}\n", line);
             }
         }
+
+        out
+    }
+
+    pub fn history_report(&self) -> String {
+        let tz = get_system_timezone();
+        let fmt_time = |time: SystemTime| {
+            let dt: DateTime<Local> = DateTime::from(time);
+            dt.with_timezone(&tz).format("%Y-%m-%d %H:%M:%S").to_string()
+        };
+
+        let mut out = format!("{:<8} {:<19} {:<19} {:<28} {}\n", "PID", "STARTED", "ENDED", "RESULT", "REASON");
+        for run in &self.history.runs {
+            out += &format!(
+                "{:<8} {:<19} {:<19} {:<28} {}\n",
+                run.pid,
+                fmt_time(run.start_time),
+                run.end_time.map(fmt_time).unwrap_or_else(|| "-".into()),
+                run.exit.as_ref().map(|e| e.to_string()).unwrap_or_else(|| "running".into()),
+                run.reason
+            );
+        }
+        out
+    }
+
+    pub fn tail_log(&self, count: usize) -> Vec<String> {
+        let content = fs::read_to_string(self.log_path()).unwrap_or_default();
+        let lines: Vec<&str> = content.lines().collect();
+        let skip = lines.len().saturating_sub(count);
+        lines[skip..].iter().map(|l| l.to_string()).collect()
+    }
+
+    fn unit_changed_since_load(&self) -> Option<SystemTime> {
+        let current = fs::metadata(&self.loaded_path).and_then(|m| m.modified()).ok()?;
+        match self.loaded_mtime {
+            Some(loaded) if loaded == current => None,
+            _ => Some(current),
+        }
     }
 }
@@ -0,0 +1,303 @@
+use std::fs::{self, File};
+use std::io::Write;
+use std::os::unix::io::RawFd;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use nix::unistd::Pid;
+use crate::systemd::history::{RunExit, RunRecord};
+use crate::systemd::service::Service;
+
+pub const DEFAULT_STATE_PATH: &str = "/run/init.state";
+
+/// Runtime state of a single service that must survive an init re-exec.
+#[derive(Debug, Clone, Default)]
+pub struct ServiceState {
+    pub name: String,
+    pub pid: Option<i32>,
+    pub enabled: bool,
+    pub start_time: Option<u64>,
+    pub stop_time: Option<u64>,
+    pub restarts: u32,
+    pub history: Vec<RunRecord>,
+}
+
+/// Snapshot of the whole manager, written before execv and read back by the new init.
+#[derive(Debug, Default)]
+pub struct ManagerState {
+    pub listener_fd: Option<RawFd>,
+    /// login or the desktop, still running under the new init
+    pub console_pid: Option<i32>,
+    pub services: Vec<ServiceState>,
+}
+
+impl ServiceState {
+    pub fn from_service(service: &Service) -> Self {
+        ServiceState {
+            name: service.name.clone(),
+            pid: service.pid.map(|p| p.as_raw()),
+            enabled: service.enabled,
+            start_time: service.start_time.and_then(to_epoch_secs),
+            stop_time: service.stop_time.and_then(to_epoch_secs),
+            restarts: service.restarts,
+            history: service.history.runs.iter().cloned().collect(),
+        }
+    }
+
+    pub fn apply_to(&self, service: &mut Service) {
+        service.pid = self.pid.map(Pid::from_raw);
+        service.enabled = self.enabled;
+        service.start_time = self.start_time.map(from_epoch_secs);
+        service.stop_time = self.stop_time.map(from_epoch_secs);
+        service.restarts = self.restarts;
+        service.history.runs = self.history.iter().cloned().collect();
+    }
+}
+
+impl ManagerState {
+    /// Format:
+    ///   listener_fd=3
+    ///   console_pid=98
+    ///   [service]
+    ///   name=sshd
+    ///   pid=120
+    ///   run=<pid>|<start>|<end>|<exit>|<stop_requested>|<reason>
+    ///   ...
+    pub fn serialize(&self) -> String {
+        let mut out = String::new();
+        if let Some(fd) = self.listener_fd {
+            out += &format!("listener_fd={}\n", fd);
+        }
+        if let Some(pid) = self.console_pid {
+            out += &format!("console_pid={}\n", pid);
+        }
+
+        for svc in &self.services {
+            out += "[service]\n";
+            out += &format!("name={}\n", svc.name);
+            out += &format!("pid={}\n", opt_to_string(svc.pid));
+            out += &format!("enabled={}\n", svc.enabled);
+            out += &format!("start_time={}\n", opt_to_string(svc.start_time));
+            out += &format!("stop_time={}\n", opt_to_string(svc.stop_time));
+            out += &format!("restarts={}\n", svc.restarts);
+            for run in &svc.history {
+                out += &format!("run={}\n", serialize_run(run));
+            }
+        }
+        out
+    }
+
+    pub fn deserialize(content: &str) -> Result<Self, String> {
+        let mut state = ManagerState::default();
+        let mut current: Option<ServiceState> = None;
+
+        for (lineno, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            if line == "[service]" {
+                if let Some(svc) = current.take() {
+                    state.services.push(svc);
+                }
+                current = Some(ServiceState::default());
+                continue;
+            }
+
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| format!("Invalid state line {}: {}", lineno + 1, line))?;
+            let bad = || format!("Invalid value for {} at line {}", key, lineno + 1);
+
+            match (&mut current, key) {
+                (None, "listener_fd") => state.listener_fd = Some(value.parse().map_err(|_| bad())?),
+                (None, "console_pid") => state.console_pid = Some(value.parse().map_err(|_| bad())?),
+                (Some(svc), "name") => svc.name = value.to_string(),
+                (Some(svc), "pid") => svc.pid = parse_opt(value).map_err(|_| bad())?,
+                (Some(svc), "enabled") => svc.enabled = value == "true",
+                (Some(svc), "start_time") => svc.start_time = parse_opt(value).map_err(|_| bad())?,
+                (Some(svc), "stop_time") => svc.stop_time = parse_opt(value).map_err(|_| bad())?,
+                (Some(svc), "restarts") => svc.restarts = value.parse().map_err(|_| bad())?,
+                (Some(svc), "run") => svc.history.push(deserialize_run(value).ok_or_else(bad)?),
+                // Unknown keys are skipped so an older init can read a newer state file
+                _ => {}
+            }
+        }
+
+        if let Some(svc) = current.take() {
+            state.services.push(svc);
+        }
+
+        Ok(state)
+    }
+
+    /// Written next to `path` and renamed over it, so `restore` never sees half a state.
+    pub fn write_to(&self, path: &str) -> Result<(), String> {
+        let tmp = format!("{}.tmp", path);
+        let result = File::create(&tmp)
+            .and_then(|mut f| {
+                f.write_all(self.serialize().as_bytes())?;
+                f.sync_all()
+            })
+            .and_then(|_| fs::rename(&tmp, path));
+
+        result.map_err(|e| {
+            let _ = fs::remove_file(&tmp);
+            format!("Failed to write {}: {}", path, e)
+        })
+    }
+
+    pub fn read_from(path: &str) -> Result<Self, String> {
+        let content = fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+        Self::deserialize(&content)
+    }
+}
+
+fn serialize_run(run: &RunRecord) -> String {
+    format!(
+        "{}|{}|{}|{}|{}|{}",
+        run.pid,
+        opt_to_string(to_epoch_secs(run.start_time)),
+        opt_to_string(run.end_time.and_then(to_epoch_secs)),
+        run.exit.as_ref().map(|e| e.serialize()).unwrap_or_default(),
+        run.stop_requested,
+        run.reason
+    )
+}
+
+fn deserialize_run(value: &str) -> Option<RunRecord> {
+    // reason is last so it may contain '|'
+    let fields: Vec<&str> = value.splitn(6, '|').collect();
+    if fields.len() != 6 {
+        return None;
+    }
+
+    Some(RunRecord {
+        pid: fields[0].parse().ok()?,
+        start_time: from_epoch_secs(fields[1].parse().ok()?),
+        end_time: parse_opt(fields[2]).ok()?.map(from_epoch_secs),
+        exit: if fields[3].is_empty() { None } else { Some(RunExit::deserialize(fields[3])?) },
+        stop_requested: fields[4] == "true",
+        reason: fields[5].to_string(),
+    })
+}
+
+fn opt_to_string<T: ToString>(value: Option<T>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_default()
+}
+
+fn parse_opt<T: std::str::FromStr>(value: &str) -> Result<Option<T>, T::Err> {
+    if value.is_empty() {
+        Ok(None)
+    } else {
+        value.parse().map(Some)
+    }
+}
+
+fn to_epoch_secs(time: SystemTime) -> Option<u64> {
+    time.duration_since(UNIX_EPOCH).ok().map(|d| d.as_secs())
+}
+
+fn from_epoch_secs(secs: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn sample() -> ManagerState {
+        let run = |pid, end: Option<u64>, exit, reason: &str| RunRecord {
+            pid,
+            start_time: from_epoch_secs(1_700_000_000),
+            end_time: end.map(from_epoch_secs),
+            exit,
+            reason: reason.to_string(),
+            stop_requested: end.is_some(),
+        };
+        ManagerState {
+            listener_fd: Some(3),
+            console_pid: Some(98),
+            services: vec![
+                ServiceState {
+                    name: "sshd".into(),
+                    pid: Some(120),
+                    enabled: true,
+                    start_time: Some(1_700_000_000),
+                    stop_time: None,
+                    restarts: 2,
+                    history: vec![
+                        run(101, Some(1_700_000_100), Some(RunExit::Signal("SIGTERM".into())), "manual start"),
+                        run(110, Some(1_700_000_200), Some(RunExit::Code(1)), "enabled at boot | again"),
+                        run(120, None, None, "manual restart"),
+                    ],
+                },
+                ServiceState { name: "crond".into(), ..Default::default() },
+            ],
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let state = sample();
+        let text = state.serialize();
+        let back = ManagerState::deserialize(&text).unwrap();
+
+        assert_eq!(back.listener_fd, Some(3));
+        assert_eq!(back.console_pid, Some(98));
+        assert_eq!(back.services.len(), 2);
+        let sshd = &back.services[0];
+        assert_eq!((sshd.name.as_str(), sshd.pid, sshd.enabled, sshd.restarts), ("sshd", Some(120), true, 2));
+        assert_eq!((sshd.start_time, sshd.stop_time), (Some(1_700_000_000), None));
+        assert_eq!(back.services[1].name, "crond");
+        assert_eq!(back.services[1].pid, None);
+        assert!(back.services[1].history.is_empty());
+        assert_eq!(back.serialize(), text);
+    }
+
+    #[test]
+    fn run_history_round_trip() {
+        let back = ManagerState::deserialize(&sample().serialize()).unwrap();
+        let runs = &back.services[0].history;
+
+        assert_eq!(runs.len(), 3);
+        assert_eq!(runs[0].pid, 101);
+        assert_eq!(runs[0].exit, Some(RunExit::Signal("SIGTERM".into())));
+        assert_eq!(runs[0].end_time, Some(from_epoch_secs(1_700_000_100)));
+        assert!(runs[0].stop_requested);
+        // '|' in the reason survives, it is the last field
+        assert_eq!(runs[1].reason, "enabled at boot | again");
+        assert_eq!(runs[1].exit, Some(RunExit::Code(1)));
+        assert_eq!((runs[2].end_time, runs[2].exit.clone()), (None, None));
+        assert!(!runs[2].stop_requested);
+    }
+
+    #[test]
+    fn unknown_keys_are_skipped() {
+        let state = ManagerState::deserialize("listener_fd=4\nfuture=1\n[service]\nname=a\nfuture=2\n").unwrap();
+        assert_eq!(state.listener_fd, Some(4));
+        assert_eq!(state.services[0].name, "a");
+    }
+
+    #[test]
+    fn bad_lines_are_errors() {
+        assert!(ManagerState::deserialize("listener_fd=x\n").is_err());
+        assert!(ManagerState::deserialize("[service]\nrun=1|2|3\n").is_err());
+        assert!(ManagerState::deserialize("[service]\nrun=1|2||bogus|false|x\n").is_err());
+        assert!(ManagerState::deserialize("no equals sign\n").is_err());
+    }
+
+    #[test]
+    fn write_replaces_whole_file() {
+        let dir = std::env::temp_dir().join(format!("vantara-state-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("init.state").display().to_string();
+        fs::write(&path, "x".repeat(10_000)).unwrap();
+
+        let state = sample();
+        state.write_to(&path).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), state.serialize());
+        assert!(!Path::new(&format!("{}.tmp", path)).exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,100 @@
+use std::fs;
+use std::os::unix::fs::symlink;
+use std::path::{Path, PathBuf};
+use crate::systemd::manager::{DEFAULT_SERVICE_AVAILABLE_PATH, DEFAULT_SERVICE_ENABLED_PATH};
+use crate::systemd::service::DEFAULT_SERVICE_LOG_PATH;
+
+/// Service directories below a root, either `/` on a running system or an image
+/// directory that is being prepared offline.
+pub struct UnitDirs {
+    pub available: PathBuf,
+    pub enabled: PathBuf,
+    pub logs: PathBuf,
+}
+
+impl UnitDirs {
+    pub fn system() -> Self {
+        Self::with_root(Path::new("/"))
+    }
+
+    pub fn with_root(root: &Path) -> Self {
+        let under_root = |path: &str| root.join(path.trim_start_matches('/'));
+        UnitDirs {
+            available: under_root(DEFAULT_SERVICE_AVAILABLE_PATH),
+            enabled: under_root(DEFAULT_SERVICE_ENABLED_PATH),
+            logs: under_root(DEFAULT_SERVICE_LOG_PATH),
+        }
+    }
+
+    pub fn unit_path(&self, name: &str) -> PathBuf {
+        self.available.join(format!("{}.service", name))
+    }
+
+    pub fn enabled_link(&self, name: &str) -> PathBuf {
+        self.enabled.join(format!("{}.service", name))
+    }
+
+    pub fn log_path(&self, name: &str) -> PathBuf {
+        self.logs.join(format!("{}.log", name))
+    }
+
+    pub fn exists(&self, name: &str) -> bool {
+        self.unit_path(name).is_file()
+    }
+
+    /// Names of all `*.service` files in the available directory, sorted.
+    pub fn list(&self) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(&self.available)
+            .map(|entries| {
+                entries
+                    .flatten()
+                    .filter_map(|e| {
+                        let path = e.path();
+                        if path.extension().is_some_and(|ext| ext == "service") {
+                            path.file_stem().and_then(|s| s.to_str()).map(|s| s.to_string())
+                        } else {
+                            None
+                        }
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        names.sort();
+        names
+    }
+
+    pub fn is_enabled(&self, name: &str) -> bool {
+        fs::symlink_metadata(self.enabled_link(name)).is_ok()
+    }
+
+    /// Link `enabled/<name>.service` to `../available/<name>.service`. The relative
+    /// target stays valid whether the tree is mounted at `/` or inspected as an image.
+    /// Returns false when the service was already enabled.
+    pub fn enable(&self, name: &str) -> Result<bool, String> {
+        if !self.exists(name) {
+            return Err(format!("{} does not exist", self.unit_path(name).display()));
+        }
+        if self.is_enabled(name) {
+            return Ok(false);
+        }
+
+        fs::create_dir_all(&self.enabled)
+            .map_err(|e| format!("Failed to create {}: {}", self.enabled.display(), e))?;
+
+        let target = Path::new("../available").join(format!("{}.service", name));
+        symlink(&target, self.enabled_link(name))
+            .map_err(|e| format!("Failed to enable '{}': {}", name, e))?;
+        Ok(true)
+    }
+
+    /// Returns false when the service was not enabled.
+    pub fn disable(&self, name: &str) -> Result<bool, String> {
+        if !self.is_enabled(name) {
+            return Ok(false);
+        }
+
+        fs::remove_file(self.enabled_link(name))
+            .map_err(|e| format!("Failed to disable '{}': {}", name, e))?;
+        Ok(true)
+    }
+}